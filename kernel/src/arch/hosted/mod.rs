@@ -39,6 +39,7 @@ enum ExitMessage {
 thread_local!(static NETWORK_LISTEN_ADDRESS: RefCell<SocketAddr> = RefCell::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0)));
 thread_local!(static SEND_ADDR: RefCell<Option<Sender<SocketAddr>>> = RefCell::new(None));
 thread_local!(static PID1_KEY: RefCell<[u8; 16]> = RefCell::new([0u8; 16]));
+thread_local!(static KERNEL_START: std::time::Instant = std::time::Instant::now());
 
 #[cfg(test)]
 pub fn set_pid1_key(new_key: [u8; 16]) {
@@ -85,6 +86,25 @@ pub fn current_pid() -> PID {
     crate::arch::process::current_pid()
 }
 
+/// The current reading of the clock that timeouts are measured against. Hosted
+/// mode uses `cpu_ticks()`, in microseconds.
+pub fn timeout_clock() -> u64 {
+    cpu_ticks()
+}
+
+/// The `timeout_clock()` reading by which at least `ms` milliseconds will have
+/// passed.
+pub fn timeout_deadline(ms: usize) -> u64 {
+    cpu_ticks().saturating_add((ms as u64).saturating_mul(1_000))
+}
+
+/// Microseconds since the kernel started. Hosted processes all run at the
+/// same time, so this measures time between kernel calls rather than time
+/// spent on a CPU.
+pub fn cpu_ticks() -> u64 {
+    KERNEL_START.with(|start| start.elapsed().as_micros() as u64)
+}
+
 /// Each client gets its own connection and its own thread, which is handled here.
 fn handle_connection(
     conn: TcpStream,
@@ -543,7 +563,27 @@ pub fn idle() -> bool {
         }
     }
 
-    while let Ok(msg) = message_receiver.recv() {
+    loop {
+        // Wake any threads that have given up waiting for a message, and
+        // don't sleep past the next thread that will.
+        let next_deadline = SystemServices::with_mut(|ss| {
+            ss.expire_timeouts();
+            ss.next_deadline()
+        });
+        let msg = match next_deadline {
+            Some(deadline) => {
+                let wait = std::time::Duration::from_micros(deadline.saturating_sub(timeout_clock()));
+                match message_receiver.recv_timeout(wait) {
+                    Ok(msg) => msg,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match message_receiver.recv() {
+                Ok(msg) => msg,
+                Err(RecvError) => break,
+            },
+        };
         match msg {
             ThreadMessage::NewConnection(conn, access_key) => {
                 // The new process should already have a PID registered. Convert its access key
@@ -608,6 +648,8 @@ pub fn idle() -> bool {
                     // println!("KERNEL: Done sending");
                 }
 
+                SystemServices::with_mut(|ss| ss.account_cpu_time(pid, thread_id));
+
                 // Handle the syscall within the Xous kernel
                 let response = crate::syscall::handle(pid, thread_id, false, call)
                     .unwrap_or_else(Result::Error);
@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub const MAX_THREAD: TID = 31;
+/// Hosted threads are numbered from 1, so the last of the `MAX_THREAD + 1` slots is TID 32
+pub const MAX_TID: TID = MAX_THREAD + 1;
 use crate::services::ProcessInner;
 use core::cell::RefCell;
 use std::io::Write;
@@ -273,6 +275,20 @@ impl Process {
         })
     }
 
+    /// Number of threads that are allocated in this process
+    pub fn thread_count(&self) -> usize {
+        PROCESS_TABLE.with(|pt| {
+            let process_table = pt.borrow();
+            let current_pid_idx = process_table.current.get() as usize - 1;
+            let process = process_table.table[current_pid_idx].as_ref().unwrap();
+            process
+                .threads
+                .iter()
+                .filter(|thread| thread.allocated)
+                .count()
+        })
+    }
+
     pub fn thread_exists(&self, _tid: TID) -> bool {
         false
     }
@@ -106,6 +106,26 @@ fn generate_exception_args(ex: &RiscvException) -> Option<[usize; 3]> {
     }
 }
 
+/// Act on what the debugger decided to do about an exception. This only returns
+/// if the debugger wasn't interested, in which case the exception should be
+/// handled as usual.
+#[cfg(feature = "gdbserver")]
+fn debugger_trap(pid: PID, trap: crate::debug::gdb_server::DebugTrap) {
+    use crate::debug::gdb_server::DebugTrap;
+    match trap {
+        DebugTrap::NotHandled => return,
+        DebugTrap::Resume => (),
+        DebugTrap::Stop => SystemServices::with_mut(|ss| {
+            ss.suspend_process(pid)
+                .expect("couldn't debug current process");
+            crate::syscall::reset_switchto_caller();
+        }),
+    }
+    ArchProcess::with_current_mut(|process| {
+        crate::arch::syscall::resume(current_pid().get() == 1, process.current_thread())
+    })
+}
+
 /// Trap entry point rust (_start_trap_rust)
 ///
 /// scause is read to determine the cause of the trap. The top bit indicates if
@@ -135,6 +155,9 @@ pub extern "C" fn trap_handler(
     }
 
     let pid = current_pid();
+    SystemServices::with_mut(|ss| {
+        ss.account_cpu_time(pid, ArchProcess::with_current(|p| p.current_tid()))
+    });
 
     if (sc.bits() == 9) || (sc.bits() == 8) {
         // We got here because of an `ecall` instruction.  When we return, skip
@@ -180,8 +203,25 @@ pub extern "C" fn trap_handler(
         // or returning from a handler or thread. If so, handle the exception
         // and return right away.
         match ex {
+            #[cfg(feature = "gdbserver")]
+            RiscvException::Breakpoint(epc) if sstatus::read().spp() == sstatus::SPP::User => {
+                let tid = ArchProcess::with_current(|process| process.current_tid());
+                debugger_trap(pid, crate::debug::gdb_server::breakpoint_hit(pid, tid, epc));
+            }
+
             RiscvException::StorePageFault(_pc, addr)
             | RiscvException::LoadPageFault(_pc, addr) => {
+                // Watched pages have their permissions taken away, so faults on them
+                // are the debugger's business.
+                #[cfg(feature = "gdbserver")]
+                if sstatus::read().spp() == sstatus::SPP::User {
+                    let tid = ArchProcess::with_current(|process| process.current_tid());
+                    let is_store = matches!(ex, RiscvException::StorePageFault(..));
+                    debugger_trap(
+                        pid,
+                        crate::debug::gdb_server::watch_fault(pid, tid, _pc, addr, is_store),
+                    );
+                }
                 #[cfg(all(feature = "debug-print", feature = "print-panics"))]
                 print!(
                     "KERNEL({}): RISC-V fault: {} @ {:08x}, addr {:08x} - ",
@@ -347,6 +387,12 @@ pub extern "C" fn trap_handler(
         let irqs_pending = sip::read();
         // println!("irqs: {:x}", irqs_pending);
 
+        // The preemption tick is also the clock that timeouts run on. It is
+        // acknowledged by its handler in `susres`, so each tick is only seen once.
+        if irqs_pending & (1 << utralib::generated::utra::timer0::TIMER0_IRQ) != 0 {
+            crate::arch::preemption_tick();
+        }
+
         // Safe to access globals since interrupts are disabled
         // when this function runs.
         unsafe {
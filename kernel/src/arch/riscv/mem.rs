@@ -19,6 +19,8 @@ const PAGE_TABLE_ROOT_OFFSET: usize = 0xff80_0000;
 
 extern "C" {
     fn flush_mmu();
+    #[cfg(feature = "gdbserver")]
+    fn flush_icache();
 }
 
 pub unsafe fn memset(s: *mut u8, c: i32, n: usize) -> *mut u8 {
@@ -403,6 +405,42 @@ pub fn poke_memory<T>(addr: *mut T, val: T) -> Result<(), xous_kernel::Error> {
     Ok(())
 }
 
+/// Make sure instruction fetches see code that was just modified through
+/// `poke_memory()`, e.g. when the debugger inserts or removes a breakpoint.
+#[cfg(feature = "gdbserver")]
+pub fn sync_instructions() {
+    unsafe { flush_icache() };
+}
+
+/// Return the read and write permissions of a page in the current process.
+#[cfg(feature = "gdbserver")]
+pub fn page_rw(virt: usize) -> Result<MMUFlags, xous_kernel::Error> {
+    let entry = pagetable_entry(virt & !0xfff)?;
+    if *entry & MMUFlags::VALID.bits() == 0 {
+        return Err(xous_kernel::Error::BadAddress);
+    }
+    Ok(MMUFlags::from_bits_truncate(
+        *entry & (MMUFlags::R | MMUFlags::W).bits(),
+    ))
+}
+
+/// Replace the read and write permissions of a page in the current process with
+/// `rw`, which may only contain `MMUFlags::R` and `MMUFlags::W`. Returns the
+/// permissions the page had before. The debugger uses this to trap accesses to
+/// watched memory.
+#[cfg(feature = "gdbserver")]
+pub fn set_page_rw(virt: usize, rw: MMUFlags) -> Result<MMUFlags, xous_kernel::Error> {
+    let entry = pagetable_entry(virt & !0xfff)?;
+    if *entry & MMUFlags::VALID.bits() == 0 {
+        return Err(xous_kernel::Error::BadAddress);
+    }
+    let mask = (MMUFlags::R | MMUFlags::W).bits();
+    let previous = MMUFlags::from_bits_truncate(*entry & mask);
+    *entry = (*entry & !mask) | (rw.bits() & mask);
+    unsafe { flush_mmu() };
+    Ok(previous)
+}
+
 /// Map the given page to the specified process table.  If necessary,
 /// allocate a new page.
 ///
@@ -14,6 +14,7 @@ pub use process::Thread;
 
 use utralib::generated::*;
 use xous_kernel::{MemoryFlags, MemoryType, PID};
+use crate::clock::TickClock;
 use crate::mem::MemoryManager;
 
 pub const WFI_KERNEL: Wfi = Wfi {
@@ -29,6 +30,44 @@ pub fn current_pid() -> PID {
     PID::new(satp::read().asid() as _).unwrap()
 }
 
+// Only touched from the trap handler and from within syscalls, both of which
+// run with interrupts disabled.
+static mut TIMEOUT_CLOCK: TickClock = TickClock::new(xous_kernel::PREEMPTION_TICK_MS as u64);
+
+/// Count one preemption tick. See `crate::clock` for why timeouts run on these.
+pub fn preemption_tick() {
+    unsafe { TIMEOUT_CLOCK.tick() };
+}
+
+/// The current reading of the clock that timeouts are measured against, in
+/// preemption ticks.
+pub fn timeout_clock() -> u64 {
+    unsafe { TIMEOUT_CLOCK.now() }
+}
+
+/// The `timeout_clock()` reading by which at least `ms` milliseconds will have
+/// passed.
+pub fn timeout_deadline(ms: usize) -> u64 {
+    unsafe { TIMEOUT_CLOCK.deadline(ms) }
+}
+
+/// The number of CPU cycles since reset. The `cycle` CSR isn't wrapped by the
+/// `riscv` crate, so call its accessors directly.
+pub fn cpu_ticks() -> u64 {
+    extern "C" {
+        fn __read_cycle() -> usize;
+        fn __read_cycleh() -> usize;
+    }
+    // Re-read the high half in case the low half rolled over in between
+    loop {
+        let high = unsafe { __read_cycleh() };
+        let low = unsafe { __read_cycle() };
+        if high == unsafe { __read_cycleh() } {
+            return ((high as u64) << 32) | low as u64;
+        }
+    }
+}
+
 pub fn init() {
     MemoryManager::with_mut(|memory_manager| {
         memory_manager
@@ -54,6 +93,10 @@ pub fn init() {
 
 /// Put the core to sleep until an interrupt hits. Returns `true`
 /// to indicate the kernel should not exit.
+///
+/// Threads waiting with a timeout don't need a wakeup of their own: the
+/// preemption tick keeps firing while the clocks are gated, and `kmain()`
+/// expires any timeouts that have run out every time it comes back around.
 pub fn idle() -> bool {
     let mut wfi_kernel_csr = CSR::new(WFI_KERNEL.base as *mut u32);
 
@@ -4,6 +4,7 @@
 use core::mem;
 static mut PROCESS: *mut ProcessImpl = 0xff80_1000 as *mut ProcessImpl;
 pub const MAX_THREAD: TID = 31;
+pub const MAX_TID: TID = MAX_THREAD;
 pub const EXCEPTION_TID: TID = 1;
 pub const INITIAL_TID: TID = 2;
 pub const IRQ_TID: TID = 0;
@@ -243,6 +244,13 @@ impl Process {
         }
     }
 
+    /// Number of threads that exist in this process, not counting the ISR thread
+    pub fn thread_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_thread_mut(|_, _| count += 1);
+        count
+    }
+
     pub fn find_free_thread(&self) -> Option<TID> {
         let process = unsafe { &mut *PROCESS };
         let start_tid = process.last_tid_allocated as usize;
@@ -0,0 +1,41 @@
+//! The clock that `ReceiveMessageTimeout` and `SendMessageTimeout` deadlines are measured
+//! against on hardware.
+//!
+//! The kernel has no timer of its own, and the cycle counter is no good for this: it stops
+//! whenever `wfi` gates the system clock, and it slows down when `llio` divides the system
+//! clock. Instead the kernel counts the preemption ticks that `susres` raises on TIMER0, which
+//! is clocked from the always-on domain and so keeps running through both. Each tick also
+//! brings the kernel back into `kmain()`, which is where deadlines are checked.
+
+/// A clock that advances in whole ticks of a fixed number of milliseconds
+pub struct TickClock {
+    ticks: u64,
+    tick_ms: u64,
+}
+
+impl TickClock {
+    pub const fn new(tick_ms: u64) -> TickClock {
+        TickClock { ticks: 0, tick_ms }
+    }
+
+    /// Count one tick
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    /// The number of ticks counted so far
+    pub fn now(&self) -> u64 {
+        self.ticks
+    }
+
+    /// The reading of `now()` by which at least `ms` milliseconds will have passed. The current
+    /// tick may be nearly over already, so it doesn't count towards the wait: a deadline can be
+    /// up to two ticks late, but is never early.
+    pub fn deadline(&self, ms: usize) -> u64 {
+        if ms == 0 {
+            return self.ticks;
+        }
+        let ticks = (ms as u64 - 1) / self.tick_ms + 1;
+        self.ticks.saturating_add(ticks).saturating_add(1)
+    }
+}
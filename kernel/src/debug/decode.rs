@@ -0,0 +1,143 @@
+//! Just enough of an RV32IMAC decoder to work out where a thread goes next. The GDB
+//! stub uses this to single-step a thread by planting a temporary breakpoint at the
+//! following instruction, as the CPU has no hardware single-step we can drive.
+
+/// A full-size `ebreak` instruction
+pub const EBREAK: u32 = 0x0010_0073;
+
+/// A compressed `c.ebreak` instruction, for replacing 16-bit instructions
+pub const C_EBREAK: u16 = 0x9002;
+
+/// Return the length, in bytes, of the instruction whose lowest halfword is `low`.
+pub fn instruction_length(low: u16) -> u32 {
+    if low & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
+
+/// Work out the address of the instruction that will run after `insn`, which is
+/// located at `pc`. `regs` holds the thread's general purpose registers; `regs[0]`
+/// is ignored and always read as zero. For 16-bit instructions only the lower half
+/// of `insn` is considered.
+pub fn next_pc(pc: u32, insn: u32, regs: &[u32; 32]) -> u32 {
+    let reg = |r: u32| if r == 0 { 0 } else { regs[r as usize] };
+
+    if instruction_length(insn as u16) == 2 {
+        return next_pc_compressed(pc, insn as u16, reg);
+    }
+
+    let rs1 = (insn >> 15) & 0x1f;
+    let rs2 = (insn >> 20) & 0x1f;
+    let funct3 = (insn >> 12) & 0x7;
+    match insn & 0x7f {
+        // JAL
+        0b110_1111 => pc.wrapping_add(j_imm(insn)),
+        // JALR
+        0b110_0111 => reg(rs1).wrapping_add(i_imm(insn)) & !1,
+        // BRANCH
+        0b110_0011 => {
+            let (a, b) = (reg(rs1), reg(rs2));
+            let taken = match funct3 {
+                0b000 => a == b,
+                0b001 => a != b,
+                0b100 => (a as i32) < (b as i32),
+                0b101 => (a as i32) >= (b as i32),
+                0b110 => a < b,
+                0b111 => a >= b,
+                _ => false,
+            };
+            if taken {
+                pc.wrapping_add(b_imm(insn))
+            } else {
+                pc.wrapping_add(4)
+            }
+        }
+        _ => pc.wrapping_add(4),
+    }
+}
+
+fn next_pc_compressed<F: Fn(u32) -> u32>(pc: u32, insn: u16, reg: F) -> u32 {
+    let insn = insn as u32;
+    let funct3 = (insn >> 13) & 0x7;
+    match (insn & 0b11, funct3) {
+        // C.JAL (RV32 only) and C.J
+        (0b01, 0b001) | (0b01, 0b101) => pc.wrapping_add(cj_imm(insn)),
+        // C.BEQZ and C.BNEZ, which can only test x8-x15
+        (0b01, 0b110) | (0b01, 0b111) => {
+            let zero = reg(8 + ((insn >> 7) & 0x7)) == 0;
+            if zero == (funct3 == 0b110) {
+                pc.wrapping_add(cb_imm(insn))
+            } else {
+                pc.wrapping_add(2)
+            }
+        }
+        // C.JR and C.JALR, as opposed to C.MV, C.ADD and C.EBREAK
+        (0b10, 0b100) => {
+            let rs1 = (insn >> 7) & 0x1f;
+            let rs2 = (insn >> 2) & 0x1f;
+            if rs1 != 0 && rs2 == 0 {
+                reg(rs1) & !1
+            } else {
+                pc.wrapping_add(2)
+            }
+        }
+        _ => pc.wrapping_add(2),
+    }
+}
+
+/// Sign-extend the lowest `bits` bits of `value`
+fn sign_extend(value: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    (((value << shift) as i32) >> shift) as u32
+}
+
+fn i_imm(insn: u32) -> u32 {
+    ((insn as i32) >> 20) as u32
+}
+
+fn b_imm(insn: u32) -> u32 {
+    sign_extend(
+        ((insn >> 19) & 0x1000)
+            | ((insn << 4) & 0x800)
+            | ((insn >> 20) & 0x7e0)
+            | ((insn >> 7) & 0x1e),
+        13,
+    )
+}
+
+fn j_imm(insn: u32) -> u32 {
+    sign_extend(
+        ((insn >> 11) & 0x10_0000)
+            | (insn & 0xf_f000)
+            | ((insn >> 9) & 0x800)
+            | ((insn >> 20) & 0x7fe),
+        21,
+    )
+}
+
+fn cj_imm(insn: u32) -> u32 {
+    sign_extend(
+        ((insn >> 1) & 0x800)
+            | ((insn >> 7) & 0x10)
+            | ((insn >> 1) & 0x300)
+            | ((insn << 2) & 0x400)
+            | ((insn >> 1) & 0x40)
+            | ((insn << 1) & 0x80)
+            | ((insn >> 2) & 0xe)
+            | ((insn << 3) & 0x20),
+        12,
+    )
+}
+
+fn cb_imm(insn: u32) -> u32 {
+    sign_extend(
+        ((insn >> 4) & 0x100)
+            | ((insn >> 7) & 0x18)
+            | ((insn << 1) & 0xc0)
+            | ((insn >> 2) & 0x6)
+            | ((insn << 3) & 0x20),
+        9,
+    )
+}
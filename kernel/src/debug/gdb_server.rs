@@ -6,10 +6,91 @@ use gdbstub::{DisconnectReason, GdbStubError};
 
 use gdbstub::state_machine::GdbStubStateMachine;
 use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::WatchKind;
 use gdbstub::target::{Target, TargetResult};
 
+use super::decode;
+use crate::arch::mem::MMUFlags;
+
+/// The number of data watchpoints that may be set at once
+const MAX_WATCHPOINTS: usize = 4;
+
+/// GDB doesn't tell us how much memory a watchpoint covers, so watch one word
+const WATCH_LEN: u32 = 4;
+
+/// A data watchpoint. These are implemented by taking away the permissions
+/// of the page the address lives on, and checking the page faults that result.
+#[derive(Copy, Clone)]
+struct Watchpoint {
+    addr: u32,
+    kind: WatchKind,
+    /// Read/write permissions of the page before any watchpoint was applied
+    original: MMUFlags,
+}
+
+impl Watchpoint {
+    fn page(&self) -> usize {
+        self.addr as usize & !0xfff
+    }
+
+    /// Permissions to take away from the page. A page can't be writable
+    /// without also being readable, so watching reads removes both.
+    fn mask(&self) -> MMUFlags {
+        match self.kind {
+            WatchKind::Write => MMUFlags::W,
+            WatchKind::Read | WatchKind::ReadWrite => MMUFlags::R | MMUFlags::W,
+        }
+    }
+
+    fn matches(&self, addr: u32, is_store: bool) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Write => is_store,
+            WatchKind::Read => !is_store,
+            WatchKind::ReadWrite => true,
+        };
+        kind_matches && addr >= self.addr && addr < self.addr + WATCH_LEN
+    }
+}
+
+/// Why a thread is being stepped over a single instruction
+#[derive(Copy, Clone)]
+enum StepReason {
+    /// GDB asked for a single step
+    Gdb,
+    /// The instruction accessed a watched address. Report it once the access is done.
+    Watch(WatchKind, u32),
+    /// The instruction accessed a watched page, but not a watched address
+    Silent,
+}
+
+/// A temporary breakpoint planted on the instruction following the one being stepped
+#[derive(Copy, Clone)]
+struct Step {
+    tid: xous_kernel::TID,
+    addr: u32,
+    original: [u8; 4],
+    len: usize,
+    reason: StepReason,
+}
+
+/// What the trap handler should do once the debugger has looked at an exception
+pub enum DebugTrap {
+    /// The debugger isn't interested; handle the exception as usual
+    NotHandled,
+    /// The debugger dealt with the exception, so resume the thread
+    Resume,
+    /// Suspend the process; GDB has been told why it stopped
+    Stop,
+}
+
 pub struct XousTarget {
     pid: Option<xous_kernel::PID>,
+    /// Thread that GDB asked to step on the next resume
+    step_tid: Option<xous_kernel::TID>,
+    /// Thread that most recently stopped, used when GDB steps without naming one
+    stopped_tid: Option<xous_kernel::TID>,
+    step: Option<Step>,
+    watchpoints: [Option<Watchpoint>; MAX_WATCHPOINTS],
 }
 pub struct XousDebugState<'a> {
     pub target: XousTarget,
@@ -25,11 +106,156 @@ impl XousTarget {
             // pid: Some(crate::services::SystemServices::with_mut(
             //     |system_services| system_services.current_pid(),
             pid: xous_kernel::PID::new(2),
+            step_tid: None,
+            stopped_tid: None,
+            step: None,
+            watchpoints: [None; MAX_WATCHPOINTS],
         }
     }
     pub fn pid(&self) -> &Option<xous_kernel::PID> {
         &self.pid
     }
+
+    /// Take away page permissions for every watchpoint. The debugged process must be active.
+    fn protect_watchpoints(&self) {
+        for wp in self.watchpoints.iter().flatten() {
+            let mut rw = wp.original;
+            for other in self.watchpoints.iter().flatten() {
+                if other.page() == wp.page() {
+                    rw &= !other.mask();
+                }
+            }
+            crate::arch::mem::set_page_rw(wp.page(), rw).ok();
+        }
+    }
+
+    /// Give watched pages their permissions back. The debugged process must be active.
+    fn unprotect_watchpoints(&self) {
+        for wp in self.watchpoints.iter().flatten() {
+            crate::arch::mem::set_page_rw(wp.page(), wp.original).ok();
+        }
+    }
+
+    /// Plant a temporary breakpoint on whatever runs after the instruction at `pc`.
+    /// The debugged process must be active, and its watched pages unprotected.
+    fn plant_step(
+        &mut self,
+        tid: xous_kernel::TID,
+        pc: u32,
+        regs: &[u32; 32],
+        reason: StepReason,
+    ) -> Result<(), &'static str> {
+        let low = peek_u16(pc).ok_or("couldn't read instruction")?;
+        let insn = if decode::instruction_length(low) == 4 {
+            let high = peek_u16(pc + 2).ok_or("couldn't read instruction")?;
+            (low as u32) | (high as u32) << 16
+        } else {
+            low as u32
+        };
+        let addr = decode::next_pc(pc, insn, regs);
+
+        let len =
+            decode::instruction_length(peek_u16(addr).ok_or("couldn't read next instruction")?)
+                as usize;
+        let mut original = [0u8; 4];
+        for (offset, b) in original[..len].iter_mut().enumerate() {
+            *b = crate::arch::mem::peek_memory((addr as usize + offset) as *mut u8)
+                .or(Err("couldn't read next instruction"))?;
+        }
+        let ebreak = if len == 4 {
+            decode::EBREAK.to_le_bytes()
+        } else {
+            let [lo, hi] = decode::C_EBREAK.to_le_bytes();
+            [lo, hi, 0, 0]
+        };
+        for (offset, b) in ebreak[..len].iter().enumerate() {
+            crate::arch::mem::poke_memory((addr as usize + offset) as *mut u8, *b)
+                .or(Err("couldn't plant temporary breakpoint"))?;
+        }
+        crate::arch::mem::sync_instructions();
+
+        self.step = Some(Step {
+            tid,
+            addr,
+            original,
+            len,
+            reason,
+        });
+        Ok(())
+    }
+
+    /// Put back the instruction a temporary breakpoint replaced. The debugged process must be active.
+    fn remove_step(&mut self) -> Option<Step> {
+        let step = self.step.take()?;
+        for (offset, b) in step.original[..step.len].iter().enumerate() {
+            crate::arch::mem::poke_memory((step.addr as usize + offset) as *mut u8, *b).ok();
+        }
+        crate::arch::mem::sync_instructions();
+        Some(step)
+    }
+
+    /// Arrange for `tid` to stop after it executes its next instruction
+    fn step_thread(&mut self, tid: xous_kernel::TID) -> Result<(), &'static str> {
+        let pid = self.pid.ok_or("no process being debugged")?;
+        with_process(pid, || {
+            let regs = thread_registers(tid);
+            self.unprotect_watchpoints();
+            self.remove_step();
+            let result = self.plant_step(tid, regs[0], &regs, StepReason::Gdb);
+            self.protect_watchpoints();
+            result
+        })
+    }
+
+    /// Undo any changes made to the debugged process, e.g. when GDB goes away
+    fn release(&mut self) {
+        if let Some(pid) = self.pid {
+            with_process(pid, || {
+                self.unprotect_watchpoints();
+                self.remove_step();
+            });
+        }
+        self.watchpoints = [None; MAX_WATCHPOINTS];
+    }
+}
+
+/// Run `f` with the address space of `pid` active, then switch back
+fn with_process<R>(pid: xous_kernel::PID, f: impl FnOnce() -> R) -> R {
+    crate::services::SystemServices::with(|system_services| {
+        let current_pid = system_services.current_pid();
+        system_services
+            .get_process(pid)
+            .unwrap()
+            .activate()
+            .unwrap();
+        let result = f();
+        system_services
+            .get_process(current_pid)
+            .unwrap()
+            .activate()
+            .unwrap();
+        result
+    })
+}
+
+/// Read a halfword from the current address space, which needn't be aligned
+fn peek_u16(addr: u32) -> Option<u16> {
+    let lo = crate::arch::mem::peek_memory(addr as *mut u8).ok()?;
+    let hi = crate::arch::mem::peek_memory((addr + 1) as *mut u8).ok()?;
+    Some(u16::from_le_bytes([lo, hi]))
+}
+
+/// Registers of a thread in the current process, laid out as the decoder wants them.
+/// `x0` always reads as zero, so its slot holds the program counter instead.
+fn thread_registers(tid: xous_kernel::TID) -> [u32; 32] {
+    let process = crate::arch::process::Process::current();
+    let thread = process.thread(tid);
+    let mut regs = [0u32; 32];
+    regs[0] = thread.sepc as u32;
+    for (reg, thr_reg) in regs[1..].iter_mut().zip(thread.registers.iter()) {
+        *reg = *thr_reg as u32;
+    }
+    regs
 }
 
 impl Target for XousTarget {
@@ -50,11 +276,16 @@ impl MultiThreadOps for XousTarget {
         _gdb_interrupt: GdbInterrupt<'_>,
     ) -> Result<Option<ThreadStopReason<u32>>, Self::Error> {
         unsafe { HALTED = false };
-        match default_resume_action {
-            ResumeAction::Step | ResumeAction::StepWithSignal(_) => {
-                return Err("single-stepping not supported")
-            }
-            _ => (),
+        let step_tid = match default_resume_action {
+            ResumeAction::Step | ResumeAction::StepWithSignal(_) => Some(
+                self.step_tid
+                    .or(self.stopped_tid)
+                    .ok_or("no thread to step")?,
+            ),
+            _ => self.step_tid,
+        };
+        if let Some(tid) = step_tid {
+            self.step_thread(tid)?;
         }
 
         crate::services::SystemServices::with_mut(|system_services| {
@@ -64,13 +295,19 @@ impl MultiThreadOps for XousTarget {
     }
 
     fn clear_resume_actions(&mut self) -> Result<(), Self::Error> {
+        self.step_tid = None;
         Ok(())
     }
 
-    fn set_resume_action(&mut self, _tid: Tid, action: ResumeAction) -> Result<(), Self::Error> {
+    fn set_resume_action(&mut self, tid: Tid, action: ResumeAction) -> Result<(), Self::Error> {
         match action {
+            // Only one thread can be stepped at a time, as there's only one temporary breakpoint
             ResumeAction::Step | ResumeAction::StepWithSignal(_) => {
-                Err("single-stepping resume action not supported")
+                if self.step_tid.is_some() {
+                    return Err("only one thread may be stepped at a time");
+                }
+                self.step_tid = Some(tid.get());
+                Ok(())
             }
             ResumeAction::Continue | ResumeAction::ContinueWithSignal(_) => Ok(()),
         }
@@ -160,10 +397,13 @@ impl MultiThreadOps for XousTarget {
                 .unwrap()
                 .activate()
                 .unwrap();
+            // The kernel can't read watched pages either, so lift the protection for now
+            self.unprotect_watchpoints();
             data.iter_mut().for_each(|b| {
                 *b = crate::arch::mem::peek_memory(current_addr as *mut u8).unwrap_or(0xff);
                 current_addr += 1;
             });
+            self.protect_watchpoints();
 
             // Restore the previous PID
             system_services
@@ -194,6 +434,7 @@ impl MultiThreadOps for XousTarget {
                 .unwrap()
                 .activate()
                 .unwrap();
+            self.unprotect_watchpoints();
             data.iter().for_each(|b| {
                 if let Err(_e) = crate::arch::mem::poke_memory(current_addr as *mut u8, *b) {
                     panic!("couldn't poke memory: {:?}", _e);
@@ -201,6 +442,8 @@ impl MultiThreadOps for XousTarget {
                 }
                 current_addr += 1;
             });
+            crate::arch::mem::sync_instructions();
+            self.protect_watchpoints();
 
             // Restore the previous PID
             system_services
@@ -250,6 +493,12 @@ impl gdbstub::target::ext::breakpoints::Breakpoints for XousTarget {
     ) -> Option<gdbstub::target::ext::breakpoints::HwBreakpointOps<Self>> {
         Some(self)
     }
+
+    fn hw_watchpoint(
+        &mut self,
+    ) -> Option<gdbstub::target::ext::breakpoints::HwWatchpointOps<Self>> {
+        Some(self)
+    }
 }
 
 impl gdbstub::target::ext::breakpoints::HwBreakpoint for XousTarget {
@@ -262,6 +511,201 @@ impl gdbstub::target::ext::breakpoints::HwBreakpoint for XousTarget {
     }
 }
 
+impl gdbstub::target::ext::breakpoints::HwWatchpoint for XousTarget {
+    fn add_hw_watchpoint(&mut self, addr: u32, kind: WatchKind) -> TargetResult<bool, Self> {
+        let slot = match self.watchpoints.iter().position(|wp| wp.is_none()) {
+            Some(slot) => slot,
+            None => return Ok(false),
+        };
+        let pid = self.pid.unwrap();
+        Ok(with_process(pid, || {
+            self.unprotect_watchpoints();
+            let added = match crate::arch::mem::page_rw(addr as usize) {
+                Ok(original) => {
+                    self.watchpoints[slot] = Some(Watchpoint {
+                        addr,
+                        kind,
+                        original,
+                    });
+                    true
+                }
+                // The page isn't backed by anything yet, so there's nothing to protect
+                Err(_) => false,
+            };
+            self.protect_watchpoints();
+            added
+        }))
+    }
+
+    fn remove_hw_watchpoint(&mut self, addr: u32, kind: WatchKind) -> TargetResult<bool, Self> {
+        let slot = match self
+            .watchpoints
+            .iter()
+            .position(|wp| matches!(wp, Some(wp) if wp.addr == addr && wp.kind == kind))
+        {
+            Some(slot) => slot,
+            None => return Ok(false),
+        };
+        let pid = self.pid.unwrap();
+        with_process(pid, || {
+            self.unprotect_watchpoints();
+            self.watchpoints[slot] = None;
+            self.protect_watchpoints();
+        });
+        Ok(true)
+    }
+}
+
+/// Take the debugger state, but only if `pid` is the process being debugged
+fn take_state_for(pid: xous_kernel::PID) -> Option<XousDebugState<'static>> {
+    let state = unsafe { GDB_STATE.take() }?;
+    if state.target.pid == Some(pid) {
+        Some(state)
+    } else {
+        unsafe { GDB_STATE = Some(state) };
+        None
+    }
+}
+
+/// Tell GDB why the target stopped, if it is waiting to hear about it, and put the
+/// debugger state back.
+fn report_stop(state: XousDebugState<'static>, reason: ThreadStopReason<u32>) {
+    let XousDebugState { mut target, server } = state;
+    let server = match server {
+        GdbStubStateMachine::DeferredStopReason(gdb_state) => {
+            match gdb_state.deferred_stop_reason(&mut target, reason) {
+                Ok((gdb, None)) => gdb,
+                Ok((_, Some(disconnect_reason))) => {
+                    target.release();
+                    cleanup();
+                    println!("client disconnected: {:?}", disconnect_reason);
+                    return;
+                }
+                Err(e) => {
+                    target.release();
+                    cleanup();
+                    println!("deferred_stop_reason_error: {:?}", e);
+                    return;
+                }
+            }
+        }
+        // GDB isn't waiting on the target, so there's nobody to tell
+        server => server,
+    };
+    unsafe {
+        HALTED = true;
+        GDB_STATE = Some(XousDebugState { target, server });
+    }
+}
+
+/// Called by the trap handler when thread `tid` of `pid` executes an `ebreak`
+/// at `epc`. The process must be active.
+pub fn breakpoint_hit(pid: xous_kernel::PID, tid: xous_kernel::TID, epc: usize) -> DebugTrap {
+    let mut state = match take_state_for(pid) {
+        Some(state) => state,
+        None => return DebugTrap::NotHandled,
+    };
+    state.target.stopped_tid = Some(tid);
+
+    let step = match state.target.step {
+        Some(step) if step.addr as usize == epc => state.target.remove_step().unwrap(),
+        // Not one of ours, so it's either a breakpoint GDB wrote into memory or an
+        // `ebreak` in the program itself. Either way, GDB expects a SIGTRAP.
+        _ => {
+            report_stop(state, ThreadStopReason::Signal(5));
+            return DebugTrap::Stop;
+        }
+    };
+    // If a different thread got here first, it stops in place of the one being
+    // stepped, as the temporary breakpoint is shared by every thread.
+    state.target.protect_watchpoints();
+    match step.reason {
+        StepReason::Gdb => {
+            report_stop(state, ThreadStopReason::DoneStep);
+            DebugTrap::Stop
+        }
+        StepReason::Watch(kind, addr) => {
+            report_stop(
+                state,
+                ThreadStopReason::Watch {
+                    tid: Tid::new(step.tid).unwrap(),
+                    kind,
+                    addr,
+                },
+            );
+            DebugTrap::Stop
+        }
+        StepReason::Silent => {
+            unsafe { GDB_STATE = Some(state) };
+            DebugTrap::Resume
+        }
+    }
+}
+
+/// Called by the trap handler when thread `tid` of `pid` takes a page fault on
+/// `addr` while executing the instruction at `epc`. The process must be active.
+///
+/// If the page is being watched, the instruction is allowed to complete by lifting
+/// the protection and stepping over it. Watchpoint hits are reported to GDB once
+/// the step is done, as GDB expects the access to have happened by then.
+pub fn watch_fault(
+    pid: xous_kernel::PID,
+    tid: xous_kernel::TID,
+    epc: usize,
+    addr: usize,
+    is_store: bool,
+) -> DebugTrap {
+    let mut state = match take_state_for(pid) {
+        Some(state) => state,
+        None => return DebugTrap::NotHandled,
+    };
+    let target = &mut state.target;
+    if !target
+        .watchpoints
+        .iter()
+        .flatten()
+        .any(|wp| wp.page() == addr & !0xfff)
+    {
+        unsafe { GDB_STATE = Some(state) };
+        return DebugTrap::NotHandled;
+    }
+
+    let reason = match target
+        .watchpoints
+        .iter()
+        .flatten()
+        .find(|wp| wp.matches(addr as u32, is_store))
+    {
+        Some(wp) => StepReason::Watch(wp.kind, addr as u32),
+        None => StepReason::Silent,
+    };
+
+    target.unprotect_watchpoints();
+    let result = match target.step.as_mut() {
+        // A step is already under way; its breakpoint will put the protection back.
+        Some(step) => {
+            if let StepReason::Watch(..) = reason {
+                step.reason = reason;
+            }
+            Ok(())
+        }
+        None => {
+            let regs = thread_registers(tid);
+            target.plant_step(tid, epc as u32, &regs, reason)
+        }
+    };
+
+    if result.is_err() {
+        // Without a way to step over the access, the thread would fault forever
+        target.protect_watchpoints();
+        target.stopped_tid = Some(tid);
+        report_stop(state, ThreadStopReason::Signal(5));
+        return DebugTrap::Stop;
+    }
+    unsafe { GDB_STATE = Some(state) };
+    DebugTrap::Resume
+}
+
 pub fn handle(b: u8) -> bool {
     if let Some(XousDebugState {
         mut target,
@@ -282,6 +726,7 @@ pub fn handle(b: u8) -> bool {
                     gdb
                 }
                 Ok((_, Some(_disconnect_reason))) => {
+                    target.release();
                     cleanup();
                     match _disconnect_reason {
                         DisconnectReason::Disconnect => println!("GDB Disconnected"),
@@ -292,11 +737,13 @@ pub fn handle(b: u8) -> bool {
                     return true;
                 }
                 Err(GdbStubError::TargetError(e)) => {
+                    target.release();
                     cleanup();
                     println!("Target raised a fatal error: {}", e);
                     return true;
                 }
                 Err(e) => {
+                    target.release();
                     cleanup();
                     println!("gdbstub internal error: {}", e);
                     return true;
@@ -315,11 +762,13 @@ pub fn handle(b: u8) -> bool {
                         gdb
                     }
                     Ok((_, Some(disconnect_reason))) => {
+                        target.release();
                         cleanup();
                         println!("client disconnected: {:?}", disconnect_reason);
                         return true;
                     }
                     Err(e) => {
+                        target.release();
                         cleanup();
                         println!("deferred_stop_reason_error: {:?}", e);
                         return true;
@@ -118,7 +118,10 @@ impl Uart {
 }
 
 #[cfg(all(feature = "gdbserver", baremetal))]
-mod gdb_server;
+pub mod gdb_server;
+
+#[cfg(any(test, all(feature = "gdbserver", baremetal)))]
+pub mod decode;
 
 #[cfg(all(feature = "gdbserver", baremetal))]
 impl gdbstub::Connection for Uart {
@@ -272,6 +275,25 @@ fn process_irq_character(b: u8) {
             });
             println!("{} k total", total_bytes / 1024);
         }
+        b'c' => {
+            println!("CPU usage (Mcycles, context switches):");
+            crate::services::SystemServices::with(|system_services| {
+                for process in &system_services.processes {
+                    if process.free() {
+                        continue;
+                    }
+                    if let Ok(stats) = system_services.process_stats(process.pid) {
+                        println!(
+                            "    PID {:>3}: {:>8} M {:>8} {}",
+                            process.pid,
+                            stats.runtime / 1_000_000,
+                            stats.switches,
+                            system_services.process_name(process.pid).unwrap_or("")
+                        );
+                    }
+                }
+            });
+        }
         #[cfg(all(feature = "gdbserver", baremetal))]
         b'g' => {
             println!("Starting GDB server -- attach your debugger now");
@@ -281,6 +303,7 @@ fn process_irq_character(b: u8) {
             println!("Xous Kernel Debug");
             println!("key | command");
             println!("--- + -----------------------");
+            println!(" c  | report CPU time used by all processes");
             #[cfg(all(feature = "gdbserver", baremetal))]
             println!(" g  | enter the gdb server");
             println!(" i  | print irq handlers");
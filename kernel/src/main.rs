@@ -16,6 +16,9 @@ mod test;
 
 mod arch;
 
+#[cfg(any(baremetal, test))]
+mod clock;
+
 #[macro_use]
 mod args;
 mod irq;
@@ -105,6 +108,8 @@ pub unsafe extern "C" fn init(arg_offset: *const u32, init_offset: *const u32, r
 }
 
 /// Loop through the SystemServices list to determine the next PID to be run.
+/// Processes with higher-priority threads waiting go first, and processes of
+/// the same priority are run round-robin.
 /// If no process is ready, return `None`.
 fn next_pid_to_run(last_pid: Option<PID>) -> Option<PID> {
     // PIDs are 1-indexed but arrays are 0-indexed.  By not subtracting
@@ -113,33 +118,20 @@ fn next_pid_to_run(last_pid: Option<PID>) -> Option<PID> {
     let current_pid = last_pid.unwrap_or(unsafe { PID::new_unchecked(1) }).get() as usize;
 
     SystemServices::with(|system_services| {
-        for test_idx in current_pid..system_services.processes.len() {
-            if system_services.processes[test_idx].ppid.get() == 1 {
-                // print!("PID {} is owned by PID1... ", test_idx + 1);
-                if system_services.processes[test_idx].runnable() {
-                    // println!(" and is runnable");
-                    return match pid_from_usize(test_idx + 1) {
-                        Ok(x) => Some(x),
-                        Err(_) => None,
-                    };
-                }
-                // println!(" and is NOT RUNNABLE");
-            }
-        }
-        for test_idx in 0..current_pid {
-            if system_services.processes[test_idx].ppid.get() == 1 {
-                // print!("PID {} is owned by PID1... ", test_idx + 1);
-                if system_services.processes[test_idx].runnable() {
-                    // println!(" and is runnable");
-                    return match pid_from_usize(test_idx + 1) {
-                        Ok(x) => Some(x),
-                        Err(_) => None,
-                    };
+        let process_count = system_services.processes.len();
+        let mut next: Option<(ThreadPriority, usize)> = None;
+        for test_idx in (current_pid..process_count).chain(0..current_pid) {
+            let process = &system_services.processes[test_idx];
+            if process.ppid.get() == 1 && process.runnable() {
+                // Only replace an earlier candidate if this one is more urgent,
+                // so that equal priorities keep their round-robin order.
+                let priority = process.priority();
+                if next.map(|(p, _)| priority > p).unwrap_or(true) {
+                    next = Some((priority, test_idx));
                 }
-                // println!(" and is NOT RUNNABLE");
             }
         }
-        None
+        next.and_then(|(_, test_idx)| pid_from_usize(test_idx + 1).ok())
     })
 }
 
@@ -160,6 +152,9 @@ pub extern "C" fn kmain() {
     }
 
     loop {
+        // Wake any threads that have given up waiting for a message, so
+        // they can be scheduled below.
+        SystemServices::with_mut(|ss| ss.expire_timeouts());
         pid = next_pid_to_run(pid);
 
         match pid {
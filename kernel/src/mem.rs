@@ -192,6 +192,13 @@ impl MemoryManager {
         owned_bytes
     }
 
+    /// Hosted processes get their memory from the host, so none of it is
+    /// tracked by the kernel.
+    #[cfg(not(baremetal))]
+    pub fn ram_used_by(&self, _pid: PID) -> usize {
+        0
+    }
+
     #[cfg(all(baremetal, feature = "print-debug"))]
     pub fn print_ownership(&self) {
         println!("Ownership ({} bytes in all):", unsafe {
@@ -456,6 +456,7 @@ impl Server {
 
         // Finally, wake up all threads that are waiting on this Server.
         while let Some(server_tid) = self.take_available_thread() {
+            ss.cancel_timeout(server_pid, server_tid);
             ss.ready_thread(server_pid, server_tid).unwrap();
             ss.set_thread_result(
                 server_pid,
@@ -1202,4 +1203,13 @@ impl Server {
         self.ready_threads |= 1 << tid;
         klog!("ready threads now: {:08b}", self.ready_threads);
     }
+
+    /// Remove the given context from the list of waiting contexts, for example
+    /// because it stopped waiting after a timeout. Returns `false` if the
+    /// context wasn't waiting.
+    pub fn unpark_thread(&mut self, tid: TID) -> bool {
+        let was_parked = self.ready_threads & (1 << tid) != 0;
+        self.ready_threads &= !(1 << tid);
+        was_parked
+    }
 }
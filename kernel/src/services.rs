@@ -13,12 +13,14 @@ use crate::filled_array;
 use crate::server::Server;
 // use core::mem;
 use xous_kernel::{
-    pid_from_usize, Error, MemoryAddress, Message, ProcessInit, ThreadInit, CID, PID, SID, TID,
+    pid_from_usize, Error, MemoryAddress, Message, ProcessInit, ProcessStats, ThreadInit,
+    ThreadPriority, ThreadStats, CID, PID, SID, TID,
 };
 
 const MAX_SERVER_COUNT: usize = 128;
+const MAX_TIMEOUT_COUNT: usize = 32;
 
-pub use crate::arch::process::{INITIAL_TID, MAX_PROCESS_COUNT};
+pub use crate::arch::process::{INITIAL_TID, MAX_PROCESS_COUNT, MAX_TID};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct ExceptionHandler {
@@ -43,6 +45,56 @@ pub struct SystemServices {
 
     /// A table of all servers in the system
     servers: [Option<Server>; MAX_SERVER_COUNT],
+
+    /// The value of `arch::cpu_ticks()` the last time the kernel was entered
+    last_tick: u64,
+
+    /// The process and thread that entered the kernel last
+    last_running: Option<(PID, TID)>,
+
+    /// Threads that have asked to stop waiting at a given time
+    timeouts: [Option<Timeout>; MAX_TIMEOUT_COUNT],
+}
+
+/// A thread that gives up waiting once `arch::timeout_clock()` reaches `deadline`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Timeout {
+    pid: PID,
+    tid: TID,
+    deadline: u64,
+
+    /// The server the thread is parked on, or `None` if the thread is
+    /// retrying a send to a full server and will check the deadline itself.
+    sidx: Option<usize>,
+}
+
+/// CPU time charged to a process and to each of its threads, measured in
+/// `arch::cpu_ticks()`.
+#[derive(Copy, Clone, PartialEq)]
+struct CpuTime {
+    /// Ticks spent in this process, including threads that have since exited
+    runtime: u64,
+
+    /// Number of times the kernel switched to this process
+    switches: u32,
+
+    /// Ticks spent in each thread. Cleared when the thread is created.
+    thread_runtime: [u64; MAX_TID + 1],
+
+    /// Number of times the kernel switched to each thread. Cleared when the
+    /// thread is created.
+    thread_switches: [u32; MAX_TID + 1],
+}
+
+impl CpuTime {
+    const fn new() -> CpuTime {
+        CpuTime {
+            runtime: 0,
+            switches: 0,
+            thread_runtime: [0; MAX_TID + 1],
+            thread_switches: [0; MAX_TID + 1],
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -134,6 +186,12 @@ pub struct Process {
 
     /// When an exception is hit, the kernel will switch to this Thread.
     exception_handler: Option<ExceptionHandler>,
+
+    /// How much CPU time this process and its threads have used
+    cpu_time: CpuTime,
+
+    /// The scheduling priority of each thread
+    thread_priority: [ThreadPriority; MAX_TID + 1],
 }
 
 impl Default for Process {
@@ -214,6 +272,40 @@ impl Process {
         matches!(self.state, ProcessState::Free)
     }
 
+    /// The highest priority of any thread in this process that is waiting
+    /// to run. Processes that are just starting up or handling an exception
+    /// run at the default priority.
+    pub fn priority(&self) -> ThreadPriority {
+        match self.state {
+            ProcessState::Ready(x) | ProcessState::Running(x) => (0..=MAX_TID)
+                .filter(|&tid| x & (1 << tid) != 0)
+                .map(|tid| self.thread_priority[tid])
+                .max()
+                .unwrap_or_default(),
+            _ => ThreadPriority::default(),
+        }
+    }
+
+    /// Pick which of the `ready` threads should run next. This is the one
+    /// with the highest priority, with threads of equal priority taking
+    /// turns in TID order starting after `after`.
+    fn next_thread(&self, ready: usize, after: TID) -> Option<TID> {
+        let mut next: Option<TID> = None;
+        for offset in 1..=MAX_TID + 1 {
+            let tid = (after + offset) % (MAX_TID + 1);
+            if ready & (1 << tid) == 0 {
+                continue;
+            }
+            if next
+                .map(|n| self.thread_priority[tid] > self.thread_priority[n])
+                .unwrap_or(true)
+            {
+                next = Some(tid);
+            }
+        }
+        next
+    }
+
     pub fn activate(&self) -> Result<(), xous_kernel::Error> {
         crate::arch::process::set_current_pid(self.pid);
         self.mapping.activate()?;
@@ -251,10 +343,15 @@ std::thread_local!(static SYSTEM_SERVICES: core::cell::RefCell<SystemServices> =
         current_thread: 0_usize,
         previous_thread: INITIAL_TID as TID,
         exception_handler: None,
+        cpu_time: CpuTime::new(),
+        thread_priority: [ThreadPriority::Normal; MAX_TID + 1],
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
     servers: filled_array![None; 128],
+    last_tick: 0,
+    last_running: None,
+    timeouts: [None; MAX_TIMEOUT_COUNT],
 }));
 
 #[cfg(baremetal)]
@@ -267,10 +364,15 @@ static mut SYSTEM_SERVICES: SystemServices = SystemServices {
         current_thread: 0_usize,
         previous_thread: INITIAL_TID as TID,
         exception_handler: None,
+        cpu_time: CpuTime::new(),
+        thread_priority: [ThreadPriority::Normal; MAX_TID + 1],
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
     servers: filled_array![None; 128],
+    last_tick: 0,
+    last_running: None,
+    timeouts: [None; MAX_TIMEOUT_COUNT],
 };
 
 impl core::fmt::Debug for Process {
@@ -396,6 +498,8 @@ impl SystemServices {
             entry.state = ProcessState::Allocated;
             entry.ppid = ppid;
             entry.pid = new_pid;
+            entry.cpu_time = CpuTime::new();
+            entry.thread_priority = [ThreadPriority::Normal; MAX_TID + 1];
             return Ok(new_pid);
         }
         Err(xous_kernel::Error::ProcessNotFound)
@@ -719,17 +823,9 @@ impl SystemServices {
             }
             ProcessState::Ready(x) => {
                 let new_thread = match tid {
-                    None => {
-                        let mut new_context = 0;
-
-                        while x & (1 << new_context) == 0 {
-                            new_context += 1;
-                            if new_context > arch::process::MAX_THREAD {
-                                new_context = 0;
-                            }
-                        }
-                        new_context
-                    }
+                    None => process
+                        .next_thread(x, MAX_TID)
+                        .expect("ready process had no threads"),
                     Some(ctx) => {
                         // Ensure the specified context is ready to run
                         if x & (1 << ctx) == 0 {
@@ -760,17 +856,9 @@ impl SystemServices {
                 let mut p = ArchProcess::current();
                 // let current_thread = p.current_thread();
                 let new_thread = match tid {
-                    None => {
-                        let mut new_thread = 0;
-
-                        while ready_threads & (1 << new_thread) == 0 {
-                            new_thread += 1;
-                            if new_thread > arch::process::MAX_THREAD {
-                                new_thread = 0;
-                            }
-                        }
-                        new_thread
-                    }
+                    None => process
+                        .next_thread(ready_threads, MAX_TID)
+                        .expect("running process had no ready threads"),
                     Some(tid) => {
                         // Ensure the specified context is ready to run, or is
                         // currently running.
@@ -933,26 +1021,18 @@ impl SystemServices {
                     // new.current_thread = new_tid;
                 }
                 ProcessState::Running(x) | ProcessState::Ready(x) => {
-                    // If no new context is specified, take the previous
-                    // context.  If that is not runnable, do a round-robin
-                    // search for the next available context.
+                    // If no new context is specified, pick the ready context
+                    // with the highest priority, doing a round-robin search
+                    // among contexts of the same priority.
                     assert!(
                         x != 0,
                         "process was {:?} but had no free contexts",
                         new.state
                     );
                     if new_tid == 0 {
-                        new_tid = (new.current_thread + 1) as usize;
-                        while x & (1 << new_tid) == 0 {
-                            new_tid += 1;
-                            if new_tid > arch::process::MAX_THREAD {
-                                new_tid = 0;
-                            } else if new_tid == (new.current_thread + 1) as usize {
-                                // If we've looped around, return an error.
-                                // println!("Looked through all contexts and couldn't find one that was ready");
-                                return Err(xous_kernel::Error::ProcessNotFound);
-                            }
-                        }
+                        new_tid = new
+                            .next_thread(x, new.current_thread)
+                            .ok_or(xous_kernel::Error::ProcessNotFound)?;
                         new.current_thread = new_tid as _;
                         klog!("picked thread ID {}", new_tid);
                     } else if x & (1 << new_tid) == 0 {
@@ -1516,6 +1596,11 @@ impl SystemServices {
             .ok_or(xous_kernel::Error::ThreadNotAvailable)?;
 
         arch_process.setup_thread(new_tid, thread_init)?;
+        if new_tid < process.cpu_time.thread_runtime.len() {
+            process.cpu_time.thread_runtime[new_tid] = 0;
+            process.cpu_time.thread_switches[new_tid] = 0;
+            process.thread_priority[new_tid] = ThreadPriority::Normal;
+        }
 
         // println!("KERNEL({}): Created new thread {}", pid, new_tid);
 
@@ -2076,6 +2161,13 @@ impl SystemServices {
             }
         }
 
+        // Nothing is left to wake up once the process is gone.
+        for timeout in self.timeouts.iter_mut() {
+            if matches!(timeout, Some(t) if t.pid == target_pid) {
+                *timeout = None;
+            }
+        }
+
         let process = self.get_process_mut(target_pid)?;
         process.activate()?;
         let parent_pid = process.ppid;
@@ -2279,4 +2371,205 @@ impl SystemServices {
         }
         None
     }
+
+    /// Charge the time since the kernel was last entered to the given
+    /// thread, which has just trapped into the kernel. This must be called
+    /// on every kernel entry. Time spent idle is charged to the kernel (PID 1).
+    pub fn account_cpu_time(&mut self, pid: PID, tid: TID) {
+        let now = arch::cpu_ticks();
+        let elapsed = now.wrapping_sub(self.last_tick);
+        self.last_tick = now;
+
+        let switched_process = !matches!(self.last_running, Some((p, _)) if p == pid);
+        let switched_thread = self.last_running != Some((pid, tid));
+        self.last_running = Some((pid, tid));
+
+        let cpu_time = &mut self.processes[pid.get() as usize - 1].cpu_time;
+        cpu_time.runtime = cpu_time.runtime.wrapping_add(elapsed);
+        if switched_process {
+            cpu_time.switches = cpu_time.switches.wrapping_add(1);
+        }
+        if let Some(runtime) = cpu_time.thread_runtime.get_mut(tid) {
+            *runtime = runtime.wrapping_add(elapsed);
+            if switched_thread {
+                cpu_time.thread_switches[tid] = cpu_time.thread_switches[tid].wrapping_add(1);
+            }
+        }
+    }
+
+    /// Arrange for the given thread to stop waiting `ms` milliseconds from
+    /// now, replacing any timeout it already had. `sidx` is the server the
+    /// thread is about to park on, if any. Returns the deadline.
+    ///
+    /// # Errors
+    ///
+    /// * **OutOfMemory**: Too many threads are already waiting with a timeout
+    pub fn add_timeout(
+        &mut self,
+        pid: PID,
+        tid: TID,
+        sidx: Option<usize>,
+        ms: usize,
+    ) -> Result<u64, xous_kernel::Error> {
+        self.cancel_timeout(pid, tid);
+        let deadline = arch::timeout_deadline(ms);
+        let slot = self
+            .timeouts
+            .iter_mut()
+            .find(|t| t.is_none())
+            .ok_or(xous_kernel::Error::OutOfMemory)?;
+        *slot = Some(Timeout {
+            pid,
+            tid,
+            deadline,
+            sidx,
+        });
+        Ok(deadline)
+    }
+
+    /// Forget the timeout belonging to the given thread, if it has one.
+    pub fn cancel_timeout(&mut self, pid: PID, tid: TID) {
+        for timeout in self.timeouts.iter_mut() {
+            if matches!(timeout, Some(t) if t.pid == pid && t.tid == tid) {
+                *timeout = None;
+            }
+        }
+    }
+
+    /// Return the deadline of the given thread's timeout, if it has one.
+    pub fn timeout_deadline(&self, pid: PID, tid: TID) -> Option<u64> {
+        self.timeouts
+            .iter()
+            .flatten()
+            .find(|t| t.pid == pid && t.tid == tid)
+            .map(|t| t.deadline)
+    }
+
+    /// The earliest deadline of any thread parked on a server, which is how
+    /// long the kernel may sleep before calling `expire_timeouts()`.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.timeouts
+            .iter()
+            .flatten()
+            .filter(|t| t.sidx.is_some())
+            .map(|t| t.deadline)
+            .min()
+    }
+
+    /// Wake every thread whose `ReceiveMessageTimeout` has run out, giving
+    /// it a result of `Error::Timeout`.
+    pub fn expire_timeouts(&mut self) {
+        let now = arch::timeout_clock();
+        for idx in 0..self.timeouts.len() {
+            let (timeout, sidx) = match self.timeouts[idx] {
+                Some(t) if t.deadline <= now => match t.sidx {
+                    Some(sidx) => (t, sidx),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            self.timeouts[idx] = None;
+
+            // Only wake the thread if it is still waiting on this server.
+            let was_parked = self
+                .server_from_sidx_mut(sidx)
+                .filter(|server| server.pid == timeout.pid)
+                .map(|server| server.unpark_thread(timeout.tid))
+                .unwrap_or(false);
+            if !was_parked {
+                continue;
+            }
+
+            self.ready_thread(timeout.pid, timeout.tid)
+                .expect("couldn't wake timed-out thread");
+            if !cfg!(baremetal) {
+                self.switch_to_thread(timeout.pid, Some(timeout.tid))
+                    .expect("couldn't wake timed-out thread");
+            }
+            self.set_thread_result(
+                timeout.pid,
+                timeout.tid,
+                xous_kernel::Result::Error(xous_kernel::Error::Timeout),
+            )
+            .expect("couldn't set result for timed-out thread");
+        }
+    }
+
+    /// Set the scheduling priority of a thread in the given process.
+    ///
+    /// # Errors
+    ///
+    /// * **ThreadNotAvailable**: The TID is out of range
+    pub fn set_thread_priority(
+        &mut self,
+        pid: PID,
+        tid: TID,
+        priority: ThreadPriority,
+    ) -> Result<(), xous_kernel::Error> {
+        let process = self.get_process_mut(pid)?;
+        *process
+            .thread_priority
+            .get_mut(tid)
+            .ok_or(xous_kernel::Error::ThreadNotAvailable)? = priority;
+        Ok(())
+    }
+
+    /// Return the scheduling priority of a thread in the given process.
+    pub fn thread_priority(&self, pid: PID, tid: TID) -> ThreadPriority {
+        self.get_process(pid)
+            .ok()
+            .and_then(|process| process.thread_priority.get(tid).copied())
+            .unwrap_or_default()
+    }
+
+    /// Gather the memory, thread, and CPU usage of the given process.
+    ///
+    /// # Errors
+    ///
+    /// * **ProcessNotFound**: The given PID is not running
+    pub fn process_stats(&self, pid: PID) -> Result<ProcessStats, xous_kernel::Error> {
+        let process = self
+            .processes
+            .get(pid.get() as usize - 1)
+            .filter(|process| !process.free())
+            .ok_or(xous_kernel::Error::ProcessNotFound)?;
+
+        // Thread state is only available for the active process, so switch to
+        // the target just long enough to count its threads.
+        let current_pid = self.current_pid();
+        process.activate()?;
+        let threads = ArchProcess::current().thread_count();
+        self.get_process(current_pid)?.activate()?;
+
+        Ok(ProcessStats {
+            ram_bytes: crate::mem::MemoryManager::with_mut(|mm| mm.ram_used_by(pid)),
+            threads,
+            runtime: process.cpu_time.runtime,
+            switches: process.cpu_time.switches as usize,
+            timestamp: arch::cpu_ticks(),
+        })
+    }
+
+    /// Return the CPU usage of a single thread in the given process.
+    ///
+    /// # Errors
+    ///
+    /// * **ProcessNotFound**: The given PID is not running
+    /// * **ThreadNotAvailable**: The TID is out of range
+    pub fn thread_stats(&self, pid: PID, tid: TID) -> Result<ThreadStats, xous_kernel::Error> {
+        let process = self
+            .processes
+            .get(pid.get() as usize - 1)
+            .filter(|process| !process.free())
+            .ok_or(xous_kernel::Error::ProcessNotFound)?;
+        let cpu_time = &process.cpu_time;
+        Ok(ThreadStats {
+            runtime: *cpu_time
+                .thread_runtime
+                .get(tid)
+                .ok_or(xous_kernel::Error::ThreadNotAvailable)?,
+            switches: cpu_time.thread_switches[tid] as usize,
+            timestamp: arch::cpu_ticks(),
+        })
+    }
 }
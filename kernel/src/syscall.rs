@@ -28,6 +28,8 @@ static mut SWITCHTO_CALLER: Option<(PID, TID)> = None;
 enum ExecutionType {
     Blocking,
     NonBlocking,
+    /// Block for at most this many milliseconds
+    Timeout(usize),
 }
 
 #[cfg(baremetal)]
@@ -187,6 +189,10 @@ fn send_message(pid: PID, thread: TID, cid: CID, message: Message) -> SysCallRes
                 e
             })?;
 
+            // The server thread has its message now, so it no longer needs waking
+            // if it was waiting with a timeout.
+            ss.cancel_timeout(server_pid, server_tid);
+
             let runnable = ss
                 .runnable(server_pid, Some(server_tid))
                 .expect("server doesn't exist");
@@ -577,7 +583,12 @@ fn receive_message(pid: PID, tid: TID, sid: SID, blocking: ExecutionType) -> Sys
             "did not have any waiting messages -- parking thread {}",
             tid
         );
-        server.park_thread(tid);
+        if let ExecutionType::Timeout(ms) = blocking {
+            ss.add_timeout(pid, tid, Some(sidx), ms)?;
+        }
+        ss.server_from_sidx_mut(sidx)
+            .expect("server couldn't be located")
+            .park_thread(tid);
 
         // For baremetal targets, switch away from this process.
         if cfg!(baremetal) {
@@ -779,6 +790,9 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
         SysCall::TryReceiveMessage(sid) => {
             receive_message(pid, tid, sid, ExecutionType::NonBlocking)
         }
+        SysCall::ReceiveMessageTimeout(sid, ms) => {
+            receive_message(pid, tid, sid, ExecutionType::Timeout(ms))
+        }
         SysCall::WaitEvent => SystemServices::with_mut(|ss| {
             let process = ss.get_process(pid).expect("Can't get current process");
             let ppid = process.ppid;
@@ -793,12 +807,15 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             }
         }),
         SysCall::CreateThread(thread_init) => SystemServices::with_mut(|ss| {
-            ss.create_thread(pid, thread_init).map(|new_tid| {
+            ss.create_thread(pid, thread_init).and_then(|new_tid| {
+                // New threads inherit the priority of the thread that created them
+                let priority = ss.thread_priority(pid, tid);
+                ss.set_thread_priority(pid, new_tid, priority)?;
                 if !cfg!(baremetal) {
                     ss.switch_to_thread(pid, Some(new_tid))
                         .expect("couldn't activate new thread");
                 }
-                xous_kernel::Result::ThreadID(new_tid)
+                Ok(xous_kernel::Result::ThreadID(new_tid))
             })
         }),
         SysCall::CreateProcess(process_init) => SystemServices::with_mut(|ss| {
@@ -871,6 +888,35 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
                 Err(e) => Err(e),
             }
         }
+        SysCall::SendMessageTimeout(cid, message, ms) => {
+            let result = send_message(pid, tid, cid, message);
+            match result {
+                // Keep retrying while the queue is full, the same as `SendMessage`,
+                // but remember when we started so we know when to give up.
+                Err(xous_kernel::Error::ServerQueueFull) => {
+                    let expired = SystemServices::with_mut(|ss| {
+                        let deadline = match ss.timeout_deadline(pid, tid) {
+                            Some(deadline) => deadline,
+                            None => ss.add_timeout(pid, tid, None, ms)?,
+                        };
+                        let expired = arch::timeout_clock() >= deadline;
+                        if expired {
+                            ss.cancel_timeout(pid, tid);
+                        }
+                        Ok(expired)
+                    })?;
+                    if expired {
+                        Err(xous_kernel::Error::Timeout)
+                    } else {
+                        retry_syscall(pid, tid)
+                    }
+                }
+                other => {
+                    SystemServices::with_mut(|ss| ss.cancel_timeout(pid, tid));
+                    other
+                }
+            }
+        }
         SysCall::Disconnect(cid) => SystemServices::with_mut(|ss| {
             ss.disconnect_from_server(cid)
                 .and(Ok(xous_kernel::Result::Ok))
@@ -893,6 +939,18 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
             MemoryManager::with_mut(|mm| mm.update_memory_flags(range, flags))?;
             Ok(xous_kernel::Result::Ok)
         }
+        SysCall::SetThreadPriority(target_tid, priority) => SystemServices::with_mut(|ss| {
+            ss.set_thread_priority(pid, target_tid, priority)
+                .and(Ok(xous_kernel::Result::Ok))
+        }),
+        SysCall::GetProcessStats(target_pid) => SystemServices::with(|ss| {
+            ss.process_stats(target_pid)
+                .map(xous_kernel::Result::ProcessStats)
+        }),
+        SysCall::GetThreadStats(target_pid, target_tid) => SystemServices::with(|ss| {
+            ss.thread_stats(target_pid, target_tid)
+                .map(xous_kernel::Result::ThreadStats)
+        }),
         /* https://github.com/betrusted-io/xous-core/issues/90
         SysCall::SetExceptionHandler(pc, sp) => SystemServices::with_mut(|ss| {
             ss.set_exception_handler(pid, pc, sp)
@@ -406,6 +406,100 @@ fn try_receive_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+#[test]
+fn receive_message_timeout() {
+    // Start the server in another thread
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "receive_message_timeout server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+
+            // Nobody knows about this server yet, so this must time out
+            assert_eq!(
+                xous_kernel::receive_message_timeout(sid, 10),
+                Err(xous_kernel::Error::Timeout)
+            );
+
+            // A message that arrives before the timeout is returned as usual
+            server_addr_send.send(sid).unwrap();
+            let envelope = xous_kernel::receive_message_timeout(sid, 10_000)
+                .expect("message didn't arrive before the timeout");
+            assert_eq!(
+                envelope.body,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 11,
+                    arg1: 12,
+                    arg2: 13,
+                    arg3: 14,
+                    arg4: 15
+                })
+            );
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "receive_message_timeout client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+            xous_kernel::send_message_timeout(
+                conn,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 11,
+                    arg1: 12,
+                    arg2: 13,
+                    arg3: 14,
+                    arg4: 15,
+                }),
+                1_000,
+            )
+            .expect("couldn't send message");
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn preemption_tick_timeouts() {
+    // The clock hardware timeouts run on, with the hardware's tick period
+    use crate::clock::TickClock;
+    let tick_ms = xous_kernel::PREEMPTION_TICK_MS as usize;
+    let mut clock = TickClock::new(tick_ms as u64);
+    for _ in 0..5 {
+        clock.tick();
+    }
+    assert_eq!(clock.now(), 5);
+
+    // A zero timeout has already expired
+    assert_eq!(clock.deadline(0), 5);
+
+    // The wait may start just before a tick, so that tick doesn't count towards it
+    assert_eq!(clock.deadline(1), 7);
+    assert_eq!(clock.deadline(tick_ms), 7);
+    assert_eq!(clock.deadline(tick_ms + 1), 8);
+
+    // Started just before a tick, the wait is only over `ms` after `deadline()` ticks
+    for ms in [1, tick_ms - 1, tick_ms, tick_ms + 1, 3 * tick_ms, 1_000] {
+        let deadline = clock.deadline(ms);
+        let shortest_wait_ms = (deadline - clock.now() - 1) as usize * tick_ms;
+        assert!(shortest_wait_ms >= ms, "{} ms timeout could expire after {} ms", ms, shortest_wait_ms);
+    }
+
+    // Huge timeouts don't overflow
+    assert_eq!(clock.deadline(usize::MAX), (usize::MAX / tick_ms) as u64 + 7);
+}
+
 #[test]
 fn send_blocking_scalar_message() {
     // Start the server in another thread
@@ -1177,3 +1271,197 @@ fn process_restart_server() {
 
     main_thread.join().expect("couldn't join kernel process");
 }
+
+#[test]
+fn process_stats() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let stats_process = xous_kernel::create_process_as_thread(
+        xous_kernel::ProcessArgsAsThread::new("process_stats process", move || {
+            let pid = xous_kernel::current_pid().expect("couldn't get pid");
+            let tid = xous_kernel::current_tid().expect("couldn't get tid");
+
+            let before = xous_kernel::get_process_stats(pid).expect("couldn't get process stats");
+            assert!(before.threads > 0);
+            assert!(before.switches > 0);
+
+            // Each of these calls goes through the kernel, so the counters can only go up
+            let after = xous_kernel::get_process_stats(pid).expect("couldn't get process stats");
+            assert!(after.runtime >= before.runtime);
+            assert!(after.switches >= before.switches);
+            assert!(after.timestamp >= before.timestamp);
+
+            let thread = xous_kernel::get_thread_stats(pid, tid).expect("couldn't get thread stats");
+            assert!(thread.switches > 0);
+            let total = xous_kernel::get_process_stats(pid).expect("couldn't get process stats");
+            assert!(thread.runtime <= total.runtime);
+
+            assert_eq!(
+                xous_kernel::get_process_stats(xous_kernel::PID::new(200).unwrap()),
+                Err(xous_kernel::Error::ProcessNotFound)
+            );
+        }),
+    )
+    .expect("couldn't create stats process");
+
+    xous_kernel::wait_process_as_thread(stats_process).expect("couldn't join stats process");
+
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn thread_priority_preempts_busy_loop() {
+    use crate::services::SystemServices;
+    use xous_kernel::{ProcessInit, ProcessKey, ThreadInit, ThreadPriority};
+
+    // Drive the scheduler directly, using this test thread's own copy of the kernel state
+    SystemServices::with_mut(|ss| {
+        let pid = ss
+            .create_process(ProcessInit {
+                key: ProcessKey::new([1; 16]),
+            })
+            .expect("couldn't create process");
+
+        // A low-priority thread is spinning away...
+        let busy = ss.create_thread(pid, ThreadInit {}).unwrap();
+        ss.set_thread_priority(pid, busy, ThreadPriority::Low).unwrap();
+        ss.switch_to_thread(pid, Some(busy)).unwrap();
+
+        // ...while another low-priority thread, and then a high-priority one, become ready.
+        let other = ss.create_thread(pid, ThreadInit {}).unwrap();
+        ss.set_thread_priority(pid, other, ThreadPriority::Low).unwrap();
+        let urgent = ss.create_thread(pid, ThreadInit {}).unwrap();
+        ss.set_thread_priority(pid, urgent, ThreadPriority::High).unwrap();
+        assert!(other < urgent, "round-robin would have picked the other thread first");
+
+        // When the busy thread's timeslice ends, the high-priority thread runs next
+        ss.switch_to_thread(pid, None).unwrap();
+        assert_eq!(ss.get_process(pid).unwrap().current_thread, urgent);
+
+        // With nothing more urgent waiting, the low-priority thread gets its turn
+        ss.switch_to_thread(pid, None).unwrap();
+        assert_eq!(ss.get_process(pid).unwrap().current_thread, other);
+    });
+}
+
+#[test]
+fn thread_priority_orders_processes() {
+    use crate::services::SystemServices;
+    use xous_kernel::{ProcessInit, ProcessKey, ThreadInit, ThreadPriority};
+
+    let (low, high, high_tid) = SystemServices::with_mut(|ss| {
+        let low = ss
+            .create_process(ProcessInit {
+                key: ProcessKey::new([1; 16]),
+            })
+            .unwrap();
+        let low_tid = ss.create_thread(low, ThreadInit {}).unwrap();
+        ss.set_thread_priority(low, low_tid, ThreadPriority::Low).unwrap();
+
+        let high = ss
+            .create_process(ProcessInit {
+                key: ProcessKey::new([2; 16]),
+            })
+            .unwrap();
+        let high_tid = ss.create_thread(high, ThreadInit {}).unwrap();
+        ss.set_thread_priority(high, high_tid, ThreadPriority::High)
+            .unwrap();
+        (low, high, high_tid)
+    });
+
+    // Round-robin would move on to the low-priority process after running the
+    // high-priority one, but as long as it's ready it keeps the CPU.
+    assert_eq!(crate::next_pid_to_run(Some(high)), Some(high));
+    assert_eq!(crate::next_pid_to_run(Some(low)), Some(high));
+
+    // Processes of equal priority take turns
+    SystemServices::with_mut(|ss| ss.set_thread_priority(high, high_tid, ThreadPriority::Low))
+        .unwrap();
+    assert_eq!(crate::next_pid_to_run(Some(high)), Some(low));
+    assert_eq!(crate::next_pid_to_run(Some(low)), Some(high));
+}
+
+#[test]
+fn gdb_decode_jumps() {
+    use crate::debug::decode::{instruction_length, next_pc};
+    let mut regs = [0u32; 32];
+    regs[10] = 0x2000_1000; // a0
+    regs[5] = 0x2000_4001; // t0
+    let pc = 0x2000_0100;
+
+    // jal ra, 256
+    assert_eq!(next_pc(pc, 0x1000_00ef, &regs), pc + 256);
+    // j -8
+    assert_eq!(next_pc(pc, 0xff9f_f06f, &regs), pc - 8);
+    // jal ra, -524288
+    assert_eq!(next_pc(0x2010_0000, 0x8008_00ef, &regs), 0x2008_0000);
+    // j 524286
+    assert_eq!(next_pc(pc, 0x7ff7_f06f, &regs), pc + 524286);
+    // jalr ra, 12(a0)
+    assert_eq!(next_pc(pc, 0x00c5_00e7, &regs), 0x2000_100c);
+    // addi a0, a0, 1 / ecall / ebreak
+    assert_eq!(next_pc(pc, 0x0015_0513, &regs), pc + 4);
+    assert_eq!(next_pc(pc, 0x0000_0073, &regs), pc + 4);
+    assert_eq!(next_pc(pc, 0x0010_0073, &regs), pc + 4);
+
+    // c.j -2
+    assert_eq!(instruction_length(0xbffd), 2);
+    assert_eq!(next_pc(pc, 0xbffd, &regs), pc - 2);
+    // c.jal 256, with junk in the upper half that must be ignored
+    assert_eq!(next_pc(pc, 0xdead_2201, &regs), pc + 256);
+    // c.jal 100
+    assert_eq!(next_pc(pc, 0x2095, &regs), pc + 100);
+    // c.jr a0
+    assert_eq!(next_pc(pc, 0x8502, &regs), 0x2000_1000);
+    // c.jalr t0 clears the lowest bit of the target
+    assert_eq!(next_pc(pc, 0x9282, &regs), 0x2000_4000);
+    // c.ebreak, c.mv a0, a1 and c.addi a0, 1 all fall through
+    assert_eq!(next_pc(pc, 0x9002, &regs), pc + 2);
+    assert_eq!(next_pc(pc, 0x852e, &regs), pc + 2);
+    assert_eq!(next_pc(pc, 0x0505, &regs), pc + 2);
+}
+
+#[test]
+fn gdb_decode_branches() {
+    use crate::debug::decode::next_pc;
+    let mut regs = [0u32; 32];
+    let pc = 0x2000_0100;
+
+    // beq a0, a1, 16
+    regs[10] = 5;
+    regs[11] = 5;
+    assert_eq!(next_pc(pc, 0x00b5_0863, &regs), pc + 16);
+    regs[11] = 6;
+    assert_eq!(next_pc(pc, 0x00b5_0863, &regs), pc + 4);
+    // bne a0, a1, -16
+    assert_eq!(next_pc(pc, 0xfeb5_18e3, &regs), pc - 16);
+
+    // blt / bltu and bge / bgeu disagree about negative numbers
+    regs[10] = -1i32 as u32;
+    regs[11] = 1;
+    // blt a0, a1, 32
+    assert_eq!(next_pc(pc, 0x02b5_4063, &regs), pc + 32);
+    // bge a0, a1, 32
+    assert_eq!(next_pc(pc, 0x02b5_5063, &regs), pc + 4);
+    // bltu a0, a1, 32
+    assert_eq!(next_pc(pc, 0x02b5_6063, &regs), pc + 4);
+    // bgeu a0, a1, 2048
+    assert_eq!(next_pc(pc, 0x00b5_70e3, &regs), pc + 2048);
+
+    // c.beqz s0, -8
+    regs[8] = 0;
+    assert_eq!(next_pc(pc, 0xdc65, &regs), pc - 8);
+    regs[8] = 1;
+    assert_eq!(next_pc(pc, 0xdc65, &regs), pc + 2);
+    // c.bnez a5, 20
+    regs[15] = 0;
+    assert_eq!(next_pc(pc, 0xeb91, &regs), pc + 2);
+    regs[15] = 3;
+    assert_eq!(next_pc(pc, 0xeb91, &regs), pc + 20);
+
+    // x0 always reads as zero, whatever the register file says: beq zero, zero, 16
+    regs[0] = 0xdead_beef;
+    assert_eq!(next_pc(pc, 0x0000_0863, &regs), pc + 16);
+}
@@ -13,6 +13,7 @@
 
 mod soft;
 mod vex;
+pub mod modes;
 
 pub use soft::{Aes128Soft, Aes192, Aes256Soft};
 
@@ -27,6 +28,8 @@ pub use soft::Aes256Soft as Aes256;
 #[cfg(feature = "ctr")]
 pub use soft::{Aes128Ctr, Aes192Ctr, Aes256Ctr};
 
+pub use modes::{AeadError, Aes128Gcm, Aes128GcmSiv, Aes256Gcm, Aes256GcmSiv, Ctr32BE, Gcm, GcmSiv};
+
 pub use cipher::{self, BlockCipher, BlockDecrypt, BlockEncrypt, NewBlockCipher};
 
 /// 128-bit AES block
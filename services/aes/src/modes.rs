@@ -0,0 +1,173 @@
+//! Modes of operation layered on top of the AES block ciphers.
+//!
+//! Everything in here is generic over `BlockEncrypt` with a 128-bit block, so the same code drives the
+//! Vex-accelerated ciphers on hardware and the fixslice ciphers in hosted mode. The `Aes*Gcm` and
+//! `Aes*GcmSiv` aliases pick up whichever backend `Aes128`/`Aes256` resolve to for the target.
+//!
+//! The AEAD constructions operate in-place with a detached 16-byte tag, which keeps them usable from
+//! `no_std` contexts without an allocator: callers that want `ciphertext || tag` can append it themselves.
+
+mod ctr;
+mod gcm;
+mod gcm_siv;
+mod ghash;
+
+pub use self::ctr::Ctr32BE;
+pub use gcm::Gcm;
+pub use gcm_siv::GcmSiv;
+
+/// Size of the authentication tag produced by the AEAD modes, in bytes
+pub const TAG_SIZE: usize = 16;
+
+/// Errors returned by the AEAD modes
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AeadError {
+    /// The tag did not match; no plaintext is released
+    InvalidTag,
+    /// The nonce, tag or message length is not supported by the mode
+    InvalidLength,
+}
+
+/// AES-128 in Galois/Counter Mode
+pub type Aes128Gcm = Gcm<crate::Aes128>;
+/// AES-256 in Galois/Counter Mode
+pub type Aes256Gcm = Gcm<crate::Aes256>;
+/// AES-128 in nonce misuse-resistant GCM-SIV mode (RFC 8452)
+pub type Aes128GcmSiv = GcmSiv<crate::Aes128>;
+/// AES-256 in nonce misuse-resistant GCM-SIV mode (RFC 8452)
+pub type Aes256GcmSiv = GcmSiv<crate::Aes256>;
+
+/// GCM and GCM-SIV both cap the plaintext at 2^36 - 32 bytes, and the AAD at 2^36 bytes.
+pub(crate) fn check_lengths(aad: &[u8], msg: &[u8]) -> Result<(), AeadError> {
+    if msg.len() as u64 > (1u64 << 36) - 32 || aad.len() as u64 > (1u64 << 36) {
+        Err(AeadError::InvalidLength)
+    } else {
+        Ok(())
+    }
+}
+
+/// Tag comparison that always walks the full length, so timing reveals nothing about where a forgery diverged.
+pub(crate) fn tag_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff: u8 = 0;
+    for (&x, &y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+/// Runs the Project Wycheproof AEAD vectors compiled by `cargo xtask wycheproof-import`. See
+/// `tools/wycheproof-import/README.md` for where the vectors come from.
+#[cfg(test)]
+mod wycheproof {
+    use super::*;
+    use crate::{Aes128Soft, Aes192, Aes256Soft, BlockCipher, BlockEncrypt, NewBlockCipher};
+    use cipher::consts::U16;
+    use core::convert::TryInto;
+
+    struct TestCase<'a> {
+        id: u32,
+        valid: bool,
+        key: &'a [u8],
+        iv: &'a [u8],
+        aad: &'a [u8],
+        msg: &'a [u8],
+        ct: &'a [u8],
+        tag: &'a [u8],
+    }
+
+    /// See `tools/wycheproof-import` for the layout: eight little-endian u32 header words
+    /// (id, valid, then the lengths of key, iv, aad, msg, ct, tag) followed by the fields themselves.
+    fn parse(data: &[u8]) -> Vec<TestCase<'_>> {
+        let mut cases = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let mut header = [0u32; 8];
+            for word in header.iter_mut() {
+                *word = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+                offset += 4;
+            }
+            let mut fields: [&[u8]; 6] = [&[]; 6];
+            for (field, &len) in fields.iter_mut().zip(header[2..].iter()) {
+                *field = &data[offset..offset + len as usize];
+                offset += len as usize;
+            }
+            cases.push(TestCase {
+                id: header[0],
+                valid: header[1] != 0,
+                key: fields[0],
+                iv: fields[1],
+                aad: fields[2],
+                msg: fields[3],
+                ct: fields[4],
+                tag: fields[5],
+            });
+        }
+        cases
+    }
+
+    trait Aead {
+        fn seal(&self, iv: &[u8], aad: &[u8], buf: &mut [u8]) -> Result<[u8; TAG_SIZE], AeadError>;
+        fn open(&self, iv: &[u8], aad: &[u8], buf: &mut [u8], tag: &[u8]) -> Result<(), AeadError>;
+    }
+    impl<C: BlockCipher<BlockSize = U16> + BlockEncrypt> Aead for Gcm<C> {
+        fn seal(&self, iv: &[u8], aad: &[u8], buf: &mut [u8]) -> Result<[u8; TAG_SIZE], AeadError> {
+            self.encrypt_in_place_detached(iv, aad, buf)
+        }
+        fn open(&self, iv: &[u8], aad: &[u8], buf: &mut [u8], tag: &[u8]) -> Result<(), AeadError> {
+            self.decrypt_in_place_detached(iv, aad, buf, tag)
+        }
+    }
+    impl<C: NewBlockCipher + BlockCipher<BlockSize = U16> + BlockEncrypt> Aead for GcmSiv<C> {
+        fn seal(&self, iv: &[u8], aad: &[u8], buf: &mut [u8]) -> Result<[u8; TAG_SIZE], AeadError> {
+            self.encrypt_in_place_detached(iv, aad, buf)
+        }
+        fn open(&self, iv: &[u8], aad: &[u8], buf: &mut [u8], tag: &[u8]) -> Result<(), AeadError> {
+            self.decrypt_in_place_detached(iv, aad, buf, tag)
+        }
+    }
+
+    fn run(name: &str, data: &[u8], make: fn(&[u8]) -> Option<Box<dyn Aead>>) {
+        let cases = parse(data);
+        assert!(!cases.is_empty(), "{} has no test cases", name);
+        for case in cases {
+            let aead = match make(case.key) {
+                Some(aead) => aead,
+                None => panic!("{} case {}: no cipher for a {}-byte key", name, case.id, case.key.len()),
+            };
+            let mut buf = case.ct.to_vec();
+            let opened = aead.open(case.iv, case.aad, &mut buf, case.tag);
+            if case.valid {
+                assert_eq!(opened, Ok(()), "case {}: decrypt failed", case.id);
+                assert_eq!(buf, case.msg, "case {}: plaintext mismatch", case.id);
+                let mut buf = case.msg.to_vec();
+                let tag = aead.seal(case.iv, case.aad, &mut buf).unwrap();
+                assert_eq!(buf, case.ct, "case {}: ciphertext mismatch", case.id);
+                assert_eq!(&tag[..], case.tag, "case {}: tag mismatch", case.id);
+            } else {
+                assert!(opened.is_err(), "case {}: forgery accepted", case.id);
+            }
+        }
+    }
+
+    #[test]
+    fn aes_gcm() {
+        run("aes_gcm_test.bin", include_bytes!("modes/aes_gcm_test.bin"), |key| match key.len() {
+            16 => Some(Box::new(Gcm::new_with_cipher(Aes128Soft::new_from_slice(key).unwrap()))),
+            24 => Some(Box::new(Gcm::new_with_cipher(Aes192::new_from_slice(key).unwrap()))),
+            32 => Some(Box::new(Gcm::new_with_cipher(Aes256Soft::new_from_slice(key).unwrap()))),
+            _ => None,
+        });
+    }
+
+    #[test]
+    fn aes_gcm_siv() {
+        run("aes_gcm_siv_test.bin", include_bytes!("modes/aes_gcm_siv_test.bin"), |key| match key.len() {
+            16 => Some(Box::new(GcmSiv::new_with_cipher(Aes128Soft::new_from_slice(key).unwrap()))),
+            32 => Some(Box::new(GcmSiv::new_with_cipher(Aes256Soft::new_from_slice(key).unwrap()))),
+            _ => None,
+        });
+    }
+}
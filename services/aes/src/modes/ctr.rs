@@ -0,0 +1,111 @@
+//! Counter mode keystream generation.
+//!
+//! Unlike the `ctr` feature, which wraps the soft ciphers only, this works over any 128-bit
+//! `BlockEncrypt`, including the Vex hardware ciphers.
+
+use crate::{Block, BLOCK_SIZE};
+use cipher::{consts::U16, BlockCipher, BlockEncrypt};
+use core::convert::TryInto;
+use zeroize::Zeroize;
+
+/// Streaming CTR mode with a 32-bit big-endian counter in the last four bytes of the counter block,
+/// as used by GCM and NIST SP 800-38A. The counter wraps modulo 2^32 without carrying into the nonce.
+///
+/// Calls to `apply_keystream` may be split at any byte boundary; the keystream picks up where it left off.
+pub struct Ctr32BE<C: BlockCipher<BlockSize = U16> + BlockEncrypt> {
+    cipher: C,
+    counter: [u8; BLOCK_SIZE],
+    keystream: Block,
+    pos: usize,
+}
+
+impl<C: BlockCipher<BlockSize = U16> + BlockEncrypt> Ctr32BE<C> {
+    /// `initial` is the first counter block: the leading 12 bytes are the nonce, the trailing 4 the counter.
+    pub fn new(cipher: C, initial: &[u8; BLOCK_SIZE]) -> Self {
+        Ctr32BE {
+            cipher,
+            counter: *initial,
+            keystream: Block::default(),
+            pos: BLOCK_SIZE,
+        }
+    }
+
+    /// XORs the keystream into `data`. Encryption and decryption are the same operation.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            if self.pos == BLOCK_SIZE {
+                self.refill();
+            }
+            *byte ^= self.keystream[self.pos];
+            self.pos += 1;
+        }
+    }
+
+    fn refill(&mut self) {
+        self.keystream.copy_from_slice(&self.counter);
+        self.cipher.encrypt_block(&mut self.keystream);
+        let ctr = u32::from_be_bytes(self.counter[12..].try_into().unwrap()).wrapping_add(1);
+        self.counter[12..].copy_from_slice(&ctr.to_be_bytes());
+        self.pos = 0;
+    }
+}
+
+impl<C: BlockCipher<BlockSize = U16> + BlockEncrypt> Drop for Ctr32BE<C> {
+    fn drop(&mut self) {
+        self.keystream.as_mut_slice().zeroize();
+    }
+}
+
+/// One-shot CTR with the 32-bit little-endian counter in the *first* four bytes of the counter block,
+/// which is the variant GCM-SIV specifies (RFC 8452 section 4).
+pub(crate) fn apply_ctr32le<C: BlockCipher<BlockSize = U16> + BlockEncrypt>(
+    cipher: &C,
+    initial: &[u8; BLOCK_SIZE],
+    data: &mut [u8],
+) {
+    let mut counter = *initial;
+    let mut keystream = Block::default();
+    for chunk in data.chunks_mut(BLOCK_SIZE) {
+        keystream.copy_from_slice(&counter);
+        cipher.encrypt_block(&mut keystream);
+        for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= k;
+        }
+        let ctr = u32::from_le_bytes(counter[..4].try_into().unwrap()).wrapping_add(1);
+        counter[..4].copy_from_slice(&ctr.to_le_bytes());
+    }
+    keystream.as_mut_slice().zeroize();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aes128Soft, NewBlockCipher};
+    use hex_literal::hex;
+
+    // NIST SP 800-38A, F.5.1 CTR-AES128.Encrypt
+    const KEY: [u8; 16] = hex!("2b7e151628aed2a6abf7158809cf4f3c");
+    const INITIAL: [u8; 16] = hex!("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+    const PT: [u8; 64] = hex!("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710");
+    const CT: [u8; 64] = hex!("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee");
+
+    #[test]
+    fn sp800_38a() {
+        let mut buf = PT;
+        let mut ctr = Ctr32BE::new(Aes128Soft::new(&KEY.into()), &INITIAL);
+        ctr.apply_keystream(&mut buf);
+        assert_eq!(buf, CT);
+    }
+
+    #[test]
+    fn split_calls_match_one_shot() {
+        let mut buf = PT;
+        let mut ctr = Ctr32BE::new(Aes128Soft::new(&KEY.into()), &INITIAL);
+        let (a, rest) = buf.split_at_mut(5);
+        let (b, c) = rest.split_at_mut(27);
+        ctr.apply_keystream(a);
+        ctr.apply_keystream(b);
+        ctr.apply_keystream(c);
+        assert_eq!(buf, CT);
+    }
+}
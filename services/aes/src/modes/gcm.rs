@@ -0,0 +1,190 @@
+//! Galois/Counter Mode (NIST SP 800-38D)
+
+use super::ctr::Ctr32BE;
+use super::ghash::GHash;
+use super::{check_lengths, tag_eq, AeadError, TAG_SIZE};
+use crate::{Block, BLOCK_SIZE};
+use cipher::{consts::U16, generic_array::GenericArray, BlockCipher, BlockEncrypt, NewBlockCipher};
+use core::convert::TryInto;
+use zeroize::Zeroize;
+
+/// GCM over any 128-bit block cipher. Only full 16-byte tags are produced or accepted.
+///
+/// 96-bit nonces are the fast path; other non-empty lengths are accepted and hashed into the
+/// initial counter as the standard specifies. Never reuse a nonce under the same key: doing so
+/// leaks the authentication key. Where nonce uniqueness can't be guaranteed, use `GcmSiv`.
+pub struct Gcm<C: BlockCipher<BlockSize = U16> + BlockEncrypt> {
+    cipher: C,
+    h: [u8; BLOCK_SIZE],
+}
+
+impl<C: NewBlockCipher + BlockCipher<BlockSize = U16> + BlockEncrypt> Gcm<C> {
+    pub fn new(key: &GenericArray<u8, C::KeySize>) -> Self {
+        Self::new_with_cipher(C::new(key))
+    }
+}
+
+impl<C: BlockCipher<BlockSize = U16> + BlockEncrypt> Gcm<C> {
+    /// Wraps an already keyed cipher, e.g. one whose key schedule is shared with other users.
+    pub fn new_with_cipher(cipher: C) -> Self {
+        let mut h = Block::default();
+        cipher.encrypt_block(&mut h);
+        let mut gcm = Gcm { cipher, h: [0u8; BLOCK_SIZE] };
+        gcm.h.copy_from_slice(&h);
+        h.as_mut_slice().zeroize();
+        gcm
+    }
+
+    /// Encrypts `buf` in place and returns the tag.
+    pub fn encrypt_in_place_detached(&self, nonce: &[u8], aad: &[u8], buf: &mut [u8]) -> Result<[u8; TAG_SIZE], AeadError> {
+        check_lengths(aad, buf)?;
+        let j0 = self.j0(nonce)?;
+        self.keystream(&j0).apply_keystream(buf);
+        Ok(self.tag(&j0, aad, buf))
+    }
+
+    /// Checks `tag` and only then decrypts `buf` in place. On failure `buf` is left untouched.
+    pub fn decrypt_in_place_detached(&self, nonce: &[u8], aad: &[u8], buf: &mut [u8], tag: &[u8]) -> Result<(), AeadError> {
+        check_lengths(aad, buf)?;
+        if tag.len() != TAG_SIZE {
+            return Err(AeadError::InvalidLength);
+        }
+        let j0 = self.j0(nonce)?;
+        let mut expected = self.tag(&j0, aad, buf);
+        let ok = tag_eq(&expected, tag);
+        expected.zeroize();
+        if !ok {
+            return Err(AeadError::InvalidTag);
+        }
+        self.keystream(&j0).apply_keystream(buf);
+        Ok(())
+    }
+
+    /// Computes the pre-counter block J0 from the nonce.
+    fn j0(&self, nonce: &[u8]) -> Result<[u8; BLOCK_SIZE], AeadError> {
+        if nonce.is_empty() || nonce.len() as u64 > u64::MAX / 8 {
+            return Err(AeadError::InvalidLength);
+        }
+        let mut j0 = [0u8; BLOCK_SIZE];
+        if nonce.len() == 12 {
+            j0[..12].copy_from_slice(nonce);
+            j0[15] = 1;
+        } else {
+            let mut ghash = GHash::new(&self.h);
+            ghash.update_padded(nonce);
+            let mut lengths = [0u8; BLOCK_SIZE];
+            lengths[8..].copy_from_slice(&(nonce.len() as u64 * 8).to_be_bytes());
+            ghash.update_block(&lengths);
+            j0 = ghash.finalize();
+        }
+        Ok(j0)
+    }
+
+    /// The payload keystream starts one counter step past J0, which is reserved for masking the tag.
+    fn keystream(&self, j0: &[u8; BLOCK_SIZE]) -> Ctr32BE<&C> {
+        let mut initial = *j0;
+        let ctr = u32::from_be_bytes(initial[12..].try_into().unwrap()).wrapping_add(1);
+        initial[12..].copy_from_slice(&ctr.to_be_bytes());
+        Ctr32BE::new(&self.cipher, &initial)
+    }
+
+    fn tag(&self, j0: &[u8; BLOCK_SIZE], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+        let mut ghash = GHash::new(&self.h);
+        ghash.update_padded(aad);
+        ghash.update_padded(ciphertext);
+        let mut lengths = [0u8; BLOCK_SIZE];
+        lengths[..8].copy_from_slice(&(aad.len() as u64 * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&(ciphertext.len() as u64 * 8).to_be_bytes());
+        ghash.update_block(&lengths);
+
+        let mut tag = ghash.finalize();
+        let mut mask = Block::clone_from_slice(j0);
+        self.cipher.encrypt_block(&mut mask);
+        for (t, m) in tag.iter_mut().zip(mask.iter()) {
+            *t ^= m;
+        }
+        mask.as_mut_slice().zeroize();
+        tag
+    }
+}
+
+impl<C: BlockCipher<BlockSize = U16> + BlockEncrypt> Drop for Gcm<C> {
+    fn drop(&mut self) {
+        self.h.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aes128Soft, Aes256Soft};
+    use hex_literal::hex;
+
+    // test cases from the original GCM submission (McGrew & Viega), as numbered there
+    const KEY: [u8; 16] = hex!("feffe9928665731c6d6a8f9467308308");
+    const AAD: [u8; 20] = hex!("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+    const PT: [u8; 60] = hex!("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39");
+
+    fn check<C: BlockCipher<BlockSize = U16> + BlockEncrypt>(gcm: &Gcm<C>, nonce: &[u8], aad: &[u8], pt: &[u8], ct: &[u8], tag: &[u8]) {
+        let mut buf = pt.to_vec();
+        let computed = gcm.encrypt_in_place_detached(nonce, aad, &mut buf).unwrap();
+        assert_eq!(buf, ct);
+        assert_eq!(&computed[..], tag);
+        gcm.decrypt_in_place_detached(nonce, aad, &mut buf, tag).unwrap();
+        assert_eq!(buf, pt);
+    }
+
+    #[test]
+    fn test_case_2() {
+        let gcm = Gcm::<Aes128Soft>::new(&[0u8; 16].into());
+        check(&gcm, &[0u8; 12], &[], &[0u8; 16],
+            &hex!("0388dace60b6a392f328c2b971b2fe78"),
+            &hex!("ab6e47d42cec13bdf53a67b21257bddf"));
+    }
+
+    #[test]
+    fn test_case_4() {
+        let gcm = Gcm::<Aes128Soft>::new(&KEY.into());
+        check(&gcm, &hex!("cafebabefacedbaddecaf888"), &AAD, &PT,
+            &hex!("42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091"),
+            &hex!("5bc94fbc3221a5db94fae95ae7121a47"));
+    }
+
+    #[test]
+    fn test_case_6_long_nonce() {
+        let gcm = Gcm::<Aes128Soft>::new(&KEY.into());
+        let nonce = hex!("9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b");
+        check(&gcm, &nonce, &AAD, &PT,
+            &hex!("8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca701e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5"),
+            &hex!("619cc5aefffe0bfa462af43c1699d050"));
+    }
+
+    #[test]
+    fn test_case_16() {
+        let mut key = [0u8; 32];
+        key[..16].copy_from_slice(&KEY);
+        key[16..].copy_from_slice(&KEY);
+        let gcm = Gcm::<Aes256Soft>::new(&key.into());
+        check(&gcm, &hex!("cafebabefacedbaddecaf888"), &AAD, &PT,
+            &hex!("522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662"),
+            &hex!("76fc6ece0f4e1768cddf8853bb2d551b"));
+    }
+
+    #[test]
+    fn tampering_is_rejected() {
+        let gcm = Gcm::<Aes128Soft>::new(&KEY.into());
+        let nonce = hex!("cafebabefacedbaddecaf888");
+        let mut buf = PT;
+        let tag = gcm.encrypt_in_place_detached(&nonce, &AAD, &mut buf).unwrap();
+        let sealed = buf;
+
+        buf[7] ^= 1;
+        assert_eq!(gcm.decrypt_in_place_detached(&nonce, &AAD, &mut buf, &tag), Err(AeadError::InvalidTag));
+        buf[7] ^= 1;
+        assert_eq!(gcm.decrypt_in_place_detached(&nonce, &AAD[1..], &mut buf, &tag), Err(AeadError::InvalidTag));
+        assert_eq!(gcm.decrypt_in_place_detached(&nonce, &AAD, &mut buf, &tag[..12]), Err(AeadError::InvalidLength));
+        assert_eq!(gcm.encrypt_in_place_detached(&[], &AAD, &mut buf), Err(AeadError::InvalidLength));
+        // failed opens must not release plaintext
+        assert_eq!(buf, sealed);
+    }
+}
@@ -0,0 +1,184 @@
+//! AES-GCM-SIV nonce misuse-resistant authenticated encryption (RFC 8452)
+
+use super::ctr::apply_ctr32le;
+use super::ghash::GHash;
+use super::{check_lengths, tag_eq, AeadError, TAG_SIZE};
+use crate::{Block, BLOCK_SIZE};
+use cipher::{
+    consts::U16,
+    generic_array::{typenum::Unsigned, GenericArray},
+    BlockCipher, BlockEncrypt, NewBlockCipher,
+};
+use zeroize::Zeroize;
+
+/// GCM-SIV nonces are always 96 bits
+pub const NONCE_SIZE: usize = 12;
+
+/// GCM-SIV over any 128-bit block cipher that can be re-keyed, since every message is encrypted under
+/// its own key derived from the nonce.
+///
+/// Repeating a nonce only reveals whether the same (AAD, plaintext) pair was sealed twice, which makes
+/// this the safer choice when nonces come from a counter that may be rolled back, e.g. after a restore.
+pub struct GcmSiv<C: NewBlockCipher + BlockCipher<BlockSize = U16> + BlockEncrypt> {
+    key_generating_key: C,
+}
+
+impl<C: NewBlockCipher + BlockCipher<BlockSize = U16> + BlockEncrypt> GcmSiv<C> {
+    pub fn new(key: &GenericArray<u8, C::KeySize>) -> Self {
+        Self::new_with_cipher(C::new(key))
+    }
+
+    pub fn new_with_cipher(key_generating_key: C) -> Self {
+        GcmSiv { key_generating_key }
+    }
+
+    /// Encrypts `buf` in place and returns the tag.
+    pub fn encrypt_in_place_detached(&self, nonce: &[u8], aad: &[u8], buf: &mut [u8]) -> Result<[u8; TAG_SIZE], AeadError> {
+        check_lengths(aad, buf)?;
+        let nonce = Self::check_nonce(nonce)?;
+        let (mut auth_key, cipher) = self.derive_keys(nonce);
+        let tag = Self::tag(&auth_key, &cipher, nonce, aad, buf);
+        auth_key.zeroize();
+        apply_ctr32le(&cipher, &Self::initial_counter(&tag), buf);
+        Ok(tag)
+    }
+
+    /// Decrypts `buf` in place and checks `tag`. SIV authenticates the plaintext, so decryption has to
+    /// happen first; if the tag does not match, `buf` is zeroed rather than left holding unauthenticated data.
+    pub fn decrypt_in_place_detached(&self, nonce: &[u8], aad: &[u8], buf: &mut [u8], tag: &[u8]) -> Result<(), AeadError> {
+        check_lengths(aad, buf)?;
+        let nonce = Self::check_nonce(nonce)?;
+        if tag.len() != TAG_SIZE {
+            return Err(AeadError::InvalidLength);
+        }
+        let mut received = [0u8; TAG_SIZE];
+        received.copy_from_slice(tag);
+        let (mut auth_key, cipher) = self.derive_keys(nonce);
+        apply_ctr32le(&cipher, &Self::initial_counter(&received), buf);
+        let mut expected = Self::tag(&auth_key, &cipher, nonce, aad, buf);
+        auth_key.zeroize();
+        let ok = tag_eq(&expected, &received);
+        expected.zeroize();
+        if ok {
+            Ok(())
+        } else {
+            buf.zeroize();
+            Err(AeadError::InvalidTag)
+        }
+    }
+
+    fn check_nonce(nonce: &[u8]) -> Result<&[u8; NONCE_SIZE], AeadError> {
+        use core::convert::TryInto;
+        nonce.try_into().map_err(|_| AeadError::InvalidLength)
+    }
+
+    /// Derives the per-nonce POLYVAL key and encryption cipher (RFC 8452 section 4). Each derivation block
+    /// is a little-endian counter followed by the nonce, and only the first half of each output is used.
+    fn derive_keys(&self, nonce: &[u8; NONCE_SIZE]) -> ([u8; BLOCK_SIZE], C) {
+        let mut auth_key = [0u8; BLOCK_SIZE];
+        let mut enc_key = GenericArray::<u8, C::KeySize>::default();
+        let mut block = Block::default();
+        for i in 0..(2 + C::KeySize::USIZE / 8) {
+            block[..4].copy_from_slice(&(i as u32).to_le_bytes());
+            block[4..].copy_from_slice(nonce);
+            self.key_generating_key.encrypt_block(&mut block);
+            if i < 2 {
+                auth_key[i * 8..(i + 1) * 8].copy_from_slice(&block[..8]);
+            } else {
+                enc_key[(i - 2) * 8..(i - 1) * 8].copy_from_slice(&block[..8]);
+            }
+        }
+        block.as_mut_slice().zeroize();
+        let cipher = C::new(&enc_key);
+        enc_key.as_mut_slice().zeroize();
+        (auth_key, cipher)
+    }
+
+    fn tag(auth_key: &[u8; BLOCK_SIZE], cipher: &C, nonce: &[u8; NONCE_SIZE], aad: &[u8], plaintext: &[u8]) -> [u8; TAG_SIZE] {
+        let mut polyval = GHash::new_polyval(auth_key);
+        polyval.update_padded(aad);
+        polyval.update_padded(plaintext);
+        let mut lengths = [0u8; BLOCK_SIZE];
+        lengths[..8].copy_from_slice(&(aad.len() as u64 * 8).to_le_bytes());
+        lengths[8..].copy_from_slice(&(plaintext.len() as u64 * 8).to_le_bytes());
+        polyval.update_block(&lengths);
+
+        let mut s = polyval.finalize();
+        for (s, n) in s.iter_mut().zip(nonce.iter()) {
+            *s ^= n;
+        }
+        s[15] &= 0x7f;
+        let mut block = Block::clone_from_slice(&s);
+        s.zeroize();
+        cipher.encrypt_block(&mut block);
+        let mut tag = [0u8; TAG_SIZE];
+        tag.copy_from_slice(&block);
+        tag
+    }
+
+    fn initial_counter(tag: &[u8; TAG_SIZE]) -> [u8; BLOCK_SIZE] {
+        let mut counter = *tag;
+        counter[15] |= 0x80;
+        counter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aes128Soft, Aes256Soft};
+    use hex_literal::hex;
+
+    // RFC 8452 appendix C
+    const NONCE: [u8; 12] = hex!("030000000000000000000000");
+
+    fn check<C: NewBlockCipher + BlockCipher<BlockSize = U16> + BlockEncrypt>(siv: &GcmSiv<C>, nonce: &[u8], aad: &[u8], pt: &[u8], sealed: &[u8]) {
+        let (ct, tag) = sealed.split_at(pt.len());
+        let mut buf = pt.to_vec();
+        let computed = siv.encrypt_in_place_detached(nonce, aad, &mut buf).unwrap();
+        assert_eq!(buf, ct);
+        assert_eq!(&computed[..], tag);
+        siv.decrypt_in_place_detached(nonce, aad, &mut buf, tag).unwrap();
+        assert_eq!(buf, pt);
+    }
+
+    #[test]
+    fn aes128_rfc8452() {
+        let siv = GcmSiv::<Aes128Soft>::new(&hex!("01000000000000000000000000000000").into());
+        check(&siv, &NONCE, &[], &[], &hex!("dc20e2d83f25705bb49e439eca56de25"));
+        check(&siv, &NONCE, &[], &hex!("0100000000000000"),
+            &hex!("b5d839330ac7b786578782fff6013b815b287c22493a364c"));
+        check(&siv, &NONCE, &hex!("01"), &hex!("0200000000000000"),
+            &hex!("1e6daba35669f4273b0a1a2560969cdf790d99759abd1508"));
+    }
+
+    #[test]
+    fn aes128_rfc8452_unaligned() {
+        let siv = GcmSiv::<Aes128Soft>::new(&hex!("8254c329a92850f6d539dd376f4816ee").into());
+        check(&siv, &hex!("1b16b1df538ba12dc3f97edb"), &hex!("ca978112ca1bbdcafac231b39a23dc4da7"),
+            &hex!("929872838cb9cfe6578e11f0a323438aee5ae7f61d41412d62db72b25dac52019de2d6a355eb2d033336fb70e73f0ec0afeca3ef36dd8a90d83f998fee23b78d2e96772232487fb3a058d58f2c"),
+            &hex!("46b672338d040e57fdd748b64b9f576026e6b2b20ffd7de9c8ab2fbf760053b06fe12eca9b2eed3dd03bb882c9b5acc4753208c6d1e13d6d477cb8d52595819d17323759b0266edb370f37e60acb8a221a80dad28edbfe13536a008b71"));
+    }
+
+    #[test]
+    fn aes256_rfc8452() {
+        let mut key = [0u8; 32];
+        key[0] = 1;
+        let siv = GcmSiv::<Aes256Soft>::new(&key.into());
+        check(&siv, &NONCE, &[], &[], &hex!("07f5f4169bbf55a8400cd47ea6fd400f"));
+        check(&siv, &NONCE, &[],
+            &hex!("0100000000000000000000000000000002000000000000000000000000000000"),
+            &hex!("4a6a9db4c8c6549201b9edb53006cba821ec9cf850948a7c86c68ac7539d027fe819e63abcd020b006a976397632eb5d"));
+    }
+
+    #[test]
+    fn forgery_zeroes_buffer() {
+        let siv = GcmSiv::<Aes128Soft>::new(&hex!("01000000000000000000000000000000").into());
+        let mut buf = *b"attack at dawn";
+        let mut tag = siv.encrypt_in_place_detached(&NONCE, b"hdr", &mut buf).unwrap();
+        tag[0] ^= 1;
+        assert_eq!(siv.decrypt_in_place_detached(&NONCE, b"hdr", &mut buf, &tag), Err(AeadError::InvalidTag));
+        assert_eq!(buf, [0u8; 14]);
+        assert_eq!(siv.encrypt_in_place_detached(&NONCE[..8], b"hdr", &mut buf), Err(AeadError::InvalidLength));
+    }
+}
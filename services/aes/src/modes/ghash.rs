@@ -0,0 +1,116 @@
+//! GHASH (GCM) and POLYVAL (GCM-SIV) universal hashes.
+//!
+//! Both are evaluated with a bit-serial, branch-free multiply over GF(2^128). It is not fast, but it
+//! uses neither secret-dependent table lookups nor carry-less multiply instructions, which the
+//! RISC-V core doesn't have anyway. POLYVAL is computed through GHASH using the mapping in RFC 8452
+//! appendix A, so there is only one field multiply to get right.
+
+use crate::BLOCK_SIZE;
+use core::convert::TryInto;
+use zeroize::Zeroize;
+
+/// Reduction constant for GCM's bit-reflected representation of x^128 + x^7 + x^2 + x + 1
+const R: u128 = 0xE1 << 120;
+
+/// Multiplies `x` by `y` in GCM's field representation.
+fn gf128_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0u128;
+    let mut v = y;
+    for i in 0..128 {
+        let bit = (x >> (127 - i)) & 1;
+        z ^= v & 0u128.wrapping_sub(bit);
+        let lsb = v & 1;
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(lsb));
+    }
+    z
+}
+
+/// Multiplies by x in GCM's field representation.
+fn gf128_mulx(v: u128) -> u128 {
+    (v >> 1) ^ (R & 0u128.wrapping_sub(v & 1))
+}
+
+pub(crate) struct GHash {
+    h: u128,
+    y: u128,
+    /// When set, blocks are interpreted as POLYVAL field elements
+    polyval: bool,
+}
+
+impl GHash {
+    pub(crate) fn new(h: &[u8; BLOCK_SIZE]) -> Self {
+        GHash { h: u128::from_be_bytes(*h), y: 0, polyval: false }
+    }
+
+    /// POLYVAL(H, X) = ByteReverse(GHASH(mulX_GHASH(ByteReverse(H)), ByteReverse(X)))
+    pub(crate) fn new_polyval(h: &[u8; BLOCK_SIZE]) -> Self {
+        GHash { h: gf128_mulx(u128::from_le_bytes(*h)), y: 0, polyval: true }
+    }
+
+    fn load(&self, block: &[u8; BLOCK_SIZE]) -> u128 {
+        if self.polyval {
+            u128::from_le_bytes(*block)
+        } else {
+            u128::from_be_bytes(*block)
+        }
+    }
+
+    pub(crate) fn update_block(&mut self, block: &[u8; BLOCK_SIZE]) {
+        self.y = gf128_mul(self.y ^ self.load(block), self.h);
+    }
+
+    /// Absorbs `data`, zero-padding the final partial block.
+    pub(crate) fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            if chunk.len() == BLOCK_SIZE {
+                self.update_block(chunk.try_into().unwrap());
+            } else {
+                let mut block = [0u8; BLOCK_SIZE];
+                block[..chunk.len()].copy_from_slice(chunk);
+                self.update_block(&block);
+                block.zeroize();
+            }
+        }
+    }
+
+    pub(crate) fn finalize(self) -> [u8; BLOCK_SIZE] {
+        if self.polyval {
+            self.y.to_le_bytes()
+        } else {
+            self.y.to_be_bytes()
+        }
+    }
+}
+
+impl Drop for GHash {
+    fn drop(&mut self) {
+        self.h.zeroize();
+        self.y.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn polyval_rfc8452() {
+        // RFC 8452 appendix A
+        let h = hex!("25629347589242761d31f826ba4b757b");
+        let mut polyval = GHash::new_polyval(&h);
+        polyval.update_block(&hex!("4f4f95668c83dfb6401762bb2d01a262"));
+        polyval.update_block(&hex!("d1a24ddd2721d006bbe45f20d3c9f362"));
+        assert_eq!(polyval.finalize(), hex!("f7a3b47b846119fae5b7866cf5e5b77e"));
+    }
+
+    #[test]
+    fn ghash_rfc8452() {
+        // the same example, run through GHASH directly
+        let h = hex!("25629347589242761d31f826ba4b757b");
+        let mut ghash = GHash::new(&h);
+        ghash.update_block(&hex!("4f4f95668c83dfb6401762bb2d01a262"));
+        ghash.update_block(&hex!("d1a24ddd2721d006bbe45f20d3c9f362"));
+        assert_eq!(ghash.finalize(), hex!("bd9b3997046731fb96251b91f9c99d7a"));
+    }
+}
@@ -315,8 +315,10 @@ impl Resolver {
     pub fn new(xns: &xous_names::XousNames) -> Resolver {
         let trng = trng::Trng::new(&xns).unwrap();
         let local_port = (49152 + trng.get_u32().unwrap() % 16384) as u16;
+        // the Net server binds UDP by port alone, so the address only matters in hosted mode, where
+        // an unspecified one keeps the resolver on a host socket that can reach real DNS servers
         let mut socket = net::UdpSocket::bind_xous(
-            format!("127.0.0.1:{}", local_port),
+            format!("0.0.0.0:{}", local_port),
             Some(DNS_PKT_MAX_LEN as u16),
         )
         .expect("couldn't create socket for DNS resolver");
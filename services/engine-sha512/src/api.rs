@@ -4,6 +4,7 @@ pub(crate) const SERVER_NAME_SHA512: &str = "_Sha512 hardware accelerator server
 pub(crate) enum Sha2Result {
     Sha512Result([u8; 64]),
     Sha512Trunc256Result([u8; 32]),
+    Sha256Result([u8; 32]),
     SuspendError,
     Uninitialized,
     IdMismatch,
@@ -20,6 +21,8 @@ pub(crate) struct Sha2Finalize {
 pub(crate) enum Sha2Config {
     Sha512,
     Sha512Trunc256,
+    /// not supported by the current SoC's engine; the server computes it in software, under the same lock
+    Sha256,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
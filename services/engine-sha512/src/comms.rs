@@ -0,0 +1,213 @@
+//! Connection state and hardware-lock boilerplate shared by every digest that is dispatched
+//! through the Sha2 engine server.
+
+use core::sync::atomic::AtomicU32;
+
+/// we have to make the HW_CONN static because the Digest crate assumes you can clone objects
+/// and recycle them. However, it's not a problem for every server to have a unique connection
+/// to the hasher service, if that's what it comes down to. The burden for tracking connections is on the
+/// connector's side, not on the server's side; so when the connecting process that calls this
+/// library dies, this static data dies with it.
+pub(crate) static HW_CONN: AtomicU32 = AtomicU32::new(0);
+/// a unique-enough random ID number to prove we own our connection to the hashing engine hardware
+pub(crate) static TOKEN: [AtomicU32; 3] = [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)];
+
+/// Size of the data area of one `Sha2Update` message
+pub(crate) const UPDATE_CHUNK: usize = 3968;
+
+// a macro for common communications libraries for SHA2 hardware interfacing
+// you can't reference fields in a trait. looks like a macro is the accepted way
+// of not having to repeat this code over and over again.
+macro_rules! sha2_comms {
+    () => {
+        pub(crate) fn ensure_conn(&self) -> u32 {
+            if HW_CONN.load(Ordering::Relaxed) == 0 {
+                let xns = xous_names::XousNames::new().unwrap();
+                HW_CONN.store(
+                    xns.request_connection_blocking(crate::api::SERVER_NAME_SHA512)
+                        .expect("Can't connect to Sha512 server"),
+                    Ordering::Relaxed,
+                );
+                let trng = trng::Trng::new(&xns).expect("Can't connect to TRNG server");
+                let id1 = trng.get_u64().unwrap();
+                let id2 = trng.get_u32().unwrap();
+                TOKEN[0].store((id1 >> 32) as u32, Ordering::Relaxed);
+                TOKEN[1].store(id1 as u32, Ordering::Relaxed);
+                TOKEN[2].store(id2, Ordering::Relaxed);
+            }
+            HW_CONN.load(Ordering::Relaxed)
+        }
+        pub fn is_idle(&self) -> Result<bool, xous::Error> {
+            let response = send_message(
+                self.ensure_conn(),
+                Message::new_blocking_scalar(Opcode::IsIdle.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .expect("Couldn't make IsIdle query");
+            if let xous::Result::Scalar1(result) = response {
+                if result != 0 {
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            } else {
+                Err(xous::Error::InternalError)
+            }
+        }
+        pub fn acquire_suspend_lock(&self) -> Result<bool, xous::Error> {
+            let response = send_message(
+                self.ensure_conn(),
+                Message::new_blocking_scalar(
+                    Opcode::AcquireSuspendLock.to_usize().unwrap(),
+                    0,
+                    0,
+                    0,
+                    0,
+                ),
+            )
+            .expect("Couldn't issue AcquireSuspendLock message");
+            if let xous::Result::Scalar1(result) = response {
+                if result != 0 {
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            } else {
+                Err(xous::Error::InternalError)
+            }
+        }
+        pub fn abort_suspend(&self) -> Result<(), xous::Error> {
+            // we ignore the result and just turn it into () once we get anything back, as abort_suspend "can't fail"
+            send_message(
+                self.ensure_conn(),
+                Message::new_blocking_scalar(
+                    Opcode::AbortSuspendLock.to_usize().unwrap(),
+                    0,
+                    0,
+                    0,
+                    0,
+                ),
+            )
+            .map(|_| ())
+        }
+        pub(crate) fn try_acquire_hw(&mut self, config: Sha2Config) {
+            if !self.in_progress && (self.strategy != FallbackStrategy::SoftwareOnly) {
+                loop {
+                    let conn = self.ensure_conn(); // also ensures the ID
+                    let response = send_message(
+                        conn,
+                        Message::new_blocking_scalar(
+                            Opcode::AcquireExclusive.to_usize().unwrap(),
+                            TOKEN[0].load(Ordering::Relaxed) as usize,
+                            TOKEN[1].load(Ordering::Relaxed) as usize,
+                            TOKEN[2].load(Ordering::Relaxed) as usize,
+                            config.to_usize().unwrap(),
+                        ),
+                    )
+                    .expect("couldn't send AcquireExclusive message to Sha2 hardware!");
+                    if let xous::Result::Scalar1(result) = response {
+                        if result != 0 {
+                            self.use_soft = false;
+                            self.in_progress = true;
+                            break;
+                        } else {
+                            if self.strategy == FallbackStrategy::HardwareThenSoftware {
+                                self.use_soft = true;
+                                self.in_progress = true;
+                                break;
+                            } else {
+                                // this is hardware-exclusive mode, we block until we can get the hardware
+                                xous::yield_slice();
+                            }
+                        }
+                    } else {
+                        log::error!("AcquireExclusive had an unexpected error: {:?}", response);
+                        panic!("Internal error in AcquireExclusive");
+                    }
+                }
+            } else if self.strategy == FallbackStrategy::SoftwareOnly {
+                self.use_soft = true;
+                self.in_progress = true;
+            }
+        }
+        pub(crate) fn reset_hw(&mut self) {
+            send_message(
+                self.ensure_conn(),
+                Message::new_blocking_scalar(
+                    Opcode::Reset.to_usize().unwrap(),
+                    TOKEN[0].load(Ordering::Relaxed) as usize,
+                    TOKEN[1].load(Ordering::Relaxed) as usize,
+                    TOKEN[2].load(Ordering::Relaxed) as usize,
+                    0,
+                ),
+            )
+            .expect("couldn't send reset to hardware");
+            // reset internal flags
+            self.length = 0;
+            self.in_progress = false;
+            self.use_soft = true;
+        }
+        /// split the incoming data to page size and send it to the engine
+        pub(crate) fn update_hw(&mut self, input: &[u8]) {
+            for chunk in input.chunks(crate::comms::UPDATE_CHUNK) {
+                // one SHA512 block (128 bytes) short of 4096 to give space for struct overhead in page remap handling
+                let mut update = Sha2Update {
+                    id: [
+                        TOKEN[0].load(Ordering::Relaxed),
+                        TOKEN[1].load(Ordering::Relaxed),
+                        TOKEN[2].load(Ordering::Relaxed),
+                    ],
+                    buffer: [0; crate::comms::UPDATE_CHUNK],
+                    len: 0,
+                };
+                self.length += (chunk.len() as u64) * 8; // we need to keep track of length in bits
+                for (&src, dest) in chunk.iter().zip(&mut update.buffer) {
+                    *dest = src;
+                }
+                update.len = chunk.len() as u16;
+                let buf = Buffer::into_buf(update).expect("couldn't map chunk into IPC buffer");
+                buf.lend(self.ensure_conn(), Opcode::Update.to_u32().unwrap())
+                    .expect("hardware rejected our hash chunk!");
+            }
+        }
+        /// requests the digest from the engine; panics on any result other than `Sha2Result::Uninitialized`
+        /// that doesn't carry a digest, and if the engine hashed a different number of bits than we sent it.
+        pub(crate) fn finalize_hw(&mut self) -> Sha2Result {
+            let result = Sha2Finalize {
+                id: [
+                    TOKEN[0].load(Ordering::Relaxed),
+                    TOKEN[1].load(Ordering::Relaxed),
+                    TOKEN[2].load(Ordering::Relaxed),
+                ],
+                result: Sha2Result::Uninitialized,
+                length_in_bits: None,
+            };
+            let mut buf =
+                Buffer::into_buf(result).expect("couldn't map memory for the return buffer");
+            buf.lend_mut(self.ensure_conn(), Opcode::Finalize.to_u32().unwrap())
+                .expect("couldn't finalize");
+
+            let returned: Sha2Finalize = buf.to_original().expect("couldn't decode return buffer");
+            match returned.result {
+                Sha2Result::SuspendError => {
+                    panic!("Hardware was suspended during Sha2 operation, result is invalid.");
+                }
+                Sha2Result::Uninitialized => {
+                    panic!("Hardware didn't copy Sha2 hash result to the return buffer.");
+                }
+                Sha2Result::IdMismatch => {
+                    panic!("Hardware is not currently processing our block, finalize call has no meaning.");
+                }
+                _ => {}
+            }
+            log::debug!("bits hashed: {}", self.length);
+            if self.length
+                != returned
+                    .length_in_bits
+                    .expect("hardware did not return a length field!")
+            {
+                panic!("Sha2 hardware did not hash as many bits as we had expected!")
+            }
+            returned.result
+        }
+    };
+}
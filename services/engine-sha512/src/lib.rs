@@ -3,6 +3,8 @@
 mod api;
 pub use api::*;
 mod consts;
+#[macro_use]
+mod comms;
 
 mod sha256;
 mod sha512;
@@ -97,6 +97,15 @@ mod implementation {
             engine512
         }
 
+        /// The sha512 core implements the SHA-512 round function only; SHA-512/256 is just a different
+        /// IV, but SHA-256 has its own round function and has to be done in software.
+        pub(crate) fn supports(&self, config: Sha2Config) -> bool {
+            match config {
+                Sha2Config::Sha512 | Sha2Config::Sha512Trunc256 => true,
+                Sha2Config::Sha256 => false,
+            }
+        }
+
         pub(crate) fn setup(&mut self, config: Sha2Config) {
             self.csr.wfo(utra::sha512::POWER_ON, 1);
             match config {
@@ -117,6 +126,7 @@ mod implementation {
                             | self.csr.ms(utra::sha512::CONFIG_SELECT_256, 1),
                     );
                 }
+                Sha2Config::Sha256 => panic!("Sha256 is not supported by the Sha512 engine"),
             }
             self.csr.wfo(utra::sha512::COMMAND_HASH_START, 1);
             self.csr.wfo(utra::sha512::EV_ENABLE_SHA512_DONE, 1);
@@ -242,7 +252,7 @@ mod implementation {
     }
 }
 
-// a stub to try to avoid breaking hosted mode for as long as possible.
+// hosted mode has no hashing hardware: the stub claims no digests, so every job is run by `SoftJob`.
 #[cfg(not(any(target_os = "none", target_os = "xous")))]
 mod implementation {
     use crate::Sha2Config;
@@ -254,6 +264,9 @@ mod implementation {
         pub(crate) fn new() -> Engine512 {
             Engine512 {}
         }
+        pub(crate) fn supports(&self, _config: Sha2Config) -> bool {
+            false
+        }
         pub(crate) fn suspend(&self) {}
         pub(crate) fn resume(&self) {}
         pub(crate) fn reset(&self) {}
@@ -268,6 +281,53 @@ mod implementation {
     }
 }
 
+/// Digests the engine can't compute are run in software by the server instead, under the same
+/// exclusive lock, so that clients see one protocol regardless of what the SoC supports. In hosted
+/// mode every digest takes this path, which is what lets the shellchat `sha` checks run there.
+enum SoftJob {
+    Sha512(sha2::Sha512),
+    Sha512Trunc256(sha2::Sha512Trunc256),
+    Sha256(sha2::Sha256),
+}
+impl SoftJob {
+    fn new(config: Sha2Config) -> SoftJob {
+        use sha2::FallbackStrategy::SoftwareOnly;
+        match config {
+            Sha2Config::Sha512 => SoftJob::Sha512(sha2::Sha512::new_with_strategy(SoftwareOnly)),
+            Sha2Config::Sha512Trunc256 => SoftJob::Sha512Trunc256(sha2::Sha512Trunc256::new_with_strategy(SoftwareOnly)),
+            Sha2Config::Sha256 => SoftJob::Sha256(sha2::Sha256::new_with_strategy(SoftwareOnly)),
+        }
+    }
+    fn update(&mut self, buf: &[u8]) {
+        use sha2::Digest;
+        match self {
+            SoftJob::Sha512(hasher) => hasher.update(buf),
+            SoftJob::Sha512Trunc256(hasher) => hasher.update(buf),
+            SoftJob::Sha256(hasher) => hasher.update(buf),
+        }
+    }
+    fn finalize(self) -> Sha2Result {
+        use sha2::Digest;
+        match self {
+            SoftJob::Sha512(hasher) => {
+                let mut hash = [0u8; 64];
+                hash.copy_from_slice(&hasher.finalize());
+                Sha2Result::Sha512Result(hash)
+            }
+            SoftJob::Sha512Trunc256(hasher) => {
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&hasher.finalize());
+                Sha2Result::Sha512Trunc256Result(hash)
+            }
+            SoftJob::Sha256(hasher) => {
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&hasher.finalize());
+                Sha2Result::Sha256Result(hash)
+            }
+        }
+    }
+}
+
 static HASH_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
 static SUSPEND_FAILURE: AtomicBool = AtomicBool::new(false);
 static SUSPEND_PENDING: AtomicBool = AtomicBool::new(false);
@@ -349,6 +409,8 @@ fn xmain() -> ! {
 
     let mut client_id: Option<[u32; 3]> = None;
     let mut mode: Option<Sha2Config> = None;
+    let mut soft_job: Option<SoftJob> = None;
+    let mut soft_length_in_bits: u64 = 0;
     let mut job_count = 0;
     loop {
         let mut msg = xous::receive_message(engine512_sid).unwrap();
@@ -358,10 +420,16 @@ fn xmain() -> ! {
                     if client_id.is_none() && !SUSPEND_PENDING.load(Ordering::Relaxed) {
                         client_id = Some([id0 as u32, id1 as u32, id2 as u32]);
                         //log::trace!("giving {:x?} an exclusive lock", client_id);
-                        mode = Some(FromPrimitive::from_usize(flags).unwrap());
+                        let config: Sha2Config = FromPrimitive::from_usize(flags).unwrap();
+                        mode = Some(config);
                         SUSPEND_FAILURE.store(false, Ordering::Relaxed);
                         HASH_IN_PROGRESS.store(true, Ordering::Relaxed);
-                        engine512.setup(mode.unwrap());
+                        if engine512.supports(config) {
+                            engine512.setup(config);
+                        } else {
+                            soft_job = Some(SoftJob::new(config));
+                            soft_length_in_bits = 0;
+                        }
                         xous::return_scalar(msg.sender, 1).unwrap();
                     } else {
                         xous::return_scalar(msg.sender, 0).unwrap();
@@ -376,6 +444,7 @@ fn xmain() -> ! {
                             HASH_IN_PROGRESS.store(false, Ordering::Relaxed);
                             client_id = None;
                             mode = None;
+                            soft_job = None;
                             engine512.reset();
                             xous::return_scalar(msg.sender, 1).unwrap();
                         } else {
@@ -394,7 +463,13 @@ fn xmain() -> ! {
                 match client_id {
                     Some(id) => {
                         if id == update.id {
-                            engine512.update(&update.buffer[..update.len as usize]);
+                            let data = &update.buffer[..update.len as usize];
+                            if let Some(job) = soft_job.as_mut() {
+                                job.update(data);
+                                soft_length_in_bits += data.len() as u64 * 8;
+                            } else {
+                                engine512.update(data);
+                            }
                         }
                     }
                     _ => {
@@ -414,7 +489,11 @@ fn xmain() -> ! {
                 match client_id {
                     Some(id) => {
                         if id == finalized.id {
-                            if SUSPEND_FAILURE.load(Ordering::Relaxed) {
+                            if let Some(job) = soft_job.take() {
+                                // software state lives in RAM, so a suspend in the middle doesn't invalidate it
+                                finalized.result = job.finalize();
+                                finalized.length_in_bits = Some(soft_length_in_bits);
+                            } else if SUSPEND_FAILURE.load(Ordering::Relaxed) {
                                 finalized.result = Sha2Result::SuspendError;
                                 finalized.length_in_bits = None;
                             } else {
@@ -430,7 +509,8 @@ fn xmain() -> ! {
                                         finalized.result = Sha2Result::Sha512Trunc256Result(trunc);
                                         finalized.length_in_bits = Some(length_in_bits);
                                     }
-                                    None => {
+                                    // not reachable: supports() routes Sha256 to a SoftJob
+                                    Some(Sha2Config::Sha256) | None => {
                                         finalized.result = Sha2Result::Uninitialized;
                                     }
                                 }
@@ -3,6 +3,15 @@ mod soft;
 use soft::compress;
 
 use crate::consts::{H224, H256, STATE_LEN};
+
+use crate::api::*;
+use num_traits::ToPrimitive;
+use xous::{send_message, Message};
+use xous_ipc::Buffer;
+
+use crate::comms::{HW_CONN, TOKEN};
+use core::sync::atomic::Ordering;
+
 use block_buffer::BlockBuffer;
 use core::slice::from_ref;
 use digest::consts::{U28, U32, U64};
@@ -51,15 +60,52 @@ impl Engine256 {
 }
 
 /// The SHA-256 hash algorithm with the SHA-256 initial hash value.
+///
+/// Hashing is dispatched through the Sha2 engine server, which holds the lock on the
+/// hardware for the duration of the hash. The engine on the current SoC has no SHA-256
+/// datapath, so the server computes it in software; clients still get the same lock and
+/// fallback semantics as `Sha512`, and pick up acceleration if a future engine adds it.
 #[derive(Clone)]
 pub struct Sha256 {
+    /// software fallback engine
     engine: Engine256,
+    /// whether or not this current hasher instance will use software or hardware acceleration
+    use_soft: bool,
+    /// specifies the strategy for fallback in case multiple hashes are initiated simultaneously
+    strategy: FallbackStrategy,
+    /// track if a hash is in progress
+    in_progress: bool,
+    /// track the length of the message processed so far
+    length: u64,
+}
+impl Sha256 {
+    // use this function instead of default for more control over configuration of the hardware engine
+    pub fn new() -> Self {
+        Sha256::new_with_strategy(FallbackStrategy::HardwareThenSoftware)
+    }
+    pub fn new_with_strategy(strat: FallbackStrategy) -> Self {
+        Sha256 {
+            use_soft: strat == FallbackStrategy::SoftwareOnly,
+            strategy: strat,
+            engine: Engine256::new(&H256),
+            in_progress: false,
+            length: 0,
+        }
+    }
+    // make all the boilerplate comms code shared between all sizes of digest
+    sha2_comms!();
 }
 
 impl Default for Sha256 {
     fn default() -> Self {
-        Sha256 {
-            engine: Engine256::new(&H256),
+        Sha256::new_with_strategy(FallbackStrategy::HardwareThenSoftware)
+    }
+}
+
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        if !self.use_soft {
+            self.reset_hw();
         }
     }
 }
@@ -70,7 +116,12 @@ impl BlockInput for Sha256 {
 
 impl Update for Sha256 {
     fn update(&mut self, input: impl AsRef<[u8]>) {
-        self.engine.update(input.as_ref());
+        self.try_acquire_hw(Sha2Config::Sha256);
+        if self.use_soft {
+            self.engine.update(input.as_ref());
+        } else {
+            self.update_hw(input.as_ref());
+        }
     }
 }
 
@@ -78,17 +129,32 @@ impl FixedOutputDirty for Sha256 {
     type OutputSize = U32;
 
     fn finalize_into_dirty(&mut self, out: &mut digest::Output<Self>) {
-        self.engine.finish();
-        let s = self.engine.state;
-        for (chunk, v) in out.chunks_exact_mut(4).zip(s.iter()) {
-            chunk.copy_from_slice(&v.to_be_bytes());
+        if self.use_soft {
+            self.engine.finish();
+            let s = self.engine.state;
+            for (chunk, v) in out.chunks_exact_mut(4).zip(s.iter()) {
+                chunk.copy_from_slice(&v.to_be_bytes());
+            }
+        } else {
+            match self.finalize_hw() {
+                Sha2Result::Sha256Result(s) => {
+                    out.copy_from_slice(&s);
+                }
+                _ => {
+                    panic!("Sha2 engine returned the wrong type of buffer!");
+                }
+            }
         }
     }
 }
 
 impl Reset for Sha256 {
     fn reset(&mut self) {
-        self.engine.reset(&H256);
+        if self.use_soft {
+            self.engine.reset(&H256);
+        } else {
+            self.reset_hw();
+        }
     }
 }
 
@@ -1,4 +1,4 @@
-use graphics_server::api::{Rectangle, TextView, Line, RoundedRectangle, Circle, Point, Gid};
+use graphics_server::api::{Rectangle, TextView, Line, RoundedRectangle, Circle, Point, Gid, Tile};
 use xous_ipc::String;
 
 pub(crate) const SERVER_NAME_GAM: &str      = "_Graphical Abstraction Manager_";
@@ -9,6 +9,8 @@ pub enum GamObjectType {
     Circ(Circle),
     Rect(Rectangle),
     RoundRect(RoundedRectangle),
+    /// a block of bitmap data; use `Gam::draw_bitmap` to send images larger than one tile
+    Tile(Tile),
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
@@ -224,14 +226,53 @@ pub(crate) struct MenuManagement {
     pub(crate) op: MenuMgrOp,
 }
 
-#[allow(dead_code)] // here until Memory types are implemented
 #[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub enum MenuPayload {
     /// memorized scalar payload
     Scalar([u32; 4]),
-    /// this a nebulous-but-TBD maybe way of bodging in a more complicated record, which would involve
-    /// casting this memorized, static payload into a Buffer and passing it on. Let's not worry too much about it for now, it's mostly apirational...
-    Memory(([u8; 256], usize)),
+    /// memorized buffer payload: the data and the number of valid bytes in it. On selection, this is
+    /// delivered to the action target as a `MenuMemoryPayload` in a `Buffer`, so one opcode can serve
+    /// many items that differ only by e.g. the name of the thing being acted upon.
+    Memory(([u8; MAX_MENU_PAYLOAD], usize)),
+}
+impl MenuPayload {
+    /// Creates a memory payload from a string. Returns `None` if the string is too long to fit.
+    pub fn from_text(s: &str) -> Option<MenuPayload> {
+        MenuPayload::from_slice(s.as_bytes())
+    }
+    /// Creates a memory payload from raw bytes, e.g. a small serialized record. Returns `None` if the data is too long to fit.
+    pub fn from_slice(data: &[u8]) -> Option<MenuPayload> {
+        if data.len() > MAX_MENU_PAYLOAD {
+            return None;
+        }
+        let mut buf = [0u8; MAX_MENU_PAYLOAD];
+        buf[..data.len()].copy_from_slice(data);
+        Some(MenuPayload::Memory((buf, data.len())))
+    }
+}
+/// Maximum size of a `MenuPayload::Memory` payload
+pub const MAX_MENU_PAYLOAD: usize = 256;
+/// What the action target of a `MenuPayload::Memory` menu item receives, as a `Buffer`
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct MenuMemoryPayload {
+    pub data: [u8; MAX_MENU_PAYLOAD],
+    pub len: u32,
+}
+impl MenuMemoryPayload {
+    /// What a `MenuPayload::Memory((data, len))` item delivers when it is selected
+    pub(crate) fn new(data: [u8; MAX_MENU_PAYLOAD], len: usize) -> MenuMemoryPayload {
+        MenuMemoryPayload {
+            data,
+            len: len.min(MAX_MENU_PAYLOAD) as u32,
+        }
+    }
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..(self.len as usize).min(MAX_MENU_PAYLOAD)]
+    }
+    /// Interprets the payload as a string, as created by `MenuPayload::from_text`
+    pub fn as_str(&self) -> Option<&str> {
+        core::str::from_utf8(self.as_slice()).ok()
+    }
 }
 #[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct MenuItem {
@@ -270,4 +311,30 @@ impl CanvasType {
 pub struct GidRecord {
     pub gid: Gid,
     pub canvas_type: CanvasType,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn delivered(payload: MenuPayload) -> MenuMemoryPayload {
+        match payload {
+            MenuPayload::Memory((data, len)) => MenuMemoryPayload::new(data, len),
+            MenuPayload::Scalar(_) => panic!("expected a memory payload"),
+        }
+    }
+    #[test]
+    fn menu_text_payload() {
+        let payload = delivered(MenuPayload::from_text("open basis: 工作").unwrap());
+        assert_eq!(payload.as_str(), Some("open basis: 工作"));
+        assert_eq!(payload.len as usize, "open basis: 工作".len());
+    }
+    #[test]
+    fn menu_payload_limits() {
+        let full = [0xa5u8; MAX_MENU_PAYLOAD];
+        assert_eq!(delivered(MenuPayload::from_slice(&full).unwrap()).as_slice(), &full[..]);
+        assert!(MenuPayload::from_slice(&[0u8; MAX_MENU_PAYLOAD + 1]).is_none());
+        // a corrupt length can't read past the end of the data
+        assert_eq!(MenuMemoryPayload::new(full, usize::MAX).as_slice().len(), MAX_MENU_PAYLOAD);
+        assert_eq!(delivered(MenuPayload::from_text("").unwrap()).as_str(), Some(""));
+    }
+}
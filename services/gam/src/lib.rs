@@ -12,7 +12,8 @@ pub mod apps;
 pub use apps::*;
 
 use graphics_server::api::{TextOp, TextView};
-use graphics_server::api::{Point, Gid, Line, Rectangle, Circle, RoundedRectangle, TokenClaim};
+use graphics_server::api::{Point, Gid, Line, Rectangle, Circle, RoundedRectangle, TokenClaim, Tile};
+pub use graphics_server::api::Bitmap;
 pub use graphics_server::api::GlyphStyle;
 pub use graphics_server::api::PixelColor;
 use api::Opcode; // if you prefer to map the api into your local namespace
@@ -196,6 +197,29 @@ impl Gam {
         let buf = Buffer::into_buf(go).or(Err(xous::Error::InternalError))?;
         buf.lend(self.conn, Opcode::RenderObject.to_u32().unwrap()).map(|_|())
     }
+    pub fn draw_tile(&self, gid: Gid, tile: Tile) -> Result<(), xous::Error> {
+        let go = GamObject {
+            canvas: gid,
+            obj: GamObjectType::Tile(tile),
+        };
+        let buf = Buffer::into_buf(go).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::RenderObject.to_u32().unwrap()).map(|_|())
+    }
+    /// Draws a bitmap of any size, by splitting it into tiles and sending them as object lists
+    pub fn draw_bitmap(&self, gid: Gid, bitmap: &Bitmap) -> Result<(), xous::Error> {
+        let mut list = GamObjectList::new(gid);
+        for tile in bitmap.tiles() {
+            if let Err(item) = list.push(GamObjectType::Tile(tile)) {
+                self.draw_list(list)?;
+                list = GamObjectList::new(gid);
+                list.push(item).unwrap();
+            }
+        }
+        if list.list[0].is_some() {
+            self.draw_list(list)?;
+        }
+        Ok(())
+    }
     pub fn draw_list(&self, list: GamObjectList) -> Result<(), xous::Error> {
         let buf = Buffer::into_buf(list).or(Err(xous::Error::InternalError))?;
         buf.lend(self.conn, Opcode::RenderObjectList.to_u32().unwrap())
@@ -337,6 +337,14 @@ fn xmain() -> ! {
                                     canvas.clip_rect(),
                                 ).expect("couldn't draw rounded rectangle");
                             }
+                            GamObjectType::Tile(mut tile) => {
+                                tile.translate(canvas.clip_rect().tl);
+                                tile.translate(canvas.pan_offset());
+                                gfx.draw_tile_clipped(
+                                    tile,
+                                    canvas.clip_rect(),
+                                ).expect("couldn't draw tile");
+                            }
                         }
                         canvas.do_drawn().expect("couldn't set canvas to drawn");
                     } else {
@@ -377,6 +385,11 @@ fn xmain() -> ! {
                                         rr.translate(canvas.pan_offset());
                                         obj_list.push(ClipObjectType::RoundRect(rr), canvas.clip_rect()).unwrap();
                                     }
+                                    GamObjectType::Tile(mut tile) => {
+                                        tile.translate(canvas.clip_rect().tl);
+                                        tile.translate(canvas.pan_offset());
+                                        obj_list.push(ClipObjectType::Tile(tile), canvas.clip_rect()).unwrap();
+                                    }
                                 }
                             } else {
                                 break;
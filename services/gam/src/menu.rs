@@ -132,10 +132,12 @@ impl<'a> Menu<'a> {
         log::debug!("add_item requesting bounds of {:?}", new_bounds);
         self.gam.set_canvas_bounds_request(&mut new_bounds).expect("couldn't call set bounds");
     }
-    // note: this routine has yet to be tested. (remove this comment once it has been actually used by something)
     pub fn delete_item(&mut self, item: &str) -> bool {
         let len_before = self.items.len();
         self.items.retain(|&candidate| candidate.name.as_str().unwrap() != item);
+        if self.index >= self.items.len() {
+            self.index = 0;
+        }
 
         // now, recompute the height
         let mut total_items = self.num_items();
@@ -310,8 +312,9 @@ impl<'a> Menu<'a> {
                                         args[0] as usize, args[1] as usize, args[2] as usize, args[3] as usize)
                                 ).expect("couldn't send menu action");
                             },
-                            MenuPayload::Memory((_buf, _len)) => {
-                                unimplemented!("menu buffer targets are a future feature");
+                            MenuPayload::Memory((data, len)) => {
+                                let buf = Buffer::into_buf(MenuMemoryPayload::new(data, len)).expect("couldn't convert menu payload");
+                                buf.send(action, mi.action_opcode).map(|_| ()).expect("couldn't send menu action");
                             }
                         }
                     }
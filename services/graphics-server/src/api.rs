@@ -26,6 +26,8 @@ pub mod glyphstyle;
 pub use glyphstyle::*;
 pub mod blitstr2;
 pub use blitstr2::*;
+pub mod bitmap;
+pub use bitmap::*;
 
 use std::hash::{Hash, Hasher};
 
@@ -116,6 +118,7 @@ pub enum ClipObjectType {
     Rect(Rectangle),
     RoundRect(RoundedRectangle),
     XorLine(Line),
+    Tile(Tile),
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
@@ -126,7 +129,8 @@ pub struct ClipObject {
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub struct ClipObjectList {
-    // ClipObject is 28 bytes, so 32 of these takes 896 bytes, which is less than a 4k page (the minimum amount that gets remapped)
+    // ClipObject is 28 bytes for shapes, and about 90 bytes once it has to hold a Tile, so 32 of these is under 3k,
+    // which is less than a 4k page (the minimum amount that gets remapped)
     // we limit the length to 32 so we can use the Default initializer to set the None's on the array, otherwise it gets a bit painful.
     pub list: [Option::<ClipObject>; 32],
     free: usize,
@@ -0,0 +1,208 @@
+use crate::api::{Point, Rectangle};
+
+/// Width of a tile in pixels; each row of a tile is one `u32`.
+pub const TILE_WIDTH: i16 = 32;
+/// Height of a tile in pixels. Kept small so that a full object list of tiles still fits in one page.
+pub const TILE_HEIGHT: i16 = 16;
+
+//////////////////////////// TILE
+
+/// A small block of 1-bit-per-pixel image data. Tiles are drawn like any other shape, so they can be
+/// mixed with lines and rectangles in an object list and get clipped to the canvas the same way.
+/// Anything larger than a tile should go through a `Bitmap`.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Tile {
+    /// Top left point of the tile
+    pub tl: Point,
+
+    /// Number of pixels in use, at most `TILE_WIDTH` by `TILE_HEIGHT`
+    pub size: Point,
+
+    /// One word per row; bit 0 is the leftmost pixel. Set bits are drawn dark, clear bits light.
+    pub rows: [u32; TILE_HEIGHT as usize],
+
+    /// Swap dark and light
+    pub invert: bool,
+}
+
+impl Tile {
+    /// Creates a blank (all light) tile. `size` is clamped to the tile dimensions.
+    pub fn new(tl: Point, size: Point) -> Tile {
+        Tile {
+            tl,
+            size: Point::new(size.x.max(0).min(TILE_WIDTH), size.y.max(0).min(TILE_HEIGHT)),
+            rows: [0; TILE_HEIGHT as usize],
+            invert: false,
+        }
+    }
+    /// Sets the pixel at `(x, y)` relative to the tile's top left. Out of range pixels are ignored.
+    pub fn set_pixel(&mut self, x: i16, y: i16, dark: bool) {
+        if x < 0 || y < 0 || x >= self.size.x || y >= self.size.y {
+            return;
+        }
+        if dark {
+            self.rows[y as usize] |= 1 << x;
+        } else {
+            self.rows[y as usize] &= !(1 << x);
+        }
+    }
+    /// Returns true if the pixel at `(x, y)` relative to the tile's top left is dark, before inversion.
+    pub fn get_pixel(&self, x: i16, y: i16) -> bool {
+        if x < 0 || y < 0 || x >= self.size.x || y >= self.size.y {
+            return false;
+        }
+        self.rows[y as usize] & (1 << x) != 0
+    }
+    /// The area covered by the tile, in the same inclusive convention as `Rectangle`
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::new(self.tl, self.tl + self.size - Point::new(1, 1))
+    }
+    pub fn translate(&mut self, offset: Point) {
+        self.tl = self.tl + offset;
+    }
+}
+
+//////////////////////////// BITMAP
+
+/// A 1-bit-per-pixel image of any size, held on the client side and sent to the screen as `Tile`s.
+///
+/// Pixel data is row-major, with each row padded to a whole byte and bit 7 of each byte being the
+/// leftmost pixel. This is the packing used by PBM files and most QR code encoders, so their output
+/// can be handed to `from_bits` as-is. Set bits are dark.
+#[derive(Debug, Clone)]
+pub struct Bitmap {
+    /// Top left point of the image
+    pub tl: Point,
+    /// Swap dark and light
+    pub invert: bool,
+    width: i16,
+    height: i16,
+    bits: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Creates a blank (all light) bitmap
+    pub fn new(tl: Point, width: i16, height: i16) -> Bitmap {
+        let width = width.max(0);
+        let height = height.max(0);
+        Bitmap {
+            tl,
+            invert: false,
+            width,
+            height,
+            bits: vec![0; Bitmap::stride(width) * height as usize],
+        }
+    }
+    /// Wraps existing pixel data. Returns `None` if `bits` is too short for the given dimensions;
+    /// any excess is ignored.
+    pub fn from_bits(tl: Point, width: i16, height: i16, bits: &[u8]) -> Option<Bitmap> {
+        let mut bitmap = Bitmap::new(tl, width, height);
+        let len = bitmap.bits.len();
+        if bits.len() < len {
+            return None;
+        }
+        bitmap.bits.copy_from_slice(&bits[..len]);
+        Some(bitmap)
+    }
+    pub fn width(&self) -> i16 {
+        self.width
+    }
+    pub fn height(&self) -> i16 {
+        self.height
+    }
+    pub fn set_pixel(&mut self, x: i16, y: i16, dark: bool) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+        let index = y as usize * Bitmap::stride(self.width) + x as usize / 8;
+        let mask = 0x80 >> (x as usize % 8);
+        if dark {
+            self.bits[index] |= mask;
+        } else {
+            self.bits[index] &= !mask;
+        }
+    }
+    pub fn get_pixel(&self, x: i16, y: i16) -> bool {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return false;
+        }
+        let index = y as usize * Bitmap::stride(self.width) + x as usize / 8;
+        self.bits[index] & (0x80 >> (x as usize % 8)) != 0
+    }
+    /// Splits the image into tiles, left to right and then top to bottom. Tiles along the right and
+    /// bottom edges are trimmed to the image size.
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..self.height).step_by(TILE_HEIGHT as usize).flat_map(move |ty| {
+            (0..self.width).step_by(TILE_WIDTH as usize).map(move |tx| {
+                let size = Point::new(
+                    (self.width - tx).min(TILE_WIDTH),
+                    (self.height - ty).min(TILE_HEIGHT),
+                );
+                let mut tile = Tile::new(self.tl + Point::new(tx, ty), size);
+                tile.invert = self.invert;
+                for y in 0..size.y {
+                    for x in 0..size.x {
+                        tile.set_pixel(x, y, self.get_pixel(tx + x, ty + y));
+                    }
+                }
+                tile
+            })
+        })
+    }
+    fn stride(width: i16) -> usize {
+        (width as usize + 7) / 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn tile_pixels() {
+        let mut t = Tile::new(Point::new(0, 0), Point::new(40, 8));
+        assert_eq!(t.size, Point::new(TILE_WIDTH, 8));
+        t.set_pixel(0, 0, true);
+        t.set_pixel(31, 7, true);
+        t.set_pixel(3, 8, true); // outside of the used rows
+        assert_eq!(t.rows[0], 1);
+        assert_eq!(t.rows[7], 0x8000_0000);
+        assert_eq!(t.rows[8], 0);
+        t.set_pixel(0, 0, false);
+        assert!(!t.get_pixel(0, 0));
+        assert!(t.get_pixel(31, 7));
+    }
+
+    #[test]
+    fn bitmap_tiling() {
+        // 40x20 needs a 2x2 grid of tiles, with the right and bottom ones trimmed
+        let mut b = Bitmap::new(Point::new(10, 100), 40, 20);
+        b.set_pixel(0, 0, true);
+        b.set_pixel(33, 2, true);
+        b.set_pixel(39, 19, true);
+        let tiles: Vec<Tile> = b.tiles().collect();
+        assert_eq!(tiles.len(), 4);
+        assert_eq!(tiles[0].tl, Point::new(10, 100));
+        assert_eq!(tiles[0].size, Point::new(32, 16));
+        assert_eq!(tiles[1].tl, Point::new(42, 100));
+        assert_eq!(tiles[1].size, Point::new(8, 16));
+        assert_eq!(tiles[2].tl, Point::new(10, 116));
+        assert_eq!(tiles[2].size, Point::new(32, 4));
+        assert_eq!(tiles[3].size, Point::new(8, 4));
+        assert!(tiles[0].get_pixel(0, 0));
+        assert!(tiles[1].get_pixel(1, 2));
+        assert!(tiles[3].get_pixel(7, 3));
+        let dark: u32 = tiles.iter().map(|t| t.rows.iter().map(|r| r.count_ones()).sum::<u32>()).sum();
+        assert_eq!(dark, 3);
+    }
+
+    #[test]
+    fn bitmap_from_bits() {
+        // 10 pixels wide packs into 2 bytes per row, MSB first
+        let b = Bitmap::from_bits(Point::new(0, 0), 10, 2, &[0x80, 0x40, 0x00, 0x01]).unwrap();
+        assert!(b.get_pixel(0, 0));
+        assert!(b.get_pixel(9, 0));
+        assert!(!b.get_pixel(1, 0));
+        assert!(!b.get_pixel(9, 1));
+        assert!(Bitmap::from_bits(Point::new(0, 0), 10, 2, &[0; 3]).is_none());
+    }
+}
@@ -4,7 +4,8 @@
 pub mod api;
 pub use api::{
     Circle, ClipObject, ClipObjectType, DrawStyle, Gid, Line, PixelColor, Point, Rectangle,
-    RoundedRectangle, TextBounds, TextOp, TextView, TokenClaim, ClipRect, Cursor, GlyphStyle, ClipObjectList
+    RoundedRectangle, TextBounds, TextOp, TextView, TokenClaim, ClipRect, Cursor, GlyphStyle, ClipObjectList,
+    Tile, Bitmap, TILE_WIDTH, TILE_HEIGHT,
 };
 pub mod op;
 
@@ -208,6 +209,20 @@ impl Gfx {
             .map(|_| ())
     }
 
+    pub fn draw_tile_clipped(
+        &self,
+        tile: Tile,
+        clip: Rectangle,
+    ) -> Result<(), xous::Error> {
+        let co = ClipObject {
+            clip,
+            obj: ClipObjectType::Tile(tile),
+        };
+        let buf = Buffer::into_buf(co).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::DrawClipObject.to_u32().unwrap())
+            .map(|_| ())
+    }
+
     pub fn draw_object_list_clipped(
         &self,
         list: ClipObjectList,
@@ -156,6 +156,9 @@ fn xmain() -> ! {
                     ClipObjectType::RoundRect(rr) => {
                         op::rounded_rectangle(display.native_buffer(), rr, Some(obj.clip));
                     }
+                    ClipObjectType::Tile(tile) => {
+                        op::tile(display.native_buffer(), tile, Some(obj.clip));
+                    }
                 }
             }
             Some(Opcode::DrawClipObjectList) => {
@@ -180,6 +183,9 @@ fn xmain() -> ! {
                             ClipObjectType::RoundRect(rr) => {
                                 op::rounded_rectangle(display.native_buffer(), rr, Some(obj.clip));
                             }
+                            ClipObjectType::Tile(tile) => {
+                                op::tile(display.native_buffer(), tile, Some(obj.clip));
+                            }
                         }
                     } else {
                         // stop at the first None entry -- if the sender packed the list with a hole in it, that's their bad
@@ -1,4 +1,4 @@
-use crate::api::{Circle, DrawStyle, Line, Pixel, PixelColor, Point, Rectangle, RoundedRectangle};
+use crate::api::{Circle, DrawStyle, Line, Pixel, PixelColor, Point, Rectangle, RoundedRectangle, Tile};
 
 /// LCD Frame buffer bounds
 pub const LCD_WORDS_PER_LINE: usize = 11;
@@ -564,3 +564,31 @@ pub fn rounded_rectangle(fb: &mut LcdFB, rr: RoundedRectangle, clip: Option<Rect
         clip,
     );
 }
+
+/////////////////////////////////////////////////// tile
+
+/// Draws a tile; it is opaque, so every pixel within its size is written, either dark or light.
+pub fn tile(fb: &mut LcdFB, tile: Tile, clip: Option<Rectangle>) {
+    let screen = Rectangle::new(Point::new(0, 0), Point::new(WIDTH - 1, HEIGHT - 1));
+    let bounds = match clip {
+        Some(c) => match screen.clip_with(c) {
+            Some(r) => r,
+            None => return,
+        },
+        None => screen,
+    };
+    for y in 0..tile.size.y {
+        for x in 0..tile.size.x {
+            let p = tile.tl + Point::new(x, y);
+            if !bounds.intersects_point(p) {
+                continue;
+            }
+            let color = if tile.get_pixel(x, y) != tile.invert {
+                PixelColor::Dark
+            } else {
+                PixelColor::Light
+            };
+            put_pixel(fb, p.x, p.y, color);
+        }
+    }
+}
@@ -76,6 +76,9 @@ pub(crate) enum Opcode {
 
     /// Suspend/resume callback
     SuspendResume,
+
+    /// used by host mode key scripts to press or release a key on the matrix
+    InjectRawKey, //(row, col, down)
 }
 
 // this structure is used to register a keyboard listener. Currently, we only accept
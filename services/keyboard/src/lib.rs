@@ -3,6 +3,7 @@
 use num_traits::*;
 
 pub mod api;
+pub mod script;
 
 pub use api::*;
 use xous::{send_message, Message};
@@ -77,6 +78,21 @@ impl Keyboard {
                c as u32 as usize, 0, 0, 0
         )).unwrap();
     }
+
+    #[cfg(not(any(target_os = "none", target_os = "xous")))]
+    pub fn hostmode_inject_raw_key(&self, rc: RowCol, down: bool) {
+        send_message(self.conn,
+            Message::new_scalar(Opcode::InjectRawKey.to_usize().unwrap(),
+               rc.r as usize, rc.c as usize, if down { 1 } else { 0 }, 0
+        )).unwrap();
+    }
+
+    /// Replay a key script, as read by `script::parse_script()`. This blocks until the whole
+    /// script has been sent.
+    #[cfg(not(any(target_os = "none", target_os = "xous")))]
+    pub fn hostmode_play_script(&self, events: &[script::ScriptEvent]) -> Result<(), xous::Error> {
+        script::play_script(self.conn, events)
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -4,6 +4,9 @@
 mod api;
 use api::*;
 mod mappings;
+mod tracking;
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+mod script;
 
 use log::info;
 
@@ -14,53 +17,20 @@ use xous::{CID, msg_scalar_unpack, msg_blocking_scalar_unpack};
 mod implementation {
     use utralib::generated::*;
     use crate::{RowCol, KeyRawStates, api::*};
-    use crate::mappings::*;
-    use ticktimer_server::Ticktimer;
+    use crate::tracking::*;
     use xous::CID;
     use num_traits::ToPrimitive;
     use susres::{RegManager, RegOrField, SuspendResume};
     use std::collections::HashSet;
     use std::convert::TryInto;
 
-    /// note: the code is structured to use at most 16 rows or 16 cols
-    const KBD_ROWS: usize = 9;
-    const KBD_COLS: usize = 10;
-
     pub(crate) struct Keyboard {
         conn: CID,
         csr: utralib::CSR<u32>,
         /// where the interrupt handler copies the new state
         new_state: HashSet::<RowCol>,
-        /// remember the last key states
-        last_state: HashSet::<RowCol>,
-        /// connection to the timer for real-time events
-        ticktimer: Ticktimer,
-        /// mapping for ScanCode translation
-        map: KeyMap,
-        /// delay in ms before a key is considered to be repeating
-        delay: u32,
-        /// rate in ms for repeating a key
-        rate: u32,
-        /// shift key state
-        shift_down: bool,
-        shift_up: bool,
-        /// alt key state
-        alt_down: bool,
-        alt_up: bool,
-        /// timestamp to track repeating key interval
-        rate_timestamp: u64,
-        /// track the last key held down, which lacks a hold alternate meaning, for repeating
-        repeating_key: Option<char>,
-        /// timestamp timekeeper for chording / hold key
-        chord_timestamp: u64,
-        /// chording sample interval
-        chord_interval: u32,
-        /// chord state array
-        chord: [[bool; KBD_COLS]; KBD_ROWS],
-        /// memoize number of keys that have been pressed
-        chord_active: u32,
-        /// indicate if the chord has been captured. Once captured, further presses are ignored, until all keys are let up.
-        chord_captured: bool,
+        /// turns key states into characters
+        tracker: KeyTracker,
         susres: RegManager::<{utra::keyboard::KEYBOARD_NUMREGS}>,
         /// a field used for debugging various keyboard issues, especially with the interrupt handler
         pub debug: usize,
@@ -142,8 +112,6 @@ mod implementation {
             )
             .expect("couldn't map Keyboard pre-boot setting");
 
-            let ticktimer = ticktimer_server::Ticktimer::new().expect("couldn't connect to ticktimer");
-            let timestamp = ticktimer.elapsed_ms();
             let settings: &[u8] = setting_page.as_slice();
 
             let default_map = if cfg!(feature = "braille") {
@@ -164,22 +132,7 @@ mod implementation {
                 conn: xous::connect(sid).unwrap(),
                 csr: CSR::new(csr.as_mut_ptr() as *mut u32),
                 new_state: HashSet::with_capacity(16), // pre-allocate space since this has to work in an interrupt context
-                last_state: HashSet::with_capacity(16),
-                ticktimer,
-                map: default_map,
-                delay: 500,
-                rate: 20,
-                shift_down: false,
-                shift_up: false,
-                alt_down: false,
-                alt_up: false,
-                repeating_key: None,
-                rate_timestamp: timestamp,
-                chord_timestamp: timestamp,
-                chord_interval: 50,
-                chord: [[false; KBD_COLS]; KBD_ROWS],
-                chord_active: 0,
-                chord_captured: false,
+                tracker: KeyTracker::new(default_map),
                 susres: RegManager::new(csr.as_mut_ptr() as *mut u32),
                 debug: 0,
                 settings: setting_page,
@@ -214,15 +167,7 @@ mod implementation {
             // clear the keyboard state vectors -- actually, if a key was being pressed at the time of suspend
             // it's not really relevant anymore; let's throw everything away and start from a clean slate.
             self.new_state.clear();
-            self.last_state.clear();
-            self.shift_down = false;
-            self.shift_up = false;
-            self.alt_down = false;
-            self.alt_up = false;
-            self.repeating_key = None;
-            self.chord_captured = false;
-            self.chord_active = 0;
-            self.chord = [[false; KBD_COLS]; KBD_ROWS];
+            self.tracker.reset();
 
             // ensure interrupts are re-enabled -- this could /shouldn't/ be necessary but we're having
             // some strange resume behavior, trying to see if this resolves it.
@@ -240,24 +185,23 @@ mod implementation {
             self.spinor.patch(settings, xous::EARLY_SETTINGS,
                 &code, 0
             ).expect("couldn't patch our keyboard code");
-            self.map = map;
+            self.tracker.map = map;
         }
         pub(crate) fn get_map(&mut self) -> KeyMap {
             // refresh the map from the setting in the FLASH
             let settings: &[u8] = self.settings.as_slice();
             let code = u32::from_le_bytes(settings[..4].try_into().unwrap());
-            self.map = KeyMap::from(code as usize);
-            self.map
+            self.tracker.map = KeyMap::from(code as usize);
+            self.tracker.map
         }
         pub(crate) fn set_repeat(&mut self, rate: u32, delay: u32) {
-            self.rate = rate;
-            self.delay = delay;
+            self.tracker.set_repeat(rate, delay);
         }
         pub(crate) fn set_chord_interval(&mut self, delay: u32) {
-            self.chord_interval = delay;
+            self.tracker.set_chord_interval(delay);
         }
         pub(crate) fn get_repeat_check_interval(&self) -> u32 {
-            self.rate
+            self.tracker.get_repeat_check_interval()
         }
 
         pub(crate) fn poll(&mut self) {
@@ -284,400 +228,73 @@ mod implementation {
         }
 
         pub(crate) fn update(&mut self) -> KeyRawStates {
-            // EV_PENDING_KEYPRESSED effectively does an XOR of the previous keyboard state
-            // to the current state, which is why update() does not repeatedly issue results
-            // for keys that are pressed & held.
-            log::trace!("update new_state:  {:?}", self.new_state);
-            log::trace!("update last_state: {:?}", self.last_state);
-
-            let mut krs = KeyRawStates::new();
-
-            // compute the key-ups: this would be codes that are in the last_state, but not in the incoming
-            // new_state
-            for &rc in self.last_state.difference(&self.new_state) {
-                krs.keyups.push(rc);
-            }
-
-            // compute key-downs: codes that are in the new_state, but not in last_state
-            for &rc in self.new_state.difference(&self.last_state) {
-                krs.keydowns.push(rc);
-            }
-
-            self.last_state.clear();
-            for &rc in self.new_state.iter() {
-                self.last_state.insert(rc);
-            }
-
-            log::trace!("krs: {:?}", krs);
-            krs
+            self.tracker.update(&self.new_state)
         }
-
         pub(crate) fn track_chord(&mut self, krs: &KeyRawStates) -> Vec<char> {
-            /*
-            Chording algorithm:
-
-            1. Wait for first keydown event to happen; record as pressed in table
-            2. Start chording timer
-            3. Record press/unpress in table
-            4. Wait for chording timer to timeout
-            5. Extract chord state and turn into scancode using lookup table
-            6. Return scancodes
-             */
-            let was_idle = self.chord_active == 0;
-            for rc in krs.keydowns.iter() {
-                log::info!("keydown r: {} c: {}", rc.r, rc.c);
-                self.chord[rc.r as usize][rc.c as usize] = true;
-                self.chord_active += 1;
-            }
-            log::trace!("self.chord: {:?}", self.chord);
-            let mut keystates: Vec<char> = Vec::new();
-
-            let now = self.ticktimer.elapsed_ms();
-            if was_idle && self.chord_active != 0 {
-                // "rising edge" of chord_active
-                self.chord_timestamp = now; // record the beginning of the chord active interval
-            }
-
-            if self.chord_active != 0 && ((now - self.chord_timestamp) >= self.chord_interval as u64) && !self.chord_captured {
-                self.chord_captured = true;
-                log::trace!("interpreting chords");
-                // extract chord state
-                /*
-                    keyboard:
-                    2 1 0 space 3 4 5
-                    braille dots:
-                    0 3
-                    1 4
-                    2 5
-                */
-                /*
-                    7/5  0/1   1/2       5/7     4/8  8/6
-                               2/3       2/3
-                    8/0  6/4                         3/9
-                    8/3  5/2 3/6
-                         8/2
-                 */
-                let keys: [bool; 6] = [
-                    self.chord[1][2],
-                    self.chord[0][1],
-                    self.chord[7][5],
-                    self.chord[5][7],
-                    self.chord[4][8],
-                    self.chord[8][6],
-                ];
-                let mut keycode: usize = 0;
-                for i in 0..keys.len() {
-                    if keys[i] {
-                        keycode |= 1 << i;
-                    }
-                }
-                log::trace!("keycode: 0x{:x}", keycode);
-                let keychar = match keycode {
-                    0b000_001 => Some('a'),
-                    0b000_011 => Some('b'),
-                    0b001_001 => Some('c'),
-                    0b011_001 => Some('d'),
-                    0b010_001 => Some('e'),
-                    0b001_011 => Some('f'),
-                    0b011_011 => Some('g'),
-                    0b010_011 => Some('h'),
-                    0b001_010 => Some('i'),
-                    0b011_010 => Some('j'),
-
-                    0b000_101 => Some('k'),
-                    0b000_111 => Some('l'),
-                    0b001_101 => Some('m'),
-                    0b011_101 => Some('n'),
-                    0b010_101 => Some('o'),
-                    0b001_111 => Some('p'),
-                    0b011_111 => Some('q'),
-                    0b010_111 => Some('r'),
-                    0b001_110 => Some('s'),
-                    0b011_110 => Some('t'),
-
-                    0b100_101 => Some('u'),
-                    0b100_111 => Some('v'),
-                    0b101_101 => Some('x'),
-                    0b111_101 => Some('y'),
-                    0b110_101 => Some('z'),
-                    //0b101_111 => Some(''),
-                    //0b111_111 => Some(''),
-                    //0b1010_111 => Some(''),
-                    //0b101_110 => Some(''),
-                    0b111_010 => Some('w'),
-                    _ => None,
-                };
-                if let Some(key) = keychar {
-                    keystates.push(key);
-                }
-
-                let up = self.chord[6][4];
-                if up { keystates.push('↑'); }
-
-                let left = self.chord[8][3];
-                if left { keystates.push('←'); }
-                let right = self.chord[3][6];
-                if right { keystates.push('→'); }
-                let down = self.chord[8][2];
-                if down { keystates.push('↓'); }
-                let center = self.chord[5][2];
-                if center { keystates.push('∴'); }
-
-                let space = self.chord[2][3];
-                if space { keystates.push(' '); }
-
-                let esc = self.chord[8][0];
-                let bs: char = 0x8_u8.into();  // back space
-                if esc { keystates.push(bs); }
-
-                let func = self.chord[3][9];
-                let cr: char = 0xd_u8.into();  // carriage return
-                if func { keystates.push(cr); }
-
-                log::debug!("up {}, left {}, right {}, down, {}, center, {}, space {}, esc {}, func {}",
-                    up, left, right, down, center, space, esc, func);
-            }
-            for rc in krs.keyups.iter() {
-                self.chord[rc.r as usize][rc.c as usize] = false;
-                if self.chord_active > 0 {
-                    self.chord_active -= 1;
-                } else {
-                    log::error!("received more keyups than we had keydowns!")
-                }
-            }
-            if self.chord_active == 0 {
-                self.chord_captured = false;
-            }
-
-            keystates
+            self.tracker.track_chord(krs)
         }
-
         pub(crate) fn track_keys(&mut self, krs: &KeyRawStates) -> Vec<char> {
-            /*
-              "conventional" keyboard algorithm. The goals of this are to differentiate
-              the cases of "shift", "alt", and "hold".
-
-              thus, we check for the special-case of shift/alt in the keydowns/keyups vectors, and
-              track them as separate modifiers
-
-              then for all others, we note the down time, and compare it to the current time
-              to determine if a "hold" modifier applies
-             */
-            let mut ks: Vec<char> = Vec::new();
-
-            // first check for shift and alt keys
-            for rc in krs.keydowns.iter() {
-                match self.map {
-                    KeyMap::Azerty => {
-                        if (rc.r == 8) && (rc.c == 5) { // left shift (orange)
-                            if self.alt_up == false {
-                                self.alt_down = true;
-                            } else {
-                                self.alt_up = false;
-                            }
-                        } else if (rc.r == 8) && (rc.c == 9) { // right shift (yellow)
-                            if self.shift_up == false {
-                                self.shift_down = true;
-                            } else {
-                                self.shift_up = false;
-                            }
-                        }
-                    },
-                    _ => { // the rest just have one color of shift
-                        if ((rc.r == 8) && (rc.c == 5)) || ((rc.r == 8) && (rc.c == 9)) {
-                            // if the shift key was tapped twice, remove the shift modifier
-                            if self.shift_up == false {
-                                //info!("shift down true");
-                                self.shift_down = true;
-                            } else {
-                                //info!("shift up false");
-                                self.shift_up = false;
-                            }
-                        }
-                    }
-                }
-            }
-            let mut keyups_noshift: Vec::<RowCol> = Vec::new();
-            for &rc in krs.keyups.iter() {
-                match self.map {
-                    KeyMap::Azerty => {
-                        if (rc.r == 8) && (rc.c == 5) { // left shift (orange)
-                            if self.alt_down {
-                                self.alt_up = true;
-                            }
-                            self.alt_down = false;
-                        } else if (rc.r == 8) && (rc.c == 9) { // right shift (yellow)
-                            if self.shift_down {
-                                self.shift_up = true;
-                            }
-                            self.shift_down = false;
-                        } else {
-                            keyups_noshift.push(RowCol{r: rc.r as _, c: rc.c as _});
-                        }
-                    },
-                    _ => { // the rest just have one color of shift
-                        if ((rc.r == 8) && (rc.c == 5)) || ((rc.r == 8) && (rc.c == 9)) {
-                            // only set the shift-up if we didn't previously clear it with a double-tap of shift
-                            if self.shift_down {
-                                //info!("shift up true");
-                                self.shift_up = true;
-                            }
-                            //info!("shift down false");
-                            self.shift_down = false;
-                        } else {
-                            //info!("adding non-shift entry {:?}", rc);
-                            keyups_noshift.push(RowCol{r: rc.r as _, c: rc.c as _});
-                        }
-                    }
-                }
-            }
-
-            // interpret keys in the context of the shift/alt modifiers
-            if !krs.keydowns.is_empty() {
-                self.chord_timestamp = self.ticktimer.elapsed_ms();
-            }
-            for &rc in krs.keydowns.iter() {
-                let code = match self.map {
-                    KeyMap::Qwerty => map_qwerty(rc),
-                    KeyMap::Dvorak => map_dvorak(rc),
-                    KeyMap::Azerty => map_azerty(rc),
-                    KeyMap::Qwertz => map_qwertz(rc),
-                    _ => ScanCode {key: None, shift: None, hold: None, alt: None},
-                };
-                if code.hold == None
-                && !((rc.r == 5) && (rc.c == 2)) // scan code for the menu key
-                 { // if there isn't a pre-defined meaning if the key is held *and* it's not the menu key: it's a repeating key
-                    if let Some(key) = code.key {
-                        self.repeating_key = Some(key);
-                    }
-                }
-            }
-
-            let now = self.ticktimer.elapsed_ms();
-            let hold: bool;
-            if (now - self.chord_timestamp) >= self.delay as u64 {
-                if self.rate_timestamp <= self.chord_timestamp {
-                    self.rate_timestamp = now;
-                }
-                hold = true;
-            } else {
-                hold = false;
-            }
-
-            for &rc in keyups_noshift.iter() {
-                // info!("interpreting keyups_noshift entry {:?}", rc);
-                let code = match self.map {
-                    KeyMap::Qwerty => map_qwerty(rc),
-                    KeyMap::Dvorak => map_dvorak(rc),
-                    KeyMap::Azerty => map_azerty(rc),
-                    KeyMap::Qwertz => map_qwertz(rc),
-                    _ => ScanCode {key: None, shift: None, hold: None, alt: None},
-                };
-                // delete the key repeat if there is one
-                if code.hold == None {
-                    if let Some(key) = code.key {
-                        if let Some(rk) = self.repeating_key {
-                            if rk == key {
-                                self.repeating_key = None;
-                            }
-                        }
-                    }
-                }
-
-                match self.map {
-                    KeyMap::Azerty => {
-                        if self.shift_down || self.shift_up {
-                            if let Some(shiftcode) = code.shift {
-                                ks.push(shiftcode);
-                            } else if let Some(keycode) = code.key {
-                                ks.push(keycode);
-                            }
-                            self.shift_down = false;
-                            self.shift_up = false;
-                        } else if self.alt_down || self.alt_up {
-                            if let Some(altcode) = code.alt {
-                                ks.push(altcode);
-                            } else if let Some(shiftcode) = code.shift {
-                                ks.push(shiftcode);
-                            } else if let Some(keycode) = code.key {
-                                ks.push(keycode);
-                            }
-                            self.alt_down = false;
-                            self.alt_up = false;
-                        } else if hold {
-                            if let Some(holdcode) = code.hold {
-                                ks.push(holdcode);
-                            }
-                        } else {
-                            if let Some(keycode) = code.key {
-                                ks.push(keycode);
-                            }
-                        }
-                    },
-                    _ => {
-                        if self.shift_down || self.alt_down || self.shift_up || self.alt_up {
-                            if let Some(shiftcode) = code.shift {
-                                ks.push(shiftcode);
-                            } else if let Some(keycode) = code.key {
-                                ks.push(keycode);
-                            }
-                            self.shift_down = false;
-                            self.alt_down = false;
-                            self.shift_up = false;
-                            self.alt_up = false;
-                        } else if hold {
-                            if let Some(holdcode) = code.hold {
-                                ks.push(holdcode);
-                            }
-                        } else {
-                            if let Some(keycode) = code.key {
-                                // info!("appeding normal key '{}'", keycode);
-                                ks.push(keycode);
-                            }
-                        }
-                    }
-                }
-            }
-
-            // if we're in a key hold state, we've passed the rate timestamp point, and there's a repeating key defined
-            if hold && ((now - self.rate_timestamp) >= self.rate as u64) && self.repeating_key.is_some() {
-                self.rate_timestamp = now;
-                if let Some(repeatkey) = self.repeating_key {
-                    ks.push(repeatkey);
-                }
-            }
-
-            ks
+            self.tracker.track_keys(krs)
         }
         pub fn is_repeating_key(&self) -> bool {
-            self.repeating_key.is_some()
+            self.tracker.is_repeating_key()
         }
     }
 }
 
-// a stub to try to avoid breaking hosted mode for as long as possible.
+// hosted mode has no key matrix: keys come from the host keyboard, and raw key states from key scripts.
 #[cfg(not(any(target_os = "none", target_os = "xous")))]
 mod implementation {
     use crate::*;
+    use crate::mappings::*;
+    use crate::tracking::*;
+    use crate::script::*;
+    use std::collections::HashSet;
+    use std::io::Write;
 
     #[allow(dead_code)]
     pub(crate) struct Keyboard {
+        sid: xous::SID,
         cid: xous::CID,
-        map: KeyMap,
-        rate: u32,
-        delay: u32,
-        chord_interval: u32,
+        ticktimer: ticktimer_server::Ticktimer,
+        /// key states, as pressed and released by `InjectRawKey`
+        new_state: HashSet::<RowCol>,
+        /// turns key states into characters
+        tracker: KeyTracker,
+        /// dots pressed since the start of the current emulated chord
+        chord: u8,
+        /// the file named by `XOUS_KEY_RECORD`, and the time of the last event written to it
+        recording: Option<(std::fs::File, u64)>,
+        /// set once the script named by `XOUS_KEY_REPLAY` has been started
+        replayed: bool,
         pub debug: u32,
     }
 
     impl Keyboard {
         pub fn new(sid: xous::SID) -> Keyboard {
+            let ticktimer = ticktimer_server::Ticktimer::new().expect("couldn't connect to ticktimer");
+            let recording = match std::env::var("XOUS_KEY_RECORD") {
+                Ok(path) => match std::fs::File::create(&path) {
+                    Ok(file) => {
+                        log::info!("recording key events to {}", path);
+                        Some((file, ticktimer.elapsed_ms()))
+                    }
+                    Err(e) => {
+                        log::error!("couldn't create key recording {}: {:?}", path, e);
+                        None
+                    }
+                },
+                Err(_) => None,
+            };
             Keyboard {
+                sid,
                 cid: xous::connect(sid).unwrap(),
-                map: KeyMap::Qwerty,
-                rate: 20,
-                delay: 200,
-                chord_interval: 50,
+                ticktimer,
+                new_state: HashSet::new(),
+                tracker: KeyTracker::new(if cfg!(feature = "braille") { KeyMap::Braille } else { KeyMap::Qwerty }),
+                chord: 0,
+                recording,
+                replayed: false,
                 debug: 0,
             }
         }
@@ -687,36 +304,136 @@ mod implementation {
         }
 
         pub fn set_map(&mut self, map: KeyMap) {
-            self.map = map;
+            self.tracker.map = map;
         }
-        pub fn get_map(&self) -> KeyMap {self.map}
+        pub fn get_map(&self) -> KeyMap {self.tracker.map}
 
-        pub fn update(&self) -> KeyRawStates {
-            KeyRawStates::new()
+        pub fn update(&mut self) -> KeyRawStates {
+            let krs = self.tracker.update(&self.new_state);
+            for &rc in krs.keydowns.iter() {
+                self.record(KeyAction::Down(rc));
+            }
+            for &rc in krs.keyups.iter() {
+                self.record(KeyAction::Up(rc));
+            }
+            krs
+        }
+
+        /// Press or release a key, as a key script does. Returns `false` if the key isn't on the matrix.
+        pub fn inject_raw_key(&mut self, rc: RowCol, down: bool) -> bool {
+            if rc.r as usize >= KBD_ROWS || rc.c as usize >= KBD_COLS {
+                return false;
+            }
+            if down {
+                self.new_state.insert(rc);
+            } else {
+                self.new_state.remove(&rc);
+            }
+            true
+        }
+
+        /// Note a character from the host keyboard in the recording, if there is one.
+        pub fn record_key(&mut self, key: char) {
+            self.record(KeyAction::Key(key));
+        }
+
+        fn record(&mut self, action: KeyAction) {
+            if let Some((file, last)) = self.recording.as_mut() {
+                let now = self.ticktimer.elapsed_ms();
+                let event = ScriptEvent { delay_ms: (now - *last) as u32, action };
+                *last = now;
+                // flush every line, as hosted mode usually ends by being killed
+                if writeln!(file, "{}", event).and_then(|_| file.flush()).is_err() {
+                    log::error!("couldn't write key recording, stopping it");
+                    self.recording = None;
+                }
+            }
+        }
+
+        /// Start replaying the script named by `XOUS_KEY_REPLAY`, if there is one. This is
+        /// done once a listener registers, so that the start of the script isn't lost.
+        pub fn start_replay(&mut self) {
+            if self.replayed {
+                return;
+            }
+            self.replayed = true;
+            let path = match std::env::var("XOUS_KEY_REPLAY") {
+                Ok(path) => path,
+                Err(_) => return,
+            };
+            let events = match std::fs::read_to_string(&path) {
+                Ok(script) => match parse_script(&script) {
+                    Ok(events) => events,
+                    Err(line) => {
+                        log::error!("key script {} has an error on line {}", path, line);
+                        return;
+                    }
+                },
+                Err(e) => {
+                    log::error!("couldn't read key script {}: {:?}", path, e);
+                    return;
+                }
+            };
+            log::info!("replaying {} key events from {}", events.len(), path);
+            let cid = self.cid;
+            std::thread::spawn(move || {
+                play_script(cid, &events).expect("couldn't replay key script");
+                log::info!("key script {} finished", path);
+            });
         }
 
-        pub fn track_chord(&mut self, _krs: &KeyRawStates) -> Vec<char> {
-            Vec::new()
+        /// Host keyboards only report one key at a time, so a chord is every dot key typed
+        /// within `chord_interval` of the first one. The first dot schedules a `HandlerTrigger`,
+        /// which is when `track_chord()` reads out the chord.
+        ///
+        /// Returns `true` if the key was taken as part of a chord.
+        pub fn inject_chord_key(&mut self, key: char) -> bool {
+            if let Some(dot) = host_key_to_dot(key) {
+                if self.chord == 0 {
+                    self.ticktimer.schedule_alarm(
+                        self.sid,
+                        Opcode::HandlerTrigger.to_u32().unwrap(),
+                        0,
+                        self.tracker.chord_interval as u64,
+                    ).expect("couldn't schedule chord timeout");
+                }
+                self.chord |= dot;
+                true
+            } else {
+                if key == ' ' {
+                    self.tracker.braille.reset();
+                }
+                false
+            }
+        }
+
+        pub fn track_chord(&mut self, krs: &KeyRawStates) -> Vec<char> {
+            // chords from key scripts, followed by any chord emulated by the host keyboard
+            let mut keystates = self.tracker.track_chord(krs);
+            let dots = core::mem::replace(&mut self.chord, 0);
+            if let Some(key) = self.tracker.braille.decode(dots) {
+                keystates.push(key);
+            }
+            keystates
         }
 
-        pub fn track_keys(&mut self, _rs: &KeyRawStates) -> Vec<char> {
-            Vec::new()
+        pub fn track_keys(&mut self, krs: &KeyRawStates) -> Vec<char> {
+            self.tracker.track_keys(krs)
         }
 
         pub fn set_repeat(&mut self, rate: u32, delay: u32) {
-            self.rate = rate;
-            self.delay = delay;
+            self.tracker.set_repeat(rate, delay);
         }
 
         pub fn set_chord_interval(&mut self, delay: u32) {
-            self.chord_interval = delay;
+            self.tracker.set_chord_interval(delay);
         }
 
         pub fn is_repeating_key(&self) -> bool {
-            false
+            self.tracker.is_repeating_key()
         }
         pub(crate) fn get_repeat_check_interval(&self) -> u32 {
-            self.rate
+            self.tracker.get_repeat_check_interval()
         }
         pub(crate) fn poll(&mut self) {}
     }
@@ -786,6 +503,8 @@ fn xmain() -> ! {
                     Ok(cid) => {
                         listener_conn = Some(cid);
                         listener_op = Some(kr.listener_op_id as usize);
+                        #[cfg(not(any(target_os = "none", target_os = "xous")))]
+                        kbd.start_replay();
                     }
                     Err(e) => {
                         log::error!("couldn't connect to listener: {:?}", e);
@@ -832,6 +551,8 @@ fn xmain() -> ! {
                 // 1b5b42 = down
                 log::trace!("{:x} {}", k, kbd.debug);
                 kbd.debug = 0;
+                #[cfg(not(any(target_os = "none", target_os = "xous")))]
+                kbd.record_key(core::char::from_u32(k as u32).unwrap_or('\u{0000}'));
 
                 let key = match esc_index {
                     Some(i) => {
@@ -880,6 +601,14 @@ fn xmain() -> ! {
                     }
                 };
 
+                // in hosted mode, the dot keys of the Braille map are collected into chords instead
+                #[cfg(not(any(target_os = "none", target_os = "xous")))]
+                let key = if let KeyMap::Braille = kbd.get_map() {
+                    if kbd.inject_chord_key(key) { '\u{0000}' } else { key }
+                } else {
+                    key
+                };
+
                 if let Some(conn) = listener_conn {
                     if key != '\u{0000}' {
                         log::info!("injecting key '{}'({:x})", key, key as u32); // always be noisy about this, it's an exploit path
@@ -894,6 +623,19 @@ fn xmain() -> ! {
                     }
                 }
             }),
+            Some(Opcode::InjectRawKey) => msg_scalar_unpack!(msg, r, c, down, _, {
+                let rc = RowCol::new(r as u8, c as u8);
+                #[cfg(not(any(target_os = "none", target_os = "xous")))]
+                if kbd.inject_raw_key(rc, down != 0) {
+                    xous::send_message(self_cid,
+                        xous::Message::new_scalar(Opcode::HandlerTrigger.to_usize().unwrap(), 0, 0, 0, 0)
+                    ).unwrap();
+                } else {
+                    log::warn!("ignoring raw key outside the key matrix: {:?}", rc);
+                }
+                #[cfg(any(target_os = "none", target_os = "xous"))]
+                log::warn!("raw key injection is only available in hosted mode, ignoring {:?} {}", rc, down);
+            }),
             Some(Opcode::HandlerTrigger) => {
                 let rawstates = kbd.update();
 
@@ -8,3 +8,5 @@ mod azerty;
 pub (crate) use azerty::*;
 mod dvorak;
 pub (crate) use dvorak::*;
+mod braille;
+pub (crate) use braille::*;
@@ -0,0 +1,251 @@
+// Braille cells are passed around as a bitmask of raised dots: dot 1 is bit 0,
+// through to dot 8 in bit 7.
+pub(crate) const DOT_1: u8 = 0x01;
+pub(crate) const DOT_2: u8 = 0x02;
+pub(crate) const DOT_3: u8 = 0x04;
+pub(crate) const DOT_4: u8 = 0x08;
+pub(crate) const DOT_5: u8 = 0x10;
+pub(crate) const DOT_6: u8 = 0x20;
+pub(crate) const DOT_7: u8 = 0x40;
+pub(crate) const DOT_8: u8 = 0x80;
+
+const CAPITAL_INDICATOR: u8 = DOT_6;
+const NUMERIC_INDICATOR: u8 = DOT_3 | DOT_4 | DOT_5 | DOT_6;
+const GRADE1_INDICATOR: u8 = DOT_5 | DOT_6;
+
+/// Turns a sequence of chorded Braille cells into characters, following Grade 1 (uncontracted)
+/// Unified English Braille. The capital, numeric and two-cell symbol indicators change how
+/// the cells after them are read, so the decoder has to see every cell in order.
+///
+/// Eight-dot cells read the same as six-dot cells, except that dot 7 makes a letter a capital
+/// on its own, and dot 7 or dot 8 pressed alone are backspace and carriage return.
+#[derive(Debug, Default)]
+pub(crate) struct BrailleDecoder {
+    /// a-j are read as digits, until a space or the Grade 1 indicator
+    numeric: bool,
+    /// the next letter is a capital
+    capital: bool,
+    /// letters are capitals until the end of the word
+    capital_word: bool,
+    /// the first cell of a two-cell symbol
+    prefix: Option<u8>,
+}
+
+impl BrailleDecoder {
+    /// Forget any indicators in effect, as happens at the end of a word.
+    pub(crate) fn reset(&mut self) {
+        *self = BrailleDecoder::default();
+    }
+
+    /// Decode one chorded cell, returning `None` if the cell is an indicator or does not
+    /// have a meaning.
+    pub(crate) fn decode(&mut self, dots: u8) -> Option<char> {
+        match dots {
+            0 => return None,
+            DOT_7 => return Some(0x8_u8.into()), // backspace
+            DOT_8 => return Some(0xd_u8.into()), // carriage return
+            _ => (),
+        }
+        if dots & DOT_7 != 0 {
+            self.prefix = None;
+            return braille_letter(dots & 0x3f).map(|c| c.to_ascii_uppercase());
+        }
+        // dot 8 only has a meaning on its own
+        let dots = dots & 0x3f;
+
+        if let Some(prefix) = self.prefix.take() {
+            return braille_symbol(prefix, dots);
+        }
+        match dots {
+            CAPITAL_INDICATOR => {
+                // two capital indicators in a row capitalise the whole word
+                self.capital_word = self.capital;
+                self.capital = true;
+                return None;
+            }
+            NUMERIC_INDICATOR => {
+                self.numeric = true;
+                return None;
+            }
+            GRADE1_INDICATOR => {
+                self.numeric = false;
+                return None;
+            }
+            // dot 4, dot 5, dots 4-6 and dots 4-5-6 start a two-cell symbol
+            0x08 | 0x10 | 0x28 | 0x38 => {
+                self.prefix = Some(dots);
+                return None;
+            }
+            _ => (),
+        }
+
+        if self.numeric {
+            match dots {
+                0x01 => return Some('1'),
+                0x03 => return Some('2'),
+                0x09 => return Some('3'),
+                0x19 => return Some('4'),
+                0x11 => return Some('5'),
+                0x0B => return Some('6'),
+                0x1B => return Some('7'),
+                0x13 => return Some('8'),
+                0x0A => return Some('9'),
+                0x1A => return Some('0'),
+                0x32 => return Some('.'), // decimal point
+                0x02 => return Some(','), // digit group separator
+                _ => self.numeric = false,
+            }
+        }
+
+        if let Some(letter) = braille_letter(dots) {
+            let capital = self.capital || self.capital_word;
+            self.capital = false;
+            if capital {
+                Some(letter.to_ascii_uppercase())
+            } else {
+                Some(letter)
+            }
+        } else {
+            self.capital = false;
+            self.capital_word = false;
+            braille_punctuation(dots)
+        }
+    }
+}
+
+fn braille_letter(dots: u8) -> Option<char> {
+    match dots {
+        0x01 => Some('a'),
+        0x03 => Some('b'),
+        0x09 => Some('c'),
+        0x19 => Some('d'),
+        0x11 => Some('e'),
+        0x0B => Some('f'),
+        0x1B => Some('g'),
+        0x13 => Some('h'),
+        0x0A => Some('i'),
+        0x1A => Some('j'),
+
+        0x05 => Some('k'),
+        0x07 => Some('l'),
+        0x0D => Some('m'),
+        0x1D => Some('n'),
+        0x15 => Some('o'),
+        0x0F => Some('p'),
+        0x1F => Some('q'),
+        0x17 => Some('r'),
+        0x0E => Some('s'),
+        0x1E => Some('t'),
+
+        0x25 => Some('u'),
+        0x27 => Some('v'),
+        0x3A => Some('w'),
+        0x2D => Some('x'),
+        0x3D => Some('y'),
+        0x35 => Some('z'),
+        _ => None,
+    }
+}
+
+fn braille_punctuation(dots: u8) -> Option<char> {
+    match dots {
+        0x02 => Some(','),
+        0x06 => Some(';'),
+        0x12 => Some(':'),
+        0x32 => Some('.'),
+        0x16 => Some('!'),
+        0x26 => Some('?'),
+        0x04 => Some('\''),
+        0x24 => Some('-'),
+        0x34 => Some('"'),
+        _ => None,
+    }
+}
+
+/// Symbols written as a prefix cell followed by a second cell
+fn braille_symbol(prefix: u8, dots: u8) -> Option<char> {
+    match (prefix, dots) {
+        // dot 5
+        (0x10, 0x23) => Some('('),
+        (0x10, 0x1C) => Some(')'),
+        (0x10, 0x36) => Some('='),
+        (0x10, 0x16) => Some('+'),
+        (0x10, 0x24) => Some('-'),
+        (0x10, 0x14) => Some('*'),
+
+        // dot 4
+        (0x08, 0x01) => Some('@'),
+        (0x08, 0x0E) => Some('$'),
+        (0x08, 0x2F) => Some('&'),
+        (0x08, 0x23) => Some('<'),
+        (0x08, 0x1C) => Some('>'),
+        (0x08, 0x14) => Some('~'),
+        (0x08, 0x22) => Some('^'),
+
+        // dots 4-6
+        (0x28, 0x34) => Some('%'),
+        (0x28, 0x23) => Some('['),
+        (0x28, 0x1C) => Some(']'),
+        (0x28, 0x24) => Some('_'),
+
+        // dots 4-5-6
+        (0x38, 0x0C) => Some('/'),
+        (0x38, 0x39) => Some('#'),
+        (0x38, 0x21) => Some('\\'),
+        (0x38, 0x23) => Some('{'),
+        (0x38, 0x1C) => Some('}'),
+        (0x38, 0x33) => Some('|'),
+        _ => None,
+    }
+}
+
+/// Host keyboards can't report chords, so hosted mode uses the usual six-key entry layout:
+/// f, d and s for dots 1 to 3, j, k and l for dots 4 to 6, and a and ; for dots 7 and 8.
+#[allow(dead_code)] // only the hosted keyboard emulates chords
+pub(crate) fn host_key_to_dot(key: char) -> Option<u8> {
+    match key {
+        'f' => Some(DOT_1),
+        'd' => Some(DOT_2),
+        's' => Some(DOT_3),
+        'j' => Some(DOT_4),
+        'k' => Some(DOT_5),
+        'l' => Some(DOT_6),
+        'a' => Some(DOT_7),
+        ';' => Some(DOT_8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(cells: &[u8]) -> String {
+        let mut decoder = BrailleDecoder::default();
+        cells.iter().filter_map(|&dots| decoder.decode(dots)).collect()
+    }
+
+    #[test]
+    fn letters_and_capitals() {
+        assert_eq!(decode_all(&[0x13, 0x11, 0x07, 0x07, 0x15]), "hello");
+        assert_eq!(decode_all(&[CAPITAL_INDICATOR, 0x3A, 0x15]), "Wo");
+        assert_eq!(decode_all(&[CAPITAL_INDICATOR, CAPITAL_INDICATOR, 0x25, 0x0E, 0x32, 0x01]), "US.a");
+        assert_eq!(decode_all(&[DOT_7 | 0x01, 0x03]), "Ab");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(decode_all(&[NUMERIC_INDICATOR, 0x01, 0x1A, 0x32, 0x11]), "10.5");
+        // a letter outside a-j ends the number, and the Grade 1 indicator ends it early
+        assert_eq!(decode_all(&[NUMERIC_INDICATOR, 0x03, 0x0E]), "2s");
+        assert_eq!(decode_all(&[NUMERIC_INDICATOR, 0x03, GRADE1_INDICATOR, 0x03]), "2b");
+    }
+
+    #[test]
+    fn punctuation_and_symbols() {
+        assert_eq!(decode_all(&[0x02, 0x26, 0x24]), ",?-");
+        assert_eq!(decode_all(&[DOT_5, 0x23, 0x01, DOT_5, 0x1C]), "(a)");
+        assert_eq!(decode_all(&[DOT_4, 0x01, DOT_4 | DOT_5 | DOT_6, 0x0C]), "@/");
+        assert_eq!(decode_all(&[DOT_7, DOT_8]), "\u{8}\r");
+    }
+}
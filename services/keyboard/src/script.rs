@@ -0,0 +1,160 @@
+// Key scripts are text, one timed key event per line:
+//
+//     # comments and blank lines are skipped
+//     500 down 1 2     -- 500 ms after the previous line, press the key at row 1, col 2
+//     80 up 1 2        -- let it up again 80 ms later
+//     0 key a          -- inject a character, as the host keyboard does
+//     0 key 0xd        -- characters can also be given as a hex code
+//
+// Raw key events go through the same hold, repeat and chord tracking as key presses on the
+// hardware, so holding a key down past the repeat delay repeats it, and keys pressed within
+// the chord interval of each other make a chord.
+use crate::api::RowCol;
+use core::fmt;
+use core::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyAction {
+    /// press a key on the matrix
+    Down(RowCol),
+    /// let a key on the matrix up
+    Up(RowCol),
+    /// inject a character
+    Key(char),
+}
+
+/// One line of a key script, which happens `delay_ms` after the line before it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ScriptEvent {
+    pub delay_ms: u32,
+    pub action: KeyAction,
+}
+
+impl fmt::Display for ScriptEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            KeyAction::Down(rc) => write!(f, "{} down {} {}", self.delay_ms, rc.r, rc.c),
+            KeyAction::Up(rc) => write!(f, "{} up {} {}", self.delay_ms, rc.r, rc.c),
+            KeyAction::Key(c) => {
+                if c.is_whitespace() || c.is_control() || c == '#' {
+                    write!(f, "{} key 0x{:x}", self.delay_ms, c as u32)
+                } else {
+                    write!(f, "{} key {}", self.delay_ms, c)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ScriptEvent {
+    type Err = ();
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace();
+        let delay_ms = tokens.next().ok_or(())?.parse::<u32>().or(Err(()))?;
+        let action = match tokens.next().ok_or(())? {
+            "down" => KeyAction::Down(parse_rowcol(&mut tokens)?),
+            "up" => KeyAction::Up(parse_rowcol(&mut tokens)?),
+            "key" => {
+                let key = tokens.next().ok_or(())?;
+                if key.len() > 2 && key.starts_with("0x") {
+                    let code = u32::from_str_radix(&key[2..], 16).or(Err(()))?;
+                    KeyAction::Key(core::char::from_u32(code).ok_or(())?)
+                } else {
+                    let mut chars = key.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => KeyAction::Key(c),
+                        _ => return Err(()),
+                    }
+                }
+            }
+            _ => return Err(()),
+        };
+        if tokens.next().is_some() {
+            return Err(());
+        }
+        Ok(ScriptEvent { delay_ms, action })
+    }
+}
+
+fn parse_rowcol<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<RowCol, ()> {
+    let r = tokens.next().ok_or(())?.parse::<u8>().or(Err(()))?;
+    let c = tokens.next().ok_or(())?.parse::<u8>().or(Err(()))?;
+    Ok(RowCol::new(r, c))
+}
+
+/// Parse a whole key script. On failure, returns the number of the first line that isn't
+/// a valid event, counting from 1.
+pub fn parse_script(script: &str) -> Result<Vec<ScriptEvent>, usize> {
+    let mut events = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        events.push(line.parse::<ScriptEvent>().or(Err(i + 1))?);
+    }
+    Ok(events)
+}
+
+/// Send the events of a key script to the keyboard server on `conn`, waiting out each delay
+/// before sending the event. Returns once the last event has been sent.
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+pub fn play_script(conn: xous::CID, events: &[ScriptEvent]) -> Result<(), xous::Error> {
+    use crate::api::Opcode;
+    use num_traits::ToPrimitive;
+    let ticktimer = ticktimer_server::Ticktimer::new()?;
+    for event in events.iter() {
+        if event.delay_ms > 0 {
+            ticktimer.sleep_ms(event.delay_ms as usize)?;
+        }
+        let msg = match event.action {
+            KeyAction::Down(rc) => xous::Message::new_scalar(
+                Opcode::InjectRawKey.to_usize().unwrap(), rc.r as usize, rc.c as usize, 1, 0),
+            KeyAction::Up(rc) => xous::Message::new_scalar(
+                Opcode::InjectRawKey.to_usize().unwrap(), rc.r as usize, rc.c as usize, 0, 0),
+            KeyAction::Key(c) => xous::Message::new_scalar(
+                Opcode::InjectKey.to_usize().unwrap(), c as u32 as usize, 0, 0, 0),
+        };
+        xous::send_message(conn, msg)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_round_trip() {
+        let script = "# a chord, then a held key and some characters\n\
+                      100 down 1 2\n\
+                      0 down 0 1\n\
+                      \n\
+                      60 up 1 2\n\
+                      0 up 0 1\n\
+                      0 down 8 9\n\
+                      1200 up 8 9\n\
+                      0 key a\n\
+                      0 key 0x20\n\
+                      5 key 0xd\n";
+        let events = parse_script(script).unwrap();
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[0], ScriptEvent { delay_ms: 100, action: KeyAction::Down(RowCol::new(1, 2)) });
+        assert_eq!(events[7].action, KeyAction::Key(' '));
+        assert_eq!(events[8].action, KeyAction::Key('\r'));
+
+        let text: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+        assert_eq!(text[6], "0 key a");
+        assert_eq!(text[7], "0 key 0x20");
+        assert_eq!(parse_script(&text.join("\n")).unwrap(), events);
+    }
+
+    #[test]
+    fn script_errors() {
+        assert_eq!(parse_script("0 down 1 2\n0 sideways 1 2\n"), Err(2));
+        assert_eq!(parse_script("\n# ok\n0 down 1\n"), Err(3));
+        assert_eq!(parse_script("0 key ab\n"), Err(1));
+        assert_eq!(parse_script("-5 up 1 2\n"), Err(1));
+    }
+}
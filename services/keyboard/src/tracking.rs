@@ -0,0 +1,432 @@
+use crate::{RowCol, KeyRawStates, api::*};
+use crate::mappings::*;
+use ticktimer_server::Ticktimer;
+use std::collections::HashSet;
+
+/// note: the code is structured to use at most 16 rows or 16 cols
+pub(crate) const KBD_ROWS: usize = 9;
+pub(crate) const KBD_COLS: usize = 10;
+
+/// Turns key matrix states into characters, according to the key map, modifier, hold and
+/// chord state. This is shared by the hardware keyboard and the hosted one, which gets its
+/// key matrix states from scripts instead of a scan.
+pub(crate) struct KeyTracker {
+    /// remember the last key states
+    last_state: HashSet::<RowCol>,
+    /// connection to the timer for real-time events
+    ticktimer: Ticktimer,
+    /// mapping for ScanCode translation
+    pub(crate) map: KeyMap,
+    /// delay in ms before a key is considered to be repeating
+    delay: u32,
+    /// rate in ms for repeating a key
+    rate: u32,
+    /// shift key state
+    shift_down: bool,
+    shift_up: bool,
+    /// alt key state
+    alt_down: bool,
+    alt_up: bool,
+    /// timestamp to track repeating key interval
+    rate_timestamp: u64,
+    /// track the last key held down, which lacks a hold alternate meaning, for repeating
+    repeating_key: Option<char>,
+    /// timestamp timekeeper for chording / hold key
+    chord_timestamp: u64,
+    /// chording sample interval
+    pub(crate) chord_interval: u32,
+    /// chord state array
+    chord: [[bool; KBD_COLS]; KBD_ROWS],
+    /// memoize number of keys that have been pressed
+    chord_active: u32,
+    /// indicate if the chord has been captured. Once captured, further presses are ignored, until all keys are let up.
+    chord_captured: bool,
+    /// turns captured chords into characters
+    pub(crate) braille: BrailleDecoder,
+}
+
+impl KeyTracker {
+    pub(crate) fn new(map: KeyMap) -> KeyTracker {
+        let ticktimer = Ticktimer::new().expect("couldn't connect to ticktimer");
+        let timestamp = ticktimer.elapsed_ms();
+        KeyTracker {
+            last_state: HashSet::with_capacity(16),
+            ticktimer,
+            map,
+            delay: 500,
+            rate: 20,
+            shift_down: false,
+            shift_up: false,
+            alt_down: false,
+            alt_up: false,
+            repeating_key: None,
+            rate_timestamp: timestamp,
+            chord_timestamp: timestamp,
+            chord_interval: 50,
+            chord: [[false; KBD_COLS]; KBD_ROWS],
+            chord_active: 0,
+            chord_captured: false,
+            braille: BrailleDecoder::default(),
+        }
+    }
+
+    /// Forget all key and modifier state, as if every key had been let up.
+    pub(crate) fn reset(&mut self) {
+        self.last_state.clear();
+        self.shift_down = false;
+        self.shift_up = false;
+        self.alt_down = false;
+        self.alt_up = false;
+        self.repeating_key = None;
+        self.chord_captured = false;
+        self.chord_active = 0;
+        self.chord = [[false; KBD_COLS]; KBD_ROWS];
+        self.braille.reset();
+    }
+
+    pub(crate) fn set_repeat(&mut self, rate: u32, delay: u32) {
+        self.rate = rate;
+        self.delay = delay;
+    }
+    pub(crate) fn set_chord_interval(&mut self, delay: u32) {
+        self.chord_interval = delay;
+    }
+    pub(crate) fn get_repeat_check_interval(&self) -> u32 {
+        self.rate
+    }
+
+    pub(crate) fn update(&mut self, new_state: &HashSet<RowCol>) -> KeyRawStates {
+        // EV_PENDING_KEYPRESSED effectively does an XOR of the previous keyboard state
+        // to the current state, which is why update() does not repeatedly issue results
+        // for keys that are pressed & held.
+        log::trace!("update new_state:  {:?}", new_state);
+        log::trace!("update last_state: {:?}", self.last_state);
+
+        let mut krs = KeyRawStates::new();
+
+        // compute the key-ups: this would be codes that are in the last_state, but not in the incoming
+        // new_state
+        for &rc in self.last_state.difference(new_state) {
+            krs.keyups.push(rc);
+        }
+
+        // compute key-downs: codes that are in the new_state, but not in last_state
+        for &rc in new_state.difference(&self.last_state) {
+            krs.keydowns.push(rc);
+        }
+
+        self.last_state.clear();
+        for &rc in new_state.iter() {
+            self.last_state.insert(rc);
+        }
+
+        log::trace!("krs: {:?}", krs);
+        krs
+    }
+
+    pub(crate) fn track_chord(&mut self, krs: &KeyRawStates) -> Vec<char> {
+        /*
+        Chording algorithm:
+
+        1. Wait for first keydown event to happen; record as pressed in table
+        2. Start chording timer
+        3. Record press/unpress in table
+        4. Wait for chording timer to timeout
+        5. Extract chord state and turn into scancode using lookup table
+        6. Return scancodes
+         */
+        let was_idle = self.chord_active == 0;
+        for rc in krs.keydowns.iter() {
+            log::info!("keydown r: {} c: {}", rc.r, rc.c);
+            self.chord[rc.r as usize][rc.c as usize] = true;
+            self.chord_active += 1;
+        }
+        log::trace!("self.chord: {:?}", self.chord);
+        let mut keystates: Vec<char> = Vec::new();
+
+        let now = self.ticktimer.elapsed_ms();
+        if was_idle && self.chord_active != 0 {
+            // "rising edge" of chord_active
+            self.chord_timestamp = now; // record the beginning of the chord active interval
+        }
+
+        if self.chord_active != 0 && ((now - self.chord_timestamp) >= self.chord_interval as u64) && !self.chord_captured {
+            self.chord_captured = true;
+            log::trace!("interpreting chords");
+            // extract chord state
+            /*
+                keyboard:
+                2 1 0 space 3 4 5
+                braille dots:
+                0 3
+                1 4
+                2 5
+                6 7   (esc / func)
+            */
+            /*
+                7/5  0/1   1/2       5/7     4/8  8/6
+                           2/3       2/3
+                8/0  6/4                         3/9
+                8/3  5/2 3/6
+                     8/2
+             */
+            let keys: [bool; 8] = [
+                self.chord[1][2],
+                self.chord[0][1],
+                self.chord[7][5],
+                self.chord[5][7],
+                self.chord[4][8],
+                self.chord[8][6],
+                self.chord[8][0],
+                self.chord[3][9],
+            ];
+            let mut keycode: u8 = 0;
+            for i in 0..keys.len() {
+                if keys[i] {
+                    keycode |= 1 << i;
+                }
+            }
+            log::trace!("keycode: 0x{:x}", keycode);
+            // esc and func on their own are dots 7 and 8, which decode to backspace and carriage return
+            if let Some(key) = self.braille.decode(keycode) {
+                keystates.push(key);
+            }
+
+            let up = self.chord[6][4];
+            if up { keystates.push('↑'); }
+
+            let left = self.chord[8][3];
+            if left { keystates.push('←'); }
+            let right = self.chord[3][6];
+            if right { keystates.push('→'); }
+            let down = self.chord[8][2];
+            if down { keystates.push('↓'); }
+            let center = self.chord[5][2];
+            if center { keystates.push('∴'); }
+
+            let space = self.chord[2][3];
+            if space {
+                // a space ends the word, and with it any capital or numeric indicator
+                self.braille.reset();
+                keystates.push(' ');
+            }
+
+            log::debug!("up {}, left {}, right {}, down, {}, center, {}, space {}, dots 0x{:x}",
+                up, left, right, down, center, space, keycode);
+        }
+        for rc in krs.keyups.iter() {
+            self.chord[rc.r as usize][rc.c as usize] = false;
+            if self.chord_active > 0 {
+                self.chord_active -= 1;
+            } else {
+                log::error!("received more keyups than we had keydowns!")
+            }
+        }
+        if self.chord_active == 0 {
+            self.chord_captured = false;
+        }
+
+        keystates
+    }
+
+    pub(crate) fn track_keys(&mut self, krs: &KeyRawStates) -> Vec<char> {
+        /*
+          "conventional" keyboard algorithm. The goals of this are to differentiate
+          the cases of "shift", "alt", and "hold".
+
+          thus, we check for the special-case of shift/alt in the keydowns/keyups vectors, and
+          track them as separate modifiers
+
+          then for all others, we note the down time, and compare it to the current time
+          to determine if a "hold" modifier applies
+         */
+        let mut ks: Vec<char> = Vec::new();
+
+        // first check for shift and alt keys
+        for rc in krs.keydowns.iter() {
+            match self.map {
+                KeyMap::Azerty => {
+                    if (rc.r == 8) && (rc.c == 5) { // left shift (orange)
+                        if self.alt_up == false {
+                            self.alt_down = true;
+                        } else {
+                            self.alt_up = false;
+                        }
+                    } else if (rc.r == 8) && (rc.c == 9) { // right shift (yellow)
+                        if self.shift_up == false {
+                            self.shift_down = true;
+                        } else {
+                            self.shift_up = false;
+                        }
+                    }
+                },
+                _ => { // the rest just have one color of shift
+                    if ((rc.r == 8) && (rc.c == 5)) || ((rc.r == 8) && (rc.c == 9)) {
+                        // if the shift key was tapped twice, remove the shift modifier
+                        if self.shift_up == false {
+                            //info!("shift down true");
+                            self.shift_down = true;
+                        } else {
+                            //info!("shift up false");
+                            self.shift_up = false;
+                        }
+                    }
+                }
+            }
+        }
+        let mut keyups_noshift: Vec::<RowCol> = Vec::new();
+        for &rc in krs.keyups.iter() {
+            match self.map {
+                KeyMap::Azerty => {
+                    if (rc.r == 8) && (rc.c == 5) { // left shift (orange)
+                        if self.alt_down {
+                            self.alt_up = true;
+                        }
+                        self.alt_down = false;
+                    } else if (rc.r == 8) && (rc.c == 9) { // right shift (yellow)
+                        if self.shift_down {
+                            self.shift_up = true;
+                        }
+                        self.shift_down = false;
+                    } else {
+                        keyups_noshift.push(RowCol{r: rc.r as _, c: rc.c as _});
+                    }
+                },
+                _ => { // the rest just have one color of shift
+                    if ((rc.r == 8) && (rc.c == 5)) || ((rc.r == 8) && (rc.c == 9)) {
+                        // only set the shift-up if we didn't previously clear it with a double-tap of shift
+                        if self.shift_down {
+                            //info!("shift up true");
+                            self.shift_up = true;
+                        }
+                        //info!("shift down false");
+                        self.shift_down = false;
+                    } else {
+                        //info!("adding non-shift entry {:?}", rc);
+                        keyups_noshift.push(RowCol{r: rc.r as _, c: rc.c as _});
+                    }
+                }
+            }
+        }
+
+        // interpret keys in the context of the shift/alt modifiers
+        if !krs.keydowns.is_empty() {
+            self.chord_timestamp = self.ticktimer.elapsed_ms();
+        }
+        for &rc in krs.keydowns.iter() {
+            let code = match self.map {
+                KeyMap::Qwerty => map_qwerty(rc),
+                KeyMap::Dvorak => map_dvorak(rc),
+                KeyMap::Azerty => map_azerty(rc),
+                KeyMap::Qwertz => map_qwertz(rc),
+                _ => ScanCode {key: None, shift: None, hold: None, alt: None},
+            };
+            if code.hold == None
+            && !((rc.r == 5) && (rc.c == 2)) // scan code for the menu key
+             { // if there isn't a pre-defined meaning if the key is held *and* it's not the menu key: it's a repeating key
+                if let Some(key) = code.key {
+                    self.repeating_key = Some(key);
+                }
+            }
+        }
+
+        let now = self.ticktimer.elapsed_ms();
+        let hold: bool;
+        if (now - self.chord_timestamp) >= self.delay as u64 {
+            if self.rate_timestamp <= self.chord_timestamp {
+                self.rate_timestamp = now;
+            }
+            hold = true;
+        } else {
+            hold = false;
+        }
+
+        for &rc in keyups_noshift.iter() {
+            // info!("interpreting keyups_noshift entry {:?}", rc);
+            let code = match self.map {
+                KeyMap::Qwerty => map_qwerty(rc),
+                KeyMap::Dvorak => map_dvorak(rc),
+                KeyMap::Azerty => map_azerty(rc),
+                KeyMap::Qwertz => map_qwertz(rc),
+                _ => ScanCode {key: None, shift: None, hold: None, alt: None},
+            };
+            // delete the key repeat if there is one
+            if code.hold == None {
+                if let Some(key) = code.key {
+                    if let Some(rk) = self.repeating_key {
+                        if rk == key {
+                            self.repeating_key = None;
+                        }
+                    }
+                }
+            }
+
+            match self.map {
+                KeyMap::Azerty => {
+                    if self.shift_down || self.shift_up {
+                        if let Some(shiftcode) = code.shift {
+                            ks.push(shiftcode);
+                        } else if let Some(keycode) = code.key {
+                            ks.push(keycode);
+                        }
+                        self.shift_down = false;
+                        self.shift_up = false;
+                    } else if self.alt_down || self.alt_up {
+                        if let Some(altcode) = code.alt {
+                            ks.push(altcode);
+                        } else if let Some(shiftcode) = code.shift {
+                            ks.push(shiftcode);
+                        } else if let Some(keycode) = code.key {
+                            ks.push(keycode);
+                        }
+                        self.alt_down = false;
+                        self.alt_up = false;
+                    } else if hold {
+                        if let Some(holdcode) = code.hold {
+                            ks.push(holdcode);
+                        }
+                    } else {
+                        if let Some(keycode) = code.key {
+                            ks.push(keycode);
+                        }
+                    }
+                },
+                _ => {
+                    if self.shift_down || self.alt_down || self.shift_up || self.alt_up {
+                        if let Some(shiftcode) = code.shift {
+                            ks.push(shiftcode);
+                        } else if let Some(keycode) = code.key {
+                            ks.push(keycode);
+                        }
+                        self.shift_down = false;
+                        self.alt_down = false;
+                        self.shift_up = false;
+                        self.alt_up = false;
+                    } else if hold {
+                        if let Some(holdcode) = code.hold {
+                            ks.push(holdcode);
+                        }
+                    } else {
+                        if let Some(keycode) = code.key {
+                            // info!("appeding normal key '{}'", keycode);
+                            ks.push(keycode);
+                        }
+                    }
+                }
+            }
+        }
+
+        // if we're in a key hold state, we've passed the rate timestamp point, and there's a repeating key defined
+        if hold && ((now - self.rate_timestamp) >= self.rate as u64) && self.repeating_key.is_some() {
+            self.rate_timestamp = now;
+            if let Some(repeatkey) = self.repeating_key {
+                ks.push(repeatkey);
+            }
+        }
+
+        ks
+    }
+    pub(crate) fn is_repeating_key(&self) -> bool {
+        self.repeating_key.is_some()
+    }
+}
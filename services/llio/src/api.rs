@@ -4,6 +4,8 @@ mod i2c_api;
 pub use i2c_api::*;
 mod rtc_api;
 pub use rtc_api::*;
+mod time_api;
+pub use time_api::*;
 
 // ///////////////////// UART TYPE
 #[allow(dead_code)]  // we use this constant, but only in the `bin` view (not `lib`), so clippy complains, but this seems more discoverable here.
@@ -52,7 +54,7 @@ impl Into<u32> for UartType {
 // ////////////////////////////////// OPCODES
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub(crate) enum Opcode {
-    /// not tested - set CRG parameters
+    /// set the system clock mode; returns the mode that was previously in effect
     CrgMode, //(ClockMode),
 
     /// not tested -- set GPIO
@@ -25,12 +25,27 @@ impl Into<usize> for VibePattern {
     }
 }
 
-// ////////////////////////////// CLOCK GATING (placeholder)
-#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+// ////////////////////////////// CLOCK MODES
+/// System clock operating points. `Low` is meant for long, mostly-idle stretches such as audio
+/// playback; `AllOn` is the nominal clock and must be used for anything throughput-bound, e.g. crypto.
+///
+/// Note that the UARTs derive their baud rate from the system clock, so the log server holds
+/// console output back while in `Low` mode, and writes out the log records from that time when
+/// the clock returns to `AllOn`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub enum ClockMode {
     Low,
     AllOn,
 }
+impl ClockMode {
+    /// how much slower than the nominal system clock this mode runs
+    pub fn divider(&self) -> u32 {
+        match self {
+            ClockMode::Low => 2,
+            ClockMode::AllOn => 1,
+        }
+    }
+}
 impl From<usize> for ClockMode {
     fn from(mode: usize) -> Self {
         match mode {
@@ -0,0 +1,26 @@
+/// This is a "well known name" used by `libstd` to connect to the time server, which lives in `status`.
+/// Most code gets the time via `libstd`; `LocalTime` and the few clients that need UTC as the
+/// time server keeps it connect to this name directly.
+pub const TIME_SERVER_PUBLIC: &'static [u8; 16] = b"timeserverpublic";
+
+/// Opcodes of the time server at `TIME_SERVER_PUBLIC`.
+/// Do not modify the discriminants in this structure. They are used in `libstd` directly.
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub enum TimeOp {
+    /// Sync offsets to hardware RTC
+    HwSync = 0,
+    /// Suspend/resume call
+    SusRes = 1,
+    /// Indicates the current time is precisely the provided number of ms since EPOCH
+    SetUtcTimeMs = 2,
+    /// Get UTC time in ms since EPOCH
+    GetUtcTimeMs = 3,
+    /// Get local time in ms since EPOCH
+    GetLocalTimeMs = 4,
+    /// Sets the timezone offset, in milliseconds.
+    SetTzOffsetMs = 5,
+    /// Query to see if timezone and time relative to UTC have been set.
+    WallClockTimeInit = 6,
+    /// Self-poll for PDDB mount
+    PddbMountPoll = 7,
+}
@@ -21,7 +21,7 @@ pub struct LocalTime {
 impl LocalTime {
     pub fn new() -> LocalTime {
         TIME_REFCOUNT.fetch_add(1, Ordering::Relaxed);
-        let conn = xous::connect(xous::SID::from_bytes(b"timeserverpublic").unwrap()).unwrap();
+        let conn = xous::connect(xous::SID::from_bytes(TIME_SERVER_PUBLIC).unwrap()).unwrap();
         LocalTime {
           conn,
           warn_count: 0,
@@ -34,7 +34,7 @@ impl LocalTime {
         if !self.time_init {
             match xous::send_message(self.conn,
                 xous::Message::new_blocking_scalar(
-                    6, // WallClockTimeInit -- this should not change because it's a libstd mapping
+                    TimeOp::WallClockTimeInit as usize,
                     0, 0, 0, 0
                 )
             ).expect("couldn't get init status") {
@@ -58,7 +58,7 @@ impl LocalTime {
         self.time_init = true;
         match xous::send_message(self.conn,
             xous::Message::new_blocking_scalar(
-                4,
+                TimeOp::GetLocalTimeMs as usize,
                 0, 0, 0, 0
             )
         ).expect("couldn't get time") {
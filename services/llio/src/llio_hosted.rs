@@ -4,6 +4,7 @@ use llio::api::*;
 #[derive(Copy, Clone, Debug)]
 pub struct Llio {
     usb_disable: bool,
+    clock_mode: ClockMode,
 }
 pub fn log_init() -> *mut u32 { 0 as *mut u32 }
 
@@ -11,10 +12,17 @@ impl Llio {
     pub fn new(_handler_conn: xous::CID, _gpio_base: *mut u32) -> Llio {
         Llio {
             usb_disable: false,
+            clock_mode: ClockMode::AllOn,
         }
     }
     pub fn suspend(&self) {}
     pub fn resume(&self) {}
+    pub fn set_clock_mode(&mut self, mode: ClockMode) -> ClockMode {
+        let previous = self.clock_mode;
+        log::info!("Imagine the system clock changing: {:?} -> {:?}", previous, mode);
+        self.clock_mode = mode;
+        previous
+    }
     pub fn gpio_dout(&self, _d: u32) {}
     pub fn gpio_din(&self, ) -> u32 { 0xDEAD_BEEF }
     pub fn gpio_drive(&self, _d: u32) {}
@@ -19,9 +19,12 @@ pub struct Llio {
     power_susres: RegManager::<{utra::power::POWER_NUMREGS}>,
     xadc_csr: utralib::CSR<u32>,  // be careful with this as XADC is shared with TRNG
     ticktimer: ticktimer_server::Ticktimer,
+    log: log_server::LogControl,
     activity_period: u32, // 12mhz clock cycles over which to sample activity
     destruct_armed: bool,
     uartmux_cache: u32, // stash a value of the uartmux -- restore from override into kernel so we can record KPs on resume
+    clock_mode: ClockMode,
+    clkout0_nominal: (u16, u16), // CLKOUT0 divider registers as configured by the bitstream
 }
 
 fn handle_event_irq(_irq_no: usize, arg: *mut usize) {
@@ -156,10 +159,14 @@ impl Llio {
             power_susres: RegManager::new(power_csr.as_mut_ptr() as *mut u32),
             xadc_csr: CSR::new(xadc_csr.as_mut_ptr() as *mut u32),
             ticktimer,
+            log: log_server::LogControl::new().expect("couldn't connect to the log server"),
             activity_period: 24_000_000, // 2 second interval initially
             destruct_armed: false,
             uartmux_cache: BOOT_UART.into(),
+            clock_mode: ClockMode::AllOn,
+            clkout0_nominal: (0, 0),
         };
+        xl.clkout0_nominal = (xl.drp_read(crate::mmcm::CLKOUT0_REG1), xl.drp_read(crate::mmcm::CLKOUT0_REG2));
 
         xous::claim_interrupt(
             utra::btevents::BTEVENTS_IRQ,
@@ -209,6 +216,12 @@ impl Llio {
         xl
     }
     pub fn suspend(&mut self) {
+        // the boot and resume paths assume the nominal clock, so go back to it for the duration of the suspend;
+        // `clock_mode` is left untouched so resume can restore it
+        if self.clock_mode != ClockMode::AllOn {
+            self.program_clkout0(ClockMode::AllOn.divider());
+            self.ticktimer.set_clock_divider(ClockMode::AllOn.divider()).expect("couldn't update ticktimer clock divider");
+        }
         self.uartmux_cache = self.gpio_csr.rf(UARTSEL_UARTSEL).into();
         self.gpio_csr.wfo(utra::gpio::UARTSEL_UARTSEL, 1); // set to console to watch on boot: 0 = kernel, 1 = console, 2 = application
 
@@ -238,6 +251,69 @@ impl Llio {
         self.gpio_susres.resume();
         // restore the UART mux setting after resume
         self.gpio_csr.wfo(utra::gpio::UARTSEL_UARTSEL, self.uartmux_cache);
+
+        if self.clock_mode != ClockMode::AllOn {
+            self.program_clkout0(self.clock_mode.divider());
+            self.ticktimer.set_clock_divider(self.clock_mode.divider()).expect("couldn't update ticktimer clock divider");
+        }
+    }
+    /// Switches the system clock to `mode` and returns the mode that was in effect before.
+    ///
+    /// Only CLKOUT0 changes. The ticktimer counts system clock ticks, so it is rebased right after the
+    /// switch and elapsed time is off by at most the IPC latency of that call. The preemption tick on
+    /// TIMER0, and with it the kernel's message timeouts, runs from the always-on 12MHz clock and needs
+    /// no adjustment. The UARTs have no baud rate setting to compensate with, so the console is held by
+    /// the log server for as long as the clock is divided, and catches up from its ring on the way back.
+    pub fn set_clock_mode(&mut self, mode: ClockMode) -> ClockMode {
+        let previous = self.clock_mode;
+        if mode != previous {
+            info!("clock mode {:?} -> {:?}", previous, mode);
+            if crate::mmcm::clkout0_divided(self.clkout0_nominal, mode.divider()).is_none() {
+                error!("clock mode {:?} is out of range of the MMCM output counter, staying at {:?}", mode, previous);
+                return previous;
+            }
+            self.log.hold_console(true).expect("couldn't hold the console");
+            self.program_clkout0(mode.divider());
+            self.ticktimer.set_clock_divider(mode.divider()).expect("couldn't update ticktimer clock divider");
+            self.clock_mode = mode;
+            if mode.divider() == 1 {
+                self.log.hold_console(false).expect("couldn't release the console");
+            }
+        }
+        previous
+    }
+    /// Reprograms the CLKOUT0 counters to the nominal divide value times `divider`, following XAPP888: the
+    /// MMCM is held in reset while its registers are written, and then given time to lock again. CLKOUT0 is
+    /// the system clock, so this runs from llio's own thread with the console held, and with the SoC told to
+    /// ignore the loss of lock, which would otherwise reset it.
+    fn program_clkout0(&mut self, divider: u32) {
+        let (reg1, reg2) = match crate::mmcm::clkout0_divided(self.clkout0_nominal, divider) {
+            Some(regs) => regs,
+            None => {
+                error!("clock divider {} is out of range of the MMCM output counter", divider);
+                return;
+            }
+        };
+        let ignore_locked = self.power_csr.rf(utra::power::POWER_IGNORE_LOCKED);
+        self.power_csr.rmwf(utra::power::POWER_IGNORE_LOCKED, 1);
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_RESET_MMCM_DRP_RESET, 1);
+        self.drp_write(crate::mmcm::CLKOUT0_REG1, reg1);
+        self.drp_write(crate::mmcm::CLKOUT0_REG2, reg2);
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_RESET_MMCM_DRP_RESET, 0);
+        while self.crg_csr.rf(utra::crg::MMCM_DRP_LOCKED_MMCM_DRP_LOCKED) == 0 {}
+        self.power_csr.rmwf(utra::power::POWER_IGNORE_LOCKED, ignore_locked);
+    }
+    fn drp_read(&mut self, adr: u32) -> u16 {
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_ADR_MMCM_DRP_ADR, adr);
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_READ_MMCM_DRP_READ, 1);
+        while self.crg_csr.rf(utra::crg::MMCM_DRP_DRDY_MMCM_DRP_DRDY) == 0 {}
+        self.crg_csr.rf(utra::crg::MMCM_DRP_DAT_R_MMCM_DRP_DAT_R) as u16
+    }
+    fn drp_write(&mut self, adr: u32, data: u16) {
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_ADR_MMCM_DRP_ADR, adr);
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_DAT_W_MMCM_DRP_DAT_W, data as u32);
+        self.crg_csr.wfo(utra::crg::MMCM_DRP_WRITE_MMCM_DRP_WRITE, 1);
+        while self.crg_csr.rf(utra::crg::MMCM_DRP_DRDY_MMCM_DRP_DRDY) == 0 {}
     }
     #[allow(dead_code)]
     pub fn activity_set_period(&mut self, period: u32) {
@@ -264,6 +264,19 @@ impl Llio {
             Message::new_blocking_scalar(Opcode::PowerCrypto.to_usize().unwrap(), arg, 0, 0, 0)
         ).map(|_| ())
     }
+    /// Sets the system clock mode, returning the mode that was previously in effect so callers can
+    /// put it back, e.g. after running something throughput-bound at `ClockMode::AllOn`.
+    pub fn set_clock_mode(&self, mode: ClockMode) -> Result<ClockMode, xous::Error> {
+        let response = send_message(self.conn,
+            Message::new_blocking_scalar(Opcode::CrgMode.to_usize().unwrap(), mode.into(), 0, 0, 0)
+        )?;
+        if let xous::Result::Scalar1(previous) = response {
+            Ok(previous.into())
+        } else {
+            log::error!("LLIO: unexpected return value: {:#?}", response);
+            Err(xous::Error::InternalError)
+        }
+    }
     // setting this to true turns off WFI capabilities, forcing power always on
     pub fn wfi_override(&self, ena: bool) -> Result<(), xous::Error> {
         let arg = if ena { 1 } else { 0 };
@@ -4,6 +4,8 @@
 mod api;
 use api::*;
 mod i2c;
+#[cfg_attr(not(any(target_os = "none", target_os = "xous")), allow(dead_code))]
+mod mmcm;
 #[cfg(any(target_os = "none", target_os = "xous"))]
 mod llio_hw;
 #[cfg(any(target_os = "none", target_os = "xous"))]
@@ -178,8 +180,9 @@ fn xmain() -> ! {
                 llio.vibe(VibePattern::Double);
                 lockstatus_force_update = true; // notify the status bar that yes, it does need to redraw the lock status, even if the value hasn't changed since the last read
             }),
-            Some(Opcode::CrgMode) => msg_scalar_unpack!(msg, _mode, _, _, _, {
-                todo!("CrgMode opcode not yet implemented.");
+            Some(Opcode::CrgMode) => msg_blocking_scalar_unpack!(msg, mode, _, _, _, {
+                let previous = llio.set_clock_mode(mode.into());
+                xous::return_scalar(msg.sender, previous.into()).expect("couldn't confirm clock mode change");
             }),
             Some(Opcode::GpioDataOut) => msg_scalar_unpack!(msg, d, _, _, _, {
                 llio.gpio_dout(d as u32);
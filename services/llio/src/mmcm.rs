@@ -0,0 +1,96 @@
+//! Encoding of the MMCM CLKOUT0 output counter, as found in the dynamic reconfiguration
+//! port registers described in Xilinx XAPP888.
+
+/// DRP address of ClkReg1 for CLKOUT0: phase mux, high time and low time
+pub const CLKOUT0_REG1: u32 = 0x08;
+/// DRP address of ClkReg2 for CLKOUT0: frac and delay settings, edge and no-count bits
+pub const CLKOUT0_REG2: u32 = 0x09;
+
+const HIGH_TIME_SHIFT: u16 = 6;
+const TIME_MASK: u16 = 0x3f;
+const NO_COUNT: u16 = 0x40;
+const EDGE: u16 = 0x80;
+/// the largest divide the 6-bit high and low counters can express together
+const MAX_DIVIDE: u32 = 2 * TIME_MASK as u32;
+
+/// The divide value that `(reg1, reg2)` programs into the output counter
+pub fn clkout0_divide(reg1: u16, reg2: u16) -> u32 {
+    // a no-count divider bypasses the counters and divides by 1
+    if reg2 & NO_COUNT != 0 {
+        1
+    } else {
+        ((reg1 >> HIGH_TIME_SHIFT) & TIME_MASK) as u32 + (reg1 & TIME_MASK) as u32
+    }
+}
+
+/// The register values that slow the output configured by `nominal` down by `divider`,
+/// keeping the phase mux and delay settings. Returns `None` if the result doesn't fit
+/// the counters.
+pub fn clkout0_divided(nominal: (u16, u16), divider: u32) -> Option<(u16, u16)> {
+    let (reg1, reg2) = nominal;
+    if divider == 1 {
+        return Some(nominal);
+    }
+    let divide = clkout0_divide(reg1, reg2).checked_mul(divider)?;
+    if divider == 0 || divide > MAX_DIVIDE {
+        return None;
+    }
+    // an odd divide puts the extra half period in the low time, with the edge bit set
+    let high = divide / 2;
+    let low = divide - high;
+    let edge = if divide & 1 != 0 { EDGE } else { 0 };
+    Some((
+        (reg1 & !((TIME_MASK << HIGH_TIME_SHIFT) | TIME_MASK)) | ((high as u16) << HIGH_TIME_SHIFT) | low as u16,
+        (reg2 & !(NO_COUNT | EDGE)) | edge,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nominal_is_untouched() {
+        assert_eq!(clkout0_divided((0x1145, 0x0c00), 1), Some((0x1145, 0x0c00)));
+    }
+
+    #[test]
+    fn even_divide() {
+        // divide by 10: high 5, low 5, phase mux bits kept
+        let nominal = (0x2000 | (5 << 6) | 5, 0x0000);
+        assert_eq!(clkout0_divide(nominal.0, nominal.1), 10);
+        let (reg1, reg2) = clkout0_divided(nominal, 2).unwrap();
+        assert_eq!(reg1, 0x2000 | (10 << 6) | 10);
+        assert_eq!(reg2 & EDGE, 0);
+        assert_eq!(clkout0_divide(reg1, reg2), 20);
+    }
+
+    #[test]
+    fn odd_divide_sets_edge() {
+        // divide by 7: high 3, low 4, edge set
+        let nominal = ((3 << 6) | 4, EDGE);
+        let (reg1, reg2) = clkout0_divided(nominal, 3).unwrap();
+        assert_eq!(clkout0_divide(reg1, reg2), 21);
+        assert_eq!(reg1, (10 << 6) | 11);
+        assert_eq!(reg2 & EDGE, EDGE);
+        // and back to an even divide clears it
+        let (_, reg2) = clkout0_divided(nominal, 2).unwrap();
+        assert_eq!(reg2 & EDGE, 0);
+    }
+
+    #[test]
+    fn no_count_leaves_the_bypass() {
+        let (reg1, reg2) = clkout0_divided((0x0000, NO_COUNT), 2).unwrap();
+        assert_eq!(reg2 & NO_COUNT, 0);
+        assert_eq!(clkout0_divide(reg1, reg2), 2);
+        assert_eq!(reg1, (1 << 6) | 1);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let nominal = ((32 << 6) | 32, 0);
+        assert_eq!(clkout0_divided(nominal, 2), None);
+        assert_eq!(clkout0_divided(nominal, 0), None);
+        assert!(clkout0_divided(((31 << 6) | 32, 0), 2).is_some());
+    }
+}
@@ -11,9 +11,55 @@ pub struct LogRecord {
     pub args: [u8; 3000],
 }
 
+/// Sets the level for a module path prefix. An empty `target` sets the default
+/// level that applies to modules with no more specific setting.
+#[repr(C)]
+pub struct LevelRequest {
+    pub target_length: u32,
+    pub target: [u8; 128],
+    /// A `log::LevelFilter` as a `u32`, or `LEVEL_INHERIT` to remove the setting
+    /// for `target` so it falls back to its parent's level.
+    pub level: u32,
+}
+
+pub const LEVEL_INHERIT: u32 = u32::MAX;
+
+/// Number of bytes of formatted log records kept by the server for `DumpRing`.
+pub const RING_CAPACITY: usize = 32 * 1024;
+
+/// A process that panicked, as assembled by the server from the `PanicStarted`,
+/// `PanicMessage` and `PanicFinished` messages it sent.
+#[repr(C)]
+pub struct CrashRecord {
+    pub pid: u32,
+    pub name_length: u32,
+    pub name: [u8; 64],
+    /// Milliseconds between boot and the end of the panic
+    pub uptime_ms: u64,
+    pub panic_length: u32,
+    pub panic: [u8; 1024],
+    /// The most recent lines from the log ring at the time of the panic
+    pub log_length: u32,
+    pub log: [u8; 2048],
+}
+
+/// Convert a `LevelFilter` that has been sent as an integer back into a `LevelFilter`.
+pub fn level_filter_from_u32(level: u32) -> Option<log::LevelFilter> {
+    match level {
+        0 => Some(log::LevelFilter::Off),
+        1 => Some(log::LevelFilter::Error),
+        2 => Some(log::LevelFilter::Warn),
+        3 => Some(log::LevelFilter::Info),
+        4 => Some(log::LevelFilter::Debug),
+        5 => Some(log::LevelFilter::Trace),
+        _ => None,
+    }
+}
+
 #[derive(Debug, PartialEq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum Opcode {
-    /// A `LogRecord` message, delivering structured log output
+    /// A `LogRecord` message, delivering structured log output. On return, `offset`
+    /// holds the most verbose `LevelFilter` enabled for the sending program, plus one.
     LogRecord = 0,
 
     /// A `&[u8]` destined for stdout
@@ -25,6 +71,25 @@ pub enum Opcode {
     /// A `xous::StringBuffer` containing this program's name
     ProgramName = 3,
 
+    /// A `LevelRequest` changing which records are emitted
+    SetLevel = 4,
+
+    /// A mutable buffer of `RING_CAPACITY` bytes, filled with the most recent log
+    /// records. `valid` is set to the number of bytes written.
+    DumpRing = 5,
+
+    /// A mutable buffer holding a `CrashRecord`. The server holds on to it until a
+    /// process panics, then fills it in and sets `valid` to its size. Only one caller
+    /// may wait at a time; any others get their buffer back immediately, with `valid`
+    /// set to `None`.
+    WaitForCrash = 6,
+
+    /// A blocking scalar: `arg1` is 1 to stop writing to the console UART, and 0 to start
+    /// again. Holding returns once the UART has finished sending. On release, the log
+    /// records that were kept back are written out from the ring; anything else printed
+    /// in the meantime is dropped.
+    HoldConsole = 7,
+
     /// A panic occurred, and a panic log is forthcoming
     PanicStarted = 1000,
 
@@ -66,6 +131,10 @@ pub enum Opcode {
     /// End of a panic
     PanicFinished = 1200,
 
+    /// Sent by the log server's own timestamp thread: the process in `arg1` panicked
+    /// `arg2 | arg3 << 32` milliseconds after boot
+    PanicUptime = 1300,
+
     /// Enable receiving messages when the system is resumed from sleep.
     EnableRx = 2000,
 }
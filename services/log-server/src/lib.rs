@@ -73,9 +73,18 @@ impl XousLoggerBacking<'_> {
             log_record.args_length = wrapper.len() as u32;
         }
 
-        self.buffer
+        let result = self
+            .buffer
             .lend(self.conn, crate::api::Opcode::LogRecord.to_u32().unwrap())
             .unwrap();
+
+        // The server replies with how verbose this program should be, which may have been
+        // changed by `set_level()` since the last record was sent.
+        if let xous::Result::MemoryReturned(Some(level), _) = result {
+            if let Some(filter) = api::level_filter_from_u32(level.get() as u32 - 1) {
+                log::set_max_level(filter);
+            }
+        }
     }
     fn resume(&self) {
         xous::send_message(
@@ -142,3 +151,127 @@ pub fn init_wait() -> Result<(), log::SetLoggerError> {
 pub fn resume() {
     unsafe { XOUS_LOGGER_BACKING.as_mut().unwrap().resume() };
 }
+
+/// A connection to the log server for changing which records it emits, and for reading
+/// back the ones it kept.
+pub struct LogControl {
+    conn: xous::CID,
+}
+
+impl LogControl {
+    pub fn new() -> Result<Self, xous::Error> {
+        Ok(LogControl {
+            conn: xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap())?,
+        })
+    }
+
+    /// Set the level of records emitted for `target`, which is a module path such as `net` or
+    /// `net::connection_manager`, and all of the modules beneath it. Passing `None` removes the
+    /// setting so the target inherits the level of its parent, and an empty `target` changes
+    /// the default level for everything.
+    ///
+    /// A process only starts sending records at a more verbose level after the next record
+    /// that it logs, as that is when it learns of the change.
+    pub fn set_level(&self, target: &str, level: Option<log::LevelFilter>) -> Result<(), xous::Error> {
+        let mut buffer = Buffer::new(core::mem::size_of::<api::LevelRequest>());
+        {
+            let request = unsafe { &mut *(buffer.as_mut_ptr() as *mut api::LevelRequest) };
+            let target = target.as_bytes();
+            if target.len() > request.target.len() {
+                return Err(xous::Error::InvalidString);
+            }
+            request.target[..target.len()].copy_from_slice(target);
+            request.target_length = target.len() as u32;
+            request.level = level.map(|l| l as u32).unwrap_or(api::LEVEL_INHERIT);
+        }
+        buffer
+            .lend(self.conn, api::Opcode::SetLevel.to_u32().unwrap())
+            .map(|_| ())
+    }
+
+    /// Fetch the most recent log records kept by the log server, oldest first, as they
+    /// were printed to the console.
+    pub fn dump_ring(&self) -> Result<String, xous::Error> {
+        let mut buffer = Buffer::new(api::RING_CAPACITY);
+        match buffer.lend_mut(self.conn, api::Opcode::DumpRing.to_u32().unwrap())? {
+            xous::Result::MemoryReturned(_, valid) => {
+                let len = valid.map(|v| v.get()).unwrap_or(0).min(api::RING_CAPACITY);
+                Ok(String::from_utf8_lossy(&buffer[..len]).into_owned())
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Block until a process panics, and return what it reported. Crashes that happened
+    /// while nobody was waiting are returned first, oldest first.
+    ///
+    /// Only one process may wait at a time. If another is already waiting, this returns
+    /// `ServerQueueFull`.
+    pub fn wait_for_crash(&self) -> Result<CrashReport, xous::Error> {
+        let mut buffer = Buffer::new(core::mem::size_of::<api::CrashRecord>());
+        match buffer.lend_mut(self.conn, api::Opcode::WaitForCrash.to_u32().unwrap())? {
+            xous::Result::MemoryReturned(_, Some(_)) => {
+                let record = unsafe { &*(buffer.as_ptr() as *const api::CrashRecord) };
+                let text = |bytes: &[u8], length: u32| {
+                    let length = (length as usize).min(bytes.len());
+                    String::from_utf8_lossy(&bytes[..length]).into_owned()
+                };
+                Ok(CrashReport {
+                    pid: record.pid,
+                    name: text(&record.name, record.name_length),
+                    uptime_ms: record.uptime_ms,
+                    panic: text(&record.panic, record.panic_length),
+                    log: text(&record.log, record.log_length),
+                })
+            }
+            xous::Result::MemoryReturned(_, None) => Err(xous::Error::ServerQueueFull),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Stop or restart output on the console UART. The UART baud rate is derived from the
+    /// system clock, so this is used around clock changes that would garble it. Log records
+    /// are still kept while the console is held, and are written out when it is released.
+    pub fn hold_console(&self, hold: bool) -> Result<(), xous::Error> {
+        xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::HoldConsole.to_usize().unwrap(),
+                if hold { 1 } else { 0 },
+                0,
+                0,
+                0,
+            ),
+        )
+        .map(|_| ())
+    }
+}
+
+/// A process that panicked, as reported by `wait_for_crash()`.
+#[derive(Debug)]
+pub struct CrashReport {
+    pub pid: u32,
+    /// The program's name, or an empty string if it never sent one or logged anything
+    pub name: String,
+    /// Milliseconds between boot and the panic
+    pub uptime_ms: u64,
+    pub panic: String,
+    /// The most recent log lines from all processes at the time of the panic
+    pub log: String,
+}
+
+impl core::fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "PID {} ({}) panicked {}.{:03}s after boot:",
+            self.pid,
+            self.name,
+            self.uptime_ms / 1000,
+            self.uptime_ms % 1000
+        )?;
+        writeln!(f, "{}", self.panic.trim_end())?;
+        writeln!(f, "Recent log:")?;
+        write!(f, "{}", self.log)
+    }
+}
@@ -10,6 +10,10 @@ mod debug;
 
 use core::fmt::Write;
 use num_traits::FromPrimitive;
+use std::collections::{HashMap, VecDeque};
+
+/// Number of crashes kept for `WaitForCrash` before the oldest is dropped
+const MAX_CRASHES: usize = 8;
 
 #[cfg(not(any(target_os = "none", target_os = "xous")))]
 mod implementation {
@@ -109,13 +113,20 @@ mod implementation {
             Ok(())
         }
     }
+
+    /// stdout doesn't depend on the system clock, so there is nothing to hold back
+    pub fn hold(_held: bool) {}
 }
 
 #[cfg(any(target_os = "none", target_os = "xous"))]
 mod implementation {
     use core::fmt::{Error, Write};
+    use core::sync::atomic::{AtomicBool, Ordering};
     use utralib::generated::*;
 
+    /// Set while the system clock is divided, which leaves the UART at the wrong baud rate
+    static HELD: AtomicBool = AtomicBool::new(false);
+
     pub struct Output {}
 
     pub fn init() -> Output {
@@ -205,7 +216,7 @@ mod implementation {
 
     impl OutputWriter {
         pub fn putc(&self, c: u8) {
-            if cfg!(feature = "logging") {
+            if cfg!(feature = "logging") && !HELD.load(Ordering::SeqCst) {
                 let mut uart_csr = CSR::new(unsafe { crate::debug::DEFAULT_UART_ADDR as *mut u32 });
 
                 // Wait until TXFULL is `0`
@@ -245,16 +256,301 @@ mod implementation {
             Ok(())
         }
     }
+
+    /// Stop or restart writing to the UART. Holding waits for the characters already in
+    /// the FIFO to go out, so nothing is sent while the clock is being changed.
+    pub fn hold(held: bool) {
+        HELD.store(held, Ordering::SeqCst);
+        if held && cfg!(feature = "logging") {
+            let uart_csr = CSR::new(unsafe { crate::debug::DEFAULT_UART_ADDR as *mut u32 });
+            while uart_csr.r(utra::uart::TXEMPTY) == 0 {}
+        }
+    }
+}
+
+/// A panic that has not been collected through `WaitForCrash` yet
+struct Crash {
+    pid: xous::PID,
+    name: String,
+    /// Filled in by the timestamp thread shortly after the panic
+    uptime_ms: Option<u64>,
+    panic: Vec<u8>,
+    log: Vec<u8>,
+}
+
+/// Per-module level settings, the most recent records kept for `DumpRing`, and
+/// crashes waiting to be collected.
+struct LogState {
+    default_level: log::LevelFilter,
+    /// Module path prefixes and their levels, as set by `SetLevel`
+    levels: Vec<(String, log::LevelFilter)>,
+    ring: VecDeque<String>,
+    ring_bytes: usize,
+    /// Program names, from `ProgramName` or else the first module each process logs from
+    names: HashMap<xous::PID, String>,
+    /// Text of panics that are still being sent
+    panics: HashMap<xous::PID, Vec<u8>>,
+    crashes: VecDeque<Crash>,
+    crash_waiter: Option<xous::MessageEnvelope>,
+    /// Connection to the thread that asks the ticktimer when each panic happened
+    stamper: xous::CID,
+    /// While the console is held, the number of records at the end of `ring` that haven't
+    /// been written to it
+    held: Option<usize>,
+}
+
+impl LogState {
+    fn new(stamper: xous::CID) -> Self {
+        LogState {
+            default_level: log::LevelFilter::Info,
+            levels: Vec::new(),
+            ring: VecDeque::new(),
+            ring_bytes: 0,
+            names: HashMap::new(),
+            panics: HashMap::new(),
+            crashes: VecDeque::new(),
+            crash_waiter: None,
+            stamper,
+            held: None,
+        }
+    }
+
+    /// The level set for the longest prefix of `module` that ends on a `::` boundary.
+    fn level_for(&self, module: &str) -> log::LevelFilter {
+        let mut best: Option<&(String, log::LevelFilter)> = None;
+        for entry in self.levels.iter() {
+            if is_within(module, &entry.0)
+                && best.map(|b| b.0.len() < entry.0.len()).unwrap_or(true)
+            {
+                best = Some(entry);
+            }
+        }
+        best.map(|b| b.1).unwrap_or(self.default_level)
+    }
+
+    /// The most verbose level enabled anywhere in the program that `module` belongs to.
+    /// Processes drop records above this level before sending them, so it must cover
+    /// every submodule that has been set to something more verbose.
+    fn program_level(&self, module: &str) -> log::LevelFilter {
+        let program = module.split("::").next().unwrap_or_default();
+        let mut level = self.level_for(program);
+        for (target, target_level) in self.levels.iter() {
+            if is_within(target, program) {
+                level = level.max(*target_level);
+            }
+        }
+        level
+    }
+
+    fn set_level(&mut self, target: &str, level: Option<log::LevelFilter>) {
+        if target.is_empty() {
+            self.default_level = level.unwrap_or(log::LevelFilter::Info);
+            return;
+        }
+        self.levels.retain(|(t, _)| t != target);
+        if let Some(level) = level {
+            self.levels.push((target.to_owned(), level));
+        }
+    }
+
+    fn record(&mut self, line: String) {
+        self.ring_bytes += line.len();
+        self.ring.push_back(line);
+        while self.ring_bytes > RING_CAPACITY {
+            match self.ring.pop_front() {
+                Some(oldest) => self.ring_bytes -= oldest.len(),
+                None => break,
+            }
+        }
+        let kept = self.ring.len();
+        if let Some(held) = self.held.as_mut() {
+            *held = (*held + 1).min(kept);
+        }
+    }
+
+    fn hold_console(&mut self, output: &mut implementation::OutputWriter, hold: bool) {
+        if hold {
+            self.held.get_or_insert(0);
+            implementation::hold(true);
+        } else if let Some(held) = self.held.take() {
+            implementation::hold(false);
+            for line in self.ring.iter().skip(self.ring.len() - held) {
+                write!(output, "{}", line).ok();
+            }
+        }
+    }
+
+    /// Copy as many of the most recent records as will fit into `buf`, oldest first,
+    /// and return the number of bytes written.
+    fn dump(&self, buf: &mut [u8]) -> usize {
+        let mut total = self.ring_bytes;
+        let mut skip = 0;
+        for line in self.ring.iter() {
+            if total <= buf.len() {
+                break;
+            }
+            total -= line.len();
+            skip += 1;
+        }
+        let mut len = 0;
+        for line in self.ring.iter().skip(skip) {
+            buf[len..len + line.len()].copy_from_slice(line.as_bytes());
+            len += line.len();
+        }
+        len
+    }
+
+    fn panic_started(&mut self, pid: xous::PID) {
+        self.panics.insert(pid, Vec::new());
+    }
+
+    fn panic_text(&mut self, pid: xous::PID, text: &[u8]) {
+        if let Some(panic) = self.panics.get_mut(&pid) {
+            panic.extend_from_slice(text);
+        }
+    }
+
+    fn panic_finished(&mut self, pid: xous::PID) {
+        // Same size as `CrashRecord::log`
+        let mut log = vec![0u8; 2048];
+        let log_length = self.dump(&mut log);
+        log.truncate(log_length);
+        let crash = Crash {
+            pid,
+            name: self.names.get(&pid).cloned().unwrap_or_default(),
+            uptime_ms: None,
+            panic: self.panics.remove(&pid).unwrap_or_default(),
+            log,
+        };
+        if self.crashes.len() >= MAX_CRASHES {
+            self.crashes.pop_front();
+        }
+        self.crashes.push_back(crash);
+        // Asking the ticktimer from here could deadlock, as it logs through this thread
+        // too, so the timestamp is taken by another thread and sent back as `PanicUptime`
+        if xous::try_send_message(
+            self.stamper,
+            xous::Message::new_scalar(0, pid.get() as usize, 0, 0, 0),
+        )
+        .is_err()
+        {
+            self.stamp_crash(pid, 0);
+        }
+    }
+
+    /// Record when the most recent crash of `pid` without a timestamp happened, and pass
+    /// it on if it's next in line.
+    fn stamp_crash(&mut self, pid: xous::PID, uptime_ms: u64) {
+        if let Some(crash) = self
+            .crashes
+            .iter_mut()
+            .filter(|crash| crash.pid == pid && crash.uptime_ms.is_none())
+            .last()
+        {
+            crash.uptime_ms = Some(uptime_ms);
+        }
+        self.deliver_crash();
+    }
+
+    fn wait_for_crash(&mut self, mut envelope: xous::MessageEnvelope) {
+        if let xous::Message::MutableBorrow(mem) = &mut envelope.body {
+            if self.crash_waiter.is_some() || mem.buf.len() < core::mem::size_of::<CrashRecord>() {
+                mem.valid = None;
+                return;
+            }
+            self.crash_waiter = Some(envelope);
+            self.deliver_crash();
+        }
+    }
+
+    /// Hand the oldest crash to the process waiting in `WaitForCrash`, if there is one.
+    /// Dropping its envelope returns the buffer and lets it continue.
+    fn deliver_crash(&mut self) {
+        if self
+            .crashes
+            .front()
+            .map(|crash| crash.uptime_ms.is_none())
+            .unwrap_or(true)
+        {
+            return;
+        }
+        if let Some(mut envelope) = self.crash_waiter.take() {
+            let crash = self.crashes.pop_front().unwrap();
+            if let xous::Message::MutableBorrow(mem) = &mut envelope.body {
+                let record = unsafe { &mut *(mem.buf.as_mut_ptr() as *mut CrashRecord) };
+                record.pid = crash.pid.get() as u32;
+                record.name_length = copy_truncated(&mut record.name, crash.name.as_bytes());
+                record.uptime_ms = crash.uptime_ms.unwrap_or_default();
+                record.panic_length = copy_truncated(&mut record.panic, &crash.panic);
+                record.log_length = copy_truncated(&mut record.log, &crash.log);
+                mem.valid = xous::MemorySize::new(core::mem::size_of::<CrashRecord>());
+            }
+        }
+    }
+}
+
+/// Copy as much of `src` as fits into `dest`, and return the number of bytes copied.
+fn copy_truncated(dest: &mut [u8], src: &[u8]) -> u32 {
+    let len = dest.len().min(src.len());
+    dest[..len].copy_from_slice(&src[..len]);
+    len as u32
+}
+
+/// Answer each message on `sid`, whose `arg1` is the PID of a process that just panicked,
+/// with a `PanicUptime` message to the log server giving the ticktimer's elapsed time. The
+/// ticktimer server itself depends on this crate, so it's messaged directly instead of
+/// through `ticktimer_server::Ticktimer`.
+fn stamp_thread(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
+    /// `ticktimer_server::api::Opcode::ElapsedMs`
+    const TICKTIMER_ELAPSED_MS: usize = 0;
+    let sid = xous::SID::from_u32(sid0 as u32, sid1 as u32, sid2 as u32, sid3 as u32);
+    let log_conn = xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap())
+        .expect("couldn't connect to the log server");
+    let ticktimer = xous::connect(xous::SID::from_bytes(b"ticktimer-server").unwrap())
+        .expect("couldn't connect to the ticktimer");
+    loop {
+        let msg = xous::receive_message(sid).expect("couldn't receive a panic to timestamp");
+        if let Some(scalar) = msg.body.scalar_message() {
+            let uptime_ms = match xous::send_message(
+                ticktimer,
+                xous::Message::new_blocking_scalar(TICKTIMER_ELAPSED_MS, 0, 0, 0, 0),
+            ) {
+                Ok(xous::Result::Scalar2(lower, upper)) => lower as u64 | ((upper as u64) << 32),
+                _ => 0,
+            };
+            xous::send_message(
+                log_conn,
+                xous::Message::new_scalar(
+                    api::Opcode::PanicUptime as usize,
+                    scalar.arg1,
+                    uptime_ms as usize,
+                    (uptime_ms >> 32) as usize,
+                    0,
+                ),
+            )
+            .ok();
+        }
+    }
+}
+
+/// Returns `true` if `module` is `target` or one of its submodules.
+fn is_within(module: &str, target: &str) -> bool {
+    module.starts_with(target)
+        && (module.len() == target.len() || module[target.len()..].starts_with("::"))
 }
 
 fn handle_scalar(
     output: &mut implementation::OutputWriter,
+    state: &mut LogState,
     sender: xous::MessageSender,
     msg: &xous::ScalarMessage,
     sender_pid: xous::PID,
 ) {
     match msg.id {
-        1000 => writeln!(output, "PANIC in PID {}:", sender_pid).unwrap(),
+        1000 => {
+            writeln!(output, "PANIC in PID {}:", sender_pid).unwrap();
+            state.panic_started(sender_pid);
+        }
         1100 => (),
         1101..=1132 => {
             let mut output_bfr = [0u8; core::mem::size_of::<usize>() * 4];
@@ -282,8 +578,19 @@ fn handle_scalar(
                 }
                 output.putc(*c);
             }
+            state.panic_text(sender_pid, &output_bfr[..total_chars.min(output_bfr.len())]);
+        }
+        1200 => {
+            writeln!(output, "Terminating process").unwrap();
+            state.panic_finished(sender_pid);
+        }
+        1300 => {
+            if sender_pid.get() as u32 == xous::process::id() {
+                if let Some(pid) = xous::PID::new(msg.arg1 as u8) {
+                    state.stamp_crash(pid, msg.arg2 as u64 | ((msg.arg3 as u64) << 32));
+                }
+            }
         }
-        1200 => writeln!(output, "Terminating process").unwrap(),
         2000 => {
             #[cfg(any(target_os = "none", target_os = "xous"))]
             crate::debug::DEFAULT.enable_rx();
@@ -300,15 +607,20 @@ fn handle_scalar(
 
 fn handle_opcode(
     output: &mut implementation::OutputWriter,
+    state: &mut LogState,
     sender: xous::MessageSender,
     opcode: api::Opcode,
-    message: &xous::Message,
+    message: &mut xous::Message,
 ) {
-    if let Some(mem) = message.memory_message() {
+    let (maybe_mem, is_mutable) = match message {
+        xous::Message::MutableBorrow(mem) => (Some(mem), true),
+        xous::Message::Borrow(mem) | xous::Message::Move(mem) => (Some(mem), false),
+        xous::Message::Scalar(_) | xous::Message::BlockingScalar(_) => (None, false),
+    };
+    if let Some(mem) = maybe_mem {
         match opcode {
             api::Opcode::LogRecord => {
-                let buffer = unsafe { xous_ipc::Buffer::from_memory_message(mem) };
-                let lr = unsafe { &*(buffer.as_ptr() as *const LogRecord) };
+                let lr = unsafe { &*(mem.buf.as_ptr() as *const LogRecord) };
                 let level = if log::Level::Error as u32 == lr.level {
                     "ERR "
                 } else if log::Level::Warn as u32 == lr.level {
@@ -337,24 +649,71 @@ fn handle_opcode(
                 let args_slice = &lr.args[0..lr.args_length as usize];
 
                 let module_slice = &lr.module[0..lr.module_length as usize];
-
-                write!(output, "{}:", level).ok();
-                for c in module_slice {
-                    output.putc(*c);
+                let module = String::from_utf8_lossy(module_slice);
+                if let Some(pid) = sender.pid() {
+                    if !state.names.contains_key(&pid) {
+                        let program = module.split("::").next().unwrap_or_default();
+                        state.names.insert(pid, program.to_owned());
+                    }
                 }
-                write!(output, ": ").ok();
-                for c in args_slice {
-                    output.putc(*c);
+
+                // Tell the sender how verbose it should be from now on
+                mem.offset = xous::MemorySize::new(state.program_level(&module) as usize + 1);
+                if lr.level > state.level_for(&module) as u32 {
+                    return;
                 }
 
-                write!(output, " (").ok();
-                for c in file_slice {
-                    output.putc(*c);
+                let mut line = String::new();
+                write!(
+                    line,
+                    "{}:{}: {} ({}",
+                    level,
+                    module,
+                    String::from_utf8_lossy(args_slice),
+                    String::from_utf8_lossy(file_slice),
+                )
+                .ok();
+                if let Some(line_number) = lr.line {
+                    write!(line, ":{}", line_number).ok();
+                }
+                writeln!(line, ")").ok();
+                write!(output, "{}", line).ok();
+                state.record(line);
+            }
+            api::Opcode::SetLevel => {
+                let buffer = unsafe { xous_ipc::Buffer::from_memory_message(mem) };
+                let request = unsafe { &*(buffer.as_ptr() as *const LevelRequest) };
+                if request.target_length as usize > request.target.len() {
+                    return;
+                }
+                let target =
+                    match core::str::from_utf8(&request.target[..request.target_length as usize]) {
+                        Ok(target) => target,
+                        Err(_) => return,
+                    };
+                let level = if request.level == LEVEL_INHERIT {
+                    None
+                } else if let Some(level) = level_filter_from_u32(request.level) {
+                    Some(level)
+                } else {
+                    return;
+                };
+                writeln!(output, "LOG: level for '{}' set to {:?}", target, level).ok();
+                state.set_level(target, level);
+            }
+            api::Opcode::DumpRing => {
+                if !is_mutable {
+                    return;
                 }
-                if let Some(line) = lr.line {
-                    write!(output, ":{}", line).ok();
+                let len = state.dump(mem.buf.as_slice_mut());
+                mem.valid = xous::MemorySize::new(len);
+            }
+            api::Opcode::ProgramName => {
+                let length = mem.valid.map(|v| v.get()).unwrap_or(0).min(mem.buf.len());
+                let name = String::from_utf8_lossy(&mem.buf.as_slice::<u8>()[..length]);
+                if let Some(pid) = sender.pid() {
+                    state.names.insert(pid, name.into_owned());
                 }
-                writeln!(output, ")").ok();
             }
             api::Opcode::StandardOutput | api::Opcode::StandardError => {
                 // let mut buffer_start_offset = mem.offset.map(|o| o.get()).unwrap_or(0);
@@ -387,7 +746,7 @@ fn handle_opcode(
         }
     } else if let Some(scalar) = message.scalar_message() {
         // Scalar message
-        handle_scalar(output, sender, scalar, sender.pid().unwrap());
+        handle_scalar(output, state, sender, scalar, sender.pid().unwrap());
     }
 }
 
@@ -398,6 +757,18 @@ fn reader_thread(arg: usize) {
     writeln!(output, "LOG: Server listening on address {:?}", server_addr).unwrap();
 
     println!("LOG: my PID is {}", xous::process::id());
+    let stamp_sid = xous::create_server().unwrap();
+    let stamp_conn = xous::connect(stamp_sid).unwrap();
+    let (sid0, sid1, sid2, sid3) = stamp_sid.to_u32();
+    xous::create_thread_4(
+        stamp_thread,
+        sid0 as usize,
+        sid1 as usize,
+        sid2 as usize,
+        sid3 as usize,
+    )
+    .unwrap();
+    let mut state = LogState::new(stamp_conn);
     let mut counter: usize = 0;
     loop {
         if counter.trailing_zeros() >= 12 {
@@ -405,10 +776,23 @@ fn reader_thread(arg: usize) {
         }
         counter += 1;
         // writeln!(output, "LOG: Waiting for an event...").unwrap();
-        let envelope = xous::syscall::receive_message(server_addr).expect("couldn't get address");
+        let mut envelope =
+            xous::syscall::receive_message(server_addr).expect("couldn't get address");
         let sender = envelope.sender;
         if let Some(opcode) = FromPrimitive::from_usize(envelope.body.id()) {
-            handle_opcode(output, sender, opcode, &envelope.body);
+            match opcode {
+                // The reply is deferred until a crash happens, so this keeps the envelope
+                api::Opcode::WaitForCrash => state.wait_for_crash(envelope),
+                api::Opcode::HoldConsole => {
+                    if let Some(scalar) = envelope.body.scalar_message() {
+                        state.hold_console(output, scalar.arg1 != 0);
+                    }
+                    if envelope.body.is_blocking() {
+                        xous::return_scalar(sender, 0).ok();
+                    }
+                }
+                opcode => handle_opcode(output, &mut state, sender, opcode, &mut envelope.body),
+            }
         } else {
             writeln!(
                 output,
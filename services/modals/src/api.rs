@@ -60,6 +60,74 @@ pub struct DynamicNotification {
     pub text: Option<xous_ipc::String::<2048>>,
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedSlider {
+    pub token: [u32; 4],
+    pub prompt: xous_ipc::String::<1024>,
+    pub min: u32,
+    /// must be larger than `min`
+    pub max: u32,
+    /// must be non-zero
+    pub step: u32,
+    pub initial: u32,
+    /// shown after the current value; must be less than 8 bytes long
+    pub units: Option<xous_ipc::String::<8>>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedYesNo {
+    pub token: [u32; 4],
+    pub prompt: xous_ipc::String::<1024>,
+    /// milliseconds to wait for an answer before giving up, or 0 to wait forever
+    pub timeout_ms: u32,
+}
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum YesNoResponse {
+    Yes,
+    No,
+    TimedOut,
+    Error,
+}
+
+/// maximum number of fields in a form; bounded so that both the form and its response fit in one page
+pub const MAX_FORM_FIELDS: usize = 8;
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct FormField {
+    pub label: xous_ipc::String::<64>,
+    /// entry is obscured, and the modal is drawn in the inverted "trusted" style
+    pub is_password: bool,
+}
+impl FormField {
+    pub fn new(label: &str) -> Self {
+        FormField { label: xous_ipc::String::from_str(label), is_password: false }
+    }
+    pub fn new_password(label: &str) -> Self {
+        FormField { label: xous_ipc::String::from_str(label), is_password: true }
+    }
+}
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedForm {
+    pub token: [u32; 4],
+    pub title: xous_ipc::String::<512>,
+    /// fields are filled in order, and the first `None` ends the form
+    pub fields: [Option<FormField>; MAX_FORM_FIELDS],
+}
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct FormPayload {
+    /// entries line up with `ManagedForm::fields`
+    pub entries: [Option<TextEntryPayload>; MAX_FORM_FIELDS],
+    /// set if the form could not be run; `entries` is empty in that case
+    pub error: bool,
+}
+impl FormPayload {
+    pub fn new() -> Self {
+        FormPayload {
+            entries: [None; MAX_FORM_FIELDS],
+            error: false,
+        }
+    }
+}
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 pub(crate) enum Opcode {
     // these are blocking calls
@@ -73,6 +141,12 @@ pub(crate) enum Opcode {
     Notification,
     /// dynamic notification - a simple non-interactive notification that allows its text to be dynamically updated
     DynamicNotification,
+    /// pick a value from a range with a slider
+    PromptWithSlider,
+    /// ask a yes/no question, optionally giving up after a timeout
+    PromptWithYesNo,
+    /// fill in a series of text fields in one go
+    PromptWithForm,
 
     // these are non-blocking calls
     /// add an item to the radio box or check box. Note that all added items
@@ -177,6 +177,87 @@ impl Modals {
         Ok(ret)
     }
 
+    /// Asks the user to pick a value between `min` and `max` in increments of `step`, starting at `initial`.
+    /// `units` is shown after the value and must be less than 8 bytes long.
+    pub fn get_slider(&self, prompt: &str, min: u32, max: u32, step: u32, initial: u32, units: Option<&str>) -> Result<u32, xous::Error> {
+        self.lock();
+        let spec = ManagedSlider {
+            token: self.token,
+            prompt: xous_ipc::String::from_str(prompt),
+            min,
+            max,
+            step,
+            initial,
+            units: if let Some(u) = units {Some(xous_ipc::String::from_str(u))} else {None},
+        };
+        let mut buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::PromptWithSlider.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        match buf.to_original::<Option<u32>, _>() {
+            Ok(Some(value)) => Ok(value),
+            _ => Err(xous::Error::InternalError)
+        }
+    }
+
+    /// Asks a yes/no question. With a `timeout`, gives up and returns `xous::Error::Timeout` if the
+    /// user hasn't answered by then; the modal is taken down in that case.
+    pub fn get_yes_no(&self, prompt: &str, timeout: Option<core::time::Duration>) -> Result<bool, xous::Error> {
+        self.lock();
+        let spec = ManagedYesNo {
+            token: self.token,
+            prompt: xous_ipc::String::from_str(prompt),
+            // a zero timeout means "wait forever" on the wire, so round sub-millisecond timeouts up
+            timeout_ms: timeout.map(|t| (t.as_millis() as u32).max(1)).unwrap_or(0),
+        };
+        let mut buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::PromptWithYesNo.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        match buf.to_original::<YesNoResponse, _>() {
+            Ok(YesNoResponse::Yes) => Ok(true),
+            Ok(YesNoResponse::No) => Ok(false),
+            Ok(YesNoResponse::TimedOut) => Err(xous::Error::Timeout),
+            _ => Err(xous::Error::InternalError)
+        }
+    }
+
+    /// Fills in a series of text fields under one `title`, returning the entries in the same order as
+    /// `fields`. At most `MAX_FORM_FIELDS` fields are supported; more returns `xous::Error::OutOfMemory`.
+    /// Callers should `volatile_clear()` any entries that came from password fields once they're done.
+    pub fn get_form(&self, title: &str, fields: &[FormField]) -> Result<Vec::<TextEntryPayload>, xous::Error> {
+        if fields.len() > MAX_FORM_FIELDS {
+            return Err(xous::Error::OutOfMemory);
+        }
+        if fields.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.lock();
+        let mut spec = ManagedForm {
+            token: self.token,
+            title: xous_ipc::String::from_str(title),
+            fields: [None; MAX_FORM_FIELDS],
+        };
+        for (dest, &field) in spec.fields.iter_mut().zip(fields.iter()) {
+            *dest = Some(field);
+        }
+        let mut buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::PromptWithForm.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let mut response = buf.to_original::<FormPayload, _>().or(Err(xous::Error::InternalError))?;
+        let mut ret = Vec::<TextEntryPayload>::new();
+        if !response.error {
+            for maybe_entry in response.entries.iter().take(fields.len()) {
+                ret.push(maybe_entry.unwrap_or(TextEntryPayload::new()));
+            }
+        }
+        for maybe_entry in response.entries.iter_mut() {
+            if let Some(entry) = maybe_entry {
+                entry.volatile_clear();
+            }
+        }
+        if response.error {
+            Err(xous::Error::InternalError)
+        } else {
+            Ok(ret)
+        }
+    }
+
     pub fn dynamic_notification(&self, title: Option<&str>, text: Option<&str>) -> Result<(), xous::Error> {
         self.lock();
         let spec = DynamicNotification {
@@ -16,7 +16,6 @@ use std::thread;
 use gam::modal::*;
 #[cfg(feature="tts")]
 use tts_frontend::TtsFrontend;
-#[cfg(feature="tts")]
 use locales::t;
 #[cfg(feature="tts")]
 const TICK_INTERVAL: u64 = 2500;
@@ -38,6 +37,14 @@ enum RendererState {
     ResponseRadio(ItemName),
     ResponseCheckBox(CheckBoxPayload),
     RunDynamicNotification(DynamicNotification),
+    RunSlider(ManagedSlider),
+    RunYesNo(ManagedYesNo),
+    RunForm(ManagedForm),
+    ResponseSlider(u32),
+    ResponseYesNo(YesNoResponse),
+    ResponseForm(FormPayload),
+    /// the renderer was asked to run something it can't; the caller gets an error response
+    Error,
 }
 
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
@@ -50,8 +57,13 @@ pub(crate) enum RendererOp {
     RadioReturn,
     CheckBoxReturn,
     NotificationReturn,
+    SliderReturn,
+    YesNoReturn,
+    FormFieldReturn,
 
     AddModalItem,
+    /// take down whatever modal is up, e.g. when a yes/no question times out
+    CancelModal,
 
     UpdateDynamicNotification,
     CloseDynamicNotification,
@@ -112,6 +124,8 @@ fn xmain() -> ! {
             let mut progress_action = Slider::new(renderer_cid, RendererOp::Gutter.to_u32().unwrap(),
                 0, 100, 1, Some("%"), 0, true, true
             );
+            let mut form_index: usize = 0;
+            let mut form_payload = FormPayload::new();
             let mut last_percentage = 0;
             let mut start_work: u32 = 0;
             let mut end_work: u32 = 100;
@@ -136,7 +150,7 @@ fn xmain() -> ! {
                 match FromPrimitive::from_usize(msg.body.id()) {
                     Some(RendererOp::InitiateOp) => {
                         log::debug!("InitiateOp called");
-                        let mutex_op = op.lock().unwrap();
+                        let mut mutex_op = op.lock().unwrap();
                         match *mutex_op {
                             RendererState::RunText(config) => {
                                 log::debug!("initiating text entry modal");
@@ -239,13 +253,55 @@ fn xmain() -> ! {
                                 );
                                 renderer_modal.activate();
                             },
+                            RendererState::RunSlider(config) => {
+                                let mut slider = Slider::new(renderer_cid, RendererOp::SliderReturn.to_u32().unwrap(),
+                                    config.min, config.max, config.step,
+                                    config.units.as_ref().map(|u| u.as_str().unwrap()),
+                                    config.initial, false, true
+                                );
+                                slider.set_state(config.initial);
+                                #[cfg(feature="tts")]
+                                tts.tts_simple(config.prompt.as_str().unwrap()).unwrap();
+                                renderer_modal.modify(
+                                    Some(ActionType::Slider(slider)),
+                                    Some(config.prompt.as_str().unwrap()), false,
+                                    None, true, None
+                                );
+                                renderer_modal.activate();
+                            },
+                            RendererState::RunYesNo(config) => {
+                                let mut yes_no = gam::modal::RadioButtons::new(
+                                    renderer_cid,
+                                    RendererOp::YesNoReturn.to_u32().unwrap()
+                                );
+                                yes_no.add_item(ItemName::new(t!("modals.yes", xous::LANG)));
+                                yes_no.add_item(ItemName::new(t!("modals.no", xous::LANG)));
+                                #[cfg(feature="tts")]
+                                {
+                                    tts.tts_blocking(t!("modals.radiobutton", xous::LANG)).unwrap();
+                                    tts.tts_blocking(config.prompt.as_str().unwrap()).unwrap();
+                                }
+                                renderer_modal.modify(
+                                    Some(ActionType::RadioButtons(yes_no)),
+                                    Some(config.prompt.as_str().unwrap()), false,
+                                    None, true, None
+                                );
+                                renderer_modal.activate();
+                            },
+                            RendererState::RunForm(config) => {
+                                form_index = 0;
+                                form_payload = FormPayload::new();
+                                #[cfg(feature="tts")]
+                                tts.tts_simple(config.title.as_str().unwrap()).unwrap();
+                                raise_form_field(&mut renderer_modal, text_action, &config, form_index);
+                            },
                             RendererState::None => {
                                 log::error!("Operation initiated with no argument specified. Ignoring request.");
                                 continue;
                             }
                             _ => {
-                                log::warn!("unimplemented arm in renderer match");
-                                unimplemented!();
+                                log::error!("Operation initiated from a state the renderer can't run: {:?}", *mutex_op);
+                                *mutex_op = RendererState::Error;
                             }
                         }
                     },
@@ -295,10 +351,7 @@ fn xmain() -> ! {
                                 );
                                 renderer_modal.redraw();
                             }
-                            _ => {
-                                log::error!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                                panic!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                            }
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
                         }
                     },
                     Some(RendererOp::CloseDynamicNotification) => {
@@ -338,10 +391,7 @@ fn xmain() -> ! {
                                 }
                             }
                             RendererState::None => log::warn!("Text entry detected a fat finger event, ignoring."),
-                            _ => {
-                                log::error!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                                panic!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                            }
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
                         }
                     }
                     Some(RendererOp::NotificationReturn) => {
@@ -349,11 +399,63 @@ fn xmain() -> ! {
                         match *mutex_op {
                             RendererState::RunNotification(_) => *mutex_op = RendererState::None,
                             RendererState::None => log::warn!("Notification detected a fat finger event, ignoring."),
-                            _ => {
-                                log::error!("UX return opcode does not match our current operation in flight: {:?}", mutex_op);
-                                panic!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
+                        }
+                    },
+                    Some(RendererOp::SliderReturn) => msg_scalar_unpack!(msg, value, _, _, _, {
+                        let mut mutex_op = op.lock().unwrap();
+                        match *mutex_op {
+                            RendererState::RunSlider(_) => *mutex_op = RendererState::ResponseSlider(value as u32),
+                            RendererState::ResponseSlider(_) => log::warn!("Slider detected a fat finger event, ignoring."),
+                            RendererState::None => log::warn!("Slider detected a fat finger event, ignoring."),
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
+                        }
+                    }),
+                    Some(RendererOp::YesNoReturn) => {
+                        let mut mutex_op = op.lock().unwrap();
+                        match *mutex_op {
+                            RendererState::RunYesNo(_) => {
+                                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                                let item = buffer.to_original::<RadioButtonPayload, _>().unwrap();
+                                *mutex_op = if item.as_str() == t!("modals.yes", xous::LANG) {
+                                    RendererState::ResponseYesNo(YesNoResponse::Yes)
+                                } else {
+                                    RendererState::ResponseYesNo(YesNoResponse::No)
+                                };
+                            }
+                            // includes answers that arrive after the question timed out
+                            RendererState::ResponseYesNo(_) => log::warn!("Yes/no buttons detected a fat finger event, ignoring."),
+                            RendererState::None => log::warn!("Yes/no buttons detected a fat finger event, ignoring."),
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
+                        }
+                    }
+                    Some(RendererOp::FormFieldReturn) => {
+                        let mut mutex_op = op.lock().unwrap();
+                        match *mutex_op {
+                            RendererState::RunForm(config) => {
+                                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                                let entry = buffer.to_original::<TextEntryPayload, _>().unwrap();
+                                form_payload.entries[form_index] = Some(entry);
+                                form_index += 1;
+                                if form_index < MAX_FORM_FIELDS && config.fields[form_index].is_some() {
+                                    raise_form_field(&mut renderer_modal, text_action, &config, form_index);
+                                } else {
+                                    *mutex_op = RendererState::ResponseForm(form_payload);
+                                    // don't keep our own copy around, the form may have had password fields
+                                    for entry in form_payload.entries.iter_mut() {
+                                        if let Some(e) = entry {
+                                            e.volatile_clear();
+                                        }
+                                    }
+                                    form_payload = FormPayload::new();
+                                }
                             }
+                            RendererState::None => log::warn!("Form detected a fat finger event, ignoring."),
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
                         }
+                    }
+                    Some(RendererOp::CancelModal) => {
+                        renderer_modal.gam.relinquish_focus().unwrap();
                     },
                     Some(RendererOp::Gutter) => {
                         log::info!("gutter op, doing nothing");
@@ -373,10 +475,7 @@ fn xmain() -> ! {
                             }
                             RendererState::ResponseRadio(_) => log::warn!("Radio buttons detected a fat finger event, ignoring."),
                             RendererState::None => log::warn!("Radio buttons detected a fat finger event, ignoring."),
-                            _ => {
-                                log::error!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                                panic!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                            }
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
                         }
                     }
                     Some(RendererOp::CheckBoxReturn) => {
@@ -389,10 +488,7 @@ fn xmain() -> ! {
                             }
                             RendererState::ResponseCheckBox(_) => log::warn!("Check boxes detected a fat finger event, ignoring."),
                             RendererState::None => log::warn!("Check boxes detected a fat finger event, ignoring."),
-                            _ => {
-                                log::error!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                                panic!("UX return opcode does not match our current operation in flight. This is a serious internal error.");
-                            }
+                            _ => log::error!("UX return opcode does not match our current operation in flight: {:?}", *mutex_op),
                         }
                     }
                     Some(RendererOp::ModalRedraw) => {
@@ -475,7 +571,9 @@ fn xmain() -> ! {
                         },
                         _ => {
                             log::error!("Illegal state transition in renderer");
-                            panic!("Illegal state transition in renderer");
+                            buffer.replace(ItemName::new("internal error")).unwrap();
+                            token_lock = None;
+                            break;
                         }
                     }
                     tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
@@ -504,7 +602,9 @@ fn xmain() -> ! {
                         },
                         _ => {
                             log::error!("Illegal state transition in renderer");
-                            panic!("Illegal state transition in renderer");
+                            buffer.replace(CheckBoxPayload::new()).unwrap();
+                            token_lock = None;
+                            break;
                         }
                     }
                     tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
@@ -533,7 +633,9 @@ fn xmain() -> ! {
                         },
                         _ => {
                             log::error!("Illegal state transition in renderer");
-                            panic!("Illegal state transition in renderer");
+                            buffer.replace(TextEntryPayload::new()).unwrap();
+                            token_lock = None;
+                            break;
                         }
                     }
                     tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
@@ -557,7 +659,137 @@ fn xmain() -> ! {
                         RendererState::None => {token_lock = None; break},
                         _ => {
                             log::error!("Illegal state transition in renderer");
-                            panic!("Illegal state transition in renderer");
+                            token_lock = None;
+                            break;
+                        }
+                    }
+                    tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
+                }
+            },
+            Some(Opcode::PromptWithSlider) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let spec = buffer.to_original::<ManagedSlider, _>().unwrap();
+                if spec.token != token_lock.unwrap_or(default_nonce) {
+                    log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                    buffer.replace(None::<u32>).unwrap();
+                    continue;
+                }
+                if spec.max <= spec.min || spec.step == 0 {
+                    log::error!("Slider range is empty or has no step: {}..{} by {}", spec.min, spec.max, spec.step);
+                    buffer.replace(None::<u32>).unwrap();
+                    token_lock = None;
+                    continue;
+                }
+                *op.lock().unwrap() = RendererState::RunSlider(spec);
+                send_message(
+                renderer_cid,
+                    Message::new_scalar(RendererOp::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0)
+                ).expect("couldn't initiate UX op");
+                loop {
+                    match *op.lock().unwrap() {
+                        RendererState::RunSlider(_) => (),
+                        RendererState::ResponseSlider(value) => {
+                            buffer.replace(Some(value)).unwrap();
+                            token_lock = None;
+                            break;
+                        },
+                        _ => {
+                            log::error!("Illegal state transition in renderer");
+                            buffer.replace(None::<u32>).unwrap();
+                            token_lock = None;
+                            break;
+                        }
+                    }
+                    tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
+                }
+            },
+            Some(Opcode::PromptWithYesNo) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let spec = buffer.to_original::<ManagedYesNo, _>().unwrap();
+                if spec.token != token_lock.unwrap_or(default_nonce) {
+                    log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                    buffer.replace(YesNoResponse::Error).unwrap();
+                    continue;
+                }
+                let deadline = if spec.timeout_ms != 0 {
+                    Some(tt.elapsed_ms() + spec.timeout_ms as u64)
+                } else {
+                    None
+                };
+                *op.lock().unwrap() = RendererState::RunYesNo(spec);
+                send_message(
+                renderer_cid,
+                    Message::new_scalar(RendererOp::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0)
+                ).expect("couldn't initiate UX op");
+                loop {
+                    {
+                        let mut state = op.lock().unwrap();
+                        match *state {
+                            RendererState::RunYesNo(_) => {
+                                if deadline.map_or(false, |d| tt.elapsed_ms() >= d) {
+                                    // moving the state on makes the renderer ignore an answer that races the timeout
+                                    *state = RendererState::ResponseYesNo(YesNoResponse::TimedOut);
+                                    send_message(
+                                        renderer_cid,
+                                        Message::new_scalar(RendererOp::CancelModal.to_usize().unwrap(), 0, 0, 0, 0)
+                                    ).expect("couldn't cancel yes/no modal");
+                                }
+                            },
+                            RendererState::ResponseYesNo(response) => {
+                                buffer.replace(response).unwrap();
+                                token_lock = None;
+                                break;
+                            },
+                            _ => {
+                                log::error!("Illegal state transition in renderer");
+                                buffer.replace(YesNoResponse::Error).unwrap();
+                                token_lock = None;
+                                break;
+                            }
+                        }
+                    }
+                    tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
+                }
+            },
+            Some(Opcode::PromptWithForm) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let spec = buffer.to_original::<ManagedForm, _>().unwrap();
+                let mut error = FormPayload::new();
+                error.error = true;
+                if spec.token != token_lock.unwrap_or(default_nonce) {
+                    log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                    buffer.replace(error).unwrap();
+                    continue;
+                }
+                if spec.fields[0].is_none() {
+                    log::error!("Form has no fields");
+                    buffer.replace(error).unwrap();
+                    token_lock = None;
+                    continue;
+                }
+                *op.lock().unwrap() = RendererState::RunForm(spec);
+                send_message(
+                renderer_cid,
+                    Message::new_scalar(RendererOp::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0)
+                ).expect("couldn't initiate UX op");
+                loop {
+                    match *op.lock().unwrap() {
+                        RendererState::RunForm(_) => (),
+                        RendererState::ResponseForm(mut payload) => {
+                            buffer.replace(payload).unwrap();
+                            for entry in payload.entries.iter_mut() {
+                                if let Some(e) = entry {
+                                    e.volatile_clear();
+                                }
+                            }
+                            token_lock = None;
+                            break;
+                        },
+                        _ => {
+                            log::error!("Illegal state transition in renderer");
+                            buffer.replace(error).unwrap();
+                            token_lock = None;
+                            break;
                         }
                     }
                     tt.sleep_ms(100).unwrap(); // don't put the idle in the match/lock(), it'll prevent the other thread from running!
@@ -676,4 +908,23 @@ fn compute_checked_percentage(current: u32, start: u32, end: u32) -> u32 {
             (((current as u64 - start as u64) * 100) / (end as u64 - start as u64)) as u32
         }
     }
-}
\ No newline at end of file
+}
+
+/// Raises the text entry for field `index` of a form. Each field gets its own entry, with the form title
+/// repeated above the field label so it's clear which form is being filled in.
+fn raise_form_field(modal: &mut Modal, template: TextEntry, form: &ManagedForm, index: usize) {
+    let field = form.fields[index].expect("form field index out of range");
+    let mut entry = template;
+    entry.action_opcode = RendererOp::FormFieldReturn.to_u32().unwrap();
+    if field.is_password {
+        entry.is_password = true;
+        entry.visibility = TextEntryVisibility::LastChars;
+    }
+    let prompt = format!("{}\n{}", form.title.as_str().unwrap(), field.label.as_str().unwrap());
+    modal.modify(
+        Some(ActionType::TextEntry(entry)),
+        Some(&prompt), false,
+        None, true, None
+    );
+    modal.activate();
+}
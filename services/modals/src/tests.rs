@@ -91,7 +91,33 @@ pub(crate) fn spawn_test() {
             }
             log::info!("text input test done");
 
-            // 3. test notificatons
+            // 3. test slider
+            match modals.get_slider("Pick a volume", 0, 100, 5, 50, Some("%")) {
+                Ok(volume) => log::info!("volume set to {}", volume),
+                _ => log::error!("get_slider failed"),
+            }
+
+            // 4. test yes/no, with a timeout so the test can also run unattended
+            match modals.get_yes_no("Do you like tests?", Some(core::time::Duration::from_millis(10_000))) {
+                Ok(answer) => log::info!("yes/no answer: {}", answer),
+                Err(xous::Error::Timeout) => log::info!("yes/no timed out"),
+                _ => log::error!("get_yes_no failed"),
+            }
+
+            // 5. test form
+            let fields = [
+                modals::api::FormField::new("Name"),
+                modals::api::FormField::new_password("Secret"),
+            ];
+            match modals.get_form("Test form", &fields) {
+                Ok(mut entries) => {
+                    log::info!("Name: {}, secret is {} chars long", entries[0].as_str(), entries[1].as_str().len());
+                    entries[1].volatile_clear();
+                }
+                _ => log::error!("get_form failed"),
+            }
+
+            // 6. test notificatons
             log::info!("testing notification");
             modals.show_notification("这是一个测验!").expect("notification failed");
             log::info!("notification test done");
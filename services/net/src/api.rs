@@ -13,7 +13,6 @@ pub(crate) use tcp::*;
 use com::SsidRecord;
 use rkyv::{Archive, Deserialize, Serialize};
 use smoltcp::wire::IpAddress;
-use std::convert::TryInto;
 use std::fmt;
 use std::fmt::Debug;
 use std::io::Write;
@@ -31,6 +30,12 @@ pub const AP_DICT_NAME: &'static str = "wlan.networks";
 /// minimum revision required for compatibility with Net crate
 pub const MIN_EC_REV: u32 = 0x00_09_06_00;
 
+/// Bytes of frames (plus their pcap record headers) that a packet capture holds. Once it is
+/// full, the oldest frames are dropped to make room.
+pub const PCAP_RING_CAPACITY: usize = 64 * 1024;
+/// Length of the pcap file header in front of the captured frames
+pub const PCAP_HEADER_LEN: usize = 24;
+
 /// Dispatch opcodes to the Net crate main loop.
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 #[repr(C)]
@@ -176,6 +181,179 @@ pub(crate) enum Opcode {
 
     /// BlockingScalar call to set the NODELAY / "Nagle" value of this connection
     StdSetNodelay = 39,
+
+    /// BlockingScalar call to permit (arg 1 = 1) or forbid (arg 1 = 0) sending to broadcast addresses from
+    /// the UDP socket bound to the port in arg 0. Returns 1 on success, 0 if the port is not bound.
+    UdpSetBroadcast = 40,
+    /// BlockingScalar call to query the broadcast permission of the UDP socket bound to the port in arg 0.
+    /// Returns 0 or 1, or `usize::MAX` if the port is not bound.
+    UdpGetBroadcast = 41,
+    /// BlockingScalar call to set whether multicast packets sent from the UDP socket bound to the port in
+    /// arg 0 are looped back to local listeners. Returns 1 on success, 0 if the port is not bound.
+    UdpSetMulticastLoopV4 = 42,
+    /// BlockingScalar call to query the multicast loopback setting. Returns 0 or 1, or `usize::MAX` if
+    /// the port is not bound.
+    UdpGetMulticastLoopV4 = 43,
+    /// BlockingScalar call to set the hop limit used for multicast packets (arg 1) on the UDP socket
+    /// bound to the port in arg 0. Returns 1 on success, 0 if the port is not bound.
+    UdpSetMulticastTtlV4 = 44,
+    /// BlockingScalar call to query the multicast hop limit. Returns the TTL, or `usize::MAX` if the port
+    /// is not bound.
+    UdpGetMulticastTtlV4 = 45,
+    /// BlockingScalar call to join the multicast group in arg 1 (an IPv4 address in network order) from the
+    /// UDP socket bound to the port in arg 0. Arg 2 is the address of the local interface to join on;
+    /// there is only one interface, so it must be either unspecified or our own address.
+    /// Returns 0 on success, or a `NetError` code.
+    UdpJoinMulticastV4 = 46,
+    /// BlockingScalar call to leave a multicast group; arguments and return codes as `UdpJoinMulticastV4`.
+    UdpLeaveMulticastV4 = 47,
+
+    /// Memory message returning an `Option<Ipv6Config>`; `None` if the link is down.
+    GetIpv6Config = 48,
+
+    /// Create a TCP listener. The listener has a backlog of one connection: a connection that
+    /// arrives while another is waiting to be accepted is refused.
+    ///
+    /// # Arguments
+    ///
+    ///  u8 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | port number, low byte; a port of 0 picks a free ephemeral port
+    ///      1 | port number, high byte
+    ///      2 | address type -- 4 = ipv4, 6 = ipv6
+    ///    ... | remaining bytes are the local address to listen on, which may be unspecified
+    ///
+    /// # Returns
+    ///
+    /// u16 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | 0 indicating success
+    ///      1 | Listener index. This is a connection index, so `StdTcpClose`, `StdGetAddress`,
+    ///        | `StdGetTtl` and `StdSetTtl` all work on it.
+    ///      2 | local port
+    ///
+    /// # Errors
+    ///
+    /// As `StdTcpConnect`
+    StdTcpListen = 49,
+
+    /// Accept a connection on a TCP listener. The listener index is OR-ed into the top 16 bits
+    /// of the opcode. As with `StdTcpRx`, the `Offset` field is the timeout in ms, and no
+    /// `Offset` waits forever.
+    ///
+    /// # Arguments
+    ///
+    ///  u8 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | 1 if the listener is nonblocking, in which case `WouldBlock` is returned
+    ///        | immediately if there is no connection waiting
+    ///
+    /// # Returns
+    ///
+    /// u16 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | 0 indicating success
+    ///      1 | Connection index of the new connection
+    ///      2 | local port
+    ///      3 | remote port
+    ///    4.. | remote address, as address type (u8) followed by the address
+    ///
+    /// # Errors
+    ///
+    /// As `StdTcpConnect`
+    StdTcpAccept = 50,
+
+    /// Bind a UDP socket. The arguments are the same as `StdTcpListen`.
+    ///
+    /// # Returns
+    ///
+    /// u16 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | 0 indicating success
+    ///      1 | Socket index, used by the other `StdUdp*` calls
+    ///      2 | local port
+    ///
+    /// # Errors
+    ///
+    /// As `StdTcpConnect`
+    StdUdpBind = 51,
+
+    /// Send a datagram from a UDP socket. The socket index is OR-ed into the top 16 bits of
+    /// the opcode. The destination is in a header of `STD_UDP_HEADER_LEN` bytes, which the
+    /// payload follows; the `Valid` field is the length of the payload. The `Offset` field is
+    /// the timeout in ms when the socket's transmit buffer is full, as with `StdTcpTx`.
+    ///
+    ///  u8 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | 1 if the socket is nonblocking
+    ///      2 | destination port, low byte
+    ///      3 | destination port, high byte
+    ///      4 | address type -- 4 = ipv4, 6 = ipv6
+    ///    ... | destination address
+    ///
+    /// # Returns
+    ///
+    /// u32 array
+    /// -------|---------
+    /// offset | Contents
+    /// =======|=========
+    ///      0 | 0 if no error, 1 if error
+    ///      1 | Number of bytes sent, or code of the error
+    StdUdpSendTo = 52,
+
+    /// Receive a datagram on a UDP socket. The socket index is OR-ed into the top 16 bits of
+    /// the opcode, and the `Offset` field is the timeout in ms, as with `StdTcpRx`. Byte 0 of
+    /// the buffer is 1 if the socket is nonblocking.
+    ///
+    /// # Returns
+    ///
+    /// The sender, in the same header layout as `StdUdpSendTo`, with byte 0 set to 0. The
+    /// payload follows the header, truncated to fit the buffer, and the `Valid` field is its
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// As `StdTcpConnect`
+    StdUdpRecvFrom = 53,
+
+    /// Close a UDP socket. The socket index is OR-ed into the top 16 bits of the opcode. This
+    /// may be any kind of message, as with `StdTcpClose`.
+    StdUdpClose = 54,
+
+    /// Start capturing every ethernet frame received or sent by the interface, throwing away
+    /// any previous capture. Blocking scalar, returns 0.
+    PcapStart = 55,
+    /// Stop capturing. Blocking scalar, returns the number of frames in the capture.
+    PcapStop = 56,
+    /// Memory message of `PCAP_HEADER_LEN + PCAP_RING_CAPACITY` bytes, filled with the
+    /// stopped capture as a pcap file. The `Valid` field is the length of the file, and is
+    /// `None` if there is no stopped capture.
+    PcapFetch = 57,
+}
+
+/// IPv6 configuration of the interface. The link-local address is derived from our MAC address;
+/// the global address and default router are learned from router advertisements.
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct Ipv6Config {
+    pub link_local: [u8; 16],
+    /// global address formed from an advertised prefix, if a router has offered one
+    pub global: Option<[u8; 16]>,
+    /// prefix length of the global address; 0 if there is none
+    pub prefix_len: u8,
+    /// link-local address of the default router
+    pub router: Option<[u8; 16]>,
 }
 
 #[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone, Default)]
@@ -250,6 +428,8 @@ pub enum NetError {
     LibraryError = 6,
     // AlreadyUsed = 7,
     TimedOut = 8,
+    /// a nonblocking call would have had to wait
+    WouldBlock = 9,
 }
 
 /////// a bunch of structures are re-derived here so we can infer `rkyv` traits on them
@@ -313,16 +493,9 @@ impl From<NetIpAddr> for IpAddress {
             NetIpAddr::Ipv4([a, b, c, d]) => {
                 IpAddress::Ipv4(smoltcp::wire::Ipv4Address::new(a, b, c, d))
             }
-            NetIpAddr::Ipv6(ipv6) => IpAddress::Ipv6(smoltcp::wire::Ipv6Address::new(
-                u16::from_be_bytes(ipv6[0..1].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[2..3].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[4..5].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[6..7].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[8..9].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[10..11].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[12..13].try_into().unwrap()),
-                u16::from_be_bytes(ipv6[14..15].try_into().unwrap()),
-            )),
+            NetIpAddr::Ipv6(ipv6) => {
+                IpAddress::Ipv6(smoltcp::wire::Ipv6Address::from_bytes(&ipv6))
+            }
         }
     }
 }
@@ -371,7 +544,7 @@ impl fmt::Display for NetIpAddr {
                     fmt.pad(buf)
                 }
             }
-            NetIpAddr::Ipv6(ip) => ip.fmt(fmt),
+            NetIpAddr::Ipv6(ip) => fmt::Display::fmt(&Ipv6Addr::from(*ip), fmt),
         }
     }
 }
@@ -3,6 +3,11 @@ use crate::api::*;
 
 //////// Intra-crate UDP structures
 pub(crate) const UDP_RESPONSE_MAX_LEN: usize = 1800;
+/// Length of the flags and address header in front of the payload of `StdUdpSendTo` and
+/// `StdUdpRecvFrom` buffers: flags, padding, a port and the longest address.
+pub(crate) const STD_UDP_HEADER_LEN: usize = 24;
+/// Datagrams each libstd UDP socket can buffer in each direction
+pub(crate) const STD_UDP_PACKETS: usize = 4;
 /// The data field for a UDP response is limited to less than the theoretical
 /// size of 64k. While UDP allows for a 64k packet, it seems no protoctols
 /// in practice utilize such a length (about 512 bytes is the biggest), due
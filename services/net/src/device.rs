@@ -1,5 +1,6 @@
 use com::Com;
 use com::api::NET_MTU;
+use crate::pcap::PcapRing;
 
 use smoltcp::Result;
 use smoltcp::phy::{self, DeviceCapabilities, Medium};
@@ -8,11 +9,15 @@ use smoltcp::{
     time::Instant,
 };
 
+use std::cell::RefCell;
+
 pub struct NetPhy {
     rx_buffer: [u8; NET_MTU],
     tx_buffer: [u8; NET_MTU],
     com: Com,
     rx_avail: Option<u16>,
+    /// frames are recorded here while a capture is running
+    capture: RefCell<Option<PcapRing>>,
 }
 
 impl<'a> NetPhy {
@@ -22,6 +27,7 @@ impl<'a> NetPhy {
             tx_buffer: [0; NET_MTU],
             com: Com::new(&xns).unwrap(),
             rx_avail: None,
+            capture: RefCell::new(None),
         }
     }
     // returns None if there was a slot to put the availability into
@@ -34,6 +40,10 @@ impl<'a> NetPhy {
             Some(len)
         }
     }
+    /// Start or stop a packet capture, returning the capture that was running, if any
+    pub fn set_capture(&mut self, capture: Option<PcapRing>) -> Option<PcapRing> {
+        self.capture.replace(capture)
+    }
 }
 
 impl<'a> phy::Device<'a> for NetPhy {
@@ -44,15 +54,15 @@ impl<'a> phy::Device<'a> for NetPhy {
         if let Some(rx_len) = self.rx_avail.take() {
             self.com.wlan_fetch_packet(&mut self.rx_buffer[..rx_len as usize]).expect("Couldn't call wlan_fetch_packet in device adapter");
 
-            Some((NetPhyRxToken{buf: &mut self.rx_buffer[..rx_len as usize]},
-            NetPhyTxToken{buf: &mut self.tx_buffer[..], com: & self.com}))
+            Some((NetPhyRxToken{buf: &mut self.rx_buffer[..rx_len as usize], capture: &self.capture},
+            NetPhyTxToken{buf: &mut self.tx_buffer[..], com: & self.com, capture: &self.capture}))
         } else {
             None
         }
     }
 
     fn transmit(&'a mut self) -> Option<Self::TxToken> {
-        Some(NetPhyTxToken{buf: &mut self.tx_buffer[..], com: &self.com})
+        Some(NetPhyTxToken{buf: &mut self.tx_buffer[..], com: &self.com, capture: &self.capture})
     }
 
     fn capabilities(&self) -> DeviceCapabilities {
@@ -66,12 +76,16 @@ impl<'a> phy::Device<'a> for NetPhy {
 
 pub struct NetPhyRxToken<'a> {
     buf: &'a mut [u8],
+    capture: &'a RefCell<Option<PcapRing>>,
 }
 
 impl<'a, 'c> phy::RxToken for NetPhyRxToken<'a> {
-    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> Result<R>
+    fn consume<R, F>(mut self, timestamp: Instant, f: F) -> Result<R>
         where F: FnOnce(&mut [u8]) -> Result<R>
     {
+        if let Some(ring) = self.capture.borrow_mut().as_mut() {
+            ring.record(timestamp, &self.buf);
+        }
         let result = f(&mut self.buf);
         //log::info!("rx: {:x?}", self.buf);
         result
@@ -81,16 +95,20 @@ impl<'a, 'c> phy::RxToken for NetPhyRxToken<'a> {
 pub struct NetPhyTxToken<'a> {
     buf: &'a mut [u8],
     com: &'a Com,
+    capture: &'a RefCell<Option<PcapRing>>,
 }
 
 impl<'a> phy::TxToken for NetPhyTxToken<'a> {
-    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> Result<R>
+    fn consume<R, F>(self, timestamp: Instant, len: usize, f: F) -> Result<R>
         where F: FnOnce(&mut [u8]) -> Result<R>
     {
         let result = f(&mut self.buf[..len]);
         //log::info!("txlen: {}", len);
 
         if result.is_ok() {
+            if let Some(ring) = self.capture.borrow_mut().as_mut() {
+                ring.record(timestamp, &self.buf[..len]);
+            }
             self.com.wlan_send_packet(&self.buf[..len]).map_err(|_| smoltcp::Error::Dropped)?;
         }
         result
@@ -0,0 +1,294 @@
+use com::api::{Ipv4Conf, NET_MTU};
+use crate::pcap::PcapRing;
+
+use smoltcp::Result;
+use smoltcp::phy::{self, DeviceCapabilities, Medium};
+
+use smoltcp::{
+    time::Instant,
+};
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// The WF200 only has room for a handful of frames in flight; beyond this, frames are dropped
+/// just as they would be on the air.
+const LOOPBACK_DEPTH: usize = 16;
+
+/// Hosted stand-in for the COM/WF200 packet interface. Every frame sent with
+/// `WlanSendPacket` comes straight back in as if fetched with `WlanFetchPacket`, so the
+/// interface can talk to itself -- connecting to its own address runs the same ARP, IP,
+/// TCP, UDP and ICMP code as a connection to the outside world.
+///
+/// The exception is IPv4 multicast: the WF200 doesn't hear its own multicast transmissions,
+/// and the net server loops them back to local group members itself, so they are dropped
+/// here rather than delivered twice.
+pub struct NetPhy {
+    /// frames that have been sent but not yet received
+    queue: VecDeque<Vec<u8>>,
+    /// frames are recorded here while a capture is running
+    capture: RefCell<Option<PcapRing>>,
+}
+
+impl NetPhy {
+    pub fn new(_xns: &xous_names::XousNames) -> NetPhy {
+        NetPhy::loopback()
+    }
+    pub fn loopback() -> NetPhy {
+        NetPhy {
+            queue: VecDeque::new(),
+            capture: RefCell::new(None),
+        }
+    }
+    // frames are received straight off the loopback queue, so there is never an
+    // availability to track
+    pub fn push_rx_avail(&mut self, _len: u16) -> Option<u16> {
+        None
+    }
+    /// Start or stop a packet capture, returning the capture that was running, if any
+    pub fn set_capture(&mut self, capture: Option<PcapRing>) -> Option<PcapRing> {
+        self.capture.replace(capture)
+    }
+}
+
+/// There is no EC to hand out a DHCP lease in hosted mode, so the loopback device comes with
+/// a fixed configuration. The interface answers to everything in 127.0.0.0/8.
+pub fn loopback_config() -> Ipv4Conf {
+    let mut config = Ipv4Conf::default();
+    config.addr = [127, 0, 0, 1];
+    config.gtwy = [127, 0, 0, 1];
+    config.dns1 = [127, 0, 0, 1];
+    config
+}
+
+impl<'a> phy::Device<'a> for NetPhy {
+    type RxToken = NetPhyRxToken<'a>;
+    type TxToken = NetPhyTxToken<'a>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        if let Some(frame) = self.queue.pop_front() {
+            Some((NetPhyRxToken{buf: frame, capture: &self.capture},
+            NetPhyTxToken{queue: &mut self.queue, capture: &self.capture}))
+        } else {
+            None
+        }
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(NetPhyTxToken{queue: &mut self.queue, capture: &self.capture})
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = NET_MTU;
+        caps.max_burst_size = Some(1);
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
+pub struct NetPhyRxToken<'a> {
+    buf: Vec<u8>,
+    capture: &'a RefCell<Option<PcapRing>>,
+}
+
+impl<'a> phy::RxToken for NetPhyRxToken<'a> {
+    fn consume<R, F>(mut self, timestamp: Instant, f: F) -> Result<R>
+        where F: FnOnce(&mut [u8]) -> Result<R>
+    {
+        if let Some(ring) = self.capture.borrow_mut().as_mut() {
+            ring.record(timestamp, &self.buf);
+        }
+        f(&mut self.buf)
+    }
+}
+
+/// IPv4 multicast groups map onto the 01:00:5e:00:00:00/25 block of Ethernet addresses
+fn is_ipv4_multicast(frame: &[u8]) -> bool {
+    frame.len() >= 4 && frame[0..3] == [0x01, 0x00, 0x5e] && frame[3] & 0x80 == 0
+}
+
+pub struct NetPhyTxToken<'a> {
+    queue: &'a mut VecDeque<Vec<u8>>,
+    capture: &'a RefCell<Option<PcapRing>>,
+}
+
+impl<'a> phy::TxToken for NetPhyTxToken<'a> {
+    fn consume<R, F>(self, timestamp: Instant, len: usize, f: F) -> Result<R>
+        where F: FnOnce(&mut [u8]) -> Result<R>
+    {
+        let mut buf = vec![0; len];
+        let result = f(&mut buf);
+
+        if result.is_ok() {
+            // the COM server refuses frames that don't fit the MTU
+            if len > NET_MTU || self.queue.len() >= LOOPBACK_DEPTH {
+                log::warn!("loopback dropped a {} byte frame", len);
+                return Err(smoltcp::Error::Dropped);
+            }
+            if let Some(ring) = self.capture.borrow_mut().as_mut() {
+                ring.record(timestamp, &buf);
+            }
+            if !is_ipv4_multicast(&buf) {
+                self.queue.push_back(buf);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache};
+    use smoltcp::socket::{
+        SocketSet, TcpSocket, TcpSocketBuffer, UdpPacketMetadata, UdpSocket, UdpSocketBuffer,
+    };
+    use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address};
+    use std::collections::BTreeMap;
+
+    fn loopback_iface() -> Interface<'static, NetPhy> {
+        InterfaceBuilder::new(NetPhy::loopback())
+            .ethernet_addr(EthernetAddress([0x02, 0, 0, 0, 0, 1]))
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(vec![IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8)])
+            .ipv4_multicast_groups(BTreeMap::new())
+            .finalize()
+    }
+
+    /// The frames in a capture, in the order they were recorded
+    fn captured_frames(capture: &PcapRing) -> Vec<Vec<u8>> {
+        let mut file = vec![0u8; 64 * 1024];
+        let len = capture.write_pcap(&mut file);
+        let mut frames = Vec::new();
+        let mut record = &file[crate::api::PCAP_HEADER_LEN..len];
+        while record.len() > 16 {
+            let frame_len = u32::from_le_bytes([record[8], record[9], record[10], record[11]]) as usize;
+            frames.push(record[16..16 + frame_len].to_vec());
+            record = &record[16 + frame_len..];
+        }
+        frames
+    }
+
+    #[test]
+    fn tcp_over_loopback() {
+        let mut iface = loopback_iface();
+        let mut sockets = SocketSet::new(vec![]);
+        let mut server = TcpSocket::new(TcpSocketBuffer::new(vec![0; 1024]), TcpSocketBuffer::new(vec![0; 1024]));
+        server.listen(1234).unwrap();
+        let server = sockets.add(server);
+        let mut client = TcpSocket::new(TcpSocketBuffer::new(vec![0; 1024]), TcpSocketBuffer::new(vec![0; 1024]));
+        client.connect((IpAddress::v4(127, 0, 0, 1), 1234), 49152).unwrap();
+        let client = sockets.add(client);
+
+        let mut sent = false;
+        let mut received = Vec::new();
+        // the first SYN is held up by ARP, so leave time for a retransmit
+        for ms in (0..10_000).step_by(10) {
+            iface.poll(&mut sockets, Instant::from_millis(ms)).ok();
+            {
+                let mut socket = sockets.get::<TcpSocket>(client);
+                if socket.may_send() && !sent {
+                    socket.send_slice(b"hello loopback").unwrap();
+                    sent = true;
+                }
+            }
+            let mut socket = sockets.get::<TcpSocket>(server);
+            if socket.can_recv() {
+                socket.recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                }).unwrap();
+            }
+            if received.len() == 14 {
+                break;
+            }
+        }
+        assert_eq!(&received[..], b"hello loopback");
+        assert_eq!(sockets.get::<TcpSocket>(server).remote_endpoint().port, 49152);
+    }
+
+    #[test]
+    fn udp_over_loopback() {
+        let mut iface = loopback_iface();
+        let mut sockets = SocketSet::new(vec![]);
+        let new_socket = || UdpSocket::new(
+            UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 2], vec![0; 256]),
+            UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 2], vec![0; 256]),
+        );
+        let mut a = new_socket();
+        a.bind(5000).unwrap();
+        let a = sockets.add(a);
+        let mut b = new_socket();
+        b.bind(5001).unwrap();
+        let b = sockets.add(b);
+
+        let to_b = IpEndpoint::new(IpAddress::v4(127, 0, 0, 1), 5001);
+        let mut received = None;
+        for ms in (0..5_000).step_by(10) {
+            {
+                // keep a datagram queued until ARP has resolved and one gets through
+                let mut socket = sockets.get::<UdpSocket>(a);
+                if socket.can_send() {
+                    socket.send_slice(b"ping", to_b).unwrap();
+                }
+            }
+            iface.poll(&mut sockets, Instant::from_millis(ms)).ok();
+            let mut socket = sockets.get::<UdpSocket>(b);
+            if let Ok((data, from)) = socket.recv() {
+                received = Some((data.to_vec(), from.port));
+                break;
+            }
+        }
+        assert_eq!(received, Some((b"ping".to_vec(), 5000)));
+    }
+
+    #[test]
+    fn oversized_frames_are_dropped() {
+        use smoltcp::phy::{Device, TxToken};
+        let mut phy = NetPhy::loopback();
+        let token = phy.transmit().unwrap();
+        assert!(token.consume(Instant::from_millis(0), NET_MTU + 1, |_| Ok(())).is_err());
+        assert!(phy.receive().is_none());
+    }
+
+    #[test]
+    fn multicast_is_captured_but_not_looped_back() {
+        let mut iface = loopback_iface();
+        let group = Ipv4Address::new(239, 1, 2, 3);
+        iface.join_multicast_group(group, Instant::from_millis(0)).unwrap();
+        iface.device_mut().set_capture(Some(PcapRing::new(16 * 1024)));
+
+        let mut sockets = SocketSet::new(vec![]);
+        let new_socket = || UdpSocket::new(
+            UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 2], vec![0; 256]),
+            UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 2], vec![0; 256]),
+        );
+        let mut tx = new_socket();
+        tx.bind(5000).unwrap();
+        tx.set_hop_limit(Some(8));
+        tx.send_slice(b"group", IpEndpoint::new(group.into(), 5001)).unwrap();
+        sockets.add(tx);
+        let mut rx = new_socket();
+        rx.bind(5001).unwrap();
+        let rx = sockets.add(rx);
+
+        for ms in (0..100).step_by(10) {
+            iface.poll(&mut sockets, Instant::from_millis(ms)).ok();
+        }
+        // the WF200 doesn't hear its own multicast, so neither does the loopback
+        assert!(sockets.get::<UdpSocket>(rx).recv().is_err());
+
+        // but it went out on the air, with the socket's hop limit
+        let capture = iface.device_mut().set_capture(None).unwrap();
+        let sent: Vec<Vec<u8>> = captured_frames(&capture).into_iter()
+            .filter(|f| f.len() > 34 && f[12..14] == [0x08, 0x00] && f[14 + 9] == 17)
+            .collect();
+        assert_eq!(sent.len(), 1);
+        let frame = &sent[0];
+        assert_eq!(&frame[0..6], &[0x01, 0x00, 0x5e, 0x01, 0x02, 0x03]);
+        assert_eq!(&frame[14 + 16..14 + 20], &[239, 1, 2, 3]);
+        assert_eq!(frame[14 + 8], 8);
+        assert_eq!(&frame[frame.len() - 5..], b"group");
+    }
+}
@@ -72,6 +72,14 @@ impl NetManager {
             None
         }
     }
+    /// Returns `None` if the link is down. The global address and router are only present once a router
+    /// on the link has advertised a prefix.
+    pub fn get_ipv6_config(&self) -> Option<Ipv6Config> {
+        let storage = Some(Ipv6Config::default());
+        let mut buf = Buffer::into_buf(storage).expect("Couldn't convert to memory structure");
+        buf.lend_mut(self.netconn.conn(), Opcode::GetIpv6Config.to_u32().unwrap()).expect("Couldn't execute GetIpv6Config opcode");
+        buf.to_original().expect("couldn't restore config structure")
+    }
     pub fn reset(&self) {
         send_message(
             self.netconn.conn(),
@@ -170,6 +178,33 @@ impl NetManager {
             Message::new_scalar(Opcode::ConnMgrStartStop.to_usize().unwrap(), 1, 0,0, 0)
         ).map(|_| ())
     }
+    /// Start recording every ethernet frame through the interface, throwing away any
+    /// previous capture. Only the most recent `PCAP_RING_CAPACITY` bytes of frames are kept.
+    pub fn pcap_start(&self) -> Result<(), xous::Error> {
+        send_message(self.netconn.conn(),
+            Message::new_blocking_scalar(Opcode::PcapStart.to_usize().unwrap(), 0, 0, 0, 0)
+        ).map(|_| ())
+    }
+    /// Stop recording frames, returning the number of frames in the capture.
+    pub fn pcap_stop(&self) -> Result<usize, xous::Error> {
+        match send_message(self.netconn.conn(),
+            Message::new_blocking_scalar(Opcode::PcapStop.to_usize().unwrap(), 0, 0, 0, 0)
+        )? {
+            xous::Result::Scalar1(frames) => Ok(frames),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+    /// Returns the stopped capture as the contents of a pcap file, or `None` if nothing
+    /// has been captured.
+    pub fn pcap_fetch(&self) -> Result<Option<Vec<u8>>, xous::Error> {
+        let mut buf = Buffer::new(PCAP_HEADER_LEN + PCAP_RING_CAPACITY);
+        match buf.lend_mut(self.netconn.conn(), Opcode::PcapFetch.to_u32().unwrap())? {
+            xous::Result::MemoryReturned(_, valid) => {
+                Ok(valid.map(|v| buf[..v.get().min(buf.len())].to_vec()))
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
 }
 impl Drop for NetManager {
     fn drop(&mut self) {
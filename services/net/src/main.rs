@@ -8,9 +8,16 @@ use num_traits::*;
 use ticktimer_server::Ticktimer;
 
 mod connection_manager;
+#[cfg(any(target_os = "none", target_os = "xous"))]
 mod device;
-
-use std::collections::{BTreeMap, HashMap};
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+mod device_hosted;
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+use device_hosted as device;
+mod pcap;
+mod slaac;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, send_message, Message, CID, SID};
 use xous_ipc::Buffer;
@@ -19,7 +26,9 @@ use byteorder::{ByteOrder, NetworkEndian};
 use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Routes};
 use smoltcp::phy::{Device, Medium};
 use smoltcp::socket::{IcmpEndpoint, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer, SocketSet};
-use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv4Cidr};
+use smoltcp::wire::{
+    EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv4Cidr, Ipv6Address,
+};
 use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr};
 
 use core::num::NonZeroU64;
@@ -46,6 +55,19 @@ where
     });
 }
 
+/// Hosted mode has a loopback device in place of the WF200, and nothing to send us a
+/// WlanIpConfigUpdate, so the loopback configuration is applied whenever the interface is built.
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+fn apply_loopback_config<DeviceT>(iface: &mut Interface<'_, DeviceT>) -> Ipv4Conf
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    let config = device::loopback_config();
+    log::info!("Hosted loopback config: {:?}", config);
+    set_ipv4_addr(iface, Ipv4Cidr::new(Ipv4Address::from_bytes(&config.addr), 8));
+    config
+}
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 enum WaitOp {
     WaitMs,
@@ -59,6 +81,57 @@ pub struct UdpState {
     handle: SocketHandle,
     cid: CID,
     sid: SID,
+    /// hop limit for unicast packets; None means the smoltcp default
+    ttl: Option<u8>,
+    /// hop limit for multicast packets
+    multicast_ttl: u8,
+    /// if true, multicast packets sent from this socket are also delivered to local listeners
+    multicast_loop: bool,
+    /// if true, this socket may send to broadcast addresses
+    broadcast: bool,
+}
+impl UdpState {
+    fn new(handle: SocketHandle, sid: SID) -> UdpState {
+        UdpState {
+            handle,
+            cid: xous::connect(sid).unwrap(),
+            sid,
+            ttl: None,
+            // defaults follow the BSD sockets API
+            multicast_ttl: 1,
+            multicast_loop: true,
+            broadcast: false,
+        }
+    }
+}
+
+/// Sends a received UDP packet to the socket's owner, and copies of it to any clones of the socket.
+fn udp_deliver(response: NetUdpResponse, cid: CID, clones: Option<&HashMap<[u32; 4], CID>>) {
+    let buf = Buffer::into_buf(response)
+        .expect("couldn't convert UDP response to memory message");
+    buf.send(cid, NetUdpCallback::RxData.to_u32().unwrap())
+        .expect("couldn't send UDP response");
+    if let Some(clone_map) = clones {
+        for &cids in clone_map.values() {
+            let buf = Buffer::into_buf(response).expect(
+                "couldn't convert UDP response to memory message",
+            );
+            buf.send(cids, NetUdpCallback::RxData.to_u32().unwrap())
+                .expect("couldn't send UDP response");
+        }
+    }
+}
+
+/// Joins every multicast group in `groups`. Used to restore memberships after the interface is rebuilt.
+fn rejoin_multicast_groups<DeviceT>(iface: &mut Interface<'_, DeviceT>, groups: &HashMap<Ipv4Address, HashSet<u16>>, timestamp: Instant)
+where
+    DeviceT: for<'d> Device<'d>,
+{
+    for &group in groups.keys() {
+        if let Err(e) = iface.join_multicast_group(group, timestamp) {
+            log::error!("couldn't rejoin multicast group {}: {:?}", group, e);
+        }
+    }
 }
 
 /// PingConnection can return a Scalar: because of the simplicity of the return data
@@ -148,7 +221,7 @@ fn write_address(address: IpAddress, data: &mut [u8]) -> Option<usize> {
             for (dest, src) in i.zip(a.as_bytes().iter()) {
                 *dest = *src;
             }
-            Some(16)
+            Some(17)
         }
         _ => {
             *i.next()? = 0;
@@ -465,6 +538,397 @@ fn std_tcp_peek(
     }
 }
 
+/// Parse the port and local address of a `StdTcpListen` or `StdUdpBind` request. A port
+/// of 0 is replaced with `ephemeral_port`.
+fn parse_bind_request(bytes: &[u8], ephemeral_port: u16) -> Option<IpEndpoint> {
+    let port = match u16::from_le_bytes([*bytes.get(0)?, *bytes.get(1)?]) {
+        0 => ephemeral_port,
+        port => port,
+    };
+    Some(IpEndpoint::new(parse_address(bytes.get(2..)?)?, port))
+}
+
+fn respond_with_bound(mut env: xous::MessageEnvelope, idx: u16, local_port: u16) {
+    let body = env.body.memory_message_mut().unwrap();
+    let bfr = body.buf.as_slice_mut::<u16>();
+
+    log::trace!("bound {} to port {}", idx, local_port);
+    bfr[0] = 0;
+    bfr[1] = idx;
+    bfr[2] = local_port;
+}
+
+fn std_tcp_listen(
+    mut msg: xous::MessageEnvelope,
+    ephemeral_port: u16,
+    sockets: &mut SocketSet,
+    tcp_listeners: &HashMap<u16, Vec<TcpState>>,
+    std_tcp_listeners: &mut HashMap<SocketHandle, IpEndpoint>,
+    our_sockets: &mut Vec<Option<SocketHandle>>,
+) {
+    let body = match msg.body.memory_message_mut() {
+        Some(b) => b,
+        None => {
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    let endpoint = match parse_bind_request(body.buf.as_slice::<u8>(), ephemeral_port) {
+        Some(endpoint) => endpoint,
+        None => {
+            log::trace!("couldn't parse address");
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    // smoltcp would hand an incoming connection to whichever listener it finds first, so
+    // only one listener may have a port.
+    if tcp_listeners
+        .get(&endpoint.port)
+        .map_or(false, |listeners| listeners.len() > 0)
+        || std_tcp_listeners
+            .values()
+            .any(|listening| listening.port == endpoint.port)
+    {
+        respond_with_error(msg, NetError::SocketInUse);
+        return;
+    }
+
+    let mut tcp_socket = TcpSocket::new(
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+    );
+    if let Err(e) = tcp_socket.listen(endpoint) {
+        log::trace!("couldn't listen: {:?}", e);
+        respond_with_error(msg, NetError::Unaddressable);
+        return;
+    }
+
+    let handle = sockets.add(tcp_socket);
+    std_tcp_listeners.insert(handle, endpoint);
+    let idx = insert_or_append(our_sockets, handle) as u16;
+    respond_with_bound(msg, idx, endpoint.port);
+}
+
+/// A listener has a connection for `StdTcpAccept` once the handshake is done. A connection
+/// that is reset during the handshake puts the socket back into the `Listen` state.
+fn std_tcp_accept_ready(sockets: &mut SocketSet, handle: SocketHandle) -> bool {
+    let socket = sockets.get::<TcpSocket>(handle);
+    socket.is_active() && socket.state() != smoltcp::socket::TcpState::SynReceived
+}
+
+/// Hand the connection on a listener to the caller of `StdTcpAccept` as a new connection
+/// index, and put a fresh listening socket in the listener's place. Returns the handle of
+/// the new listening socket.
+fn respond_with_accepted(
+    mut env: xous::MessageEnvelope,
+    listener: SocketHandle,
+    sockets: &mut SocketSet,
+    std_tcp_listeners: &mut HashMap<SocketHandle, IpEndpoint>,
+    our_sockets: &mut Vec<Option<SocketHandle>>,
+) -> SocketHandle {
+    let listener_idx = (env.body.id() >> 16) & 0xffff;
+    let endpoint = std_tcp_listeners
+        .remove(&listener)
+        .expect("accepted a connection on a socket that isn't listening");
+    let (hop_limit, local_port, remote) = {
+        let socket = sockets.get::<TcpSocket>(listener);
+        (
+            socket.hop_limit(),
+            socket.local_endpoint().port,
+            socket.remote_endpoint(),
+        )
+    };
+
+    let mut tcp_socket = TcpSocket::new(
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+    );
+    tcp_socket.set_hop_limit(hop_limit);
+    // this endpoint was good enough to listen on the first time around
+    tcp_socket.listen(endpoint).unwrap();
+    let new_listener = sockets.add(tcp_socket);
+    std_tcp_listeners.insert(new_listener, endpoint);
+    our_sockets[listener_idx] = Some(new_listener);
+    let idx = insert_or_append(our_sockets, listener) as u16;
+
+    let body = env.body.memory_message_mut().unwrap();
+    let bfr = body.buf.as_slice_mut::<u16>();
+    log::trace!("accepted connection {} from {}", idx, remote);
+    bfr[0] = 0;
+    bfr[1] = idx;
+    bfr[2] = local_port;
+    bfr[3] = remote.port;
+    write_address(remote.addr, &mut body.buf.as_slice_mut::<u8>()[8..]);
+    new_listener
+}
+
+fn std_tcp_accept(
+    mut msg: xous::MessageEnvelope,
+    timer: &Ticktimer,
+    sockets: &mut SocketSet,
+    tcp_accept_waiting: &mut Vec<Option<WaitingSocket>>,
+    std_tcp_listeners: &mut HashMap<SocketHandle, IpEndpoint>,
+    our_sockets: &mut Vec<Option<SocketHandle>>,
+) {
+    let connection_handle_index = (msg.body.id() >> 16) & 0xffff;
+    let body = match msg.body.memory_message_mut() {
+        Some(body) => body,
+        None => {
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    let handle = match our_sockets.get(connection_handle_index) {
+        Some(Some(val)) if std_tcp_listeners.contains_key(val) => *val,
+        _ => {
+            respond_with_error(msg, NetError::Invalid);
+            return;
+        }
+    };
+
+    if std_tcp_accept_ready(sockets, handle) {
+        respond_with_accepted(msg, handle, sockets, std_tcp_listeners, our_sockets);
+        return;
+    }
+
+    if body.buf.as_slice::<u8>()[0] == 1 {
+        respond_with_error(msg, NetError::WouldBlock);
+        return;
+    }
+
+    log::trace!("no connection yet, adding it to list of waiting messages");
+    let expiry = body
+        .offset
+        .map(|x| unsafe { NonZeroU64::new_unchecked(x.get() as u64 + timer.elapsed_ms()) });
+    insert_or_append(
+        tcp_accept_waiting,
+        WaitingSocket {
+            env: msg,
+            handle,
+            expiry,
+        },
+    );
+}
+
+fn std_udp_bind(
+    mut msg: xous::MessageEnvelope,
+    ephemeral_port: u16,
+    sockets: &mut SocketSet,
+    udp_handles: &HashMap<u16, UdpState>,
+    process_udp_sockets: &mut HashMap<Option<xous::PID>, Vec<Option<SocketHandle>>>,
+) {
+    let pid = msg.sender.pid();
+    let body = match msg.body.memory_message_mut() {
+        Some(b) => b,
+        None => {
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    let endpoint = match parse_bind_request(body.buf.as_slice::<u8>(), ephemeral_port) {
+        Some(endpoint) => endpoint,
+        None => {
+            log::trace!("couldn't parse address");
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    // Ports are shared with the callback-based UDP sockets, which deliver every datagram
+    // they get to their own listeners.
+    if udp_handles.contains_key(&endpoint.port)
+        || process_udp_sockets
+            .values()
+            .flatten()
+            .flatten()
+            .any(|&handle| sockets.get::<UdpSocket>(handle).endpoint().port == endpoint.port)
+    {
+        respond_with_error(msg, NetError::SocketInUse);
+        return;
+    }
+
+    let mut udp_socket = UdpSocket::new(
+        UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; STD_UDP_PACKETS],
+            vec![0; NET_MTU * STD_UDP_PACKETS],
+        ),
+        UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; STD_UDP_PACKETS],
+            vec![0; NET_MTU * STD_UDP_PACKETS],
+        ),
+    );
+    if let Err(e) = udp_socket.bind(endpoint) {
+        log::trace!("couldn't bind: {:?}", e);
+        respond_with_error(msg, NetError::Unaddressable);
+        return;
+    }
+
+    let handle = sockets.add(udp_socket);
+    let idx = insert_or_append(process_udp_sockets.entry(pid).or_default(), handle) as u16;
+    respond_with_bound(msg, idx, endpoint.port);
+}
+
+/// Send the datagram in a `StdUdpSendTo` message, and fill in the response if it was sent.
+fn std_udp_send(env: &mut xous::MessageEnvelope, socket: &mut UdpSocket) -> Result<(), NetError> {
+    let body = env
+        .body
+        .memory_message_mut()
+        .ok_or(NetError::LibraryError)?;
+    let sent_octets = {
+        let data = body.buf.as_slice::<u8>();
+        let port = u16::from_le_bytes([data[2], data[3]]);
+        let address = parse_address(&data[4..STD_UDP_HEADER_LEN]).ok_or(NetError::LibraryError)?;
+        let payload = &data[STD_UDP_HEADER_LEN..];
+        let length = body.valid.map(|v| v.get().min(payload.len())).unwrap_or(0);
+        socket
+            .send_slice(&payload[..length], IpEndpoint::new(address, port))
+            .map_err(|e| match e {
+                smoltcp::Error::Unaddressable => NetError::Unaddressable,
+                _ => NetError::LibraryError,
+            })?;
+        length
+    };
+
+    log::trace!("sent {}", sent_octets);
+    let response_data = body.buf.as_slice_mut::<u32>();
+    body.valid = xous::MemorySize::new(sent_octets);
+    response_data[0] = 0;
+    response_data[1] = sent_octets as u32;
+    Ok(())
+}
+
+/// Receive a datagram into a `StdUdpRecvFrom` message. The socket must have one waiting.
+fn std_udp_recv(env: &mut xous::MessageEnvelope, socket: &mut UdpSocket) -> Result<(), NetError> {
+    let body = env
+        .body
+        .memory_message_mut()
+        .ok_or(NetError::LibraryError)?;
+    let (data, endpoint) = socket.recv().map_err(|_| NetError::LibraryError)?;
+    let bfr = body.buf.as_slice_mut::<u8>();
+    let payload = &mut bfr[STD_UDP_HEADER_LEN..];
+    // datagrams too long for the buffer are truncated, as with `recv_from()` on other platforms
+    let length = data.len().min(payload.len());
+    payload[..length].copy_from_slice(&data[..length]);
+
+    for b in bfr[..STD_UDP_HEADER_LEN].iter_mut() {
+        *b = 0;
+    }
+    bfr[2..4].copy_from_slice(&endpoint.port.to_le_bytes());
+    write_address(endpoint.addr, &mut bfr[4..STD_UDP_HEADER_LEN]);
+    body.valid = xous::MemorySize::new(length);
+    Ok(())
+}
+
+fn std_udp_send_to(
+    mut msg: xous::MessageEnvelope,
+    timer: &Ticktimer,
+    sockets: &mut SocketSet,
+    udp_tx_waiting: &mut Vec<Option<WaitingSocket>>,
+    our_sockets: &Vec<Option<SocketHandle>>,
+) {
+    let connection_handle_index = (msg.body.id() >> 16) & 0xffff;
+    let body = match msg.body.memory_message_mut() {
+        Some(body) if body.buf.len() > STD_UDP_HEADER_LEN => body,
+        _ => {
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    let handle = match our_sockets.get(connection_handle_index) {
+        Some(Some(val)) => *val,
+        _ => {
+            respond_with_error(msg, NetError::Invalid);
+            return;
+        }
+    };
+
+    let mut socket = sockets.get::<UdpSocket>(handle);
+    if !socket.can_send() {
+        if body.buf.as_slice::<u8>()[0] == 1 {
+            respond_with_error(msg, NetError::WouldBlock);
+            return;
+        }
+        log::trace!("udp tx can't send, will retry");
+        let expiry = body
+            .offset
+            .map(|x| unsafe { NonZeroU64::new_unchecked(x.get() as u64 + timer.elapsed_ms()) });
+        insert_or_append(
+            udp_tx_waiting,
+            WaitingSocket {
+                env: msg,
+                handle,
+                expiry,
+            },
+        );
+        return;
+    }
+
+    if let Err(e) = std_udp_send(&mut msg, &mut socket) {
+        respond_with_error(msg, e);
+    }
+}
+
+fn std_udp_recv_from(
+    mut msg: xous::MessageEnvelope,
+    timer: &Ticktimer,
+    sockets: &mut SocketSet,
+    udp_rx_waiting: &mut Vec<Option<WaitingSocket>>,
+    our_sockets: &Vec<Option<SocketHandle>>,
+) {
+    let connection_handle_index = (msg.body.id() >> 16) & 0xffff;
+    let body = match msg.body.memory_message_mut() {
+        Some(body) if body.buf.len() > STD_UDP_HEADER_LEN => body,
+        _ => {
+            respond_with_error(msg, NetError::LibraryError);
+            return;
+        }
+    };
+
+    // Default to having no valid data upon return, indicating an error
+    body.valid = None;
+
+    let handle = match our_sockets.get(connection_handle_index) {
+        Some(Some(val)) => *val,
+        _ => {
+            respond_with_error(msg, NetError::Invalid);
+            return;
+        }
+    };
+
+    let mut socket = sockets.get::<UdpSocket>(handle);
+    if socket.can_recv() {
+        log::trace!("receiving datagram right away");
+        if let Err(e) = std_udp_recv(&mut msg, &mut socket) {
+            respond_with_error(msg, e);
+        }
+        return;
+    }
+
+    if body.buf.as_slice::<u8>()[0] == 1 {
+        respond_with_error(msg, NetError::WouldBlock);
+        return;
+    }
+
+    log::trace!("socket was not able to receive, adding it to list of waiting messages");
+    let expiry = body
+        .offset
+        .map(|x| unsafe { NonZeroU64::new_unchecked(x.get() as u64 + timer.elapsed_ms()) });
+    insert_or_append(
+        udp_rx_waiting,
+        WaitingSocket {
+            env: msg,
+            handle,
+            expiry,
+        },
+    );
+}
+
 #[xous::xous_main]
 fn xmain() -> ! {
     log_server::init_wait().unwrap();
@@ -531,6 +995,25 @@ fn xmain() -> ! {
         )>,
     > = Vec::new();
 
+    // When a client issues an Accept request, it will get placed here until a connection
+    // comes in on the listener.
+    let mut tcp_accept_waiting: Vec<Option<WaitingSocket>> = Vec::new();
+
+    // Listening sockets created by `StdTcpListen`, and the endpoint each one listens on
+    let mut std_tcp_listeners: HashMap<SocketHandle, IpEndpoint> = HashMap::new();
+
+    // libstd UDP sockets are kept per-process just like the TCP ones, but in their own table
+    // so that a socket index can't be used as the wrong kind of socket.
+    let mut process_udp_sockets: HashMap<Option<xous::PID>, Vec<Option<SocketHandle>>> =
+        HashMap::new();
+
+    // When a client issues a RecvFrom request, it will get placed here until a datagram arrives.
+    let mut udp_rx_waiting: Vec<Option<WaitingSocket>> = Vec::new();
+
+    // When a client issues a SendTo request, it will get placed here until there is room to
+    // queue the datagram.
+    let mut udp_tx_waiting: Vec<Option<WaitingSocket>> = Vec::new();
+
     // ping storage
     // up to four concurrent pings in the queue
     let icmp_rx_buffer = IcmpSocketBuffer::new(
@@ -568,14 +1051,20 @@ fn xmain() -> ! {
     // for Rx, copies of a CID,SID tuple are kept for every clone is kept in a HashMap. This
     // allows for the Rx data to be cc:'d to each clone, and identified by SID upon drop
     let mut udp_clones = HashMap::<u16, HashMap<[u32; 4], CID>>::new(); // additional clones for UDP responders
+    // multicast groups joined by UDP sockets, and the local ports that joined them. The interface is a member
+    // of a group for as long as any port has joined it.
+    let mut multicast_groups = HashMap::<Ipv4Address, HashSet<u16>>::new();
 
     // tcp storage
     let mut tcp_handles = HashMap::<TcpConnection, TcpState>::new();
     let mut tcp_listeners = HashMap::<u16, Vec<TcpState>>::new();
 
+    // a stopped packet capture, held until it is fetched
+    let mut pcap_capture: Option<pcap::PcapRing> = None;
+
     // other link storage
     let neighbor_cache = NeighborCache::new(BTreeMap::new());
-    let ip_addrs = [IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)];
+    let ip_addrs = slaac::default_ip_addrs();
     let routes = Routes::new(BTreeMap::new());
 
     let device = device::NetPhy::new(&xns);
@@ -584,13 +1073,20 @@ fn xmain() -> ! {
     let medium = device.capabilities().medium;
     let mut builder = InterfaceBuilder::new(device)
         .ip_addrs(ip_addrs)
-        .routes(routes);
+        .routes(routes)
+        .ipv4_multicast_groups(BTreeMap::new());
     if medium == Medium::Ethernet {
         builder = builder
             .ethernet_addr(EthernetAddress::from_bytes(&[0; 6]))
             .neighbor_cache(neighbor_cache);
     }
     let mut iface = builder.finalize();
+    // IPv6 addressing is configured locally, as the EC only reports an IPv4 configuration
+    let mut ipv6_autoconf = slaac::Slaac::new(&mut sockets);
+    #[cfg(not(any(target_os = "none", target_os = "xous")))]
+    {
+        net_config = Some(apply_loopback_config(&mut iface));
+    }
 
     // DNS hooks - the DNS server can ask the Net crate to tickle it when IP configs change using these hooks
     // Currently, we assume there is only one DNS server in Xous. I suppose you could
@@ -713,8 +1209,11 @@ fn xmain() -> ! {
                             icmp_repr.emit(&mut icmp_packet, &device_caps.checksum);
                         }
                         IpAddress::Ipv6(_) => {
-                            // not sure if this is a valid thing to do, to just assign the source some number like this??
-                            let src_ipv6 = IpAddress::v6(0xfdaa, 0, 0, 0, 0, 0, 0, 1);
+                            // smoltcp re-computes the checksum with the real source address on the way out,
+                            // but give it the right one anyways
+                            let src_ipv6 = IpAddress::Ipv6(
+                                ipv6_autoconf.source_address().unwrap_or(Ipv6Address::UNSPECIFIED),
+                            );
                             let icmp_repr = Icmpv6Repr::EchoRequest {
                                 ident,
                                 seq_no: seq,
@@ -881,9 +1380,16 @@ fn xmain() -> ! {
                 };
                 sockets.get::<TcpSocket>(handle).close();
                 sockets.remove(handle);
+                if std_tcp_listeners.remove(&handle).is_some() {
+                    for waiting in tcp_accept_waiting.iter_mut() {
+                        if waiting.as_ref().map_or(false, |w| w.handle == handle) {
+                            respond_with_error(waiting.take().unwrap().env, NetError::Invalid);
+                        }
+                    }
+                }
                 if let Some(response) = msg.body.memory_message_mut() {
                     response.buf.as_slice_mut::<u8>()[0] = 0;
-                } else if !msg.body.is_blocking() && msg.body.is_blocking() {
+                } else if msg.body.is_blocking() {
                     xous::return_scalar(msg.sender, 0).ok();
                 }
             }
@@ -1021,6 +1527,100 @@ fn xmain() -> ! {
                 };
             }
 
+            Some(Opcode::StdTcpListen) => {
+                let ephemeral_port = (trng.get_u32().unwrap() % 16384 + 49152) as u16;
+                let pid = msg.sender.pid();
+                std_tcp_listen(
+                    msg,
+                    ephemeral_port,
+                    &mut sockets,
+                    &tcp_listeners,
+                    &mut std_tcp_listeners,
+                    process_sockets.entry(pid).or_default(),
+                );
+            }
+
+            Some(Opcode::StdTcpAccept) => {
+                let pid = msg.sender.pid();
+                std_tcp_accept(
+                    msg,
+                    &timer,
+                    &mut sockets,
+                    &mut tcp_accept_waiting,
+                    &mut std_tcp_listeners,
+                    process_sockets.entry(pid).or_default(),
+                );
+            }
+
+            Some(Opcode::StdUdpBind) => {
+                let ephemeral_port = (trng.get_u32().unwrap() % 16384 + 49152) as u16;
+                std_udp_bind(
+                    msg,
+                    ephemeral_port,
+                    &mut sockets,
+                    &udp_handles,
+                    &mut process_udp_sockets,
+                );
+            }
+
+            Some(Opcode::StdUdpSendTo) => {
+                let pid = msg.sender.pid();
+                std_udp_send_to(
+                    msg,
+                    &timer,
+                    &mut sockets,
+                    &mut udp_tx_waiting,
+                    process_udp_sockets.entry(pid).or_default(),
+                );
+                // the send call merely queues the datagram, so pump the stack to transmit it
+                xous::try_send_message(
+                    net_conn,
+                    Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .ok();
+            }
+
+            Some(Opcode::StdUdpRecvFrom) => {
+                let pid = msg.sender.pid();
+                std_udp_recv_from(
+                    msg,
+                    &timer,
+                    &mut sockets,
+                    &mut udp_rx_waiting,
+                    process_udp_sockets.entry(pid).or_default(),
+                );
+            }
+
+            Some(Opcode::StdUdpClose) => {
+                let pid = msg.sender.pid();
+                let connection_idx = msg.body.id() >> 16;
+                let handle = match process_udp_sockets
+                    .entry(pid)
+                    .or_default()
+                    .get_mut(connection_idx)
+                    .and_then(|connection| connection.take())
+                {
+                    Some(handle) => handle,
+                    None => {
+                        respond_with_error(msg, NetError::Invalid);
+                        continue;
+                    }
+                };
+                sockets.get::<UdpSocket>(handle).close();
+                sockets.remove(handle);
+                // anyone still waiting on the socket gets an error, rather than waiting forever
+                for waiting in udp_rx_waiting.iter_mut().chain(udp_tx_waiting.iter_mut()) {
+                    if waiting.as_ref().map_or(false, |w| w.handle == handle) {
+                        respond_with_error(waiting.take().unwrap().env, NetError::Invalid);
+                    }
+                }
+                if let Some(response) = msg.body.memory_message_mut() {
+                    response.buf.as_slice_mut::<u8>()[0] = 0;
+                } else if msg.body.is_blocking() {
+                    xous::return_scalar(msg.sender, 0).ok();
+                }
+            }
+
             Some(Opcode::TcpConnect) => {
                 let mut buf = unsafe {
                     Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
@@ -1374,12 +1974,7 @@ fn xmain() -> ! {
                     let mut udp_socket = UdpSocket::new(udp_rx_buffer, udp_tx_buffer);
                     match udp_socket.bind(udpspec.port) {
                         Ok(_) => {
-                            let sid = SID::from_array(udpspec.cb_sid);
-                            let udpstate = UdpState {
-                                handle: sockets.add(udp_socket),
-                                cid: xous::connect(sid).unwrap(),
-                                sid,
-                            };
+                            let udpstate = UdpState::new(sockets.add(udp_socket), SID::from_array(udpspec.cb_sid));
                             udp_handles.insert(udpspec.port, udpstate);
                             buf.replace(NetMemResponse::Ok).unwrap();
                         }
@@ -1395,6 +1990,8 @@ fn xmain() -> ! {
                     Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
                 };
                 let udpspec = buf.to_original::<NetUdpBind, _>().unwrap();
+                // set if the last handle to the socket is closed, so its multicast memberships can be dropped
+                let mut udp_leave_all_groups: Option<u16> = None;
                 // need to find the SID that matches either in the clone array, or the primary binding.
                 // first check the clone array, then fall back to the primary binding
                 match udp_clones.get_mut(&udpspec.port) {
@@ -1416,6 +2013,7 @@ fn xmain() -> ! {
                                 None => {
                                     sockets.get::<UdpSocket>(udpstate.handle).close();
                                     sockets.remove(udpstate.handle);
+                                    udp_leave_all_groups = Some(udpspec.port);
                                     buf.replace(NetMemResponse::Ok).unwrap();
                                 }
                                 // if the clone map has entries, promote an arbitrary map entry to the primary handle
@@ -1425,14 +2023,15 @@ fn xmain() -> ! {
                                         udp_clones.remove(&udpspec.port);
                                         sockets.get::<UdpSocket>(udpstate.handle).close();
                                         sockets.remove(udpstate.handle);
+                                        udp_leave_all_groups = Some(udpspec.port);
                                         buf.replace(NetMemResponse::Ok).unwrap();
                                     } else {
                                         // take an arbitrary key, re-insert it into the handles map.
                                         let new_primary_sid = *clone_map.keys().next().unwrap(); // unwrap is appropriate because len already checked as not 0
                                         let udpstate = UdpState {
-                                            handle: udpstate.handle,
                                             cid: *clone_map.get(&new_primary_sid).unwrap(),
                                             sid: SID::from_array(new_primary_sid),
+                                            ..udpstate
                                         };
                                         udp_handles.insert(udpspec.port, udpstate);
                                         // now remove it from the clone map
@@ -1449,6 +2048,18 @@ fn xmain() -> ! {
                     }
                     _ => buf.replace(NetMemResponse::Invalid).unwrap(),
                 }
+                if let Some(port) = udp_leave_all_groups {
+                    let timestamp = Instant::from_millis(timer.elapsed_ms() as i64);
+                    multicast_groups.retain(|group, ports| {
+                        ports.remove(&port);
+                        if ports.len() == 0 {
+                            iface.leave_multicast_group(*group, timestamp).ok();
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
             }
             Some(Opcode::UdpTx) => {
                 use std::convert::TryInto;
@@ -1463,11 +2074,66 @@ fn xmain() -> ! {
                                 dest_socket.addr.try_into().unwrap(),
                                 dest_socket.port,
                             );
+                            let (is_broadcast, is_multicast) = match endpoint.addr {
+                                IpAddress::Ipv4(addr) => (
+                                    addr.is_broadcast() || iface.ip_addrs().iter().any(|cidr|
+                                        if let IpCidr::Ipv4(v4cidr) = cidr { v4cidr.broadcast() == Some(addr) } else { false }
+                                    ),
+                                    addr.is_multicast()
+                                ),
+                                _ => (false, endpoint.addr.is_multicast()),
+                            };
+                            if is_broadcast && !udpstate.broadcast {
+                                // same as SO_BROADCAST: broadcasts must be explicitly enabled on the socket
+                                buf.replace(NetMemResponse::AccessDenied).unwrap();
+                                continue;
+                            }
                             let mut socket = sockets.get::<UdpSocket>(udpstate.handle);
+                            // the hop limit is set on the socket, not the packet, so it can only change once the
+                            // Tx buffer (a single datagram) is empty. The Pump that sends a datagram is queued
+                            // asynchronously, so the previous one may still be waiting: push it out now.
+                            if !socket.can_send() {
+                                drop(socket);
+                                iface.poll(&mut sockets, Instant::from_millis(timer.elapsed_ms() as i64)).ok();
+                                socket = sockets.get::<UdpSocket>(udpstate.handle);
+                            }
+                            if !socket.can_send() {
+                                buf.replace(NetMemResponse::SocketInUse).unwrap();
+                                continue;
+                            }
+                            if is_multicast {
+                                socket.set_hop_limit(Some(udpstate.multicast_ttl));
+                            } else {
+                                socket.set_hop_limit(udpstate.ttl);
+                            }
                             match socket.send_slice(&udp_tx.data[..udp_tx.len as usize], endpoint) {
                                 Ok(_) => buf.replace(NetMemResponse::Sent(udp_tx.len)).unwrap(),
                                 _ => buf.replace(NetMemResponse::LibraryError).unwrap(),
                             }
+                            // smoltcp doesn't loop multicast back to the host, so emulate it for local members of the group
+                            if is_multicast && udpstate.multicast_loop {
+                                let joined = if let IpAddress::Ipv4(group) = endpoint.addr {
+                                    multicast_groups.get(&group).map_or(false, |ports| ports.contains(&dest_socket.port))
+                                } else {
+                                    false
+                                };
+                                if let (true, Some(listener)) = (joined, udp_handles.get(&dest_socket.port)) {
+                                    let local_addr = match &net_config {
+                                        Some(config) => NetIpAddr::Ipv4(config.addr),
+                                        None => NetIpAddr::Ipv4([0; 4]),
+                                    };
+                                    let mut response = NetUdpResponse {
+                                        endpoint_ip_addr: local_addr,
+                                        len: udp_tx.len,
+                                        endpoint_port: udp_tx.local_port,
+                                        data: [0; UDP_RESPONSE_MAX_LEN],
+                                    };
+                                    for (&src, dst) in udp_tx.data[..udp_tx.len as usize].iter().zip(response.data.iter_mut()) {
+                                        *dst = src;
+                                    }
+                                    udp_deliver(response, listener.cid, udp_clones.get(&dest_socket.port));
+                                }
+                            }
                             // fire off a Pump to get the stack to actually transmit the ping; the send call merely queues it for sending
                             xous::try_send_message(
                                 net_conn,
@@ -1490,9 +2156,9 @@ fn xmain() -> ! {
             Some(Opcode::UdpSetTtl) => msg_scalar_unpack!(msg, ttl, port, _, _, {
                 match udp_handles.get_mut(&(port as u16)) {
                     Some(udpstate) => {
-                        let mut socket = sockets.get::<UdpSocket>(udpstate.handle);
                         let checked_ttl = if ttl > 255 || ttl == 0 { 64 } else { ttl as u8 };
-                        socket.set_hop_limit(Some(checked_ttl));
+                        // applied to the socket on the next unicast transmission
+                        udpstate.ttl = Some(checked_ttl);
                     }
                     None => {
                         log::error!(
@@ -1506,8 +2172,7 @@ fn xmain() -> ! {
             Some(Opcode::UdpGetTtl) => msg_blocking_scalar_unpack!(msg, port, _, _, _, {
                 match udp_handles.get_mut(&(port as u16)) {
                     Some(udpstate) => {
-                        let socket = sockets.get::<UdpSocket>(udpstate.handle);
-                        let ttl = socket.hop_limit().unwrap_or(64); // 64 is the value used by smoltcp if hop limit isn't set
+                        let ttl = udpstate.ttl.unwrap_or(64); // 64 is the value used by smoltcp if hop limit isn't set
                         xous::return_scalar(msg.sender, ttl as usize).expect("couldn't return TTL");
                     }
                     None => {
@@ -1519,6 +2184,96 @@ fn xmain() -> ! {
                     }
                 }
             }),
+            Some(Opcode::UdpSetBroadcast) => msg_blocking_scalar_unpack!(msg, port, enable, _, _, {
+                if let Some(udpstate) = udp_handles.get_mut(&(port as u16)) {
+                    udpstate.broadcast = enable != 0;
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                } else {
+                    log::error!("Set broadcast message received, but no port was bound! port {}", port);
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                }
+            }),
+            Some(Opcode::UdpGetBroadcast) => msg_blocking_scalar_unpack!(msg, port, _, _, _, {
+                match udp_handles.get(&(port as u16)) {
+                    Some(udpstate) => xous::return_scalar(msg.sender, udpstate.broadcast as usize).unwrap(),
+                    None => xous::return_scalar(msg.sender, usize::MAX).unwrap(),
+                }
+            }),
+            Some(Opcode::UdpSetMulticastLoopV4) => msg_blocking_scalar_unpack!(msg, port, enable, _, _, {
+                if let Some(udpstate) = udp_handles.get_mut(&(port as u16)) {
+                    udpstate.multicast_loop = enable != 0;
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                } else {
+                    log::error!("Set multicast loop message received, but no port was bound! port {}", port);
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                }
+            }),
+            Some(Opcode::UdpGetMulticastLoopV4) => msg_blocking_scalar_unpack!(msg, port, _, _, _, {
+                match udp_handles.get(&(port as u16)) {
+                    Some(udpstate) => xous::return_scalar(msg.sender, udpstate.multicast_loop as usize).unwrap(),
+                    None => xous::return_scalar(msg.sender, usize::MAX).unwrap(),
+                }
+            }),
+            Some(Opcode::UdpSetMulticastTtlV4) => msg_blocking_scalar_unpack!(msg, port, ttl, _, _, {
+                if let Some(udpstate) = udp_handles.get_mut(&(port as u16)) {
+                    udpstate.multicast_ttl = if ttl > 255 { 255 } else { ttl as u8 };
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                } else {
+                    log::error!("Set multicast TTL message received, but no port was bound! port {}", port);
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                }
+            }),
+            Some(Opcode::UdpGetMulticastTtlV4) => msg_blocking_scalar_unpack!(msg, port, _, _, _, {
+                match udp_handles.get(&(port as u16)) {
+                    Some(udpstate) => xous::return_scalar(msg.sender, udpstate.multicast_ttl as usize).unwrap(),
+                    None => xous::return_scalar(msg.sender, usize::MAX).unwrap(),
+                }
+            }),
+            Some(Opcode::UdpJoinMulticastV4) => msg_blocking_scalar_unpack!(msg, port, group, interface, _, {
+                let port = port as u16;
+                let group = Ipv4Address::from_bytes(&(group as u32).to_be_bytes());
+                let interface = Ipv4Address::from_bytes(&(interface as u32).to_be_bytes());
+                let local_addr = net_config.as_ref().map(|config| Ipv4Address::from_bytes(&config.addr));
+                let result = if !udp_handles.contains_key(&port) || !group.is_multicast() {
+                    NetError::Invalid as usize
+                } else if !interface.is_unspecified() && Some(interface) != local_addr {
+                    NetError::Unaddressable as usize
+                } else {
+                    let ports = multicast_groups.entry(group).or_insert(HashSet::new());
+                    if ports.len() == 0 {
+                        match iface.join_multicast_group(group, Instant::from_millis(timer.elapsed_ms() as i64)) {
+                            Ok(_) => {
+                                ports.insert(port);
+                                0
+                            }
+                            Err(e) => {
+                                log::error!("couldn't join multicast group {}: {:?}", group, e);
+                                multicast_groups.remove(&group);
+                                NetError::LibraryError as usize
+                            }
+                        }
+                    } else {
+                        ports.insert(port);
+                        0
+                    }
+                };
+                xous::return_scalar(msg.sender, result).unwrap();
+            }),
+            Some(Opcode::UdpLeaveMulticastV4) => msg_blocking_scalar_unpack!(msg, port, group, _, _, {
+                let port = port as u16;
+                let group = Ipv4Address::from_bytes(&(group as u32).to_be_bytes());
+                let (was_member, now_empty) = match multicast_groups.get_mut(&group) {
+                    Some(ports) => (ports.remove(&port), ports.len() == 0),
+                    None => (false, false),
+                };
+                if now_empty {
+                    multicast_groups.remove(&group);
+                    iface.leave_multicast_group(group, Instant::from_millis(timer.elapsed_ms() as i64)).ok();
+                }
+                // leaving a group that wasn't joined is an error
+                let result = if was_member { 0 } else { NetError::Invalid as usize };
+                xous::return_scalar(msg.sender, result).unwrap();
+            }),
 
             Some(Opcode::ComInterrupt) => {
                 com_int_list.clear();
@@ -1573,20 +2328,24 @@ fn xmain() -> ! {
                                     // to neatly clear the ARP cache as the BTreeMap that underlies it is moved into the container and
                                     // no "clear" API is exposed, so let's just rebuild the whole interface if we get a DHCP renewal.
                                     let neighbor_cache = NeighborCache::new(BTreeMap::new());
-                                    let ip_addrs =
-                                        [IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)];
+                                    let ip_addrs = slaac::default_ip_addrs();
                                     let routes = Routes::new(BTreeMap::new());
-                                    let device = device::NetPhy::new(&xns);
+                                    // a packet capture carries on across the rebuild
+                                    let capture = iface.device_mut().set_capture(None);
+                                    let mut device = device::NetPhy::new(&xns);
+                                    device.set_capture(capture);
                                     let medium = device.capabilities().medium;
                                     let mut builder = InterfaceBuilder::new(device)
                                         .ip_addrs(ip_addrs)
-                                        .routes(routes);
+                                        .routes(routes)
+                                        .ipv4_multicast_groups(BTreeMap::new());
                                     if medium == Medium::Ethernet {
                                         builder = builder
                                             .ethernet_addr(mac)
                                             .neighbor_cache(neighbor_cache);
                                     }
                                     iface = builder.finalize();
+                                    rejoin_multicast_groups(&mut iface, &multicast_groups, Instant::from_millis(timer.elapsed_ms() as i64));
 
                                     let ip_addr = Ipv4Cidr::new(
                                         Ipv4Address::new(
@@ -1614,6 +2373,15 @@ fn xmain() -> ! {
                                         ),
                                         Err(e) => log::error!("routing table update error: {}", e),
                                     }
+                                    if medium == Medium::Ethernet {
+                                        ipv6_autoconf.link_up(&mut iface, &mut sockets, mac, &device_caps.checksum);
+                                        // get the router solicitation out the door
+                                        xous::try_send_message(
+                                            net_conn,
+                                            Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0),
+                                        )
+                                        .ok();
+                                    }
                                     dns_allclear_hook.notify();
                                     dns_ipv4_hook.notify_custom_args([
                                         Some(u32::from_be_bytes(config.dns1)),
@@ -1882,6 +2650,123 @@ fn xmain() -> ! {
                     response_data[1] = sent_octets as u32;
                 }
 
+                // This block handles TCP Accept for libstd callers. Accepting a connection puts a new
+                // socket in place of the listener, so anyone else waiting on the listener is moved
+                // over to the new socket.
+                for i in 0..tcp_accept_waiting.len() {
+                    let WaitingSocket {
+                        env,
+                        handle,
+                        expiry: _,
+                    } = {
+                        match &tcp_accept_waiting[i] {
+                            None => continue,
+                            Some(s) => {
+                                if !std_tcp_accept_ready(&mut sockets, s.handle) {
+                                    if let Some(trigger) = s.expiry {
+                                        if trigger.get() < now {
+                                            // timer expired
+                                        } else {
+                                            continue;
+                                        }
+                                    } else {
+                                        continue;
+                                    }
+                                }
+                            }
+                        }
+                        tcp_accept_waiting[i].take().unwrap()
+                    };
+
+                    if !std_tcp_accept_ready(&mut sockets, handle) {
+                        respond_with_error(env, NetError::TimedOut);
+                        continue;
+                    }
+
+                    let pid = env.sender.pid();
+                    let new_listener = respond_with_accepted(
+                        env,
+                        handle,
+                        &mut sockets,
+                        &mut std_tcp_listeners,
+                        process_sockets.entry(pid).or_default(),
+                    );
+                    for waiting in tcp_accept_waiting.iter_mut().flatten() {
+                        if waiting.handle == handle {
+                            waiting.handle = new_listener;
+                        }
+                    }
+                }
+
+                // This block handles UDP Rx for libstd callers
+                for connection in udp_rx_waiting.iter_mut() {
+                    let mut socket;
+                    let mut env = {
+                        match connection {
+                            &mut None => continue,
+                            Some(s) => {
+                                socket = sockets.get::<UdpSocket>(s.handle);
+                                if !socket.can_recv() {
+                                    if let Some(trigger) = s.expiry {
+                                        if trigger.get() < now {
+                                            // timer expired
+                                        } else {
+                                            continue;
+                                        }
+                                    } else {
+                                        continue;
+                                    }
+                                }
+                            }
+                        }
+                        connection.take().unwrap().env
+                    };
+
+                    // If it can't receive, then the only explanation was that it timed out
+                    if !socket.can_recv() {
+                        respond_with_error(env, NetError::TimedOut);
+                        continue;
+                    }
+
+                    if let Err(e) = std_udp_recv(&mut env, &mut socket) {
+                        respond_with_error(env, e);
+                    }
+                }
+
+                // This block handles UDP Tx for libstd callers
+                for connection in udp_tx_waiting.iter_mut() {
+                    let mut socket;
+                    let mut env = {
+                        match connection {
+                            &mut None => continue,
+                            Some(s) => {
+                                socket = sockets.get::<UdpSocket>(s.handle);
+                                if !socket.can_send() {
+                                    if let Some(trigger) = s.expiry {
+                                        if trigger.get() < now {
+                                            // timer expired
+                                        } else {
+                                            continue;
+                                        }
+                                    } else {
+                                        continue;
+                                    }
+                                }
+                            }
+                        }
+                        connection.take().unwrap().env
+                    };
+
+                    if !socket.can_send() {
+                        respond_with_error(env, NetError::TimedOut);
+                        continue;
+                    }
+
+                    if let Err(e) = std_udp_send(&mut env, &mut socket) {
+                        respond_with_error(env, e);
+                    }
+                }
+
                 // this block handles TCP listeners
                 // There is no lock on sending Rx messages to the listener as it is being transformed
                 // because all messages come from *this thread* and by definition it is not re-entrant.
@@ -1973,20 +2858,8 @@ fn xmain() -> ! {
                                 for (&src, dst) in data.iter().zip(response.data.iter_mut()) {
                                     *dst = src;
                                 }
-                                let buf = Buffer::into_buf(response)
-                                    .expect("couldn't convert UDP response to memory message");
-                                buf.send(udpstate.cid, NetUdpCallback::RxData.to_u32().unwrap())
-                                    .expect("couldn't send UDP response");
-                                // now send copies to the cloned receiver array, if they exist
-                                if let Some(clone_map) = udp_clones.get(port) {
-                                    for &cids in clone_map.values() {
-                                        let buf = Buffer::into_buf(response).expect(
-                                            "couldn't convert UDP response to memory message",
-                                        );
-                                        buf.send(cids, NetUdpCallback::RxData.to_u32().unwrap())
-                                            .expect("couldn't send UDP response");
-                                    }
-                                }
+                                // send to the owner, and copies to the cloned receiver array, if they exist
+                                udp_deliver(response, udpstate.cid, udp_clones.get(port));
                             }
                             Err(_) => {
                                 // do nothing
@@ -2093,8 +2966,10 @@ fn xmain() -> ! {
                                 }
 
                                 IpAddress::Ipv6(_) => {
-                                    // NOTE: actually not sure what src_ipv6 should be. This is just from an example.
-                                    let src_ipv6 = IpAddress::v6(0xfdaa, 0, 0, 0, 0, 0, 0, 1);
+                                    // the reply is addressed to whatever source smoltcp picked for the request
+                                    let src_ipv6 = IpAddress::Ipv6(
+                                        ipv6_autoconf.source_address().unwrap_or(Ipv6Address::UNSPECIFIED),
+                                    );
                                     let icmp_packet = Icmpv6Packet::new_checked(&payload).unwrap();
                                     let icmp_repr = Icmpv6Repr::parse(
                                         &remote_addr,
@@ -2251,6 +3126,11 @@ fn xmain() -> ! {
                     }
                 }
 
+                // this block picks up router advertisements for IPv6 address autoconfiguration
+                if ipv6_autoconf.poll(&mut iface, &mut sockets, &device_caps.checksum, now) {
+                    log::info!("IPv6 config updated: {:?}", ipv6_autoconf.config());
+                }
+
                 // establish our next check-up interval
                 let timestamp = Instant::from_millis(timer.elapsed_ms() as i64);
                 if let Some(delay) = iface.poll_delay(&sockets, timestamp) {
@@ -2299,6 +3179,35 @@ fn xmain() -> ! {
                     }
                 }
             }
+            Some(Opcode::PcapStart) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                iface
+                    .device_mut()
+                    .set_capture(Some(pcap::PcapRing::new(PCAP_RING_CAPACITY)));
+                pcap_capture = None;
+                log::info!("packet capture started");
+                xous::return_scalar(msg.sender, 0).ok();
+            }),
+            Some(Opcode::PcapStop) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                if let Some(capture) = iface.device_mut().set_capture(None) {
+                    log::info!("packet capture stopped with {} frames", capture.len());
+                    pcap_capture = Some(capture);
+                }
+                let frames = pcap_capture.as_ref().map_or(0, |capture| capture.len());
+                xous::return_scalar(msg.sender, frames).ok();
+            }),
+            Some(Opcode::PcapFetch) => {
+                let body = match msg.body.memory_message_mut() {
+                    Some(body) => body,
+                    None => {
+                        log::error!("PcapFetch must be a memory message");
+                        continue;
+                    }
+                };
+                let len = pcap_capture
+                    .as_ref()
+                    .map(|capture| capture.write_pcap(body.buf.as_slice_mut()));
+                body.valid = len.and_then(xous::MemorySize::new);
+            }
             Some(Opcode::GetIpv4Config) => {
                 let mut buffer = unsafe {
                     Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
@@ -2310,6 +3219,12 @@ fn xmain() -> ! {
                 };
                 buffer.replace(ser).expect("couldn't return config");
             }
+            Some(Opcode::GetIpv6Config) => {
+                let mut buffer = unsafe {
+                    Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
+                };
+                buffer.replace(ipv6_autoconf.config()).expect("couldn't return config");
+            }
             Some(Opcode::SubscribeWifiStats) => {
                 let buffer =
                     unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -2401,20 +3316,30 @@ fn xmain() -> ! {
             Some(Opcode::Reset) => {
                 net_config = None;
                 let neighbor_cache = NeighborCache::new(BTreeMap::new());
-                let ip_addrs = [IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)];
+                let ip_addrs = slaac::default_ip_addrs();
                 let routes = Routes::new(BTreeMap::new());
-                let device = device::NetPhy::new(&xns);
+                // a packet capture carries on across the rebuild
+                let capture = iface.device_mut().set_capture(None);
+                let mut device = device::NetPhy::new(&xns);
+                device.set_capture(capture);
                 let medium = device.capabilities().medium;
                 let mut builder = InterfaceBuilder::new(device)
                     .ip_addrs(ip_addrs)
-                    .routes(routes);
+                    .routes(routes)
+                    .ipv4_multicast_groups(BTreeMap::new());
                 if medium == Medium::Ethernet {
                     builder = builder
                         .ethernet_addr(EthernetAddress::from_bytes(&[0; 6]))
                         .neighbor_cache(neighbor_cache);
                 }
                 iface = builder.finalize();
+                rejoin_multicast_groups(&mut iface, &multicast_groups, Instant::from_millis(timer.elapsed_ms() as i64));
                 iface.routes_mut().remove_default_ipv4_route();
+                #[cfg(not(any(target_os = "none", target_os = "xous")))]
+                {
+                    net_config = Some(apply_loopback_config(&mut iface));
+                }
+                ipv6_autoconf.link_down();
                 dns_allclear_hook.notify();
                 // question: do we need to clear the UDP and ICMP states?
                 xous::return_scalar(msg.sender, 1).unwrap();
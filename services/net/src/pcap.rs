@@ -0,0 +1,112 @@
+use com::api::NET_MTU;
+use smoltcp::time::Instant;
+use std::collections::VecDeque;
+
+/// Each frame in a pcap file is preceded by its timestamp and length
+const RECORD_HEADER_LEN: usize = 16;
+/// LINKTYPE_ETHERNET
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// The most recent frames seen by the interface, as they will appear in a pcap file.
+/// Timestamps are the interface's time since boot.
+pub(crate) struct PcapRing {
+    frames: VecDeque<(i64, Vec<u8>)>,
+    /// bytes the frames take up in a pcap file, including their record headers
+    bytes: usize,
+    capacity: usize,
+}
+
+impl PcapRing {
+    pub(crate) fn new(capacity: usize) -> PcapRing {
+        PcapRing {
+            frames: VecDeque::new(),
+            bytes: 0,
+            capacity,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub(crate) fn record(&mut self, timestamp: Instant, frame: &[u8]) {
+        let len = RECORD_HEADER_LEN + frame.len();
+        if len > self.capacity {
+            return;
+        }
+        while self.bytes + len > self.capacity {
+            if let Some((_, old)) = self.frames.pop_front() {
+                self.bytes -= RECORD_HEADER_LEN + old.len();
+            }
+        }
+        self.frames.push_back((timestamp.total_millis(), frame.to_vec()));
+        self.bytes += len;
+    }
+
+    /// Write the capture out as a pcap file, returning its length. Frames that don't fit in
+    /// `buf` are left off the end.
+    pub(crate) fn write_pcap(&self, buf: &mut [u8]) -> usize {
+        if buf.len() < crate::api::PCAP_HEADER_LEN {
+            return 0;
+        }
+        buf[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        buf[4..6].copy_from_slice(&2u16.to_le_bytes()); // version 2.4
+        buf[6..8].copy_from_slice(&4u16.to_le_bytes());
+        buf[8..16].copy_from_slice(&[0; 8]); // UTC, and no timestamp accuracy
+        buf[16..20].copy_from_slice(&(NET_MTU as u32).to_le_bytes());
+        buf[20..24].copy_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+
+        let mut len = crate::api::PCAP_HEADER_LEN;
+        for (ms, frame) in self.frames.iter() {
+            let record = &mut buf[len..];
+            if record.len() < RECORD_HEADER_LEN + frame.len() {
+                break;
+            }
+            record[0..4].copy_from_slice(&((ms / 1000) as u32).to_le_bytes());
+            record[4..8].copy_from_slice(&((ms % 1000) as u32 * 1000).to_le_bytes());
+            record[8..12].copy_from_slice(&(frame.len() as u32).to_le_bytes());
+            record[12..16].copy_from_slice(&(frame.len() as u32).to_le_bytes());
+            record[RECORD_HEADER_LEN..RECORD_HEADER_LEN + frame.len()].copy_from_slice(frame);
+            len += RECORD_HEADER_LEN + frame.len();
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::PCAP_HEADER_LEN;
+    use std::convert::TryInto;
+
+    #[test]
+    fn ring_drops_oldest() {
+        let mut ring = PcapRing::new(3 * (RECORD_HEADER_LEN + 10));
+        for i in 0..5u8 {
+            ring.record(Instant::from_millis(i as i64 * 1500), &[i; 10]);
+        }
+        assert_eq!(ring.len(), 3);
+        // too big to ever fit
+        ring.record(Instant::from_millis(0), &[0; 100]);
+        assert_eq!(ring.len(), 3);
+
+        let mut file = [0u8; 256];
+        let len = ring.write_pcap(&mut file);
+        assert_eq!(len, PCAP_HEADER_LEN + 3 * (RECORD_HEADER_LEN + 10));
+        assert_eq!(&file[0..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&file[20..24], &[1, 0, 0, 0]);
+        // the first frame left is the third one, sent 3 seconds in
+        let record = &file[PCAP_HEADER_LEN..];
+        assert_eq!(&record[0..4], &[3, 0, 0, 0]);
+        assert_eq!(&record[4..8], &[0, 0, 0, 0]);
+        assert_eq!(&record[8..12], &[10, 0, 0, 0]);
+        assert_eq!(record[RECORD_HEADER_LEN], 2);
+        // and the next one 4.5 seconds in
+        let record = &record[RECORD_HEADER_LEN + 10..];
+        assert_eq!(&record[0..4], &[4, 0, 0, 0]);
+        assert_eq!(u32::from_le_bytes(record[4..8].try_into().unwrap()), 500_000);
+
+        // frames that don't fit are left off
+        assert_eq!(ring.write_pcap(&mut file[..PCAP_HEADER_LEN + 30]), PCAP_HEADER_LEN + 26);
+    }
+}
@@ -1,4 +1,25 @@
-#[cfg(any(target_os = "none", target_os = "xous"))]
+// Hosted builds compile the same client code as hardware, which the `*_hosted` wrappers use for
+// loopback traffic: the net server serves 127.0.0.0/8 from its smoltcp stack, over the loopback
+// device in `device_hosted.rs`. Everything else goes to host sockets.
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+macro_rules! hosted_dispatch {
+    ($backend:expr, $kind:ident, $s:ident => $call:expr) => {
+        match $backend {
+            $kind::Host($s) => $call,
+            $kind::Loopback($s) => $call,
+        }
+    };
+}
+
+/// Whether hosted clients reach `addr` through the net server rather than a host socket
+#[cfg(not(any(target_os = "none", target_os = "xous")))]
+pub(crate) fn served_by_loopback(addr: &std::net::SocketAddr) -> bool {
+    match addr.ip() {
+        std::net::IpAddr::V4(ip) => ip.is_loopback(),
+        std::net::IpAddr::V6(_) => false,
+    }
+}
+
 pub mod udp;
 #[cfg(any(target_os = "none", target_os = "xous"))]
 pub use udp::*;
@@ -19,11 +40,9 @@ pub use dns_hosted::*;
 pub mod ping;
 pub use ping::*;
 
-#[cfg(any(target_os = "none", target_os = "xous"))]
 pub mod tcp_stream;
 #[cfg(any(target_os = "none", target_os = "xous"))]
 pub use tcp_stream::*;
-#[cfg(any(target_os = "none", target_os = "xous"))]
 pub mod tcp_listener;
 #[cfg(any(target_os = "none", target_os = "xous"))]
 pub use tcp_listener::*;
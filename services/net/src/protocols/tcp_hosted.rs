@@ -8,277 +8,235 @@ use std::net::Shutdown;
 use std::unimplemented;
 use std::net::TcpListener as TcpListenerHosted;
 
+fn to_std_duration(timeout: Option<Duration>) -> Option<std::time::Duration> {
+    timeout.map(|d| std::time::Duration::from_millis(d.total_millis()))
+}
+fn from_std_duration(timeout: io::Result<Option<std::time::Duration>>) -> io::Result<Option<Duration>> {
+    timeout.map(|maybe_t| maybe_t.map(|t| Duration::from_millis(t.as_millis() as u64)))
+}
+fn first_addr<A: ToSocketAddrs>(addr: A) -> io::Result<SocketAddr> {
+    match addr.to_socket_addrs() {
+        Ok(mut socks) => socks.next().ok_or(Error::new(ErrorKind::InvalidInput, "IP address invalid")),
+        _ => Err(Error::new(ErrorKind::InvalidInput, "IP address invalid")),
+    }
+}
+
+/// Connections to an IPv4 loopback address are served by the net server, over its loopback
+/// device; all others are host sockets, so hosted programs can still reach the outside world.
+#[derive(Debug)]
 pub struct TcpStream {
-    stream: TcpStreamHosted,
+    stream: Stream,
+}
+
+#[derive(Debug)]
+enum Stream {
+    Host(TcpStreamHosted),
+    Loopback(super::tcp_stream::TcpStream),
 }
 
 impl TcpStream {
     pub fn connect(maybe_socket: io::Result<&SocketAddr>) -> io::Result<TcpStream> {
         if let Ok(socket) = maybe_socket {
-            match TcpStreamHosted::connect(socket) {
-                Ok(stream) => {
-                    Ok(TcpStream {
-                        stream,
-                    })
-                },
-                Err(e) => Err(e),
-            }
+            TcpStream::connect_xous(socket, None, None)
         } else {
             Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
         }
     }
     pub fn connect_timeout(maybe_socket: io::Result<&SocketAddr>, duration: Duration) -> io::Result<TcpStream> {
         if let Ok(socket) = maybe_socket {
-            let d = std::time::Duration::from_millis(duration.total_millis());
-            match TcpStreamHosted::connect_timeout(socket, d) {
-                Ok(stream) => {
-                    Ok(TcpStream {
-                        stream,
-                    })
-                },
-                Err(e) => Err(e),
-            }
+            TcpStream::connect_xous(socket, Some(duration), None)
         } else {
             Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
         }
     }
 
     /// This API call uses the stdlib-internal calling convention to assist with the process of migrating this into a true libstd
-    pub fn connect_xous<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>, _keepalive: Option<Duration>) -> Result<TcpStream> {
-        if let Some (t) = timeout {
-            let d = std::time::Duration::from_millis(t.total_millis());
-            match addr.to_socket_addrs() {
-                Ok(socks) => {
-                    match socks.into_iter().next() {
-                        Some(socket_addr) => {
-                            match TcpStreamHosted::connect_timeout(&socket_addr, d) {
-                                Ok(stream) => {
-                                    Ok(TcpStream {
-                                        stream,
-                                    })
-                                },
-                                Err(e) => Err(e),
-                            }
-                        }
-                        _ => Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
-                    }
-                }
-                _ => Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
-            }
+    pub fn connect_xous<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>, keepalive: Option<Duration>) -> Result<TcpStream> {
+        let socket_addr = first_addr(addr)?;
+        let stream = if super::served_by_loopback(&socket_addr) {
+            Stream::Loopback(super::tcp_stream::TcpStream::connect_xous(socket_addr, timeout, keepalive)?)
+        } else if let Some(d) = to_std_duration(timeout) {
+            Stream::Host(TcpStreamHosted::connect_timeout(&socket_addr, d)?)
         } else {
-            match TcpStreamHosted::connect(addr) {
-                Ok(stream) => {
-                    Ok(TcpStream {
-                        stream,
-                    })
-                },
-                Err(e) => Err(e),
-            }
-        }
+            Stream::Host(TcpStreamHosted::connect(socket_addr)?)
+        };
+        Ok(TcpStream { stream })
     }
-    /// dev note: if you need this function in hosted mode, contact bunnie.
-    pub fn set_scalar_notification(&mut self, _cid: xous::CID, _op: usize, _args: [Option<usize>; 4]) {
-        unimplemented!()
+    /// dev note: if you need this function on a host socket, contact bunnie.
+    pub fn set_scalar_notification(&mut self, cid: xous::CID, op: usize, args: [Option<usize>; 4]) {
+        match &mut self.stream {
+            Stream::Host(_) => unimplemented!(),
+            Stream::Loopback(s) => s.set_scalar_notification(cid, op, args),
+        }
     }
-    /// dev note: if you need this function in hosted mode, contact bunnie.
+    /// dev note: if you need this function on a host socket, contact bunnie.
     pub fn clear_scalar_notification(&mut self) {
-        unimplemented!()
+        match &mut self.stream {
+            Stream::Host(_) => unimplemented!(),
+            Stream::Loopback(s) => s.clear_scalar_notification(),
+        }
     }
 
     pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
-        let t = match timeout {
-            Some(d) => {
-                Some(std::time::Duration::from_millis(d.total_millis()))
-            }
-            None => None,
-        };
-        self.stream.set_read_timeout(t)
+        match &mut self.stream {
+            Stream::Host(s) => s.set_read_timeout(to_std_duration(timeout)),
+            Stream::Loopback(s) => s.set_read_timeout(timeout),
+        }
     }
     pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
-        let t = match timeout {
-            Some(d) => {
-                Some(std::time::Duration::from_millis(d.total_millis()))
-            }
-            None => None,
-        };
-        self.stream.set_write_timeout(t)
+        match &mut self.stream {
+            Stream::Host(s) => s.set_write_timeout(to_std_duration(timeout)),
+            Stream::Loopback(s) => s.set_write_timeout(timeout),
+        }
     }
     pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
-        match self.stream.read_timeout() {
-            Ok(maybe_t) => {
-                match maybe_t {
-                    Some(t) => {
-                        Ok(Some(Duration::from_millis(t.as_millis() as u64)))
-                    }
-                    None => Ok(None)
-                }
-            },
-            Err(e) => Err(e),
+        match &self.stream {
+            Stream::Host(s) => from_std_duration(s.read_timeout()),
+            Stream::Loopback(s) => s.read_timeout(),
         }
     }
     pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
-        match self.stream.write_timeout() {
-            Ok(maybe_t) => {
-                match maybe_t {
-                    Some(t) => {
-                        Ok(Some(Duration::from_millis(t.as_millis() as u64)))
-                    }
-                    None => Ok(None)
-                }
-            },
-            Err(e) => Err(e),
+        match &self.stream {
+            Stream::Host(s) => from_std_duration(s.write_timeout()),
+            Stream::Loopback(s) => s.write_timeout(),
         }
     }
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.stream.peer_addr()
+        hosted_dispatch!(&self.stream, Stream, s => s.peer_addr())
     }
     pub fn socket_addr(&self) -> io::Result<SocketAddr> {
-        self.stream.local_addr()
+        match &self.stream {
+            Stream::Host(s) => s.local_addr(),
+            Stream::Loopback(s) => s.socket_addr(),
+        }
     }
 
     pub fn shutdown(&mut self, request: Shutdown) -> io::Result<()> {
-        self.stream.shutdown(request)
+        hosted_dispatch!(&mut self.stream, Stream, s => s.shutdown(request))
     }
 
     pub fn set_nodelay(&self, setting: bool) -> io::Result<()> {
-        self.stream.set_nodelay(setting)
+        hosted_dispatch!(&self.stream, Stream, s => s.set_nodelay(setting))
     }
 
     pub fn nodelay(&self) -> io::Result<bool> {
-        self.stream.nodelay()
+        hosted_dispatch!(&self.stream, Stream, s => s.nodelay())
     }
 
     pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
-        self.stream.set_ttl(ttl)
+        hosted_dispatch!(&self.stream, Stream, s => s.set_ttl(ttl))
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
-        self.stream.ttl()
+        hosted_dispatch!(&self.stream, Stream, s => s.ttl())
     }
 
-    /// I'm not quite sure this has any meaning on Xous. Right now this just checks if there is an
-    /// Rx state error in progress.
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        self.stream.take_error()
+        hosted_dispatch!(&self.stream, Stream, s => s.take_error())
     }
 
     pub fn set_nonblocking(&mut self, setting: bool) -> io::Result<()> {
-        self.stream.set_nonblocking(setting)
+        hosted_dispatch!(&mut self.stream, Stream, s => s.set_nonblocking(setting))
     }
 
     pub fn duplicate(&self) -> io::Result<TcpStream> {
-        match self.stream.try_clone() {
-            Ok(stream) => {
-                Ok(TcpStream {
-                    stream
-                })
-            },
-            Err(e) => Err(e)
-        }
+        let stream = match &self.stream {
+            Stream::Host(s) => Stream::Host(s.try_clone()?),
+            Stream::Loopback(s) => Stream::Loopback(s.duplicate()?),
+        };
+        Ok(TcpStream { stream })
     }
 
     pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.stream.peek(buf)
+        hosted_dispatch!(&self.stream, Stream, s => s.peek(buf))
     }
 }
 
 impl Read for TcpStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.stream.read(buf)
+        hosted_dispatch!(&mut self.stream, Stream, s => s.read(buf))
     }
 }
 
 impl Write for TcpStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stream.write(buf)
+        hosted_dispatch!(&mut self.stream, Stream, s => s.write(buf))
     }
 
     /// In the case that Nagle's algo is enabled, packets can get stuck in the outgoing buffer.
     /// This basically waits until Nagle's algo times out, and the data is sent.
     fn flush(&mut self) -> io::Result<()> {
-        self.stream.flush()
+        hosted_dispatch!(&mut self.stream, Stream, s => s.flush())
     }
 }
 
 
-
+/// Listeners bound to an IPv4 loopback address are served by the net server, and accept
+/// connections made through it; all others are host sockets.
+#[derive(Debug)]
 pub struct TcpListener {
-    listener: TcpListenerHosted,
+    listener: Listener,
+}
+
+#[derive(Debug)]
+enum Listener {
+    Host(TcpListenerHosted),
+    Loopback(super::tcp_listener::TcpListener),
 }
 
 impl TcpListener {
     pub fn bind(maybe_socket: io::Result<&SocketAddr>) -> io::Result<TcpListener> {
-        match maybe_socket {
-            Ok(socket) => {
-                match TcpListenerHosted::bind(socket) {
-                    Ok(listener) => {
-                        Ok(
-                            TcpListener {
-                                listener,
-                            }
-                        )
-                    }
-                    Err(e) => Err(e)
-                }
-            }
-            Err(e) => Err(e)
-        }
+        TcpListener::bind_xous(maybe_socket?)
     }
 
     pub fn bind_xous<A: ToSocketAddrs>(addr: A) -> Result<TcpListener> {
-        match TcpListenerHosted::bind(addr) {
-            Ok(listener) => {
-                Ok(
-                    TcpListener {
-                        listener,
-                    }
-                )
-            }
-            Err(e) => Err(e)
-        }
+        let socket_addr = first_addr(addr)?;
+        let listener = if super::served_by_loopback(&socket_addr) {
+            Listener::Loopback(super::tcp_listener::TcpListener::bind_xous(socket_addr)?)
+        } else {
+            Listener::Host(TcpListenerHosted::bind(socket_addr)?)
+        };
+        Ok(TcpListener { listener })
     }
 
-    pub fn set_scalar_notification(&mut self, _cid: xous::CID, _op: usize, _args: [Option<usize>; 4]) {
-        unimplemented!()
+    pub fn set_scalar_notification(&mut self, cid: xous::CID, op: usize, args: [Option<usize>; 4]) {
+        match &mut self.listener {
+            Listener::Host(_) => unimplemented!(),
+            Listener::Loopback(l) => l.set_scalar_notification(cid, op, args),
+        }
     }
     pub fn clear_scalar_notification(&mut self) {
-        unimplemented!()
+        match &mut self.listener {
+            Listener::Host(_) => unimplemented!(),
+            Listener::Loopback(l) => l.clear_scalar_notification(),
+        }
     }
 
     pub fn accept(&mut self) -> io::Result<(TcpStream, SocketAddr)> {
-        match self.listener.accept() {
-            Ok((stream, addr)) => {
-                Ok((
-                    TcpStream {
-                        stream,
-                    },
-                    addr
-                ))
-            }
-            Err(e) => Err(e)
+        match &mut self.listener {
+            Listener::Host(l) => l.accept().map(|(s, addr)| (TcpStream { stream: Stream::Host(s) }, addr)),
+            Listener::Loopback(l) => l.accept().map(|(s, addr)| (TcpStream { stream: Stream::Loopback(s) }, addr)),
         }
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.listener.local_addr()
+        hosted_dispatch!(&self.listener, Listener, l => l.local_addr())
     }
 
     pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
-        self.listener.set_ttl(ttl)
+        hosted_dispatch!(&self.listener, Listener, l => l.set_ttl(ttl))
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
-        self.listener.ttl()
+        hosted_dispatch!(&self.listener, Listener, l => l.ttl())
     }
 
-    /// I'm not really sure what this does on Xous, as we don't have a SO_ERROR value in our OS.
-    /// Just returning Ok(None) for now.
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        self.listener.take_error()
+        hosted_dispatch!(&self.listener, Listener, l => l.take_error())
     }
 
     pub fn set_nonblocking(&mut self, setting: bool) -> io::Result<()> {
-        self.listener.set_nonblocking(setting)
+        hosted_dispatch!(&mut self.listener, Listener, l => l.set_nonblocking(setting))
     }
 
     pub fn duplicate(&self) -> io::Result<TcpListener> {
@@ -9,7 +9,7 @@ use std::collections::VecDeque;
 
 use xous::{Message, SID, CID};
 use xous_ipc::Buffer;
-use crate::TcpStream;
+use super::tcp_stream::TcpStream;
 use crate::NetConn;
 use crate::api::*;
 use num_traits::*;
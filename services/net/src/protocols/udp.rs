@@ -1,4 +1,3 @@
-use std::convert::TryInto;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 use std::unimplemented;
 use std::io;
@@ -228,16 +227,7 @@ impl UdpSocket {
                         IpAddr::V4(Ipv4Addr::new(ipv4.0[0], ipv4.0[1], ipv4.0[2], ipv4.0[3]))
                     },
                     IpAddress::Ipv6(ipv6) => {
-                        IpAddr::V6(Ipv6Addr::new(
-                            u16::from_be_bytes(ipv6.0[0..1].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[2..3].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[4..5].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[6..7].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[8..9].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[10..11].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[12..13].try_into().unwrap()),
-                            u16::from_be_bytes(ipv6.0[14..15].try_into().unwrap()),
-                        ))
+                        IpAddr::V6(Ipv6Addr::from(ipv6.0))
                     },
                     _ => {
                         panic!("malformed endpoint record");
@@ -329,6 +319,8 @@ impl UdpSocket {
             .or(Err(Error::new(ErrorKind::Other, "can't send to Net server")))?;
         match buf.to_original().unwrap() {
             NetMemResponse::Sent(len) => Ok(len as usize),
+            NetMemResponse::AccessDenied => Err(Error::new(ErrorKind::PermissionDenied, "broadcast is not enabled on this socket")),
+            NetMemResponse::SocketInUse => Err(Error::new(ErrorKind::WouldBlock, "the previous datagram is still queued")),
             _ => Err(Error::new(ErrorKind::Other, "send failed")),
         }
     }
@@ -394,57 +386,121 @@ impl UdpSocket {
         Ok(())
     }
 
-    //////// the remaining functions are currently unimplemented: we don't have multicast or broadcast support //////////
-    pub fn set_broadcast(&self, _: bool) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        self.set_option(Opcode::UdpSetBroadcast, broadcast as usize)
     }
 
     pub fn broadcast(&self) -> io::Result<bool> {
-        unimplemented!("work in progress")
+        self.get_option(Opcode::UdpGetBroadcast).map(|b| b != 0)
     }
 
-    pub fn set_multicast_loop_v4(&self, _: bool) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn set_multicast_loop_v4(&self, multicast_loop_v4: bool) -> io::Result<()> {
+        self.set_option(Opcode::UdpSetMulticastLoopV4, multicast_loop_v4 as usize)
     }
 
     pub fn multicast_loop_v4(&self) -> io::Result<bool> {
-        unimplemented!("work in progress")
+        self.get_option(Opcode::UdpGetMulticastLoopV4).map(|l| l != 0)
     }
 
-    pub fn set_multicast_ttl_v4(&self, _: u32) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn set_multicast_ttl_v4(&self, multicast_ttl_v4: u32) -> io::Result<()> {
+        if multicast_ttl_v4 > 255 {
+            return Err(Error::new(ErrorKind::InvalidInput, "TTL must be less than 256"))
+        }
+        self.set_option(Opcode::UdpSetMulticastTtlV4, multicast_ttl_v4 as usize)
     }
 
     pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
-        unimplemented!("work in progress")
+        self.get_option(Opcode::UdpGetMulticastTtlV4).map(|ttl| ttl as u32)
     }
 
-    pub fn join_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        self.multicast_v4_membership(Opcode::UdpJoinMulticastV4, multiaddr, interface)
     }
 
-    pub fn leave_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        self.multicast_v4_membership(Opcode::UdpLeaveMulticastV4, multiaddr, interface)
     }
 
-    pub fn set_multicast_loop_v6(&self, _: bool) -> io::Result<()> {
-        unimplemented!("ipv6 not implemented")
+    fn multicast_v4_membership(&self, op: Opcode, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        if !multiaddr.is_multicast() {
+            return Err(Error::new(ErrorKind::InvalidInput, "not a multicast address"));
+        }
+        let result = send_message(
+            self.net.conn(),
+            Message::new_blocking_scalar(op.to_usize().unwrap(),
+                self.socket_addr.port() as usize,
+                u32::from(*multiaddr) as usize,
+                u32::from(*interface) as usize,
+                0)
+        ).or(Err(Error::new(ErrorKind::ConnectionRefused, "can't send multicast membership message")))?;
+        match result {
+            xous::Result::Scalar1(0) => Ok(()),
+            xous::Result::Scalar1(code) if code == NetError::Unaddressable as usize =>
+                Err(Error::new(ErrorKind::AddrNotAvailable, "interface address is not ours")),
+            xous::Result::Scalar1(code) if code == NetError::Invalid as usize =>
+                Err(Error::new(ErrorKind::InvalidInput, "socket or group membership is invalid")),
+            _ => Err(Error::new(ErrorKind::Other, "multicast membership change failed")),
+        }
+    }
+
+    /// sets a per-socket option that is kept by the Net server
+    fn set_option(&self, op: Opcode, value: usize) -> io::Result<()> {
+        let result = send_message(
+            self.net.conn(),
+            Message::new_blocking_scalar(op.to_usize().unwrap(), self.socket_addr.port() as usize, value, 0, 0)
+        ).or(Err(Error::new(ErrorKind::ConnectionRefused, "can't send socket option to Net server")))?;
+        match result {
+            xous::Result::Scalar1(1) => Ok(()),
+            _ => Err(Error::new(ErrorKind::NotConnected, "socket is not bound")),
+        }
+    }
+
+    /// retrieves a per-socket option that is kept by the Net server
+    fn get_option(&self, op: Opcode) -> io::Result<usize> {
+        let result = send_message(
+            self.net.conn(),
+            Message::new_blocking_scalar(op.to_usize().unwrap(), self.socket_addr.port() as usize, 0, 0, 0)
+        ).or(Err(Error::new(ErrorKind::ConnectionRefused, "can't get socket option from Net server")))?;
+        match result {
+            xous::Result::Scalar1(value) if value != usize::MAX => Ok(value),
+            _ => Err(Error::new(ErrorKind::NotConnected, "socket is not bound")),
+        }
+    }
+
+    /// The Net server keeps a single multicast loopback setting per socket, shared with `set_multicast_loop_v4`.
+    pub fn set_multicast_loop_v6(&self, multicast_loop_v6: bool) -> io::Result<()> {
+        self.set_option(Opcode::UdpSetMulticastLoopV4, if multicast_loop_v6 { 1 } else { 0 })
     }
 
     pub fn multicast_loop_v6(&self) -> io::Result<bool> {
-        unimplemented!("ipv6 not implemented")
+        self.get_option(Opcode::UdpGetMulticastLoopV4).map(|l| l != 0)
     }
 
-    pub fn join_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> io::Result<()> {
-        unimplemented!("ipv6 not implemented")
+    /// smoltcp doesn't speak MLD, so the only IPv6 group we can be a member of is the all-nodes group
+    /// (ff02::1), which every interface belongs to anyways. Joining it always succeeds; any other group
+    /// is refused with `ErrorKind::Unsupported`.
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, _interface: u32) -> io::Result<()> {
+        multicast_v6_membership(multiaddr)
     }
 
-    pub fn leave_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> io::Result<()> {
-        unimplemented!("ipv6 not implemented")
+    /// Leaving the all-nodes group is accepted, but has no effect: the interface stays a member.
+    /// Any other group is refused with `ErrorKind::Unsupported`, as it can't have been joined.
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, _interface: u32) -> io::Result<()> {
+        multicast_v6_membership(multiaddr)
     }
 
 }
 
+pub(crate) fn multicast_v6_membership(multiaddr: &Ipv6Addr) -> io::Result<()> {
+    if !multiaddr.is_multicast() {
+        Err(Error::new(ErrorKind::InvalidInput, "not a multicast address"))
+    } else if multiaddr.octets() == Ipv6Address::LINK_LOCAL_ALL_NODES.0 {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::Unsupported, "only the IPv6 all-nodes group (ff02::1) can be joined; MLD is not supported"))
+    }
+}
+
 impl std::fmt::Debug for UdpSocket {
     fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         unimplemented!("work in progress")
@@ -481,4 +537,17 @@ impl Drop for UdpSocket {
         // now we can detroy the server id of the responder thread
         xous::destroy_server(self.cb_sid).unwrap();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_all_nodes_v6_group_is_accepted() {
+        assert!(multicast_v6_membership(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1)).is_ok());
+        let err = multicast_v6_membership(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        let err = multicast_v6_membership(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
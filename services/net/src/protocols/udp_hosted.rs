@@ -1,5 +1,4 @@
 use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
-use std::unimplemented;
 use std::io;
 use std::io::{Error, ErrorKind, Result};
 use std::net::UdpSocket as UdpSocketHosted;
@@ -18,7 +17,20 @@ struct UdpRx {
     from: SocketAddr,
 }
 ///////// UdpSocket implementation
+/// Sockets bound to an IPv4 loopback address are served by the net server, over its loopback
+/// device; all others are host sockets, so hosted programs can still reach the outside world.
+/// Note that this follows the bound address: a host socket sending to 127.0.0.1 reaches the
+/// host's own loopback, not the net server.
 pub struct UdpSocket {
+    backend: Backend,
+}
+
+enum Backend {
+    Host(HostUdpSocket),
+    Loopback(super::udp::UdpSocket),
+}
+
+struct HostUdpSocket {
     handle: Option<JoinHandle::<()>>,
     nonblocking: bool,
     socket: Arc<Mutex<UdpSocketHosted>>,
@@ -35,21 +47,20 @@ pub(crate) enum UdpHostedOpcode {
     Drop,
 }
 
-// next steps: build this stub, and figure out how to clean up the error handling code.
 impl UdpSocket {
     pub fn bind(maybe_socket: io::Result<&SocketAddr>) -> Result<UdpSocket> {
         if let Ok(socket) = maybe_socket {
-            UdpSocket::bind_inner(socket)
+            UdpSocket::bind_inner(socket, None)
         } else {
             Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
         }
     }
-    pub fn bind_xous<A: ToSocketAddrs>(socket: A, _max_payload: Option<u16>) -> Result<UdpSocket> {
+    pub fn bind_xous<A: ToSocketAddrs>(socket: A, max_payload: Option<u16>) -> Result<UdpSocket> {
         match socket.to_socket_addrs() {
             Ok(socks) => {
                 match socks.into_iter().next() {
                     Some(socket_addr) => {
-                        UdpSocket::bind_inner(&socket_addr)
+                        UdpSocket::bind_inner(&socket_addr, max_payload)
                     }
                     _ => Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
                 }
@@ -57,7 +68,142 @@ impl UdpSocket {
             _ => Err(Error::new(ErrorKind::InvalidInput, "IP address invalid"))
         }
     }
-    fn bind_inner(socket: &SocketAddr) -> Result<UdpSocket> {
+    fn bind_inner(socket: &SocketAddr, max_payload: Option<u16>) -> Result<UdpSocket> {
+        let backend = if super::served_by_loopback(socket) {
+            Backend::Loopback(super::udp::UdpSocket::bind_xous(socket, max_payload)?)
+        } else {
+            Backend::Host(HostUdpSocket::bind_inner(socket)?)
+        };
+        Ok(UdpSocket { backend })
+    }
+
+    pub fn get_nonblocking(&self) -> bool {
+        hosted_dispatch!(&self.backend, Backend, s => s.get_nonblocking())
+    }
+
+    pub fn set_scalar_notification(&mut self, cid: xous::CID, op: usize, args: [Option<usize>; 4]) {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.set_scalar_notification(cid, op, args))
+    }
+    pub fn clear_scalar_notification(&mut self) {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.clear_scalar_notification())
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.set_read_timeout(timeout))
+    }
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.set_write_timeout(timeout))
+    }
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        hosted_dispatch!(&self.backend, Backend, s => s.read_timeout())
+    }
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        hosted_dispatch!(&self.backend, Backend, s => s.write_timeout())
+    }
+
+    pub fn recv(&self, pkt: &mut [u8]) -> io::Result<usize> {
+        hosted_dispatch!(&self.backend, Backend, s => s.recv(pkt))
+    }
+    pub fn peek(&self, pkt: &mut [u8]) -> io::Result<usize> {
+        hosted_dispatch!(&self.backend, Backend, s => s.peek(pkt))
+    }
+    pub fn recv_from(&self, pkt: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        hosted_dispatch!(&self.backend, Backend, s => s.recv_from(pkt))
+    }
+    pub fn peek_from(&self, pkt: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        hosted_dispatch!(&self.backend, Backend, s => s.peek_from(pkt))
+    }
+
+    pub fn connect(&mut self, maybe_socket_addr: io::Result<&SocketAddr>) -> io::Result<()> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.connect(maybe_socket_addr))
+    }
+    pub fn connect_xous<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<()> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.connect_xous(addr))
+    }
+
+    pub fn send(&mut self, pkt: &[u8]) -> io::Result<usize> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.send(pkt))
+    }
+    pub fn send_to(&mut self, pkt: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.send_to(pkt, addr))
+    }
+    pub fn send_to_xous<A: ToSocketAddrs>(&mut self, pkt: &[u8], addr: A) -> io::Result<usize> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.send_to_xous(pkt, addr))
+    }
+
+    pub fn socket_addr(&self) -> io::Result<SocketAddr> {
+        hosted_dispatch!(&self.backend, Backend, s => s.socket_addr())
+    }
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        hosted_dispatch!(&self.backend, Backend, s => s.peer_addr())
+    }
+
+    pub fn duplicate(&self) -> io::Result<UdpSocket> {
+        let backend = match &self.backend {
+            Backend::Host(s) => Backend::Host(s.duplicate()?),
+            Backend::Loopback(s) => Backend::Loopback(s.duplicate()?),
+        };
+        Ok(UdpSocket { backend })
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.set_ttl(ttl))
+    }
+    pub fn ttl(&self) -> io::Result<u32> {
+        hosted_dispatch!(&self.backend, Backend, s => s.ttl())
+    }
+
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        hosted_dispatch!(&self.backend, Backend, s => s.take_error())
+    }
+
+    pub fn set_nonblocking(&mut self, nb: bool) -> io::Result<()> {
+        hosted_dispatch!(&mut self.backend, Backend, s => s.set_nonblocking(nb))
+    }
+
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.set_broadcast(broadcast))
+    }
+    pub fn broadcast(&self) -> io::Result<bool> {
+        hosted_dispatch!(&self.backend, Backend, s => s.broadcast())
+    }
+
+    pub fn set_multicast_loop_v4(&self, multicast_loop_v4: bool) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.set_multicast_loop_v4(multicast_loop_v4))
+    }
+    pub fn multicast_loop_v4(&self) -> io::Result<bool> {
+        hosted_dispatch!(&self.backend, Backend, s => s.multicast_loop_v4())
+    }
+    pub fn set_multicast_ttl_v4(&self, multicast_ttl_v4: u32) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.set_multicast_ttl_v4(multicast_ttl_v4))
+    }
+    pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        hosted_dispatch!(&self.backend, Backend, s => s.multicast_ttl_v4())
+    }
+    pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.join_multicast_v4(multiaddr, interface))
+    }
+    pub fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.leave_multicast_v4(multiaddr, interface))
+    }
+
+    pub fn set_multicast_loop_v6(&self, multicast_loop_v6: bool) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.set_multicast_loop_v6(multicast_loop_v6))
+    }
+    pub fn multicast_loop_v6(&self) -> io::Result<bool> {
+        hosted_dispatch!(&self.backend, Backend, s => s.multicast_loop_v6())
+    }
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.join_multicast_v6(multiaddr, interface))
+    }
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        hosted_dispatch!(&self.backend, Backend, s => s.leave_multicast_v6(multiaddr, interface))
+    }
+}
+
+// next steps: build this stub, and figure out how to clean up the error handling code.
+impl HostUdpSocket {
+    fn bind_inner(socket: &SocketAddr) -> Result<HostUdpSocket> {
         if let Ok(udpsocket_naked) = UdpSocketHosted::bind(socket) {
             let rx_buf = Arc::new(Mutex::new(Vec::new()));
             let notify = Arc::new(Mutex::new(XousScalarEndpoint::new()));
@@ -87,7 +233,7 @@ impl UdpSocket {
                     }
                 }
             });
-            Ok(UdpSocket {
+            Ok(HostUdpSocket {
                 handle: Some(handle),
                 nonblocking: false,
                 socket: udpsocket,
@@ -236,10 +382,10 @@ impl UdpSocket {
         self.socket.lock().unwrap().peer_addr()
     }
 
-    pub fn duplicate(&self) -> io::Result<UdpSocket> {
+    pub fn duplicate(&self) -> io::Result<HostUdpSocket> {
         // do the basic clone connection
         let sa = self.socket_addr()?;
-        let mut cloned_socket = UdpSocket::bind_inner(&sa)?;
+        let mut cloned_socket = HostUdpSocket::bind_inner(&sa)?;
         // now copy all the properties over as required by spec
         cloned_socket.set_read_timeout(self.read_timeout)?;
         cloned_socket.set_nonblocking(self.get_nonblocking())?;
@@ -264,57 +410,56 @@ impl UdpSocket {
         Ok(())
     }
 
-    //////// the remaining functions are currently unimplemented: we don't have multicast or broadcast support //////////
-    pub fn set_broadcast(&self, _: bool) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        self.socket.lock().unwrap().set_broadcast(broadcast)
     }
 
     pub fn broadcast(&self) -> io::Result<bool> {
-        unimplemented!("work in progress")
+        self.socket.lock().unwrap().broadcast()
     }
 
-    pub fn set_multicast_loop_v4(&self, _: bool) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn set_multicast_loop_v4(&self, multicast_loop_v4: bool) -> io::Result<()> {
+        self.socket.lock().unwrap().set_multicast_loop_v4(multicast_loop_v4)
     }
 
     pub fn multicast_loop_v4(&self) -> io::Result<bool> {
-        unimplemented!("work in progress")
+        self.socket.lock().unwrap().multicast_loop_v4()
     }
 
-    pub fn set_multicast_ttl_v4(&self, _: u32) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn set_multicast_ttl_v4(&self, multicast_ttl_v4: u32) -> io::Result<()> {
+        self.socket.lock().unwrap().set_multicast_ttl_v4(multicast_ttl_v4)
     }
 
     pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
-        unimplemented!("work in progress")
+        self.socket.lock().unwrap().multicast_ttl_v4()
     }
 
-    pub fn join_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        self.socket.lock().unwrap().join_multicast_v4(multiaddr, interface)
     }
 
-    pub fn leave_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> io::Result<()> {
-        unimplemented!("work in progress")
+    pub fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        self.socket.lock().unwrap().leave_multicast_v4(multiaddr, interface)
     }
 
-    pub fn set_multicast_loop_v6(&self, _: bool) -> io::Result<()> {
-        unimplemented!("ipv6 not implemented")
+    pub fn set_multicast_loop_v6(&self, multicast_loop_v6: bool) -> io::Result<()> {
+        self.socket.lock().unwrap().set_multicast_loop_v6(multicast_loop_v6)
     }
 
     pub fn multicast_loop_v6(&self) -> io::Result<bool> {
-        unimplemented!("ipv6 not implemented")
+        self.socket.lock().unwrap().multicast_loop_v6()
     }
 
-    pub fn join_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> io::Result<()> {
-        unimplemented!("ipv6 not implemented")
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.socket.lock().unwrap().join_multicast_v6(multiaddr, interface)
     }
 
-    pub fn leave_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> io::Result<()> {
-        unimplemented!("ipv6 not implemented")
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        self.socket.lock().unwrap().leave_multicast_v6(multiaddr, interface)
     }
 }
 
-impl Drop for UdpSocket {
+impl Drop for HostUdpSocket {
     fn drop(&mut self) {
         self.should_drop.store(true, Ordering::Relaxed);
         // this will block until the responder thread exits, which it should because it received the Drop message
@@ -0,0 +1,398 @@
+// IPv6 stateless address autoconfiguration (RFC 4862), as far as smoltcp lets us take it.
+//
+// smoltcp answers neighbor solicitations and resolves neighbors on its own, but it ignores
+// router advertisements. So we keep a raw ICMPv6 socket open, send a router solicitation when
+// the link comes up, and build a global address out of any advertised /64 prefix that permits
+// autoconfiguration. Duplicate address detection is not performed: the interface identifier is
+// derived from our MAC address, which is assumed to be unique on the link.
+//
+// The IPv6 addresses occupy two slots of the interface address table (see `IPV6_SLOTS`). smoltcp
+// picks the first address of a matching family as the source of outgoing packets, so the global
+// address is placed ahead of the link-local address once we have one.
+
+use crate::api::Ipv6Config;
+use smoltcp::iface::Interface;
+use smoltcp::phy::{ChecksumCapabilities, Device};
+use smoltcp::socket::{RawPacketMetadata, RawSocket, RawSocketBuffer, SocketHandle, SocketSet};
+use smoltcp::wire::{
+    EthernetAddress, Icmpv6Packet, Icmpv6Repr, IpAddress, IpCidr, IpProtocol, IpVersion,
+    Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr, NdiscPrefixInfoFlags, NdiscRepr,
+};
+
+/// Indices into the interface address table that hold IPv6 addresses. Slot 0 is IPv4.
+pub(crate) const IPV6_SLOTS: [usize; 2] = [1, 2];
+
+/// Neighbor discovery messages must be sent, and received, with a hop limit of 255.
+const NDISC_HOP_LIMIT: u8 = 255;
+/// A lifetime of all ones means "forever".
+const INFINITE_LIFETIME_SECS: u64 = 0xffff_ffff;
+
+/// Interface address table used when (re)building the interface: IPv4 first, then the IPv6 slots.
+pub(crate) fn default_ip_addrs() -> [IpCidr; 3] {
+    [
+        IpCidr::new(smoltcp::wire::Ipv4Address::UNSPECIFIED.into(), 0),
+        IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 0),
+        IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 0),
+    ]
+}
+
+/// Forms an address from a /64 prefix and the modified EUI-64 interface identifier of `mac`.
+fn eui64_address(prefix: &Ipv6Address, mac: &EthernetAddress) -> Ipv6Address {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&prefix.as_bytes()[..8]);
+    let m = mac.as_bytes();
+    bytes[8] = m[0] ^ 0x02; // flip the universal/local bit
+    bytes[9] = m[1];
+    bytes[10] = m[2];
+    bytes[11] = 0xff;
+    bytes[12] = 0xfe;
+    bytes[13] = m[3];
+    bytes[14] = m[4];
+    bytes[15] = m[5];
+    Ipv6Address::from_bytes(&bytes)
+}
+
+fn expiry(now_ms: u64, lifetime_secs: u64) -> Option<u64> {
+    if lifetime_secs == INFINITE_LIFETIME_SECS {
+        None
+    } else {
+        Some(now_ms + lifetime_secs * 1000)
+    }
+}
+
+fn expired(expiry_ms: Option<u64>, now_ms: u64) -> bool {
+    match expiry_ms {
+        Some(t) => now_ms >= t,
+        None => false,
+    }
+}
+
+struct Lease {
+    address: Ipv6Address,
+    /// `None` means the lease never expires
+    expiry_ms: Option<u64>,
+}
+
+pub(crate) struct Slaac {
+    handle: SocketHandle,
+    mac: Option<EthernetAddress>,
+    link_local: Option<Ipv6Address>,
+    global: Option<Lease>,
+    router: Option<Lease>,
+}
+
+impl Slaac {
+    pub(crate) fn new(sockets: &mut SocketSet) -> Slaac {
+        let rx_buffer = RawSocketBuffer::new(
+            vec![
+                RawPacketMetadata::EMPTY,
+                RawPacketMetadata::EMPTY,
+                RawPacketMetadata::EMPTY,
+                RawPacketMetadata::EMPTY,
+            ],
+            vec![0; 1024],
+        );
+        let tx_buffer = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY], vec![0; 256]);
+        let socket = RawSocket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx_buffer, tx_buffer);
+        Slaac {
+            handle: sockets.add(socket),
+            mac: None,
+            link_local: None,
+            global: None,
+            router: None,
+        }
+    }
+
+    /// Call after the interface has been (re)built with a valid MAC address. Any previously learned
+    /// global address is dropped, as we may have moved to a different network; a router solicitation
+    /// is queued so the routers on the new link tell us about themselves. The caller is responsible
+    /// for pumping the interface afterwards.
+    pub(crate) fn link_up<DeviceT>(
+        &mut self,
+        iface: &mut Interface<'_, DeviceT>,
+        sockets: &mut SocketSet,
+        mac: EthernetAddress,
+        checksum: &ChecksumCapabilities,
+    ) where
+        DeviceT: for<'d> Device<'d>,
+    {
+        let link_local = eui64_address(&Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), &mac);
+        log::info!("IPv6 link-local address: {}", link_local);
+
+        self.mac = Some(mac);
+        self.link_local = Some(link_local);
+        self.global = None;
+        self.router = None;
+        self.apply(iface);
+        self.solicit(sockets, checksum);
+    }
+
+    /// Call when the link is torn down, after the interface has been rebuilt without addresses.
+    pub(crate) fn link_down(&mut self) {
+        self.mac = None;
+        self.link_local = None;
+        self.global = None;
+        self.router = None;
+    }
+
+    /// Address that smoltcp uses as the source of outgoing IPv6 packets, if any.
+    pub(crate) fn source_address(&self) -> Option<Ipv6Address> {
+        self.global.as_ref().map(|g| g.address).or(self.link_local)
+    }
+
+    pub(crate) fn config(&self) -> Option<Ipv6Config> {
+        let link_local = self.link_local?;
+        Some(Ipv6Config {
+            link_local: link_local.0,
+            global: self.global.as_ref().map(|g| g.address.0),
+            prefix_len: if self.global.is_some() { 64 } else { 0 },
+            router: self.router.as_ref().map(|r| r.address.0),
+        })
+    }
+
+    /// Processes any router advertisements received since the last call, and expires stale leases.
+    /// Returns `true` if the IPv6 configuration changed.
+    pub(crate) fn poll<DeviceT>(
+        &mut self,
+        iface: &mut Interface<'_, DeviceT>,
+        sockets: &mut SocketSet,
+        checksum: &ChecksumCapabilities,
+        now_ms: u64,
+    ) -> bool
+    where
+        DeviceT: for<'d> Device<'d>,
+    {
+        let mut changed = false;
+        {
+            let mut socket = sockets.get::<RawSocket>(self.handle);
+            while socket.can_recv() {
+                let packet = match socket.recv() {
+                    Ok(p) => p,
+                    Err(_) => break,
+                };
+                if self.link_local.is_some() {
+                    changed |= self.process(packet, checksum, now_ms);
+                }
+            }
+        }
+        if self.global.as_ref().map_or(false, |g| expired(g.expiry_ms, now_ms)) {
+            log::info!("IPv6 global address lease expired");
+            self.global = None;
+            changed = true;
+        }
+        if self.router.as_ref().map_or(false, |r| expired(r.expiry_ms, now_ms)) {
+            log::info!("IPv6 default router lifetime expired");
+            self.router = None;
+            changed = true;
+        }
+        if changed {
+            self.apply(iface);
+        }
+        changed
+    }
+
+    /// Handles one ICMPv6 packet from the raw socket; everything but router advertisements is ignored.
+    fn process(&mut self, packet: &[u8], checksum: &ChecksumCapabilities, now_ms: u64) -> bool {
+        let ipv6_packet = match Ipv6Packet::new_checked(packet) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        let ipv6_repr = match Ipv6Repr::parse(&ipv6_packet) {
+            Ok(r) => r,
+            Err(_) => return false,
+        };
+        // advertisements only count if they come from a router on this link
+        if ipv6_repr.hop_limit != NDISC_HOP_LIMIT || !ipv6_repr.src_addr.is_link_local() {
+            return false;
+        }
+        let icmp_packet = match Icmpv6Packet::new_checked(ipv6_packet.payload()) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        let icmp_repr = match Icmpv6Repr::parse(
+            &IpAddress::Ipv6(ipv6_repr.src_addr),
+            &IpAddress::Ipv6(ipv6_repr.dst_addr),
+            &icmp_packet,
+            checksum,
+        ) {
+            Ok(r) => r,
+            Err(_) => return false,
+        };
+        let (router_lifetime, prefix_info) = match icmp_repr {
+            Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert { router_lifetime, prefix_info, .. }) => {
+                (router_lifetime, prefix_info)
+            }
+            _ => return false,
+        };
+        log::debug!("router advertisement from {}: {:?}", ipv6_repr.src_addr, prefix_info);
+
+        let mut changed = false;
+        if router_lifetime.secs() == 0 {
+            // the router is telling us it is no longer a default router
+            if self.router.as_ref().map_or(false, |r| r.address == ipv6_repr.src_addr) {
+                self.router = None;
+                changed = true;
+            }
+        } else {
+            changed |= self.router.as_ref().map_or(true, |r| r.address != ipv6_repr.src_addr);
+            self.router = Some(Lease {
+                address: ipv6_repr.src_addr,
+                expiry_ms: Some(now_ms + router_lifetime.secs() * 1000),
+            });
+        }
+
+        if let (Some(info), Some(mac)) = (prefix_info, self.mac) {
+            if info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+                && info.prefix_len == 64
+                && !info.prefix.is_link_local()
+                && info.preferred_lifetime <= info.valid_lifetime
+            {
+                let address = eui64_address(&info.prefix, &mac);
+                if info.valid_lifetime.secs() == 0 {
+                    if self.global.as_ref().map_or(false, |g| g.address == address) {
+                        self.global = None;
+                        changed = true;
+                    }
+                } else {
+                    if self.global.as_ref().map_or(true, |g| g.address != address) {
+                        log::info!("IPv6 global address: {}/64", address);
+                        changed = true;
+                    }
+                    self.global = Some(Lease {
+                        address,
+                        expiry_ms: expiry(now_ms, info.valid_lifetime.secs()),
+                    });
+                }
+            }
+        }
+        changed
+    }
+
+    /// Writes our addresses and the default route into the interface.
+    fn apply<DeviceT>(&self, iface: &mut Interface<'_, DeviceT>)
+    where
+        DeviceT: for<'d> Device<'d>,
+    {
+        let link_local = match self.link_local {
+            Some(ll) => IpCidr::Ipv6(Ipv6Cidr::new(ll, 64)),
+            None => IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 0),
+        };
+        // with no global address, the link-local address fills both slots
+        let first = match &self.global {
+            Some(g) => IpCidr::Ipv6(Ipv6Cidr::new(g.address, 64)),
+            None => link_local,
+        };
+        iface.update_ip_addrs(|addrs| {
+            addrs[IPV6_SLOTS[0]] = first;
+            addrs[IPV6_SLOTS[1]] = link_local;
+        });
+        iface.routes_mut().remove_default_ipv6_route();
+        if let Some(router) = &self.router {
+            if let Err(e) = iface.routes_mut().add_default_ipv6_route(router.address) {
+                log::error!("couldn't add default IPv6 route: {}", e);
+            }
+        }
+    }
+
+    /// Queues a router solicitation to the all-routers group.
+    fn solicit(&self, sockets: &mut SocketSet, checksum: &ChecksumCapabilities) {
+        let src_addr = match self.link_local {
+            Some(ll) => ll,
+            None => return,
+        };
+        let dst_addr = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+        let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit { lladdr: None });
+        let ipv6_repr = Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: NDISC_HOP_LIMIT,
+        };
+        let mut buf = vec![0u8; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+        let mut ipv6_packet = Ipv6Packet::new_unchecked(&mut buf[..]);
+        ipv6_repr.emit(&mut ipv6_packet);
+        let mut icmp_packet = Icmpv6Packet::new_unchecked(ipv6_packet.payload_mut());
+        icmp_repr.emit(
+            &IpAddress::Ipv6(src_addr),
+            &IpAddress::Ipv6(dst_addr),
+            &mut icmp_packet,
+            checksum,
+        );
+        let mut socket = sockets.get::<RawSocket>(self.handle);
+        if let Err(e) = socket.send_slice(&buf) {
+            log::warn!("couldn't queue router solicitation: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::NetPhy;
+    use smoltcp::iface::{InterfaceBuilder, NeighborCache, Routes};
+    use std::collections::BTreeMap;
+
+    const MAC: EthernetAddress = EthernetAddress([0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde]);
+
+    #[test]
+    fn eui64_interface_identifier() {
+        // the universal/local bit is flipped, and ff:fe goes in the middle of the MAC
+        let prefix = Ipv6Address::new(0x2001, 0xdb8, 0, 0x42, 0, 0, 0, 0);
+        assert_eq!(
+            eui64_address(&prefix, &MAC),
+            Ipv6Address::new(0x2001, 0xdb8, 0, 0x42, 0x3656, 0x78ff, 0xfe9a, 0xbcde)
+        );
+        // only the first 64 bits of the prefix are used
+        let noisy_prefix = Ipv6Address::new(0xfe80, 0, 0, 0, 0x1111, 0x2222, 0x3333, 0x4444);
+        assert_eq!(
+            eui64_address(&noisy_prefix, &EthernetAddress([0x02, 0, 0, 0, 0, 1])),
+            Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0xff, 0xfe00, 1)
+        );
+    }
+
+    #[test]
+    fn lifetimes() {
+        assert_eq!(expiry(1_000, 60), Some(61_000));
+        assert_eq!(expiry(1_000, INFINITE_LIFETIME_SECS), None);
+        assert!(!expired(Some(61_000), 60_999));
+        assert!(expired(Some(61_000), 61_000));
+        assert!(!expired(None, u64::MAX));
+    }
+
+    #[test]
+    fn leases_expire_on_poll() {
+        let mut iface = InterfaceBuilder::new(NetPhy::loopback())
+            .ethernet_addr(MAC)
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(default_ip_addrs())
+            .routes(Routes::new(BTreeMap::new()))
+            .finalize();
+        let mut sockets = SocketSet::new(vec![]);
+        let checksum = ChecksumCapabilities::default();
+        let mut slaac = Slaac::new(&mut sockets);
+        slaac.link_up(&mut iface, &mut sockets, MAC, &checksum);
+        let link_local = slaac.source_address().unwrap();
+        let global = eui64_address(&Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), &MAC);
+        let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        // as left by an advertisement at t = 0 with a 10 minute prefix and a 30 minute router lifetime
+        slaac.global = Some(Lease { address: global, expiry_ms: expiry(0, 600) });
+        slaac.router = Some(Lease { address: router, expiry_ms: expiry(0, 1800) });
+
+        assert!(!slaac.poll(&mut iface, &mut sockets, &checksum, 599_999));
+        assert_eq!(slaac.source_address(), Some(global));
+
+        assert!(slaac.poll(&mut iface, &mut sockets, &checksum, 600_000));
+        let config = slaac.config().unwrap();
+        assert_eq!(config.global, None);
+        assert_eq!(config.prefix_len, 0);
+        assert_eq!(config.router, Some(router.0));
+        assert_eq!(slaac.source_address(), Some(link_local));
+        assert_eq!(iface.ip_addrs()[IPV6_SLOTS[0]], IpCidr::Ipv6(Ipv6Cidr::new(link_local, 64)));
+
+        assert!(slaac.poll(&mut iface, &mut sockets, &checksum, 1_800_000));
+        assert_eq!(slaac.config().unwrap().router, None);
+        // nothing left to expire
+        assert!(!slaac.poll(&mut iface, &mut sockets, &checksum, 10_000_000));
+    }
+}
@@ -111,8 +111,14 @@ pub(crate) enum Opcode {
     /// drops any connection state associated with a given key
     KeyDrop,
 
+    /// re-packs the dictionaries of a basis, reclaiming space left behind by deleted dictionaries and keys
+    CompactBasis,
+
     /// Menu opcodes
     MenuListBasis,
+    MenuCompact,
+    /// closes the basis named in a `gam::MenuMemoryPayload`
+    MenuCloseBasis,
 
     /// Suspend/resume callback
     SuspendResume,
@@ -926,6 +926,41 @@ impl BasisCache {
         }
     }
 
+    /// Runs through the dictionary listing in a basis and compacts them: dictionaries are moved down into
+    /// the slots left behind by deleted dictionaries, and dictionaries with sparse key descriptors or small
+    /// pools are re-packed. Call when the dictionary space becomes sufficiently fragmented that accesses are
+    /// becoming inefficient. If `basis_name` is None, every open basis is compacted.
+    pub(crate) fn dict_compact(&mut self, hw: &mut PddbOs, basis_name: Option<&str>) -> Result<()> {
+        let targets: Vec<usize> = if basis_name.is_some() {
+            if let Some(basis_index) = self.select_basis(basis_name) {
+                vec![basis_index]
+            } else {
+                return Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."));
+            }
+        } else {
+            (0..self.cache.len()).collect()
+        };
+        for basis_index in targets {
+            // everything has to be in cache and on disk before we start shuffling dictionaries around
+            self.cache[basis_index].populate_caches(hw);
+            self.cache[basis_index].sync(hw)?;
+            let mut repacked = HashSet::<String>::new();
+            while let Some((dict, dest)) = self.cache[basis_index].dict_compact_next(&repacked) {
+                let pages_needed = self.cache[basis_index].dicts.get(&dict)
+                    .expect("compaction candidate is not in cache").alloc_estimate_relocate();
+                if !hw.ensure_fast_space_alloc(pages_needed, &self.cache) {
+                    return Err(Error::new(ErrorKind::OutOfMemory, "No free space to relocate dict"));
+                }
+                self.cache[basis_index].dict_relocate(hw, &dict, dest)?;
+                repacked.insert(dict);
+            }
+            let basis = &mut self.cache[basis_index];
+            basis.free_dict_offset = basis.dict_lowest_free_slot();
+            log::info!("compacted basis {}: {} dicts re-packed", basis.name, repacked.len());
+        }
+        Ok(())
+    }
+
     pub(crate) fn sync(&mut self, hw: &mut PddbOs, basis_name: Option<&str>) -> Result<()> {
         if basis_name.is_some() {
             if let Some(basis_index) = self.select_basis(basis_name) {
@@ -1107,6 +1142,7 @@ impl BasisCacheEntry {
         } else { // scan the full index
             let mut try_entry = 1;
             let mut dict_count = 0;
+            let mut released = false;
             while try_entry <= DICT_MAXCOUNT && dict_count < self.num_dicts {
                 let dict_vaddr = VirtAddr::new(try_entry as u64 * DICT_VSIZE).unwrap();
                 if let Some(pp) = self.v2p_map.get(&dict_vaddr) {
@@ -1114,11 +1150,31 @@ impl BasisCacheEntry {
                     if let Some(dict) = self.dict_decrypt(hw, &pp) {
                         if dict.flags.valid() {
                             let dict_name = std::str::from_utf8(&dict.name.data[..dict.name.len as usize]).expect("dict name is not valid utf-8").to_string();
-                            let dict_present_and_valid = if let Some(d) = self.dicts.get(&dict_name) {
-                                d.flags.valid()
+                            let (dict_present_and_valid, present_index, present_age) = if let Some(d) = self.dicts.get(&dict_name) {
+                                (d.flags.valid(), d.index.get(), d.age)
                             } else {
-                                false
+                                (false, 0, 0)
                             };
+                            if dict_present_and_valid && present_index != try_entry as u32 {
+                                // two valid copies of the same dictionary: a dict_relocate() was interrupted after its commit point.
+                                // The copy with the greater age is the new one; retire the other. This doesn't count toward
+                                // num_dicts, as it's the same dictionary.
+                                if dict.age > present_age {
+                                    log::warn!("dict {} found in slots {} and {}, retiring slot {}", dict_name, present_index, try_entry, present_index);
+                                    self.dicts.remove(&dict_name);
+                                    self.dict_slot_release(hw, present_index);
+                                    let mut dcache = DictCacheEntry::new(dict, try_entry, &self.aad);
+                                    let max_large_alloc = dcache.fill(hw, &self.v2p_map, &self.cipher);
+                                    self.dicts.insert(dict_name.to_string(), dcache);
+                                    self.large_pool_update(max_large_alloc.get());
+                                } else {
+                                    log::warn!("dict {} found in slots {} and {}, retiring slot {}", dict_name, present_index, try_entry, try_entry);
+                                    self.dict_slot_release(hw, try_entry as u32);
+                                }
+                                released = true;
+                                try_entry += 1;
+                                continue;
+                            }
                             if !dict_present_and_valid {
                                 let mut dcache = DictCacheEntry::new(dict, try_entry, &self.aad);
                                 let max_large_alloc = dcache.fill(hw, &self.v2p_map, &self.cipher);
@@ -1131,6 +1187,12 @@ impl BasisCacheEntry {
                             }
                             dict_count += 1;
                         } else {
+                            // dict_delete() erases the whole slot, so a readable but invalid header can only be a copy left
+                            // behind by a dict_relocate() that was interrupted before its commit point. Clean it out, so its
+                            // stale key descriptors don't get picked up if the slot is re-used.
+                            log::warn!("releasing uncommitted dict copy in slot {}", try_entry);
+                            self.dict_slot_release(hw, try_entry as u32);
+                            released = true;
                             // this is an empty dictionary entry. we could stick a dictionary in here later on, take note if we haven't already computed that
                             if self.free_dict_offset.is_none() { self.free_dict_offset = Some(try_entry as u32); }
                         }
@@ -1145,6 +1207,50 @@ impl BasisCacheEntry {
             if try_entry <= DICT_MAXCOUNT {
                 if self.free_dict_offset.is_none() { self.free_dict_offset = Some(try_entry as u32); }
             }
+            // The scan stops once all the dictionaries are accounted for, but an interrupted dict_relocate() can leave
+            // a copy in a slot above that. Sweep any remaining mapped header pages for such copies.
+            let mut leftover_slots: Vec<u32> = self.v2p_map.iter()
+                .filter(|(&vaddr, pp)| pp.valid()
+                    && vaddr.get() % DICT_VSIZE == 0
+                    && vaddr.get() / DICT_VSIZE >= try_entry as u64
+                    && vaddr.get() / DICT_VSIZE <= DICT_MAXCOUNT as u64)
+                .map(|(&vaddr, _)| (vaddr.get() / DICT_VSIZE) as u32)
+                .collect();
+            leftover_slots.sort();
+            for slot in leftover_slots {
+                let pp = *self.v2p_map.get(&VirtAddr::new(slot as u64 * DICT_VSIZE).unwrap()).expect("slot went missing");
+                if let Some(dict) = self.dict_decrypt(hw, &pp) {
+                    let dict_name = std::str::from_utf8(&dict.name.data[..dict.name.len as usize]).unwrap_or("").to_string();
+                    let present = self.dicts.get(&dict_name).filter(|d| d.flags.valid()).map(|d| (d.index.get(), d.age));
+                    if !dict.flags.valid() {
+                        log::warn!("releasing uncommitted dict copy in slot {}", slot);
+                        self.dict_slot_release(hw, slot);
+                    } else if let Some((present_index, present_age)) = present {
+                        if dict.age > present_age {
+                            log::warn!("dict {} found in slots {} and {}, retiring slot {}", dict_name, present_index, slot, present_index);
+                            self.dicts.remove(&dict_name);
+                            self.dict_slot_release(hw, present_index);
+                            let mut dcache = DictCacheEntry::new(dict, slot as usize, &self.aad);
+                            let max_large_alloc = dcache.fill(hw, &self.v2p_map, &self.cipher);
+                            self.dicts.insert(dict_name.to_string(), dcache);
+                            self.large_pool_update(max_large_alloc.get());
+                            if self.free_dict_offset.map_or(true, |f| present_index < f) {
+                                self.free_dict_offset = Some(present_index);
+                            }
+                        } else {
+                            log::warn!("dict {} found in slots {} and {}, retiring slot {}", dict_name, present_index, slot, slot);
+                            self.dict_slot_release(hw, slot);
+                        }
+                    } else {
+                        log::warn!("dict {} in slot {} is not accounted for in the basis root, ignoring", dict_name, slot);
+                        continue;
+                    }
+                    released = true;
+                }
+            }
+            if released {
+                self.pt_sync(hw);
+            }
         }
     }
 
@@ -1477,12 +1583,173 @@ impl BasisCacheEntry {
         }
     }
 
-    /// Runs through the dictionary listing in a basis and compacts them. Call when the
-    /// the dictionary space becomes sufficiently fragmented that accesses are becoming
-    /// inefficient.
-    #[allow(dead_code)]
-    pub(crate) fn dict_compact(&self, _basis_name: Option<&str>) -> Result<()> {
-        unimplemented!();
+    /// Returns the lowest dictionary slot that is not occupied by a valid dictionary. Only accurate if
+    /// all the dictionaries are in the cache, i.e. after a `populate_caches()` call.
+    pub(crate) fn dict_lowest_free_slot(&self) -> Option<u32> {
+        let used: HashSet<u32> = self.dicts.values()
+            .filter(|d| d.flags.valid())
+            .map(|d| d.index.get())
+            .collect();
+        (1..=DICT_MAXCOUNT as u32).find(|index| !used.contains(index))
+    }
+
+    /// Picks the next dictionary to relocate during a compaction, along with its destination slot.
+    ///
+    /// Holes in the dictionary space are filled first, by moving the highest-indexed dictionary into the lowest
+    /// free slot. Once there are no more holes, any sparse dictionaries that have not yet been re-packed are moved
+    /// to the lowest free slot; each such move leaves a hole that the next sparse dictionary drops into, so the
+    /// compaction ends with at most one free slot below the top of the dictionary space, which the next
+    /// `dict_add()` will claim.
+    pub(crate) fn dict_compact_next(&self, repacked: &HashSet<String>) -> Option<(String, u32)> {
+        let free = self.dict_lowest_free_slot()?;
+        let highest = self.dicts.iter()
+            .filter(|(_, d)| d.flags.valid())
+            .max_by_key(|(_, d)| d.index.get());
+        if let Some((name, dict)) = highest {
+            if dict.index.get() > free && !repacked.contains(name) {
+                return Some((name.to_string(), free));
+            }
+        }
+        self.dicts.iter()
+            .filter(|(name, d)| d.flags.valid() && !repacked.contains(*name) && d.is_sparse())
+            .min_by_key(|(_, d)| d.index.get())
+            .map(|(name, _)| (name.to_string(), free))
+    }
+
+    /// Moves the dictionary `name` into the free dictionary slot `dest_index`, re-packing its key descriptors
+    /// and small pool data along the way. Large pool data stays where it is, because large pool addresses
+    /// don't depend on the dictionary's slot.
+    ///
+    /// The move is ordered so that a power loss at any point leaves a mountable basis with no data lost:
+    ///   1. The re-packed copy is written into `dest_index` with its header marked as invalid, and the page tables are synced.
+    ///      A crash here leaves garbage in a free slot, which `populate_caches()` cleans up on the next mount.
+    ///   2. The header of the copy is re-written as valid. This is a single page write, and is the commit point.
+    ///      A crash after this leaves two valid copies; the copy with the greater `age` is kept on the next mount.
+    ///   3. The old slot is erased and de-allocated, header page last, and the page tables are synced.
+    ///
+    /// The caller must ensure there is enough FastSpace to hold the copy; see `DictCacheEntry::alloc_estimate_relocate()`.
+    pub(crate) fn dict_relocate(&mut self, hw: &mut PddbOs, name: &str, dest_index: u32) -> Result<()> {
+        if !self.ensure_dict_in_cache(hw, name) {
+            return Err(Error::new(ErrorKind::NotFound, "Dictionary not found"));
+        }
+        // flush any pending changes, so the disk copy of the dictionary matches the cache before it is retired
+        {
+            let dcache = self.dicts.get_mut(name).expect("entry was ensured, but somehow missing");
+            dcache.fill(hw, &self.v2p_map, &self.cipher);
+            if !dcache.sync_small_pool(hw, &mut self.v2p_map, &self.cipher) {
+                return Err(Error::new(ErrorKind::OutOfMemory, "Ran out of memory syncing small pool"));
+            }
+        }
+        self.dict_sync(hw, name)?;
+        let old_dict = self.dicts.remove(name).expect("entry was ensured, but somehow missing");
+        log::info!("relocating dict {} from slot {} to slot {}", name, old_dict.index, dest_index);
+
+        // build the re-packed copy. It starts out invalid, and only becomes valid once it is entirely on disk.
+        let mut new_flags = old_dict.flags;
+        new_flags.set_valid(false);
+        let record = Dictionary {
+            flags: new_flags,
+            // the copy is always "newer" than the original, so a crash between the commit and the retirement of the old slot can be resolved
+            age: old_dict.age.saturating_add(1),
+            num_keys: 0,
+            free_key_index: 1,
+            name: DictName::try_from_str(name).or(Err(Error::new(ErrorKind::InvalidInput, "dictionary name invalid: invalid utf-8 or length")))?,
+        };
+        let mut new_dict = DictCacheEntry::new(record, dest_index as usize, &self.aad);
+        let mut key_list: Vec<(&String, &KeyCacheEntry)> = old_dict.keys.iter()
+            .filter(|(_, k)| k.flags.valid())
+            .collect();
+        key_list.sort_by_key(|(_, k)| k.descriptor_index);
+        let mut insert_result = Ok(());
+        for (key_name, kcache) in key_list {
+            let small_data = if small_storage_index_from_key(kcache, old_dict.index).is_some() {
+                if let Some(KeyCacheData::Small(small)) = &kcache.data {
+                    Some(&small.data[..kcache.len as usize])
+                } else {
+                    // don't touch the disk if we can't account for all the data
+                    log::error!("Key {}:{} has no data in cache, aborting relocation", name, key_name);
+                    insert_result = Err(Error::new(ErrorKind::InvalidData, "small key data is unreadable"));
+                    break;
+                }
+            } else {
+                None
+            };
+            if let Err(e) = new_dict.key_insert_relocated(key_name, kcache, small_data) {
+                insert_result = Err(e);
+                break;
+            }
+        }
+        if let Err(e) = insert_result {
+            self.dicts.insert(name.to_string(), old_dict);
+            return Err(e);
+        }
+
+        // 1. write out the invalid copy. Clear out anything left in the destination by a previously interrupted relocation.
+        self.dict_slot_release(hw, dest_index);
+        self.pt_sync(hw);
+        self.dicts.insert(name.to_string(), new_dict);
+        if let Err(e) = self.dict_relocate_write(hw, name) {
+            log::error!("Couldn't write relocated copy of dict {}: {:?}", name, e);
+            self.dict_slot_release(hw, dest_index);
+            self.pt_sync(hw);
+            self.dicts.insert(name.to_string(), old_dict);
+            return Err(e);
+        }
+        // 2. commit
+        {
+            let dcache = self.dicts.get_mut(name).expect("relocated entry went missing");
+            dcache.flags.set_valid(true);
+            dcache.clean = false;
+        }
+        self.dict_sync(hw, name)?;
+        // 3. retire the old copy
+        self.dict_slot_release(hw, old_dict.index.get());
+        self.pt_sync(hw);
+        Ok(())
+    }
+
+    /// Writes out the small pool and descriptors of a dictionary, along with the page table entries that map them.
+    fn dict_relocate_write(&mut self, hw: &mut PddbOs, name: &str) -> Result<()> {
+        let dcache = self.dicts.get_mut(name).expect("relocated entry went missing");
+        if !dcache.sync_small_pool(hw, &mut self.v2p_map, &self.cipher) {
+            return Err(Error::new(ErrorKind::OutOfMemory, "Ran out of memory syncing small pool"));
+        }
+        self.dict_sync(hw, name)?;
+        self.pt_sync(hw);
+        Ok(())
+    }
+
+    /// Erases and de-allocates every page mapped into the key descriptor region and the small pool region of
+    /// the dictionary slot `index`. Pages in the large pool are not touched, as they could be referenced by a
+    /// relocated copy of the dictionary. The header page is erased last, so that an interrupted release leaves
+    /// either a recognizable (and releasable) dictionary, or nothing at all. Call `pt_sync()` afterwards to
+    /// commit the de-allocations.
+    pub(crate) fn dict_slot_release(&mut self, hw: &mut PddbOs, index: u32) {
+        let dict_base = index as u64 * DICT_VSIZE;
+        let pool_base = small_storage_base_vaddr_from_indices(NonZeroU32::new(index).expect("dictionary slots are 1-offset"), 0);
+        let mut release_list = Vec::<VirtAddr>::new();
+        for (&vaddr, pp) in self.v2p_map.iter() {
+            let va = vaddr.get();
+            if pp.valid() &&
+            ((va > dict_base && va < dict_base + DICT_VSIZE) || (va >= pool_base && va < pool_base + SMALL_POOL_STRIDE)) {
+                release_list.push(vaddr);
+            }
+        }
+        // the header page goes last
+        if let Some(pp) = self.v2p_map.get(&VirtAddr::new(dict_base).unwrap()) {
+            if pp.valid() {
+                release_list.push(VirtAddr::new(dict_base).unwrap());
+            }
+        }
+        for vaddr in release_list {
+            let pp = self.v2p_map.get_mut(&vaddr).expect("page went missing during release");
+            log::debug!("releasing dict slot {} page 0x{:x}/0x{:x}", index, vaddr, pp.page_number() as usize * PAGE_SIZE);
+            let mut random = [0u8; PAGE_SIZE];
+            hw.trng_slice(&mut random);
+            hw.patch_data(&random, pp.page_number() * PAGE_SIZE as u32);
+            hw.fast_space_free(pp);
+            assert!(pp.valid() == false, "pp is still marked as valid!");
+        }
     }
 
     /// Syncs *only* the basis header to disk.
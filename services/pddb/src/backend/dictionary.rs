@@ -553,12 +553,6 @@ impl DictCacheEntry {
             // try to fit the key in the small pool first
             if ((data.len() + offset) < SMALL_CAPACITY) && (alloc_hint.unwrap_or(0) < SMALL_CAPACITY) {
                 log::debug!("creating small key {}", name);
-                // handle the case that we're a brand new dictionary and no small keys have ever been stored before.
-                if self.small_pool.len() == 0 {
-                    self.small_pool.push(KeySmallPool::new());
-                    self.rebuild_free_pool();
-                }
-                let pool_candidate = self.small_pool_free.pop().expect("Free pool was allocated & rebuilt, but still empty.");
                 let mut reservation = if alloc_hint.unwrap_or(0) > data.len() + offset {
                     alloc_hint.unwrap_or(0)
                 } else {
@@ -567,28 +561,7 @@ impl DictCacheEntry {
                 if reservation == 0 { // this case happens if someone tries to just create an empty key entry without giving an alloc hint
                     reservation = 1; // at least make a reservation for 1 byte of data
                 }
-                let index = if pool_candidate.avail as usize >= reservation {
-                    // it fits in the current candidate slot, use this as the index
-                    let ksp = &mut self.small_pool[pool_candidate.index];
-                    ksp.contents.push(name.to_string());
-                    ksp.avail -= reservation as u16;
-                    ksp.clean = false;
-                    self.small_pool_free.push(KeySmallPoolOrd { avail: ksp.avail, index: pool_candidate.index });
-                    pool_candidate.index
-                } else {
-                    self.small_pool_free.push(pool_candidate);
-                    // allocate a new small pool slot
-                    let mut ksp = KeySmallPool::new();
-                    ksp.contents.push(name.to_string());
-                    ksp.avail -= reservation as u16;
-                    ksp.clean = false;
-                    // update the free pool with the current candidate
-                    // we don't subtract 1 from len because we're about to push the ksp onto the end of the small_pool, consuming it
-                    self.small_pool_free.push(KeySmallPoolOrd { avail: ksp.avail, index: self.small_pool.len() });
-                    self.small_pool.push(ksp);
-                    // the actual location is at len-1 now because we have done the push
-                    self.small_pool.len() - 1
-                };
+                let index = self.small_pool_reserve(name, reservation);
                 let mut kf = KeyFlags(0);
                 kf.set_valid(true);
                 kf.set_unresolved(true);
@@ -666,6 +639,101 @@ impl DictCacheEntry {
         }
         Ok(large_alloc_ptr)
     }
+    /// Finds a slot in the small pool for `reservation` bytes of data belonging to the key `name`, allocating
+    /// a new small pool entry if none of the existing ones have enough space. Returns the index of the small
+    /// pool entry that holds the reservation.
+    fn small_pool_reserve(&mut self, name: &str, reservation: usize) -> usize {
+        // handle the case that we're a brand new dictionary and no small keys have ever been stored before.
+        if self.small_pool.len() == 0 {
+            self.small_pool.push(KeySmallPool::new());
+            self.rebuild_free_pool();
+        }
+        let pool_candidate = self.small_pool_free.pop().expect("Free pool was allocated & rebuilt, but still empty.");
+        if pool_candidate.avail as usize >= reservation {
+            // it fits in the current candidate slot, use this as the index
+            let ksp = &mut self.small_pool[pool_candidate.index];
+            ksp.contents.push(name.to_string());
+            ksp.avail -= reservation as u16;
+            ksp.clean = false;
+            self.small_pool_free.push(KeySmallPoolOrd { avail: ksp.avail, index: pool_candidate.index });
+            pool_candidate.index
+        } else {
+            self.small_pool_free.push(pool_candidate);
+            // allocate a new small pool slot
+            let mut ksp = KeySmallPool::new();
+            ksp.contents.push(name.to_string());
+            ksp.avail -= reservation as u16;
+            ksp.clean = false;
+            // update the free pool with the current candidate
+            // we don't subtract 1 from len because we're about to push the ksp onto the end of the small_pool, consuming it
+            self.small_pool_free.push(KeySmallPoolOrd { avail: ksp.avail, index: self.small_pool.len() });
+            self.small_pool.push(ksp);
+            // the actual location is at len-1 now because we have done the push
+            self.small_pool.len() - 1
+        }
+    }
+    /// Inserts a key carried over from another copy of this dictionary, as part of a compaction. The key gets the
+    /// next free descriptor index, so inserting keys in order of their old index packs the descriptors densely.
+    /// Small keys (`small_data` is `Some`) are re-packed into this dictionary's small pool; large keys keep their
+    /// existing allocation, because the large pool is shared by the whole basis and does not depend on the
+    /// dictionary's index. The new records are dirty; call `sync_small_pool()` and then `dict_sync()` to commit them.
+    pub(crate) fn key_insert_relocated(&mut self, name: &str, src: &KeyCacheEntry, small_data: Option<&[u8]>) -> Result<()> {
+        let descriptor_index = if let Some(di) = self.get_free_key_index() {
+            di
+        } else {
+            return Err(Error::new(ErrorKind::OutOfMemory, "Ran out of key indices in dictionary"));
+        };
+        let mut flags = src.flags;
+        let (start, reserved, data) = if let Some(small_data) = small_data {
+            // same floor as key_update(): a reservation always covers the data, and is at least one byte
+            let reservation = (src.reserved as usize).max(small_data.len()).max(1);
+            assert!(reservation < SMALL_CAPACITY, "small key has a reservation that is too large for the small pool");
+            let pool_index = self.small_pool_reserve(name, reservation);
+            // the final location within the pool is resolved when the pool is synced
+            flags.set_unresolved(true);
+            (small_storage_base_vaddr_from_indices(self.index, pool_index),
+                reservation as u64,
+                Some(KeyCacheData::Small(KeySmallData {
+                    clean: false,
+                    data: small_data.to_vec(),
+                }))
+            )
+        } else {
+            (src.start, src.reserved, None)
+        };
+        let kcache = KeyCacheEntry {
+            start,
+            len: src.len,
+            reserved,
+            flags,
+            age: src.age,
+            descriptor_index,
+            clean: false,
+            data,
+        };
+        self.keys.insert(name.to_string(), kcache);
+        self.key_count += 1;
+        Ok(())
+    }
+    /// Reports if the key descriptors or the small pool of this dictionary occupy noticeably more pages than
+    /// a freshly packed copy would. Only meaningful after a `fill()`.
+    pub(crate) fn is_sparse(&self) -> bool {
+        // index 0 is the dictionary header, so a packed set of keys occupies indices 1..=key_count
+        let packed_index_pages = self.key_count as usize / DK_PER_VPAGE + 1;
+        let index_pages = (self.last_disk_key_index.saturating_sub(1)) as usize / DK_PER_VPAGE + 1;
+        let mut small_bytes = 0;
+        for ksp in self.small_pool.iter() {
+            small_bytes += SMALL_CAPACITY - ksp.avail as usize;
+        }
+        let packed_small_pages = (small_bytes + SMALL_CAPACITY - 1) / SMALL_CAPACITY;
+        // allow one page of slop on the small pool, as the packing in small_pool_reserve() is greedy and not optimal
+        index_pages > packed_index_pages || self.small_pool.len() > packed_small_pages + 1
+    }
+    /// estimates the number of pages needed to write out a re-packed copy of this dictionary. Pass this to
+    /// ensure_fast_space_alloc() before relocating the dictionary.
+    pub(crate) fn alloc_estimate_relocate(&self) -> usize {
+        (self.key_count as usize / DK_PER_VPAGE + 1) + self.small_pool.len() + 1
+    }
     #[allow(dead_code)]
     pub fn key_contains(&mut self, name: &str) -> bool {
         self.keys.contains_key(&String::from(name))
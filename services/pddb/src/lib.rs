@@ -447,6 +447,43 @@ impl Pddb {
             _ => Err(Error::new(ErrorKind::Other, "Internal error"))
         }
     }
+    /// re-packs the dictionaries in a basis, reclaiming space left over from deleted dictionaries and keys.
+    /// If `basis_name` is None, all the open bases are compacted.
+    pub fn compact_basis(&mut self, basis_name: Option<&str>) -> Result<()> {
+        let bname = if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+            xous_ipc::String::<BASIS_NAME_LEN>::from_str(bname)
+        } else {
+            xous_ipc::String::<BASIS_NAME_LEN>::new()
+        };
+
+        let request = PddbKeyRequest {
+            basis_specified: basis_name.is_some(),
+            basis: bname,
+            dict: xous_ipc::String::<DICT_NAME_LEN>::new(),
+            key: xous_ipc::String::<KEY_NAME_LEN>::new(),
+            create_dict: false,
+            create_key: false,
+            token: None,
+            result: PddbRequestCode::Uninit,
+            cb_sid: self.cb_sid.to_array(),
+            alloc_hint: None,
+        };
+        let mut buf = Buffer::into_buf(request)
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::CompactBasis.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+
+        let response = buf.to_original::<PddbKeyRequest, _>().unwrap();
+        match response.result {
+            PddbRequestCode::NoErr => Ok(()),
+            PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Basis was not found")),
+            PddbRequestCode::NoFreeSpace => Err(Error::new(ErrorKind::OutOfMemory, "Ran out of space while compacting")),
+            _ => Err(Error::new(ErrorKind::Other, "Internal error"))
+        }
+    }
 
     pub fn sync(&mut self) -> Result<()> {
         let response = send_message(
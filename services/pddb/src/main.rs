@@ -491,12 +491,7 @@ fn xmain() -> ! {
     });
     // our menu handler
     let my_cid = xous::connect(pddb_sid).unwrap();
-    let _ = thread::spawn({
-        let my_cid = my_cid.clone();
-        move || {
-            pddb_menu(my_cid);
-        }
-    });
+    let mut menu = PddbMenu::new(my_cid);
     // spawn a delayed mount command, shortly after boot. There's too much going on at boot, and it blocks other things from coming up.
     let _ = thread::spawn({
         let my_cid = my_cid.clone();
@@ -636,6 +631,7 @@ fn xmain() -> ! {
                                     mgmt.policy.unwrap_or(BasisRetentionPolicy::Persist)
                                 ) {
                                     basis_cache.basis_add(basis);
+                                    menu.basis_opened(mgmt.name.as_str().unwrap());
                                     finished = true;
                                     mgmt.code = PddbRequestCode::NoErr;
                                 }
@@ -671,7 +667,10 @@ fn xmain() -> ! {
                 match mgmt.code {
                     PddbRequestCode::Close => {
                         match basis_cache.basis_unmount(&mut pddb_os, mgmt.name.as_str().expect("name is not valid utf-8")) {
-                            Ok(_) => mgmt.code = PddbRequestCode::NoErr,
+                            Ok(_) => {
+                                menu.basis_closed(mgmt.name.as_str().unwrap());
+                                mgmt.code = PddbRequestCode::NoErr;
+                            }
                             Err(e) => match e.kind() {
                                 ErrorKind::NotFound => mgmt.code = PddbRequestCode::NotFound,
                                 _ => mgmt.code = PddbRequestCode::InternalError,
@@ -690,7 +689,10 @@ fn xmain() -> ! {
                 match mgmt.code {
                     PddbRequestCode::Delete => {
                         match basis_cache.basis_delete(&mut pddb_os, mgmt.name.as_str().expect("name is not valid utf-8")) {
-                            Ok(_) => mgmt.code = PddbRequestCode::NoErr,
+                            Ok(_) => {
+                                menu.basis_closed(mgmt.name.as_str().unwrap());
+                                mgmt.code = PddbRequestCode::NoErr;
+                            }
                             Err(e) => match e.kind() {
                                 ErrorKind::NotFound => mgmt.code = PddbRequestCode::NotFound,
                                 _ => mgmt.code = PddbRequestCode::InternalError,
@@ -887,6 +889,28 @@ fn xmain() -> ! {
                 }
                 buffer.replace(req).unwrap();
             }
+            Some(Opcode::CompactBasis) => {
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req: PddbKeyRequest = buffer.to_original::<PddbKeyRequest, _>().unwrap();
+                let bname = if req.basis_specified {
+                    Some(req.basis.as_str().unwrap())
+                } else {
+                    None
+                };
+                log::info!("compacting basis {:?}", bname);
+                match basis_cache.dict_compact(&mut pddb_os, bname) {
+                    Ok(_) => req.result = PddbRequestCode::NoErr,
+                    Err(e) => {
+                        log::error!("compaction failed: {:?}", e);
+                        match e.kind() {
+                            std::io::ErrorKind::NotFound => req.result = PddbRequestCode::NotFound,
+                            std::io::ErrorKind::OutOfMemory => req.result = PddbRequestCode::NoFreeSpace,
+                            _ => req.result = PddbRequestCode::InternalError,
+                        }
+                    }
+                }
+                buffer.replace(req).unwrap();
+            }
             Some(Opcode::KeyAttributes) => {
                 let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req = buffer.to_original::<PddbKeyAttrIpc, _>().unwrap();
@@ -1104,6 +1128,32 @@ fn xmain() -> ! {
                 }
                 modals.show_notification(&note).expect("couldn't show basis list");
             },
+            Some(Opcode::MenuCompact) => {
+                let note = match basis_cache.dict_compact(&mut pddb_os, None) {
+                    Ok(_) => String::from(t!("pddb.menu.compact_done", xous::LANG)),
+                    Err(e) => {
+                        log::error!("compaction failed: {:?}", e);
+                        String::from(t!("pddb.menu.compact_fail", xous::LANG))
+                    }
+                };
+                modals.show_notification(&note).expect("couldn't show compaction result");
+            },
+            Some(Opcode::MenuCloseBasis) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let payload = buffer.to_original::<gam::MenuMemoryPayload, _>().unwrap();
+                let name = payload.as_str().expect("basis name is not valid utf-8");
+                let note = match basis_cache.basis_unmount(&mut pddb_os, name) {
+                    Ok(_) => {
+                        menu.basis_closed(name);
+                        format!("{} {}", t!("pddb.menu.closebasis_done", xous::LANG), name)
+                    }
+                    Err(e) => {
+                        log::error!("couldn't close basis {}: {:?}", name, e);
+                        format!("{} {}", t!("pddb.menu.closebasis_fail", xous::LANG), name)
+                    }
+                };
+                modals.show_notification(&note).expect("couldn't show basis close result");
+            },
             #[cfg(not(any(target_os = "none", target_os = "xous")))]
             Some(Opcode::DangerousDebug) => {
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -3,8 +3,9 @@ use num_traits::*;
 use locales::t;
 use crate::api::*;
 use xous_ipc::String;
+use std::thread::{self, JoinHandle};
 
-pub(crate) fn pddb_menu(conn: xous::CID) {
+pub(crate) fn pddb_menu(conn: xous::CID, menu_mgr: xous::SID) -> MenuMatic {
     let mut menu_items = Vec::<MenuItem>::new();
 
     menu_items.push(
@@ -16,6 +17,15 @@ pub(crate) fn pddb_menu(conn: xous::CID) {
             close_on_select: true,
         }
     );
+    menu_items.push(
+        MenuItem {
+            name: String::from_str(t!("pddb.menu.compact", xous::LANG)),
+            action_conn: Some(conn),
+            action_opcode: Opcode::MenuCompact.to_u32().unwrap(),
+            action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        }
+    );
     menu_items.push(MenuItem {
         name: String::from_str(t!("mainmenu.closemenu", xous::LANG)),
         action_conn: None,
@@ -24,5 +34,53 @@ pub(crate) fn pddb_menu(conn: xous::CID) {
         close_on_select: true,
     });
 
-    menu_matic(menu_items, PDDB_MENU_NAME, None);
+    menu_matic(menu_items, PDDB_MENU_NAME, Some(menu_mgr)).expect("couldn't create MenuMatic manager")
+}
+
+/// The PDDB menu, plus an item to close each secret basis that is open. The close items all share one
+/// opcode, and carry the name of their basis as a memory payload.
+pub(crate) struct PddbMenu {
+    conn: xous::CID,
+    // the menu is built on its own thread, as it has to wait for the GAM; its manager is collected on first use
+    builder: Option<JoinHandle<MenuMatic>>,
+    menu: Option<MenuMatic>,
+}
+impl PddbMenu {
+    pub(crate) fn new(conn: xous::CID) -> PddbMenu {
+        let menu_mgr = xous::create_server().expect("couldn't create a server for the PDDB menu manager");
+        PddbMenu {
+            conn,
+            builder: Some(thread::spawn(move || pddb_menu(conn, menu_mgr))),
+            menu: None,
+        }
+    }
+    fn menu(&mut self) -> &MenuMatic {
+        if self.menu.is_none() {
+            let builder = self.builder.take().unwrap();
+            self.menu = Some(builder.join().expect("couldn't build the PDDB menu"));
+        }
+        self.menu.as_ref().unwrap()
+    }
+    fn close_item_name(basis: &str) -> String::<64> {
+        String::from_str(&format!("{} {}", t!("pddb.menu.closebasis", xous::LANG), basis))
+    }
+    /// Adds an item to close `basis`
+    pub(crate) fn basis_opened(&mut self, basis: &str) {
+        let item = MenuItem {
+            name: PddbMenu::close_item_name(basis),
+            action_conn: Some(self.conn),
+            action_opcode: Opcode::MenuCloseBasis.to_u32().unwrap(),
+            action_payload: MenuPayload::from_text(basis).expect("basis name doesn't fit in a menu payload"),
+            close_on_select: true,
+        };
+        // a basis that is opened twice keeps a single item
+        let menu = self.menu();
+        menu.delete_item(item.name.as_str().unwrap());
+        menu.add_item(item);
+    }
+    /// Removes the item to close `basis`, if there is one
+    pub(crate) fn basis_closed(&mut self, basis: &str) {
+        let name = PddbMenu::close_item_name(basis);
+        self.menu().delete_item(name.as_str().unwrap());
+    }
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use rand_chacha::rand_core::RngCore;
 use rand_chacha::rand_core::SeedableRng;
 use crate::*;
 use core::sync::atomic::{AtomicU64, Ordering};
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::io::Result;
 
 const UPPER_BOUND: usize = 9000;
@@ -342,6 +342,35 @@ pub(crate) fn delete_pattern(hw: &mut PddbOs, basis_cache: &mut BasisCache,
     Ok(())
 }
 
+/// Reads back every key in a basis, indexed by `dict:key`.
+fn snapshot_basis(hw: &mut PddbOs, basis_cache: &mut BasisCache, basis_name: Option<&str>) -> HashMap<String, Vec<u8>> {
+    let mut snapshot = HashMap::<String, Vec<u8>>::new();
+    for dict in basis_cache.dict_list(hw, basis_name).iter() {
+        for key in basis_cache.key_list(hw, dict, basis_name).unwrap().iter() {
+            let len = basis_cache.key_attributes(hw, dict, key, basis_name).unwrap().len;
+            let mut data = vec![0u8; len];
+            let readlen = basis_cache.key_read(hw, dict, key, &mut data, Some(0), basis_name).unwrap();
+            assert!(readlen == len, "key {}:{} read back a different length than its attributes", dict, key);
+            snapshot.insert(format!("{}:{}", dict, key), data);
+        }
+    }
+    snapshot
+}
+
+/// Compacts a basis, and confirms that every key reads back the same as before.
+pub(crate) fn compact_test(hw: &mut PddbOs, basis_cache: &mut BasisCache, basis_name: Option<&str>) -> Result<()> {
+    let before = snapshot_basis(hw, basis_cache, basis_name);
+    basis_cache.dict_compact(hw, basis_name)?;
+    let after = snapshot_basis(hw, basis_cache, basis_name);
+    assert!(before.len() == after.len(), "compaction changed the number of keys: {} -> {}", before.len(), after.len());
+    for (name, data) in before.iter() {
+        let compacted = after.get(name).expect("key went missing during compaction");
+        assert!(data == compacted, "key {} changed during compaction", name);
+    }
+    log::info!("compaction preserved {} keys", after.len());
+    Ok(())
+}
+
 pub(crate) fn list_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
     #[cfg(feature = "deterministic")]
     let mut dict_list = BTreeSet::<String>::new();
@@ -388,6 +417,8 @@ pub(crate) fn list_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
         note: for faster stress-testing, we dialed the FSCB_PAGES to 4 and the FASTSPACE_PAGES to 1.
     - [done] basis search: create basis A, populate with general integrity. create basis B, add test entries.
         hide basis B, confirm original A; mount basis B, confirm B overlay.
+    - [done] compaction: after the delete/add torture tests, compact the basis and confirm all keys read back identically,
+        then remount to confirm the compacted layout persists.
 */
 
 #[allow(dead_code)]
@@ -466,6 +497,11 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
         log::info!("Saving `dachecke4` to local host");
         pddb_os.dbg_dump(Some("dachecke4".to_string()), None);
 
+        log::info!("Doing compaction test");
+        compact_test(pddb_os, &mut basis_cache, None)?;
+        log::info!("Saving `compacte` to local host");
+        pddb_os.dbg_dump(Some("compacte".to_string()), None);
+
         let mut pre_list = HashSet::<String>::new();
         for dict in basis_cache.dict_list(pddb_os, None).iter() {
             for key in basis_cache.key_list(pddb_os, dict, None).unwrap().iter() {
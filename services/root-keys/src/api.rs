@@ -39,6 +39,14 @@ pub(crate) enum Opcode {
     UxSignXousPasswordReturn,
     UxSignXousRun,
 
+    /// change the boot or update password; the argument is the `PasswordType` to change
+    UxTryUpdatePassword,
+    UxChangeOldPasswordReturn,
+    UxChangeNewPasswordReturn,
+    UxChangeConfirmPasswordReturn,
+    UxChangeUpdatePasswordReturn,
+    UxChangePasswordRun,
+
     /// Ux AES calls
     UxAesEnsurePassword,
     UxAesPasswordPolicy,
@@ -81,7 +89,7 @@ pub enum PasswordRetentionPolicy {
 /// I don't think it hurts; more importantly, it also prevents an off-the-shelf "hashcat" run from
 /// being used to brute force both passwords in a single go, as the salt has to be (slightly)
 /// recomputed for each type of password.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive, Copy, Clone, PartialEq, Eq)]
 pub enum PasswordType {
     Boot = 1,
     Update = 2,
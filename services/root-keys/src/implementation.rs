@@ -14,6 +14,7 @@ use locales::t;
 
 use crate::bcrypt::*;
 use crate::api::PasswordType;
+use crate::rewrap::*;
 
 use core::convert::TryInto;
 use ed25519_dalek::{Keypair, Signature, PublicKey, Signer, ExpandedSecretKey, SecretKey};
@@ -68,6 +69,9 @@ struct PasswordCache {
     hashed_update_pw_valid: u32,
     fpga_key: [u8; 32],
     fpga_key_valid: u32,
+    /// holds the replacement password while a password change is being confirmed
+    hashed_new_pw: [u8; 32],
+    hashed_new_pw_valid: u32,
 }
 
 #[repr(C)]
@@ -162,6 +166,7 @@ pub(crate) struct RootKeys {
     boot_password_policy: PasswordRetentionPolicy,
     update_password_policy: PasswordRetentionPolicy,
     cur_password_type: Option<PasswordType>, // for tracking which password we're dealing with at the UX layer
+    password_change: Option<PasswordType>, // the password being rotated by a password change flow, if any
     susres: susres::Susres, // for disabling suspend/resume
     trng: trng::Trng,
     gfx: graphics_server::Gfx, // for reading out font planes for signing verification
@@ -256,6 +261,7 @@ impl<'a> RootKeys {
             update_password_policy: PasswordRetentionPolicy::AlwaysPurge,
             boot_password_policy: PasswordRetentionPolicy::AlwaysKeep,
             cur_password_type: None,
+            password_change: None,
             susres: susres::Susres::new_without_hook(&xns).expect("couldn't connect to susres without hook"),
             trng,
             gfx: graphics_server::Gfx::new(&xns).expect("couldn't connect to gfx"),
@@ -550,6 +556,16 @@ impl<'a> RootKeys {
         }
         core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
     }
+    fn purge_new_password(&mut self) {
+        unsafe {
+            let pcache_ptr: *mut PasswordCache = self.pass_cache.as_mut_ptr() as *mut PasswordCache;
+            for p in (*pcache_ptr).hashed_new_pw.iter_mut() {
+                *p = 0;
+            }
+            (*pcache_ptr).hashed_new_pw_valid = 0;
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
     pub fn purge_sensitive_data(&mut self) {
         for d in self.sensitive_data.borrow_mut().as_slice_mut::<u32>().iter_mut() {
             *d = 0;
@@ -647,6 +663,30 @@ impl<'a> RootKeys {
             log::error!("got an unexpected password from the UX");
             return;
         };
+        let digest = self.hash_password(pw, pw_type);
+
+        let pcache_ptr: *mut PasswordCache = self.pass_cache.as_mut_ptr() as *mut PasswordCache;
+        unsafe {
+            match pw_type {
+                PasswordType::Boot => {
+                    for (&src, dst) in digest.iter().zip((*pcache_ptr).hashed_boot_pw.iter_mut()) {
+                        *dst = src;
+                    }
+                    (*pcache_ptr).hashed_boot_pw_valid = 1;
+                }
+                PasswordType::Update => {
+                    for (&src, dst) in digest.iter().zip((*pcache_ptr).hashed_update_pw.iter_mut()) {
+                        *dst = src;
+                    }
+                    (*pcache_ptr).hashed_update_pw_valid = 1;
+                }
+            }
+        }
+    }
+
+    /// Computes the hashed form of a password of type `pw_type` and returns it. The caller is responsible
+    /// for storing the result in the password cache, or otherwise erasing it.
+    fn hash_password(&mut self, pw: &str, pw_type: PasswordType) -> [u8; 32] {
         let mut hashed_password: [u8; 24] = [0; 24];
         let mut salt = self.get_salt();
         // we change the salt ever-so-slightly for every password. This doesn't make any one password more secure;
@@ -667,24 +707,13 @@ impl<'a> RootKeys {
         let mut hasher = Sha512Trunc256::new_with_strategy(FallbackStrategy::SoftwareOnly);
         hasher.update(hashed_password);
         let digest = hasher.finalize();
-
-        let pcache_ptr: *mut PasswordCache = self.pass_cache.as_mut_ptr() as *mut PasswordCache;
-        unsafe {
-            match pw_type {
-                PasswordType::Boot => {
-                    for (&src, dst) in digest.iter().zip((*pcache_ptr).hashed_boot_pw.iter_mut()) {
-                        *dst = src;
-                    }
-                    (*pcache_ptr).hashed_boot_pw_valid = 1;
-                }
-                PasswordType::Update => {
-                    for (&src, dst) in digest.iter().zip((*pcache_ptr).hashed_update_pw.iter_mut()) {
-                        *dst = src;
-                    }
-                    (*pcache_ptr).hashed_update_pw_valid = 1;
-                }
-            }
+        for b in hashed_password.iter_mut() {
+            *b = 0;
         }
+
+        let mut ret: [u8; 32] = [0; 32];
+        ret.copy_from_slice(&digest);
+        ret
     }
 
     /// Reads a 256-bit key at a given index offset
@@ -2225,4 +2254,289 @@ impl<'a> RootKeys {
         // re-allow suspend/resume ops
         self.susres.set_suspendable(true).expect("couldn't re-allow suspend/resume");
     }
+
+    /// Derives the current signing keypair from the encrypted root key in the KEYROM, using `hashed_update_pw`.
+    /// Returns `None` if the resulting public key does not match the one stored in the KEYROM, which means
+    /// the update password was wrong. This is the only way we have of checking an update password, since
+    /// unlike the boot password it isn't checked by a downstream consumer such as the PDDB.
+    fn derive_signing_keypair(&mut self, hashed_update_pw: &[u8; 32]) -> Option<Keypair> {
+        let mut derived_sk = self.read_key_256(KeyRomLocs::SELFSIGN_PRIVKEY);
+        for (key, &pw) in derived_sk.iter_mut().zip(hashed_update_pw.iter()) {
+            *key = *key ^ pw;
+        }
+        self.compute_key_rollback(&mut derived_sk);
+        let maybe_sk = SecretKey::from_bytes(&derived_sk);
+        for b in derived_sk.iter_mut() {
+            *b = 0;
+        }
+        let sk = maybe_sk.ok()?;
+        let pk: PublicKey = (&sk).into();
+        if hashed_pw_eq(&pk.to_bytes(), &self.read_key_256(KeyRomLocs::SELFSIGN_PUBKEY)) {
+            // keypair zeroizes on drop
+            Some(Keypair{public: pk, secret: sk})
+        } else {
+            None
+        }
+    }
+
+    /// Called by the UX layer to set up a password change. Like `setup_key_init`, it disables suspend/resume
+    /// until `finish_password_change` is called. Returns `false` if the device can't have its passwords
+    /// changed: the keys must be initialized, and for the boot password, the device must already be unlocked.
+    pub fn setup_password_change(&mut self, which: PasswordType) -> bool {
+        if !self.is_initialized() {
+            return false;
+        }
+        if which == PasswordType::Boot && !self.is_pcache_boot_password_valid() {
+            return false;
+        }
+        self.xous_init_interlock();
+        self.susres.set_suspendable(false).expect("couldn't block suspend/resume");
+        self.purge_new_password();
+        self.password_change = Some(which);
+        true
+    }
+
+    /// Checks the current password entered by the user against the password being changed. A correct update
+    /// password is retained in the cache, as it's needed to re-sign the gateware at the end of the change.
+    pub fn check_old_password(&mut self, pw: &str) -> bool {
+        let which = if let Some(which) = self.password_change {
+            which
+        } else {
+            log::error!("got an unexpected password from the UX");
+            return false;
+        };
+        let mut hashed = self.hash_password(pw, which);
+        let pcache: &mut PasswordCache = unsafe{&mut *(self.pass_cache.as_mut_ptr() as *mut PasswordCache)};
+        let ok = match which {
+            PasswordType::Boot => {
+                pcache.hashed_boot_pw_valid != 0 && hashed_pw_eq(&hashed, &pcache.hashed_boot_pw)
+            }
+            PasswordType::Update => {
+                if self.derive_signing_keypair(&hashed).is_some() {
+                    pcache.hashed_update_pw.copy_from_slice(&hashed);
+                    pcache.hashed_update_pw_valid = 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        for b in hashed.iter_mut() {
+            *b = 0;
+        }
+        ok
+    }
+
+    /// Hashes and stages the new password, pending confirmation.
+    pub fn stage_new_password(&mut self, pw: &str) {
+        let which = if let Some(which) = self.password_change {
+            which
+        } else {
+            log::error!("got an unexpected password from the UX");
+            return;
+        };
+        let mut hashed = self.hash_password(pw, which);
+        let pcache: &mut PasswordCache = unsafe{&mut *(self.pass_cache.as_mut_ptr() as *mut PasswordCache)};
+        pcache.hashed_new_pw.copy_from_slice(&hashed);
+        pcache.hashed_new_pw_valid = 1;
+        for b in hashed.iter_mut() {
+            *b = 0;
+        }
+    }
+
+    /// Checks that the re-typed new password matches the staged one. On a mismatch, the staged password is discarded.
+    pub fn confirm_new_password(&mut self, pw: &str) -> bool {
+        let which = if let Some(which) = self.password_change {
+            which
+        } else {
+            log::error!("got an unexpected password from the UX");
+            return false;
+        };
+        let mut hashed = self.hash_password(pw, which);
+        let pcache: &PasswordCache = unsafe{& *(self.pass_cache.as_ptr() as *const PasswordCache)};
+        let ok = pcache.hashed_new_pw_valid != 0 && hashed_pw_eq(&hashed, &pcache.hashed_new_pw);
+        for b in hashed.iter_mut() {
+            *b = 0;
+        }
+        if !ok {
+            self.purge_new_password();
+        }
+        ok
+    }
+
+    /// Re-wraps the KEYROM secrets guarded by the password being changed, and commits them to the gateware.
+    ///
+    /// Only the wrapping changes: the root user key, the signing key and the FPGA key are all
+    /// unchanged, so PDDB data, wrapped keys and existing signatures stay valid. Because the KEYROM
+    /// contents come from the gateware, the change takes effect on the next reboot; all passwords are
+    /// purged on the way out so that nothing is decrypted with a stale wrapping in the meantime.
+    ///
+    /// Requires the update password to be in the cache, as the patched gateware has to be re-signed.
+    pub fn do_password_change(&mut self, rootkeys_modal: &mut Modal, main_cid: xous::CID) -> Result<(), RootkeyResult> {
+        self.xous_init_interlock();
+        let which = if let Some(which) = self.password_change {
+            which
+        } else {
+            log::error!("password change run without a password change set up");
+            return Err(RootkeyResult::KeyError);
+        };
+
+        let mut progress_action = Slider::new(main_cid, Opcode::UxGutter.to_u32().unwrap(),
+        0, 100, 10, Some("%"), 0, true, true
+        );
+        progress_action.set_is_password(true);
+        rootkeys_modal.modify(
+            Some(ActionType::Slider(progress_action)),
+            Some(t!("rootkeys.pwchange.starting", xous::LANG)), false,
+            None, true, None);
+        rootkeys_modal.activate();
+        xous::yield_slice(); // give some time to the GAM to render
+        let mut pb = ProgressBar::new(rootkeys_modal, &mut progress_action);
+        pb.set_percentage(1);
+
+        // any step of the change can bail out, and every one of those exits has to restore the staging area protection
+        with_staging_protected(self, |keys| keys.rewrap_and_commit(which, &mut pb))?;
+
+        // always purge, we're going to reboot; the cached passwords no longer match the KEYROM after the reboot
+        self.purge_password(PasswordType::Boot);
+        self.purge_password(PasswordType::Update);
+        self.purge_new_password();
+        self.purge_sensitive_data();
+
+        pb.set_percentage(96);
+        let ret = if !self.verify_gateware_self_signature() {
+            Err(RootkeyResult::IntegrityError)
+        } else {
+            Ok(())
+        };
+        pb.set_percentage(100);
+        self.ticktimer.sleep_ms(500).expect("couldn't show final message");
+        ret
+    }
+
+    /// The body of `do_password_change`: re-wraps the secrets guarded by `which`, patches them into the
+    /// gateware, and re-signs it. Must be run with the staging area protected.
+    fn rewrap_and_commit(&mut self, which: PasswordType, pb: &mut ProgressBar) -> Result<(), RootkeyResult> {
+        let pcache: &mut PasswordCache = unsafe{&mut *(self.pass_cache.as_mut_ptr() as *mut PasswordCache)};
+        if pcache.hashed_update_pw_valid == 0 || pcache.hashed_new_pw_valid == 0
+        || (which == PasswordType::Boot && pcache.hashed_boot_pw_valid == 0) {
+            log::error!("passwords were not set going into the password change routine");
+            return Err(RootkeyResult::KeyError);
+        }
+        // the update password may have been cached by an earlier flow without being checked; confirm it before we touch anything
+        let keypair = match self.derive_signing_keypair(&pcache.hashed_update_pw) {
+            Some(kp) => kp,
+            None => {
+                log::error!("update password did not match the signing key");
+                return Err(RootkeyResult::KeyError);
+            }
+        };
+
+        // recover the FPGA key, so we can decrypt and re-encrypt the bitstream
+        for (dst, (&enc, &pw)) in pcache.fpga_key.iter_mut()
+        .zip(self.read_key_256(KeyRomLocs::FPGA_KEY).iter().zip(pcache.hashed_update_pw.iter())) {
+            *dst = enc ^ pw;
+        }
+        pcache.fpga_key_valid = 1;
+
+        // stage the keyrom data, and re-wrap the secrets guarded by the password being changed
+        pb.set_percentage(3);
+        self.populate_sensitive_data();
+        match which {
+            PasswordType::Boot => {
+                rewrap_key_words(
+                    &mut self.sensitive_data.borrow_mut().as_slice_mut::<u32>()[KeyRomLocs::USER_KEY as usize..KeyRomLocs::USER_KEY as usize + 256/(size_of::<u32>()*8)],
+                    &pcache.hashed_boot_pw, &pcache.hashed_new_pw
+                );
+            }
+            PasswordType::Update => {
+                rewrap_key_words(
+                    &mut self.sensitive_data.borrow_mut().as_slice_mut::<u32>()[KeyRomLocs::SELFSIGN_PRIVKEY as usize..KeyRomLocs::SELFSIGN_PRIVKEY as usize + 256/(size_of::<u32>()*8)],
+                    &pcache.hashed_update_pw, &pcache.hashed_new_pw
+                );
+                rewrap_key_words(
+                    &mut self.sensitive_data.borrow_mut().as_slice_mut::<u32>()[KeyRomLocs::FPGA_KEY as usize..KeyRomLocs::FPGA_KEY as usize + 256/(size_of::<u32>()*8)],
+                    &pcache.hashed_update_pw, &pcache.hashed_new_pw
+                );
+            }
+        }
+
+        // the running gateware is the source of the patch, so back it up to the staging area first
+        pb.update_text(t!("rootkeys.init.backup_gateware", xous::LANG));
+        pb.rebase_subtask_percentage(5, 30);
+        self.make_gateware_backup(Some(&mut *pb), false)?;
+
+        log::debug!("making destination oracle");
+        let mut dst_oracle = match BitstreamOracle::new(&pcache.fpga_key, &pcache.fpga_key, self.gateware(), self.gateware_base()) {
+            Ok(o) => o,
+            Err(e) => {
+                log::error!("couldn't create oracle (most likely FPGA key mismatch): {:?}", e);
+                self.purge_sensitive_data();
+                return Err(e);
+            }
+        };
+        log::debug!("making source oracle");
+        let mut src_oracle = match BitstreamOracle::new(&pcache.fpga_key, &pcache.fpga_key, self.staging(), self.staging_base()) {
+            Ok(o) => o,
+            Err(e) => {
+                log::error!("couldn't create oracle (most likely FPGA key mismatch): {:?}", e);
+                dst_oracle.clear();
+                self.purge_sensitive_data();
+                return Err(e);
+            }
+        };
+
+        let ret = self.patch_and_sign(&src_oracle, &dst_oracle, &keypair, pb);
+        // clean up the oracles, whether or not the patch went through
+        pb.set_percentage(95);
+        src_oracle.clear();
+        dst_oracle.clear();
+        // ed25519 keypair zeroizes on drop
+        ret
+    }
+
+    fn patch_and_sign(&self, src_oracle: &BitstreamOracle, dst_oracle: &BitstreamOracle, keypair: &Keypair,
+        pb: &mut ProgressBar) -> Result<(), RootkeyResult> {
+        pb.update_text(t!("rootkeys.init.patching_keys", xous::LANG));
+        pb.rebase_subtask_percentage(30, 60);
+        self.gateware_copy_and_patch(src_oracle, dst_oracle, Some(&mut *pb))?;
+
+        // make a copy of the plaintext metadata and csr records
+        self.spinor.patch(self.gateware(), self.gateware_base(),
+        &self.staging()[METADATA_OFFSET..SELFSIG_OFFSET], METADATA_OFFSET as u32
+        ).map_err(|_| RootkeyResult::FlashError)?;
+
+        pb.update_text(t!("rootkeys.init.verifying_gateware", xous::LANG));
+        pb.rebase_subtask_percentage(60, 90);
+        self.verify_gateware(dst_oracle, Some(&mut *pb))?;
+
+        // the signing key itself didn't change, only its wrapping, so the patched gateware is signed with the same key
+        pb.update_text(t!("rootkeys.init.commit_signatures", xous::LANG));
+        pb.set_percentage(92);
+        let (gateware_sig, gateware_len) = self.sign_gateware(keypair);
+        log::debug!("gateware signature ({}): {:x?}", gateware_len, gateware_sig.to_bytes());
+        self.commit_signature(gateware_sig, gateware_len, SignatureType::Gateware)
+    }
+
+    /// Called by the UX layer at the end of a password change, whether or not it succeeded. Discards the
+    /// staged password and the keyrom copy, and re-allows suspend/resume.
+    pub fn finish_password_change(&mut self) {
+        if self.update_password_policy == PasswordRetentionPolicy::AlwaysPurge {
+            self.purge_password(PasswordType::Update);
+        }
+        self.purge_new_password();
+        self.purge_sensitive_data();
+        self.password_change = None;
+        self.susres.set_suspendable(true).expect("couldn't re-allow suspend/resume");
+    }
+}
+
+impl StagingProtect for RootKeys {
+    fn set_staging_protect(&mut self, protect: bool) {
+        if protect {
+            self.spinor.set_staging_write_protect(true).expect("couldn't protect the staging area");
+        } else {
+            self.spinor.set_staging_write_protect(false).expect("couldn't un-protect the staging area");
+        }
+    }
 }
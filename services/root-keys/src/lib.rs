@@ -51,10 +51,20 @@ impl RootKeys {
     pub fn get_try_selfsign_op(&self) -> u32 {
         Opcode::UxSelfSignXous.to_u32().unwrap()
     }
+    /// for use in menus: the first scalar argument of the payload is the `PasswordType` to change
+    pub fn get_try_update_password_op(&self) -> u32 {
+        Opcode::UxTryUpdatePassword.to_u32().unwrap()
+    }
 
     /// this initiates an attempt to update passwords. User must unlock their device first, and can cancel out if not expected.
-    pub fn try_update_password(&mut self, _which: PasswordType) -> Result<(), xous::Error> {
-        unimplemented!();
+    /// The user is prompted for the old password, then the new one (twice), and then the keys guarded by the password are
+    /// re-wrapped and written into the gateware. The keys themselves don't change, so anything encrypted or signed with them
+    /// remains valid. The device reboots once the change is committed.
+    pub fn try_update_password(&mut self, which: PasswordType) -> Result<(), xous::Error> {
+        send_message(self.conn,
+            Message::new_scalar(Opcode::UxTryUpdatePassword.to_usize().unwrap(),
+            which.to_usize().unwrap(), 0, 0, 0)
+        ).map(|_| ())
     }
 
     /// checks to see if the KEYROM has been initialized, and if not, generates keys. In the process of doing so, the user will be
@@ -373,6 +383,8 @@ impl BlockDecrypt for RootKeys {
 
 #[cfg(test)]
 mod bcrypt;
+#[cfg(test)]
+mod rewrap;
 
 // some short tests to just confirm we're not totally broken.
 #[cfg(test)]
@@ -412,4 +424,130 @@ mod tests {
         crate::bcrypt::bcrypt(10,  &salt, pw, &mut output);
         assert_eq!(output, [46, 39, 41, 217, 39, 103, 62, 189, 120, 3, 248, 84, 175, 40, 134, 190, 76, 43, 232, 147, 129, 237, 116, 61]);
     }
+
+    // stand-in for the password hashing done by the keys enclave: bcrypt, stretched out to 256 bits so it can wrap a key
+    fn hashed_pw(pw: &str) -> [u8; 32] {
+        let salt: [u8; 16] = [
+            38, 113, 212, 141, 108, 213, 195, 166, 201, 38, 20, 13, 47, 40, 104, 18,
+        ];
+        let mut output: [u8; 24] = [0; 24];
+        crate::bcrypt::bcrypt(4, &salt, pw, &mut output);
+        let mut hashed = [0u8; 32];
+        for (i, dst) in hashed.iter_mut().enumerate() {
+            *dst = output[i % 24] ^ (i as u8);
+        }
+        hashed
+    }
+    fn unwrap_key_words(words: &[u32], hashed_pw: &[u8; 32]) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        for (dst, (word, pw)) in key.chunks_mut(4).zip(words.iter().zip(hashed_pw.chunks(4))) {
+            for (d, (&w, &p)) in dst.iter_mut().zip(word.to_be_bytes().iter().zip(pw.iter())) {
+                *d = w ^ p;
+            }
+        }
+        key
+    }
+
+    #[test]
+    fn password_change_preserves_user_key() {
+        use aes::{Aes256, NewBlockCipher, BlockDecrypt, BlockEncrypt};
+        use cipher::generic_array::GenericArray;
+
+        let root_key: [u32; 8] = [
+            0x0123_4567, 0x89ab_cdef, 0xdead_beef, 0xc0de_600d, 0x1234_5678, 0x5555_3333, 0x8000_0000, 0x0f0f_f0f0,
+        ];
+        let old_pw = hashed_pw("old PIN");
+        let new_pw = hashed_pw("new PIN");
+        assert!(!crate::rewrap::hashed_pw_eq(&old_pw, &new_pw));
+
+        // provision the KEYROM image: wrapping a key the first time is a change from an all-zeroes password
+        let mut keyrom = root_key;
+        crate::rewrap::rewrap_key_words(&mut keyrom, &[0u8; 32], &old_pw);
+
+        // encrypt some data under the old password
+        let plaintext: [u8; 16] = *b"PDDB basis key!!";
+        let mut block = GenericArray::clone_from_slice(&plaintext);
+        let key = unwrap_key_words(&keyrom, &old_pw);
+        Aes256::new(GenericArray::from_slice(&key)).encrypt_block(&mut block);
+        assert_ne!(block.as_slice(), &plaintext);
+
+        // rotate the password, and check the old data still decrypts with the new one
+        crate::rewrap::rewrap_key_words(&mut keyrom, &old_pw, &new_pw);
+        assert_ne!(keyrom, root_key);
+        let key = unwrap_key_words(&keyrom, &new_pw);
+        let mut decrypted = block.clone();
+        Aes256::new(GenericArray::from_slice(&key)).decrypt_block(&mut decrypted);
+        assert_eq!(decrypted.as_slice(), &plaintext);
+
+        // the old password no longer unlocks the key
+        let stale_key = unwrap_key_words(&keyrom, &old_pw);
+        let mut garbage = block.clone();
+        Aes256::new(GenericArray::from_slice(&stale_key)).decrypt_block(&mut garbage);
+        assert_ne!(garbage.as_slice(), &plaintext);
+    }
+
+    #[test]
+    fn password_change_round_trip() {
+        let root_key: [u32; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+        let pw_a = hashed_pw("correct horse");
+        let pw_b = hashed_pw("battery staple");
+        let mut keyrom = root_key;
+        crate::rewrap::rewrap_key_words(&mut keyrom, &[0u8; 32], &pw_a);
+        let wrapped_a = keyrom;
+        crate::rewrap::rewrap_key_words(&mut keyrom, &pw_a, &pw_b);
+        crate::rewrap::rewrap_key_words(&mut keyrom, &pw_b, &pw_a);
+        assert_eq!(keyrom, wrapped_a);
+        assert_eq!(unwrap_key_words(&keyrom, &pw_a), unwrap_key_words(&root_key, &[0u8; 32]));
+    }
+
+    // records the staging protect state, and fails the flow at a chosen step the way the gateware routines do
+    struct MockStaging {
+        protected: bool,
+        transitions: Vec<bool>,
+        fail_at: Option<usize>,
+        steps_run: usize,
+    }
+    impl crate::rewrap::StagingProtect for MockStaging {
+        fn set_staging_protect(&mut self, protect: bool) {
+            self.protected = protect;
+            self.transitions.push(protect);
+        }
+    }
+    impl MockStaging {
+        fn step(&mut self, n: usize) -> Result<(), &'static str> {
+            assert!(self.protected, "step {} ran with the staging area unprotected", n);
+            self.steps_run += 1;
+            if self.fail_at == Some(n) { Err("flash error") } else { Ok(()) }
+        }
+        // backup, patch, verify and commit, in the order the password change runs them
+        fn password_change(&mut self) -> Result<(), &'static str> {
+            self.step(0)?;
+            self.step(1)?;
+            self.step(2)?;
+            self.step(3)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn password_change_error_restores_staging_protect() {
+        for fail_at in [None, Some(0), Some(1), Some(2), Some(3)] {
+            let mut staging = MockStaging { protected: false, transitions: Vec::new(), fail_at, steps_run: 0 };
+            let ret = crate::rewrap::with_staging_protected(&mut staging, |s| s.password_change());
+            assert_eq!(ret.is_err(), fail_at.is_some());
+            assert_eq!(staging.steps_run, fail_at.map_or(4, |n| n + 1));
+            assert!(!staging.protected, "staging area left protected after failing at {:?}", fail_at);
+            assert_eq!(staging.transitions, [true, false]);
+        }
+    }
+
+    #[test]
+    fn hashed_password_compare() {
+        let pw = hashed_pw("My S3cre7 P@55w0rd!");
+        let mut other = pw;
+        assert!(crate::rewrap::hashed_pw_eq(&pw, &other));
+        other[31] ^= 1;
+        assert!(!crate::rewrap::hashed_pw_eq(&pw, &other));
+        assert!(!crate::rewrap::hashed_pw_eq(&pw, &hashed_pw("My S3cre7 P@55w0rd?")));
+    }
 }
\ No newline at end of file
@@ -31,6 +31,8 @@ const CONSOLE_SENTINEL: &'static str = "CONS_SENTINEL|";
 
 #[cfg(any(target_os = "none", target_os = "xous"))]
 mod bcrypt;
+#[cfg(any(target_os = "none", target_os = "xous"))]
+mod rewrap;
 
 pub enum SignatureResult {
     SelfSignOk,
@@ -178,6 +180,22 @@ mod implementation {
 
         pub fn get_ux_password_type(&self) -> Option<PasswordType> {self.password_type}
         pub fn finish_key_init(&mut self) {}
+        pub fn setup_password_change(&mut self, _which: PasswordType) -> bool {true}
+        pub fn check_old_password(&mut self, pw: &str) -> bool {
+            log::info!("got old password plaintext: {}", pw);
+            true
+        }
+        pub fn stage_new_password(&mut self, pw: &str) {
+            log::info!("got new password plaintext: {}", pw);
+        }
+        pub fn confirm_new_password(&mut self, pw: &str) -> bool {
+            log::info!("got confirmation password plaintext: {}", pw);
+            true
+        }
+        pub fn do_password_change(&mut self, rootkeys_modal: &mut Modal, main_cid: xous::CID) -> Result<(), RootkeyResult> {
+            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.pwchange.starting", xous::LANG))
+        }
+        pub fn finish_password_change(&mut self) {}
         pub fn verify_gateware_self_signature(&mut self) -> bool {
             true
         }
@@ -394,6 +412,8 @@ fn xmain() -> ! {
 
     let mut reboot_initiated = false;
     let mut aes_sender: Option<xous::MessageSender> = None;
+    // tracks which password is being changed, while a password change flow is in progress
+    let mut pw_change_type: Option<PasswordType> = None;
     loop {
         let mut msg = xous::receive_message(keys_sid).unwrap();
         log::debug!("message: {:?}", msg);
@@ -852,6 +872,207 @@ fn xmain() -> ! {
                     }
                 }
             }
+            Some(Opcode::UxTryUpdatePassword) => msg_scalar_unpack!(msg, pw_type_code, _, _, _, {
+                // overall flow:
+                //  - check that the device is in a state where the password can be changed
+                //  - confirm the user wants to proceed
+                //  - prompt for the current password, and check it
+                //  - prompt for the new password, twice
+                //  - prompt for the update password, if it is needed and not already cached
+                //  - re-wrap the keys and commit them to the gateware
+                //  - reboot
+                let which: PasswordType = match FromPrimitive::from_usize(pw_type_code) {
+                    Some(pt) => pt,
+                    None => {
+                        log::error!("got an invalid password type for a password change: {}", pw_type_code);
+                        continue;
+                    }
+                };
+                if pw_change_type.is_some() {
+                    log::error!("password change already in progress, ignoring request");
+                    continue;
+                }
+                if !keys.is_initialized() {
+                    modals.show_notification(t!("rootkeys.pwchange.not_init", xous::LANG)).expect("modals error");
+                    continue;
+                }
+                if which == PasswordType::Boot && !keys.is_pcache_boot_password_valid() {
+                    // the PDDB is responsible for checking the boot password; we rely on it having done so
+                    modals.show_notification(t!("rootkeys.pwchange.unlock_first", xous::LANG)).expect("modals error");
+                    continue;
+                }
+                modals.add_list_item(t!("rootkeys.gwup.yes", xous::LANG)).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.no", xous::LANG)).expect("modals error");
+                let prompt = match which {
+                    PasswordType::Boot => t!("rootkeys.pwchange.confirm_boot", xous::LANG),
+                    PasswordType::Update => t!("rootkeys.pwchange.confirm_update", xous::LANG),
+                };
+                match modals.get_radiobutton(prompt) {
+                    Ok(response) => {
+                        if response == t!("rootkeys.gwup.no", xous::LANG) {
+                            continue;
+                        } else if response != t!("rootkeys.gwup.yes", xous::LANG) {
+                            log::error!("got unexpected response from radio box: {:?}", response);
+                            continue;
+                        } else {
+                            // proceed forward
+                        }
+                    }
+                    _ => {
+                        log::error!("modals error, aborting");
+                        continue;
+                    }
+                }
+                if !keys.setup_password_change(which) {
+                    modals.show_notification(t!("rootkeys.pwchange.unlock_first", xous::LANG)).expect("modals error");
+                    continue;
+                }
+                pw_change_type = Some(which);
+                let prompt = match which {
+                    PasswordType::Boot => t!("rootkeys.pwchange.old_boot", xous::LANG),
+                    PasswordType::Update => t!("rootkeys.pwchange.old_update", xous::LANG),
+                };
+                password_action.set_action_opcode(Opcode::UxChangeOldPasswordReturn.to_u32().unwrap());
+                rootkeys_modal.modify(
+                    Some(ActionType::TextEntry(password_action)),
+                    Some(prompt), false,
+                    None, true, None
+                );
+                #[cfg(feature="tts")]
+                tts.tts_blocking(prompt).unwrap();
+                rootkeys_modal.activate();
+            }),
+            Some(Opcode::UxChangeOldPasswordReturn) => {
+                let mut buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let mut plaintext_pw = buf.to_original::<gam::modal::TextEntryPayload, _>().unwrap();
+
+                let old_ok = keys.check_old_password(plaintext_pw.as_str());
+                plaintext_pw.volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
+                buf.volatile_clear();
+
+                let which = if let Some(which) = pw_change_type {
+                    which
+                } else {
+                    log::warn!("UxChangeOldPasswordReturn without a password change in progress. Ignoring.");
+                    continue;
+                };
+                if !old_ok {
+                    keys.finish_password_change();
+                    pw_change_type = None;
+                    modals.show_notification(t!("rootkeys.pwchange.bad_password", xous::LANG)).expect("modals error");
+                    continue;
+                }
+                let prompt = match which {
+                    PasswordType::Boot => t!("rootkeys.pwchange.new_boot", xous::LANG),
+                    PasswordType::Update => t!("rootkeys.pwchange.new_update", xous::LANG),
+                };
+                password_action.set_action_opcode(Opcode::UxChangeNewPasswordReturn.to_u32().unwrap());
+                rootkeys_modal.modify(
+                    Some(ActionType::TextEntry(password_action)),
+                    Some(prompt), false,
+                    None, true, None
+                );
+                #[cfg(feature="tts")]
+                tts.tts_blocking(prompt).unwrap();
+                rootkeys_modal.activate();
+            }
+            Some(Opcode::UxChangeNewPasswordReturn) => {
+                let mut buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let mut plaintext_pw = buf.to_original::<gam::modal::TextEntryPayload, _>().unwrap();
+
+                keys.stage_new_password(plaintext_pw.as_str());
+                plaintext_pw.volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
+                buf.volatile_clear();
+
+                password_action.set_action_opcode(Opcode::UxChangeConfirmPasswordReturn.to_u32().unwrap());
+                rootkeys_modal.modify(
+                    Some(ActionType::TextEntry(password_action)),
+                    Some(t!("rootkeys.pwchange.confirm_new", xous::LANG)), false,
+                    None, true, None
+                );
+                #[cfg(feature="tts")]
+                tts.tts_blocking(t!("rootkeys.pwchange.confirm_new", xous::LANG)).unwrap();
+                rootkeys_modal.activate();
+            }
+            Some(Opcode::UxChangeConfirmPasswordReturn) => {
+                let mut buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let mut plaintext_pw = buf.to_original::<gam::modal::TextEntryPayload, _>().unwrap();
+
+                let confirmed = keys.confirm_new_password(plaintext_pw.as_str());
+                plaintext_pw.volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
+                buf.volatile_clear();
+
+                if !confirmed {
+                    keys.finish_password_change();
+                    pw_change_type = None;
+                    modals.show_notification(t!("rootkeys.pwchange.mismatch", xous::LANG)).expect("modals error");
+                    continue;
+                }
+                // the gateware has to be re-signed once the keys are patched in, which requires the update password.
+                // if we're changing the update password, it was cached when the old password was checked.
+                if keys.is_pcache_update_password_valid() {
+                    send_message(main_cid,
+                        xous::Message::new_scalar(Opcode::UxChangePasswordRun.to_usize().unwrap(), 0, 0, 0, 0)
+                    ).expect("couldn't initiate password change");
+                } else {
+                    keys.set_ux_password_type(Some(PasswordType::Update));
+                    password_action.set_action_opcode(Opcode::UxChangeUpdatePasswordReturn.to_u32().unwrap());
+                    rootkeys_modal.modify(
+                        Some(ActionType::TextEntry(password_action)),
+                        Some(t!("rootkeys.get_update_password", xous::LANG)), false,
+                        None, true, None
+                    );
+                    #[cfg(feature="tts")]
+                    tts.tts_blocking(t!("rootkeys.get_update_password", xous::LANG)).unwrap();
+                    rootkeys_modal.activate();
+                }
+            }
+            Some(Opcode::UxChangeUpdatePasswordReturn) => {
+                let mut buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let mut plaintext_pw = buf.to_original::<gam::modal::TextEntryPayload, _>().unwrap();
+
+                keys.hash_and_save_password(plaintext_pw.as_str());
+                plaintext_pw.volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
+                buf.volatile_clear();
+                keys.set_ux_password_type(None);
+
+                send_message(main_cid,
+                    xous::Message::new_scalar(Opcode::UxChangePasswordRun.to_usize().unwrap(), 0, 0, 0, 0)
+                ).expect("couldn't initiate password change");
+            }
+            Some(Opcode::UxChangePasswordRun) => {
+                let result = keys.do_password_change(&mut rootkeys_modal, main_cid);
+                // the stop emoji, when sent to the slider action bar in progress mode, will cause it to close and relinquish focus
+                rootkeys_modal.key_event(['🛑', '\u{0000}', '\u{0000}', '\u{0000}']);
+
+                log::info!("password change result: {:?}", result);
+                // clear the staged password, re-enable suspend/resume
+                keys.finish_password_change();
+                pw_change_type = None;
+
+                match result {
+                    Ok(_) => {
+                        log::info!("going to into reboot arc");
+                        send_message(main_cid,
+                            xous::Message::new_scalar(Opcode::UxTryReboot.to_usize().unwrap(), 0, 0, 0, 0)
+                        ).expect("couldn't initiate dialog box");
+                    }
+                    Err(RootkeyResult::AlignmentError) => {
+                        modals.show_notification(t!("rootkeys.init.fail_alignment", xous::LANG)).expect("modals error");
+                    }
+                    Err(RootkeyResult::KeyError) => {
+                        // probably a bad update password, purge it, so the user can try again
+                        keys.purge_password(PasswordType::Update);
+                        modals.show_notification(t!("rootkeys.init.fail_key", xous::LANG)).expect("modals error");
+                    }
+                    Err(RootkeyResult::IntegrityError) => {
+                        modals.show_notification(t!("rootkeys.init.fail_verify", xous::LANG)).expect("modals error");
+                    }
+                    Err(RootkeyResult::FlashError) => {
+                        modals.show_notification(t!("rootkeys.init.fail_burn", xous::LANG)).expect("modals error");
+                    }
+                }
+            }
             Some(Opcode::UxAesEnsurePassword) => msg_blocking_scalar_unpack!(msg, key_index, _, _, _, {
                 if key_index as u8 == AesRootkeyType::User0.to_u8().unwrap() {
                     if keys.is_pcache_boot_password_valid() {
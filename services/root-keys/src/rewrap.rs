@@ -0,0 +1,45 @@
+//! Password rotation helpers.
+//!
+//! Secrets in the KEYROM are stored XOR'd against the hashed password that guards them. Rotating a
+//! password thus never touches the secret itself: XOR'ing in the old hash strips the old wrapping, and
+//! XOR'ing in the new hash applies the new one. Because the underlying key is unchanged, anything that
+//! was encrypted or signed with it before the change remains valid afterwards.
+//!
+//! These routines have no hardware dependencies, so they can be exercised by the hosted unit tests.
+
+use core::convert::TryInto;
+
+/// Re-wraps a key stored as big-endian KEYROM words so that it is guarded by `new_pw` instead of `old_pw`.
+///
+/// The old and new hashes are combined into a single mask *before* touching the key words, so the
+/// plaintext key is never present in memory during the operation.
+pub fn rewrap_key_words(words: &mut [u32], old_pw: &[u8; 32], new_pw: &[u8; 32]) {
+    for (word, (old, new)) in words.iter_mut().zip(old_pw.chunks(4).zip(new_pw.chunks(4))) {
+        let mask = u32::from_be_bytes(old.try_into().unwrap()) ^ u32::from_be_bytes(new.try_into().unwrap());
+        *word ^= mask;
+    }
+}
+
+/// Compares two hashed passwords without an early exit, so the time taken leaks nothing about
+/// how many leading bytes matched.
+pub fn hashed_pw_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let mut diff: u8 = 0;
+    for (&x, &y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+/// The write protect on the gateware staging area, which a password change must restore however it exits.
+pub trait StagingProtect {
+    fn set_staging_protect(&mut self, protect: bool);
+}
+
+/// Runs `f` with the staging area write-protected, and lifts the protection again once `f` returns,
+/// including when it bails out early with an error.
+pub fn with_staging_protected<S: StagingProtect, T>(s: &mut S, f: impl FnOnce(&mut S) -> T) -> T {
+    s.set_staging_protect(true);
+    let ret = f(s);
+    s.set_staging_protect(false);
+    ret
+}
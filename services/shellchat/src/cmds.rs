@@ -98,6 +98,8 @@ mod wlan;     use wlan::*;
 mod jtag_cmd; use jtag_cmd::*;
 mod net_cmd;  use net_cmd::*;
 mod pddb_cmd; use pddb_cmd::*;
+mod log_cmd;  use log_cmd::*;
+mod crash_cmd; use crash_cmd::*;
 
 #[cfg(feature="tts")]
 mod tts;
@@ -138,6 +140,8 @@ pub struct CmdEnv {
     jtag_cmd: JtagCmd,
     net_cmd: NetCmd,
     pddb_cmd: PddbCmd,
+    log_cmd: LogCmd,
+    crash_cmd: CrashCmd,
     wlan_cmd: Wlan,
 
     #[cfg(feature="tts")]
@@ -206,6 +210,8 @@ impl CmdEnv {
             jtag_cmd: JtagCmd::new(&xns),
             net_cmd: NetCmd::new(&xns),
             pddb_cmd: PddbCmd::new(&xns),
+            log_cmd: LogCmd::new(),
+            crash_cmd: CrashCmd::new(&xns),
             wlan_cmd: Wlan::new(),
 
             #[cfg(feature="tts")]
@@ -252,6 +258,8 @@ impl CmdEnv {
             &mut self.jtag_cmd,
             &mut self.net_cmd,
             &mut self.pddb_cmd,
+            &mut self.log_cmd,
+            &mut self.crash_cmd,
 
             #[cfg(feature="tts")]
             &mut self.tts_cmd,
@@ -0,0 +1,152 @@
+use crate::{ShellCmdApi, CommonEnv};
+use core::fmt::Write;
+use std::io::{Read, Write as PddbWrite};
+use xous_ipc::String;
+use chrono::{DateTime, Utc};
+use std::time::SystemTime;
+
+/// PDDB dictionary that crash reports from the log server are saved to, one key per crash
+const CRASH_DICT: &'static str = "sys.crash";
+
+pub struct CrashCmd {
+    pddb: pddb::Pddb,
+}
+impl CrashCmd {
+    pub fn new(_xns: &xous_names::XousNames) -> CrashCmd {
+        let _ = std::thread::spawn(save_crashes);
+        CrashCmd {
+            pddb: pddb::Pddb::new(),
+        }
+    }
+
+    fn read_report(&mut self, key_name: &str) -> Option<std::string::String> {
+        let mut key = self.pddb.get(CRASH_DICT, key_name, None, false, false, None, None::<fn()>).ok()?;
+        let mut report = std::string::String::new();
+        key.read_to_string(&mut report).ok()?;
+        Some(report)
+    }
+}
+
+/// Collect crash reports from the log server for as long as shellchat runs, and save each
+/// one to the PDDB. Crashes that happen before the PDDB is mounted are held until it is.
+fn save_crashes() {
+    let log = match log_server::LogControl::new() {
+        Ok(log) => log,
+        Err(e) => {
+            log::warn!("not collecting crash reports: {:?}", e);
+            return;
+        }
+    };
+    let mut pddb = pddb::Pddb::new();
+    loop {
+        match log.wait_for_crash() {
+            Ok(report) => {
+                let datetime: DateTime<Utc> = SystemTime::now().into();
+                let key_name = format!("{}-{}", datetime.timestamp(), report.pid);
+                let text = format!("Reported {} UTC\n{}", datetime.format("%m/%d/%Y %T"), report);
+                pddb.is_mounted_blocking(None);
+                match pddb.get(CRASH_DICT, &key_name, None, true, true, None, None::<fn()>) {
+                    Ok(mut key) => {
+                        match key.write_all(text.as_bytes()) {
+                            Ok(_) => match key.flush() {
+                                Ok(_) => log::info!("saved crash report {}:{}", CRASH_DICT, key_name),
+                                Err(e) => log::error!("couldn't sync crash report: {:?}", e),
+                            }
+                            Err(e) => log::error!("couldn't save crash report: {:?}", e),
+                        }
+                    }
+                    Err(e) => log::error!("couldn't create crash report key: {:?}", e),
+                }
+            }
+            Err(e) => {
+                log::warn!("not collecting crash reports: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for CrashCmd {
+    cmd_api!(crash); // inserts boilerplate for command API
+
+    fn process(&mut self, args: String::<1024>, _env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "crash [list] [show <id>] [export <id>] [delete <id|all>]";
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        if let Some(sub_cmd) = tokens.next() {
+            match sub_cmd {
+                "list" => {
+                    match self.pddb.list_keys(CRASH_DICT, None) {
+                        Ok(mut list) => {
+                            list.sort();
+                            write!(ret, "{} crash reports:", list.len()).unwrap();
+                            for key_name in list.iter() {
+                                // the first line after the date names the process
+                                let summary = self.read_report(key_name)
+                                    .and_then(|r| r.lines().nth(1).map(|l| std::string::String::from(l)))
+                                    .unwrap_or_default();
+                                write!(ret, "\n{}: {}", key_name, summary).unwrap();
+                            }
+                        }
+                        Err(_) => write!(ret, "No crash reports").unwrap(),
+                    }
+                }
+                "show" => {
+                    if let Some(key_name) = tokens.next() {
+                        match self.read_report(key_name) {
+                            // the shell only has room for the start of the report
+                            Some(report) => write!(ret, "{}", report).unwrap(),
+                            None => write!(ret, "No crash report {}", key_name).unwrap(),
+                        }
+                    } else {
+                        write!(ret, "Missing crash report id").unwrap();
+                    }
+                }
+                "export" => {
+                    if let Some(key_name) = tokens.next() {
+                        match self.read_report(key_name) {
+                            Some(report) => {
+                                for line in report.lines() {
+                                    log::info!("{}", line);
+                                }
+                                write!(ret, "Crash report {} copied to the log", key_name).unwrap();
+                            }
+                            None => write!(ret, "No crash report {}", key_name).unwrap(),
+                        }
+                    } else {
+                        write!(ret, "Missing crash report id").unwrap();
+                    }
+                }
+                "delete" => {
+                    let result = match tokens.next() {
+                        Some("all") => self.pddb.delete_dict(CRASH_DICT, None),
+                        Some(key_name) => self.pddb.delete_key(CRASH_DICT, key_name, None),
+                        None => {
+                            write!(ret, "Missing crash report id").unwrap();
+                            return Ok(Some(ret));
+                        }
+                    };
+                    match result {
+                        Ok(_) => {
+                            // you must call sync after all deletions are done
+                            write!(ret, "Deleted. Sync: {}",
+                                self.pddb.sync()
+                                .map_or_else(|e| e.to_string(), |_| "Ok".to_string())
+                            ).unwrap();
+                        }
+                        Err(e) => write!(ret, "Crash report not found or other error: {:?}", e).unwrap(),
+                    }
+                }
+                _ => {
+                    write!(ret, "{}", helpstring).unwrap();
+                }
+            }
+
+        } else {
+            write!(ret, "{}", helpstring).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{ShellCmdApi, CommonEnv};
+use core::fmt::Write;
+use std::io::Write as PddbWrite;
+use xous_ipc::String;
+
+/// PDDB dictionary and key that `log save` writes the log server's ring buffer to
+const LOG_DICT: &'static str = "sys.log";
+const LOG_KEY: &'static str = "ring";
+
+pub struct LogCmd {
+    log: log_server::LogControl,
+}
+impl LogCmd {
+    pub fn new() -> LogCmd {
+        LogCmd {
+            log: log_server::LogControl::new().expect("couldn't connect to the log server"),
+        }
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for LogCmd {
+    cmd_api!(log); // inserts boilerplate for command API
+
+    fn process(&mut self, args: String::<1024>, _env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "log [level <module|*> <off|error|warn|info|debug|trace|inherit>] [dump] [save]";
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        if let Some(sub_cmd) = tokens.next() {
+            match sub_cmd {
+                "level" => {
+                    let target = match tokens.next() {
+                        Some("*") => "",
+                        Some(target) => target,
+                        None => {
+                            write!(ret, "{}", helpstring).unwrap();
+                            return Ok(Some(ret));
+                        }
+                    };
+                    let level = match tokens.next() {
+                        Some("off") => Some(log::LevelFilter::Off),
+                        Some("error") => Some(log::LevelFilter::Error),
+                        Some("warn") => Some(log::LevelFilter::Warn),
+                        Some("info") => Some(log::LevelFilter::Info),
+                        Some("debug") => Some(log::LevelFilter::Debug),
+                        Some("trace") => Some(log::LevelFilter::Trace),
+                        Some("inherit") => None,
+                        _ => {
+                            write!(ret, "{}", helpstring).unwrap();
+                            return Ok(Some(ret));
+                        }
+                    };
+                    match self.log.set_level(target, level) {
+                        Ok(_) => write!(ret, "Log level for {} set to {:?}", if target.len() > 0 { target } else { "*" }, level).unwrap(),
+                        Err(e) => write!(ret, "Couldn't set log level: {:?}", e).unwrap(),
+                    }
+                }
+                "dump" => {
+                    match self.log.dump_ring() {
+                        Ok(records) => {
+                            // only the most recent records fit in the shell's output
+                            let mut start = records.len().saturating_sub(1024);
+                            while !records.is_char_boundary(start) {
+                                start += 1;
+                            }
+                            write!(ret, "{}", &records[start..]).unwrap();
+                        }
+                        Err(e) => write!(ret, "Couldn't read the log buffer: {:?}", e).unwrap(),
+                    }
+                }
+                "save" => {
+                    match self.log.dump_ring() {
+                        Ok(records) => {
+                            let mut pddb = pddb::Pddb::new();
+                            // replace any previous save, rather than writing over the start of it
+                            pddb.delete_key(LOG_DICT, LOG_KEY, None).ok();
+                            match pddb.get(LOG_DICT, LOG_KEY, None, true, true, None, None::<fn()>) {
+                                Ok(mut key) => {
+                                    match key.write_all(records.as_bytes()) {
+                                        Ok(_) => match key.flush() {
+                                            Ok(_) => write!(ret, "Saved {} bytes of log to {}:{}", records.len(), LOG_DICT, LOG_KEY).unwrap(),
+                                            Err(e) => write!(ret, "PDDB error syncing log: {:?}", e).unwrap(),
+                                        }
+                                        Err(e) => write!(ret, "PDDB error storing log: {:?}", e).unwrap(),
+                                    }
+                                }
+                                Err(e) => write!(ret, "PDDB error creating key: {:?}", e).unwrap(),
+                            }
+                        }
+                        Err(e) => write!(ret, "Couldn't read the log buffer: {:?}", e).unwrap(),
+                    }
+                }
+                _ => {
+                    write!(ret, "{}", helpstring).unwrap();
+                }
+            }
+
+        } else {
+            write!(ret, "{}", helpstring).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
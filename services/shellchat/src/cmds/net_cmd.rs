@@ -10,6 +10,10 @@ use std::io::Write;
 use std::io::Read;
 use dns::Dns; // necessary to work around https://github.com/rust-lang/rust/issues/94182
 
+/// PDDB dictionary and key that `net pcap stop` saves the packet capture to
+const PCAP_DICT: &'static str = "sys.net";
+const PCAP_KEY: &'static str = "capture.pcap";
+
 pub struct NetCmd {
     udp: Option<net::UdpSocket>,
     udp_clone: Option<net::UdpSocket>,
@@ -17,6 +21,7 @@ pub struct NetCmd {
     callback_conn: u32,
     udp_count: u32,
     dns: Dns,
+    netmgr: net::NetManager,
     #[cfg(any(target_os = "none", target_os = "xous"))]
     ping: Option<net::Ping>,
 }
@@ -29,6 +34,7 @@ impl NetCmd {
             callback_conn: xns.request_connection_blocking(crate::SERVER_NAME_SHELLCHAT).unwrap(),
             udp_count: 0,
             dns: dns::Dns::new(&xns).unwrap(),
+            netmgr: net::NetManager::new(),
             #[cfg(any(target_os = "none", target_os = "xous"))]
             ping: None,
         }
@@ -55,10 +61,12 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
         #[cfg(any(target_os = "none", target_os = "xous"))]
-        let helpstring = "net [udp [port]] [udpclose] [udpclone] [udpcloneclose] [ping [host] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [port]] [udpclose] [udpclone] [udpcloneclose] [ping [host] [count]] [tcpget host/path] [pcap start|stop|dump]";
         // no ping in hosted mode -- why would you need it? we're using the host's network connection.
-        #[cfg(not(any(target_os = "none", target_os = "xous")))]
-        let helpstring = "net [udp [port]] [udpclose] [udpclone] [udpcloneclose] [count]] [tcpget host/path]";
+        #[cfg(all(not(any(target_os = "none", target_os = "xous")), not(feature="nettest")))]
+        let helpstring = "net [udp [port]] [udpclose] [udpclone] [udpcloneclose] [count]] [tcpget host/path] [pcap start|stop|dump]";
+        #[cfg(all(not(any(target_os = "none", target_os = "xous")), feature="nettest"))]
+        let helpstring = "net [udp [port]] [udpclose] [udpclone] [udpcloneclose] [count]] [tcpget host/path] [pcap start|stop|dump] [test]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
@@ -258,6 +266,73 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                         }
                     }
                 }
+                "pcap" => {
+                    match tokens.next() {
+                        Some("start") => {
+                            match self.netmgr.pcap_start() {
+                                Ok(_) => write!(ret, "Packet capture started").unwrap(),
+                                Err(e) => write!(ret, "Couldn't start packet capture: {:?}", e).unwrap(),
+                            }
+                        }
+                        Some("stop") => {
+                            let frames = match self.netmgr.pcap_stop() {
+                                Ok(frames) => frames,
+                                Err(e) => {
+                                    write!(ret, "Couldn't stop packet capture: {:?}", e).unwrap();
+                                    return Ok(Some(ret));
+                                }
+                            };
+                            match self.netmgr.pcap_fetch() {
+                                Ok(Some(capture)) => {
+                                    let mut pddb = pddb::Pddb::new();
+                                    // replace any previous capture, rather than writing over the start of it
+                                    pddb.delete_key(PCAP_DICT, PCAP_KEY, None).ok();
+                                    match pddb.get(PCAP_DICT, PCAP_KEY, None, true, true, None, None::<fn()>) {
+                                        Ok(mut key) => {
+                                            match key.write_all(&capture).and_then(|_| key.flush()) {
+                                                Ok(_) => write!(ret, "Captured {} frames, saved {} bytes to {}:{}", frames, capture.len(), PCAP_DICT, PCAP_KEY).unwrap(),
+                                                Err(e) => write!(ret, "PDDB error storing capture: {:?}", e).unwrap(),
+                                            }
+                                        }
+                                        Err(e) => write!(ret, "PDDB error creating key: {:?}", e).unwrap(),
+                                    }
+                                }
+                                Ok(None) => write!(ret, "No packet capture was running").unwrap(),
+                                Err(e) => write!(ret, "Couldn't fetch packet capture: {:?}", e).unwrap(),
+                            }
+                        }
+                        Some("dump") => {
+                            match self.netmgr.pcap_fetch() {
+                                Ok(Some(capture)) => {
+                                    // one line of hex per 32 bytes; strip the log prefixes and use `xxd -r -p` to get the pcap file back
+                                    for chunk in capture.chunks(32) {
+                                        let mut line = std::string::String::new();
+                                        for b in chunk {
+                                            line.push_str(&format!("{:02x}", b));
+                                        }
+                                        log::info!("{}", line);
+                                    }
+                                    write!(ret, "Packet capture of {} bytes copied to the log", capture.len()).unwrap();
+                                }
+                                Ok(None) => write!(ret, "No stopped packet capture to dump").unwrap(),
+                                Err(e) => write!(ret, "Couldn't fetch packet capture: {:?}", e).unwrap(),
+                            }
+                        }
+                        _ => write!(ret, "net pcap start|stop|dump").unwrap(),
+                    }
+                }
+                // note that this feature only works in hosted mode, where 127.0.0.1 is served by the
+                // net server's loopback device
+                #[cfg(feature="nettest")]
+                "test" => {
+                    match loopback_test(&self.netmgr) {
+                        Ok(()) => write!(ret, "net loopback test PASSED").unwrap(),
+                        Err(e) => {
+                            log::error!("net loopback test FAILED: {}", e);
+                            write!(ret, "net loopback test FAILED: {}", e).unwrap();
+                        }
+                    }
+                }
                 #[cfg(any(target_os = "none", target_os = "xous"))]
                 "ping" => {
                     if let Some(name) = tokens.next() {
@@ -441,3 +516,117 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
         Ok(Some(ret))
     }
 }
+
+/// Runs the `net` library's TCP and UDP client paths against the net server, over the loopback
+/// address. Each step goes through the same opcodes as traffic to the outside world would.
+#[cfg(feature="nettest")]
+fn loopback_test(netmgr: &net::NetManager) -> Result<(), std::string::String> {
+    fn failed<E: std::fmt::Debug>(step: &'static str) -> impl Fn(E) -> std::string::String {
+        move |e| format!("{}: {:?}", step, e)
+    }
+    let timeout = Some(Duration::from_millis(10_000));
+
+    // TCP: a listener echoes back what a stream sends it
+    let mut listener = net::TcpListener::bind_xous("127.0.0.1:7100").map_err(failed("TCP bind"))?;
+    let echo = std::thread::spawn(move || -> std::io::Result<SocketAddr> {
+        let (mut stream, peer) = listener.accept()?;
+        let mut buf = [0u8; 14];
+        stream.read_exact(&mut buf)?;
+        stream.write_all(&buf)?;
+        Ok(peer)
+    });
+    let mut stream = net::TcpStream::connect_xous("127.0.0.1:7100", timeout, None).map_err(failed("TCP connect"))?;
+    stream.set_read_timeout(timeout).map_err(failed("TCP read timeout"))?;
+    stream.write_all(b"hello loopback").map_err(failed("TCP write"))?;
+    let mut echoed = [0u8; 14];
+    stream.read_exact(&mut echoed).map_err(failed("TCP read"))?;
+    if &echoed != b"hello loopback" {
+        return Err(format!("TCP echo mismatch: {:?}", echoed));
+    }
+    let peer = echo.join().unwrap().map_err(failed("TCP accept"))?;
+    if peer != stream.socket_addr().map_err(failed("TCP socket_addr"))? {
+        return Err(format!("TCP listener saw the connection from {:?}", peer));
+    }
+    log::info!("net test: TCP echo passed");
+
+    // UDP: a datagram goes from one bound socket to another
+    let mut tx = net::UdpSocket::bind_xous("127.0.0.1:7101", None).map_err(failed("UDP bind"))?;
+    let mut rx = net::UdpSocket::bind_xous("127.0.0.1:7102", None).map_err(failed("UDP bind"))?;
+    rx.set_read_timeout(timeout).map_err(failed("UDP read timeout"))?;
+    tx.send_to(b"ping", &"127.0.0.1:7102".parse().unwrap()).map_err(failed("UDP send"))?;
+    let mut pkt = [0u8; 16];
+    let (len, from) = rx.recv_from(&mut pkt).map_err(failed("UDP receive"))?;
+    if &pkt[..len] != b"ping" || from.port() != 7101 {
+        return Err(format!("UDP received {:?} from {:?}", &pkt[..len], from));
+    }
+    log::info!("net test: UDP datagram passed");
+
+    // multicast: a member of a group hears datagrams sent to it, until it leaves. The sender's
+    // multicast TTL is checked on the wire, by way of a packet capture.
+    let group = std::net::Ipv4Addr::new(239, 1, 2, 3);
+    let to_group = SocketAddr::new(IpAddr::V4(group), 7102);
+    let any = std::net::Ipv4Addr::UNSPECIFIED;
+    if tx.multicast_ttl_v4().map_err(failed("multicast TTL"))? != 1 || !tx.multicast_loop_v4().map_err(failed("multicast loop"))? {
+        return Err("multicast defaults aren't TTL 1 with loopback".into());
+    }
+    tx.set_multicast_ttl_v4(8).map_err(failed("set multicast TTL"))?;
+    if tx.multicast_ttl_v4().map_err(failed("multicast TTL"))? != 8 {
+        return Err("multicast TTL didn't stick".into());
+    }
+    match rx.join_multicast_v4(&group, &std::net::Ipv4Addr::new(10, 9, 8, 7)) {
+        Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => (),
+        r => return Err(format!("joining on a foreign interface gave {:?}", r)),
+    }
+    rx.join_multicast_v4(&group, &any).map_err(failed("multicast join"))?;
+    netmgr.pcap_start().map_err(failed("pcap start"))?;
+    tx.send_to(b"group", &to_group).map_err(failed("multicast send"))?;
+    let (len, from) = rx.recv_from(&mut pkt).map_err(failed("multicast receive"))?;
+    if &pkt[..len] != b"group" || from.port() != 7101 {
+        return Err(format!("multicast received {:?} from {:?}", &pkt[..len], from));
+    }
+    netmgr.pcap_stop().map_err(failed("pcap stop"))?;
+    let capture = netmgr.pcap_fetch().map_err(failed("pcap fetch"))?.unwrap_or_default();
+    match multicast_ttl_on_wire(&capture, &group) {
+        Some(8) => (),
+        ttl => return Err(format!("multicast went out with TTL {:?}", ttl)),
+    }
+
+    // with loopback off, local members don't hear it
+    rx.set_read_timeout(Some(Duration::from_millis(1000))).map_err(failed("UDP read timeout"))?;
+    tx.set_multicast_loop_v4(false).map_err(failed("set multicast loop"))?;
+    tx.send_to(b"quiet", &to_group).map_err(failed("multicast send"))?;
+    if let Ok((len, _)) = rx.recv_from(&mut pkt) {
+        return Err(format!("multicast loopback was off, but {:?} was received", &pkt[..len]));
+    }
+    tx.set_multicast_loop_v4(true).map_err(failed("set multicast loop"))?;
+
+    // and after leaving, nothing arrives; leaving twice is an error
+    rx.leave_multicast_v4(&group, &any).map_err(failed("multicast leave"))?;
+    match rx.leave_multicast_v4(&group, &any) {
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => (),
+        r => return Err(format!("leaving a group twice gave {:?}", r)),
+    }
+    tx.send_to(b"gone", &to_group).map_err(failed("multicast send"))?;
+    if let Ok((len, _)) = rx.recv_from(&mut pkt) {
+        return Err(format!("left the group, but {:?} was received", &pkt[..len]));
+    }
+    log::info!("net test: UDP multicast passed");
+    Ok(())
+}
+
+/// The IPv4 TTL of the first UDP datagram to `group` in a pcap file, if there is one
+#[cfg(feature="nettest")]
+fn multicast_ttl_on_wire(pcap: &[u8], group: &std::net::Ipv4Addr) -> Option<u8> {
+    // 24-byte file header, then a 16-byte header ahead of each frame
+    let mut records = pcap.get(24..)?;
+    while records.len() > 16 {
+        let len = u32::from_le_bytes([records[8], records[9], records[10], records[11]]) as usize;
+        let frame = records.get(16..16 + len)?;
+        // Ethernet, then IPv4: TTL at byte 8, protocol at 9, destination at 16
+        if frame.len() > 34 && frame[12..14] == [0x08, 0x00] && frame[23] == 17 && frame[30..34] == group.octets() {
+            return Some(frame[22]);
+        }
+        records = &records[16 + len..];
+    }
+    None
+}
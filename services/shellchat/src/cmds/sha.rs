@@ -150,7 +150,7 @@ impl<'a> ShellCmdApi<'a> for Sha {
     fn process(&mut self, args: String::<1024>, env: &mut CommonEnv) -> Result<Option<String::<1024>>, xous::Error> {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
-        let helpstring = "sha [check] [check256] [hwbench] [swbench] [susres]";
+        let helpstring = "sha [check] [check256] [checksha256] [hwbench] [swbench] [susres]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
@@ -208,6 +208,31 @@ impl<'a> ShellCmdApi<'a> for Sha {
                         write!(ret, "Sha512/256 failed: {:x?}", digest).unwrap();
                     }
                 }
+                "checksha256" => {
+                    // SHA-256 has no hardware datapath, but is still dispatched through the engine server
+                    const K_DATA: &'static [u8; 142] = b"Every one suspects himself of at least one of the cardinal virtues, and this is mine: I am one of the few honest people that I have ever known";
+                    const K_EXPECTED_DIGEST_SHA256: [u8; 32] = [
+                        0x3d,0xc2,0x96,0xdc,0x68,0xe2,0x36,0xaf,0x71,0xff,0x68,0xcb,0xe2,0x76,0x2f,0xe9,
+                        0x9d,0x37,0xa8,0xb8,0x45,0xc7,0x6d,0x42,0xf7,0xcf,0xf5,0x19,0xd6,0xc6,0xc9,0x4e,
+                    ];
+
+                    let mut pass: bool = true;
+                    let mut hasher = sha2::Sha256::new_with_strategy(FallbackStrategy::WaitForHardware);
+
+                    hasher.update(K_DATA);
+                    let digest = hasher.finalize();
+
+                    for(&expected, result) in K_EXPECTED_DIGEST_SHA256.iter().zip(digest) {
+                        if expected != result {
+                            pass = false;
+                        }
+                    }
+                    if pass {
+                        write!(ret, "Sha256 passed.").unwrap();
+                    } else {
+                        write!(ret, "Sha256 failed: {:x?}", digest).unwrap();
+                    }
+                }
                 "hwbench" => {
                     let start = env.ticktimer.elapsed_ms();
                     self.start_time = Some(start);
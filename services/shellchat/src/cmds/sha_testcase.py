@@ -33,3 +33,16 @@ for byte in digest2:
     print("0x{:02x},".format(byte), end='')
     i = i + 1
 print("\n];")
+
+m3 = hashlib.sha256()
+m3.update(test_string)
+digest3 = m3.digest()
+
+print("const K_EXPECTED_DIGEST_SHA256: [u8; 32] = [", end='')
+i = 0
+for byte in digest3:
+    if (i % 16) == 0:
+        print("\n   ", end='')
+    print("0x{:02x},".format(byte), end='')
+    i = i + 1
+print("\n];")
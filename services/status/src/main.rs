@@ -9,6 +9,7 @@ mod kbdmenu;
 use kbdmenu::*;
 mod app_autogen;
 mod time;
+mod sntp;
 
 use com::api::*;
 use core::fmt::Write;
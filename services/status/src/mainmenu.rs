@@ -1,6 +1,7 @@
 use gam::*;
 use locales::t;
 use root_keys::RootKeys;
+use root_keys::api::PasswordType;
 use std::sync::{Arc, Mutex};
 use xous_ipc::String;
 use num_traits::*;
@@ -74,6 +75,22 @@ pub fn create_main_menu(keys: Arc<Mutex<RootKeys>>, status_conn: xous::CID, com:
             close_on_select: true,
         });
 
+        menuitems.push(MenuItem {
+            name: String::from_str(t!("mainmenu.change_boot_pw", xous::LANG)),
+            action_conn: Some(key_conn),
+            action_opcode: keys.lock().unwrap().get_try_update_password_op(),
+            action_payload: MenuPayload::Scalar([PasswordType::Boot.to_u32().unwrap(), 0, 0, 0]),
+            close_on_select: true,
+        });
+
+        menuitems.push(MenuItem {
+            name: String::from_str(t!("mainmenu.change_update_pw", xous::LANG)),
+            action_conn: Some(key_conn),
+            action_opcode: keys.lock().unwrap().get_try_update_password_op(),
+            action_payload: MenuPayload::Scalar([PasswordType::Update.to_u32().unwrap(), 0, 0, 0]),
+            close_on_select: true,
+        });
+
         menuitems.push(MenuItem {
             name: String::from_str(t!("mainmenu.set_rtc", xous::LANG)),
             action_conn: Some(time_ux_conn),
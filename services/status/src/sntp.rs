@@ -0,0 +1,535 @@
+//! SNTP (RFC 4330) client for the time server.
+//!
+//! Once the PDDB is mounted and the net service has an IPv4 configuration, this thread asks an
+//! NTP server for the time and steps the time server's clock with `SetUtcTimeMs`, which in turn
+//! writes the new `utc_offset` back to the `sys.rtc` dictionary.
+//!
+//! SNTP is unauthenticated, so replies are sanity checked before they are allowed near the clock:
+//!   - the reply has to answer our request, come from a synchronized server, and arrive quickly
+//!   - the server's time can't be before 2022
+//!   - once synced, a correction can't be larger than the RTC could plausibly have drifted since
+//!     the last step. A correction that is out of bounds is only taken if the server insists on it
+//!     several polls in a row (this is what happens after the RTC has been reset).
+//!
+//! Between steps, the accumulated offset is used to estimate the RTC's drift, which sets how
+//! often the server is polled.
+
+use crate::time::TIME_SERVER_DICT;
+use llio::{TimeOp, TIME_SERVER_PUBLIC};
+use pddb::{Pddb, PddbMountPoller};
+use num_traits::*;
+use std::convert::TryInto;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::thread;
+
+/// Host name or IP address of the NTP server, optionally followed by `:port`
+pub(crate) const TIME_SERVER_SNTP_SERVER: &'static str = "sntp_server";
+/// UTC in ms when SNTP last stepped the clock. Deleted when the time is set by hand.
+pub(crate) const TIME_SERVER_SNTP_LAST_STEP: &'static str = "sntp_last_step";
+/// Measured drift of the RTC, in parts per billion; positive when the RTC runs slow
+pub(crate) const TIME_SERVER_SNTP_DRIFT: &'static str = "sntp_drift_ppb";
+
+const SNTP_DEFAULT_SERVER: &'static str = "pool.ntp.org";
+const SNTP_PORT: u16 = 123;
+const SNTP_PACKET_LEN: usize = 48;
+/// The local port is picked at random from the dynamic range, to make blind spoofing harder
+const SNTP_LOCAL_PORT_BASE: u16 = 49152;
+const SNTP_TIMEOUT_MS: u64 = 3000;
+/// 1900-01-01 (the NTP epoch) to 1970-01-01 (the UNIX epoch)
+const NTP_UNIX_EPOCH_DELTA_SECS: i64 = 2_208_988_800;
+
+/// Replies that took longer than this to come back can't pin down the time well enough
+const MAX_ROUND_TRIP_MS: i64 = 2000;
+/// No server can legitimately report a time before this code was written (2022-01-01)
+const EARLIEST_PLAUSIBLE_UTC_MS: i64 = 1_640_995_200_000;
+/// The RTC crystal is good to a few tens of ppm; this leaves room for temperature and aging
+const MAX_DRIFT_PPM: i64 = 200;
+/// Added to the drift bound to cover network jitter and the RTC's one second resolution
+const STEP_SLACK_MS: i64 = 2000;
+/// Out-of-bound corrections are taken once this many polls in a row agree on them
+const STEP_CONFIRMATIONS: usize = 3;
+/// `SetUtcTimeMs` can only line the clock up to the RTC's whole seconds, so smaller offsets are
+/// tracked rather than stepped
+const STEP_THRESHOLD_MS: i64 = 1000;
+/// The drift estimate is only updated over intervals long enough to swamp the step resolution
+const DRIFT_MIN_INTERVAL_MS: i64 = 6 * 3600 * 1000;
+
+/// Poll interval bounds; within them, the interval is how long the RTC takes to drift by
+/// `STEP_THRESHOLD_MS`
+const POLL_MIN_MS: i64 = 3600 * 1000;
+const POLL_MAX_MS: i64 = 24 * 3600 * 1000;
+/// How often to check for a network connection, and to retry after a failed poll
+const LINK_POLL_MS: usize = 30_000;
+const RETRY_MS: usize = 60_000;
+
+#[derive(Debug)]
+pub(crate) enum SntpError {
+    /// the server couldn't be reached, or didn't reply in time
+    Io(io::Error),
+    /// the reply is too short, or isn't a reply from a server
+    Malformed,
+    /// the server isn't synchronized itself, or has asked us to go away (kiss-o'-death)
+    Unsynchronized,
+    /// the reply doesn't answer our request
+    Mismatch,
+    /// the round trip, in ms, took too long for the reply to be useful
+    SlowRoundTrip(i64),
+    /// the server's time, in ms since the UNIX epoch, is before any date we could be running at
+    Implausible(i64),
+    /// the correction is larger than the RTC could have drifted since the last step
+    OutOfBound { offset_ms: i64, bound_ms: i64 },
+    /// the time server couldn't be read or set
+    TimeServer,
+}
+
+/// The outcome of one request/reply exchange
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SntpSample {
+    /// how far the local clock is behind the server, in ms
+    pub offset_ms: i64,
+    /// network round trip, less the server's processing time
+    pub delay_ms: i64,
+    /// local clock when the reply arrived
+    pub local_ms: i64,
+}
+impl SntpSample {
+    /// The server's time when the reply arrived
+    pub fn server_ms(&self) -> i64 {
+        self.local_ms + self.offset_ms
+    }
+}
+
+fn ntp_to_unix_ms(ts: &[u8]) -> i64 {
+    let mut secs = u32::from_be_bytes(ts[0..4].try_into().unwrap()) as i64;
+    let frac = u32::from_be_bytes(ts[4..8].try_into().unwrap()) as i64;
+    // era 0 runs out in 2036; timestamps with the top bit clear are in era 1 (RFC 4330 section 3)
+    if secs & 0x8000_0000 == 0 {
+        secs += 1 << 32;
+    }
+    (secs - NTP_UNIX_EPOCH_DELTA_SECS) * 1000 + ((frac * 1000) >> 32)
+}
+
+fn unix_ms_to_ntp(ms: i64) -> [u8; 8] {
+    // truncating to 32 bits wraps times after 2036 into era 1, as intended
+    let secs = (ms.div_euclid(1000) + NTP_UNIX_EPOCH_DELTA_SECS) as u32;
+    // round the fraction up, so that it converts back to the same ms
+    let frac = ((ms.rem_euclid(1000) << 32) + 999) / 1000;
+    let mut ts = [0u8; 8];
+    ts[0..4].copy_from_slice(&secs.to_be_bytes());
+    ts[4..8].copy_from_slice(&(frac as u32).to_be_bytes());
+    ts
+}
+
+/// A client request sent at `local_ms`. The server echoes the transmit timestamp back, which is
+/// how the reply is matched to the request.
+fn request(local_ms: i64) -> [u8; SNTP_PACKET_LEN] {
+    let mut pkt = [0u8; SNTP_PACKET_LEN];
+    pkt[0] = (4 << 3) | 3; // no leap indicator, version 4, client
+    pkt[40..48].copy_from_slice(&unix_ms_to_ntp(local_ms));
+    pkt
+}
+
+/// Check a `reply` to `request`, which was sent at local time `t1` and answered at `t4`
+fn parse_reply(request: &[u8], reply: &[u8], t1: i64, t4: i64) -> Result<SntpSample, SntpError> {
+    if reply.len() < SNTP_PACKET_LEN {
+        return Err(SntpError::Malformed);
+    }
+    let leap = reply[0] >> 6;
+    let version = (reply[0] >> 3) & 0x7;
+    let mode = reply[0] & 0x7;
+    let stratum = reply[1];
+    if mode != 4 || version < 3 || version > 4 {
+        return Err(SntpError::Malformed);
+    }
+    if leap == 3 || stratum == 0 || stratum > 15 {
+        return Err(SntpError::Unsynchronized);
+    }
+    if reply[24..32] != request[40..48] || reply[40..48] == [0u8; 8] {
+        return Err(SntpError::Mismatch);
+    }
+    let t2 = ntp_to_unix_ms(&reply[32..40]);
+    let t3 = ntp_to_unix_ms(&reply[40..48]);
+    Ok(SntpSample {
+        offset_ms: ((t2 - t1) + (t3 - t4)) / 2,
+        delay_ms: (t4 - t1) - (t3 - t2),
+        local_ms: t4,
+    })
+}
+
+/// The part of a UDP socket that a query needs
+pub(crate) trait SntpSocket {
+    fn send_to(&mut self, pkt: &[u8], addr: &SocketAddr) -> io::Result<usize>;
+    fn recv_from(&mut self, pkt: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+}
+impl SntpSocket for net::UdpSocket {
+    fn send_to(&mut self, pkt: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+        net::UdpSocket::send_to(self, pkt, addr)
+    }
+    fn recv_from(&mut self, pkt: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        net::UdpSocket::recv_from(self, pkt)
+    }
+}
+
+/// Ask `server` for the time. `now` reads the local clock, in ms since the UNIX epoch. The
+/// socket's read timeout bounds how long to wait for the reply.
+pub(crate) fn query(socket: &mut dyn SntpSocket, server: SocketAddr, now: &dyn Fn() -> Result<i64, SntpError>) -> Result<SntpSample, SntpError> {
+    let t1 = now()?;
+    let req = request(t1);
+    socket.send_to(&req, &server).map_err(SntpError::Io)?;
+    let mut reply = [0u8; SNTP_PACKET_LEN];
+    loop {
+        let (len, from) = socket.recv_from(&mut reply).map_err(SntpError::Io)?;
+        let t4 = now()?;
+        if from == server {
+            // `len` is the length of the whole datagram, which can run past the buffer if the
+            // server appended extension fields or a MAC
+            return parse_reply(&req, &reply[..len.min(reply.len())], t1, t4);
+        }
+        log::debug!("ignoring a stray packet from {:?}", from);
+    }
+}
+
+/// What earlier syncs have established about the RTC. Kept in the `sys.rtc` dictionary.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct SyncHistory {
+    /// UTC in ms when SNTP last stepped the clock
+    pub last_step_ms: Option<i64>,
+    /// drift of the RTC since that step, in parts per billion; positive when the RTC runs slow
+    pub drift_ppb: Option<i64>,
+}
+impl SyncHistory {
+    /// Sanity check a sample before it's used to set the clock
+    pub fn check(&self, sample: &SntpSample) -> Result<(), SntpError> {
+        if sample.delay_ms < 0 || sample.delay_ms > MAX_ROUND_TRIP_MS {
+            return Err(SntpError::SlowRoundTrip(sample.delay_ms));
+        }
+        if sample.server_ms() < EARLIEST_PLAUSIBLE_UTC_MS {
+            return Err(SntpError::Implausible(sample.server_ms()));
+        }
+        // until the first step, the clock could be anywhere -- it may never have been set
+        if let Some(last_step) = self.last_step_ms {
+            let elapsed = (sample.local_ms - last_step).max(0);
+            let bound_ms = elapsed * MAX_DRIFT_PPM / 1_000_000 + STEP_SLACK_MS;
+            if sample.offset_ms.abs() > bound_ms {
+                return Err(SntpError::OutOfBound { offset_ms: sample.offset_ms, bound_ms });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fold an accepted sample into the history. `in_bound` is false if the sample failed the
+    /// drift bound and was accepted on confirmation. Returns true if the clock should be stepped.
+    pub fn update(&mut self, sample: &SntpSample, in_bound: bool) -> bool {
+        if !in_bound {
+            // whatever happened to the clock, it wasn't drift
+            self.drift_ppb = None;
+        } else if let Some(last_step) = self.last_step_ms {
+            let elapsed = sample.local_ms - last_step;
+            if elapsed >= DRIFT_MIN_INTERVAL_MS {
+                self.drift_ppb = Some(sample.offset_ms * 1_000_000_000 / elapsed);
+            }
+        }
+        if self.last_step_ms.is_none() || !in_bound || sample.offset_ms.abs() >= STEP_THRESHOLD_MS {
+            self.last_step_ms = Some(sample.server_ms());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How long to wait before polling the server again
+    pub fn poll_interval_ms(&self) -> i64 {
+        match self.drift_ppb {
+            Some(drift) if drift != 0 => (STEP_THRESHOLD_MS * 1_000_000_000 / drift.abs()).max(POLL_MIN_MS).min(POLL_MAX_MS),
+            Some(_) => POLL_MAX_MS,
+            None => POLL_MIN_MS,
+        }
+    }
+
+    fn load(pddb: &mut Pddb) -> SyncHistory {
+        SyncHistory {
+            last_step_ms: read_i64(pddb, TIME_SERVER_SNTP_LAST_STEP),
+            drift_ppb: read_i64(pddb, TIME_SERVER_SNTP_DRIFT),
+        }
+    }
+
+    fn store(&self, pddb: &mut Pddb) -> io::Result<()> {
+        write_i64(pddb, TIME_SERVER_SNTP_LAST_STEP, self.last_step_ms)?;
+        write_i64(pddb, TIME_SERVER_SNTP_DRIFT, self.drift_ppb)
+    }
+}
+
+fn read_i64(pddb: &mut Pddb, key_name: &str) -> Option<i64> {
+    let mut key = pddb.get(TIME_SERVER_DICT, key_name, None, false, false, None, None::<fn()>).ok()?;
+    let mut buf = [0u8; 8];
+    if key.read(&mut buf).unwrap_or(0) == 8 {
+        Some(i64::from_le_bytes(buf))
+    } else {
+        None
+    }
+}
+
+fn write_i64(pddb: &mut Pddb, key_name: &str, value: Option<i64>) -> io::Result<()> {
+    if let Some(value) = value {
+        let mut key = pddb.get(TIME_SERVER_DICT, key_name, None, true, true, Some(8), None::<fn()>)?;
+        key.seek(SeekFrom::Start(0))?;
+        key.write_all(&value.to_le_bytes())?;
+        key.flush()
+    } else {
+        match pddb.delete_key(TIME_SERVER_DICT, key_name, None) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Split a server setting into host and port
+fn parse_server(setting: &str) -> (&str, u16) {
+    let setting = setting.trim();
+    if setting.is_empty() {
+        return (SNTP_DEFAULT_SERVER, SNTP_PORT);
+    }
+    match setting.rsplit_once(':') {
+        // more than one colon is an IPv6 address, which can't carry a port in this format
+        Some((host, port)) if !host.contains(':') => match port.parse::<u16>() {
+            Ok(port) => (host, port),
+            Err(_) => (setting, SNTP_PORT),
+        },
+        _ => (setting, SNTP_PORT),
+    }
+}
+
+fn utc_now_ms(time_cid: xous::CID) -> Result<i64, SntpError> {
+    match xous::send_message(time_cid,
+        xous::Message::new_blocking_scalar(TimeOp::GetUtcTimeMs.to_usize().unwrap(), 0, 0, 0, 0)
+    ) {
+        Ok(xous::Result::Scalar2(hi, lo)) => Ok((((hi as u64) << 32) | lo as u64) as i64),
+        r => {
+            log::error!("couldn't get UTC time: {:?}", r);
+            Err(SntpError::TimeServer)
+        }
+    }
+}
+
+/// One poll of the configured server. `pending` holds the out-of-bound offsets seen on
+/// consecutive polls so far.
+fn sync(pddb: &mut Pddb, dns: &dns::Dns, trng: &trng::Trng, time_cid: xous::CID, pending: &mut Vec<i64>) -> Result<SyncHistory, SntpError> {
+    let mut setting = String::new();
+    if let Ok(mut key) = pddb.get(TIME_SERVER_DICT, TIME_SERVER_SNTP_SERVER, None, false, false, None, None::<fn()>) {
+        key.read_to_string(&mut setting).ok();
+    }
+    let (host, port) = parse_server(&setting);
+    let ip = match host.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => IpAddr::from(dns.lookup(host).map_err(|e| {
+            SntpError::Io(io::Error::new(io::ErrorKind::NotFound, format!("couldn't resolve {}: {:?}", host, e)))
+        })?),
+    };
+    let server = SocketAddr::new(ip, port);
+
+    let local_port = SNTP_LOCAL_PORT_BASE + (trng.get_u32().unwrap() % 16384) as u16;
+    let mut socket = net::UdpSocket::bind_xous((IpAddr::V4(Ipv4Addr::UNSPECIFIED), local_port), None)
+        .map_err(SntpError::Io)?;
+    socket.set_read_timeout(Some(net::Duration::from_millis(SNTP_TIMEOUT_MS))).unwrap();
+    let sample = query(&mut socket, server, &|| utc_now_ms(time_cid))?;
+    log::debug!("SNTP sample from {:?}: {:?}", server, sample);
+
+    let mut history = SyncHistory::load(pddb);
+    let in_bound = match history.check(&sample) {
+        Ok(()) => true,
+        Err(SntpError::OutOfBound { offset_ms, bound_ms }) => {
+            pending.retain(|&p| (p - offset_ms).abs() <= STEP_SLACK_MS);
+            pending.push(offset_ms);
+            if pending.len() < STEP_CONFIRMATIONS {
+                return Err(SntpError::OutOfBound { offset_ms, bound_ms });
+            }
+            log::warn!("{} polls agree on a {} ms correction; taking it", pending.len(), offset_ms);
+            false
+        }
+        Err(e) => return Err(e),
+    };
+    pending.clear();
+
+    if history.update(&sample, in_bound) {
+        // carry the server's time forward to now, so the time spent in here isn't lost
+        let utc_ms = sample.server_ms() + (utc_now_ms(time_cid)? - sample.local_ms);
+        log::info!("SNTP: stepping clock by {} ms", sample.offset_ms);
+        xous::send_message(time_cid,
+            xous::Message::new_scalar(
+                TimeOp::SetUtcTimeMs.to_usize().unwrap(),
+                ((utc_ms as u64) >> 32) as usize,
+                (utc_ms as u64 & 0xFFFF_FFFF) as usize,
+                0, 0,
+            )
+        ).map_err(|e| {
+            log::error!("couldn't set time: {:?}", e);
+            SntpError::TimeServer
+        })?;
+    } else {
+        log::info!("SNTP: clock is {} ms behind, drift {:?} ppb", sample.offset_ms, history.drift_ppb);
+    }
+    // a failed write is retried with the next poll, which stores the history afresh
+    history.store(pddb).map_err(SntpError::Io)?;
+    Ok(history)
+}
+
+pub fn start_sntp_client() {
+    thread::spawn({
+        move || {
+            let xns = xous_names::XousNames::new().unwrap();
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            let netmgr = net::NetManager::new();
+            let dns = dns::Dns::new(&xns).unwrap();
+            let trng = trng::Trng::new(&xns).unwrap();
+            let time_cid = xous::connect(xous::SID::from_bytes(TIME_SERVER_PUBLIC).unwrap()).unwrap();
+            let pddb_poller = PddbMountPoller::new();
+            let mut pddb = Pddb::new();
+            let mut pending = Vec::new();
+            loop {
+                if !pddb_poller.is_mounted_nonblocking() || netmgr.get_ipv4_config().is_none() {
+                    tt.sleep_ms(LINK_POLL_MS).unwrap();
+                    continue;
+                }
+                let wait = match sync(&mut pddb, &dns, &trng, time_cid, &mut pending) {
+                    Ok(history) => history.poll_interval_ms() as usize,
+                    Err(e) => {
+                        log::warn!("SNTP sync failed: {:?}", e);
+                        RETRY_MS
+                    }
+                };
+                tt.sleep_ms(wait).unwrap();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    /// 2023-06-01T12:00:00Z
+    const SERVER_MS: i64 = 1_685_620_800_000;
+
+    impl SntpSocket for UdpSocket {
+        fn send_to(&mut self, pkt: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+            UdpSocket::send_to(self, pkt, addr)
+        }
+        // like `net::UdpSocket`, report the length of the whole datagram even if it was cut short
+        fn recv_from(&mut self, pkt: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+            let mut datagram = [0u8; 1500];
+            let (len, from) = UdpSocket::recv_from(self, &mut datagram)?;
+            let copied = len.min(pkt.len());
+            pkt[..copied].copy_from_slice(&datagram[..copied]);
+            Ok((len, from))
+        }
+    }
+
+    /// A one-shot SNTP server on the loopback. It answers the first request with `SERVER_MS`,
+    /// after letting `mangle` tamper with the reply.
+    fn stand_in(mangle: fn(&mut [u8])) -> SocketAddr {
+        stand_in_sized(SNTP_PACKET_LEN, mangle)
+    }
+
+    /// As `stand_in`, but the reply is `len` bytes long; anything past the basic header is zeroed.
+    fn stand_in_sized(len: usize, mangle: fn(&mut [u8])) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut req = [0u8; SNTP_PACKET_LEN];
+            let (_, client) = socket.recv_from(&mut req).unwrap();
+            let mut reply = vec![0u8; len];
+            reply[0] = (4 << 3) | 4; // no leap indicator, version 4, server
+            reply[1] = 2; // stratum
+            reply[24..32].copy_from_slice(&req[40..48]);
+            reply[32..40].copy_from_slice(&unix_ms_to_ntp(SERVER_MS));
+            reply[40..48].copy_from_slice(&unix_ms_to_ntp(SERVER_MS));
+            mangle(&mut reply);
+            socket.send_to(&reply, client).unwrap();
+        });
+        addr
+    }
+
+    /// Query the stand-in with the local clock stopped at `local_ms`
+    fn query_stand_in(server: SocketAddr, local_ms: i64) -> Result<SntpSample, SntpError> {
+        let mut socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(SNTP_TIMEOUT_MS))).unwrap();
+        query(&mut socket, server, &|| Ok(local_ms))
+    }
+
+    #[test]
+    fn sync_against_stand_in() {
+        // a clock that has never been set counts up from the RTC's reset value
+        let local_ms = 300_000_000_000;
+        let sample = query_stand_in(stand_in(|_| ()), local_ms).unwrap();
+        assert_eq!(sample.server_ms(), SERVER_MS);
+        assert_eq!(sample.delay_ms, 0);
+
+        let mut history = SyncHistory::default();
+        assert!(history.check(&sample).is_ok());
+        assert!(history.update(&sample, true));
+        assert_eq!(history.last_step_ms, Some(SERVER_MS));
+        assert_eq!(history.drift_ppb, None);
+    }
+
+    #[test]
+    fn long_reply_is_accepted() {
+        // a 48-byte header followed by a 20-byte MAC (key ID and MD5 digest), as sent by servers
+        // that authenticate with a symmetric key
+        let sample = query_stand_in(stand_in_sized(68, |r| r[48..].fill(0xA5)), SERVER_MS).unwrap();
+        assert_eq!(sample.server_ms(), SERVER_MS);
+        assert_eq!(sample.offset_ms, 0);
+    }
+
+    #[test]
+    fn bad_replies_are_refused() {
+        let local_ms = SERVER_MS;
+        assert!(matches!(query_stand_in(stand_in(|r| r[0] |= 3 << 6), local_ms), Err(SntpError::Unsynchronized)));
+        assert!(matches!(query_stand_in(stand_in(|r| r[1] = 0), local_ms), Err(SntpError::Unsynchronized)));
+        assert!(matches!(query_stand_in(stand_in(|r| r[0] = (4 << 3) | 3), local_ms), Err(SntpError::Malformed)));
+        assert!(matches!(query_stand_in(stand_in(|r| r[31] ^= 1), local_ms), Err(SntpError::Mismatch)));
+        let ancient = |r: &mut [u8]| {
+            r[32..40].copy_from_slice(&unix_ms_to_ntp(0));
+            r[40..48].copy_from_slice(&unix_ms_to_ntp(0));
+        };
+        let sample = query_stand_in(stand_in(ancient), 0).unwrap();
+        assert!(matches!(SyncHistory::default().check(&sample), Err(SntpError::Implausible(0))));
+    }
+
+    #[test]
+    fn drift_is_tracked_within_bound() {
+        let six_hours = DRIFT_MIN_INTERVAL_MS;
+        let mut history = SyncHistory { last_step_ms: Some(SERVER_MS - six_hours), drift_ppb: None };
+        // 6 hours at 200 ppm is 4.3 seconds, plus slack
+        let slow = SntpSample { offset_ms: 500, delay_ms: 20, local_ms: SERVER_MS };
+        assert!(history.check(&slow).is_ok());
+        // too small to step, but enough to estimate the drift from
+        assert!(!history.update(&slow, true));
+        assert_eq!(history.last_step_ms, Some(SERVER_MS - six_hours));
+        assert_eq!(history.drift_ppb, Some(500 * 1_000_000_000 / six_hours));
+        // at that rate, it takes 12 hours to drift by a second
+        assert_eq!(history.poll_interval_ms() / (3600 * 1000), 12);
+
+        let jump = SntpSample { offset_ms: 3600 * 1000, delay_ms: 20, local_ms: SERVER_MS };
+        assert!(matches!(history.check(&jump), Err(SntpError::OutOfBound { offset_ms: 3_600_000, .. })));
+        assert!(history.update(&jump, false));
+        assert_eq!(history.last_step_ms, Some(SERVER_MS + 3600 * 1000));
+        assert_eq!(history.drift_ppb, None);
+
+        let slow_rtt = SntpSample { offset_ms: 0, delay_ms: MAX_ROUND_TRIP_MS + 1, local_ms: SERVER_MS };
+        assert!(matches!(history.check(&slow_rtt), Err(SntpError::SlowRoundTrip(_))));
+    }
+
+    #[test]
+    fn timestamps_and_settings() {
+        // 1970, now-ish, and after the 2036 era rollover
+        for &ms in [1, SERVER_MS + 999, 2_100_000_000_000].iter() {
+            assert_eq!(ntp_to_unix_ms(&unix_ms_to_ntp(ms)), ms);
+        }
+        assert_eq!(parse_server(""), (SNTP_DEFAULT_SERVER, SNTP_PORT));
+        assert_eq!(parse_server(" time.example.org\n"), ("time.example.org", SNTP_PORT));
+        assert_eq!(parse_server("10.0.0.1:1123"), ("10.0.0.1", 1123));
+        assert_eq!(parse_server("2001:db8::1"), ("2001:db8::1", SNTP_PORT));
+    }
+}
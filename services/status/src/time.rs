@@ -34,11 +34,6 @@ use chrono::prelude::*;
 use xous::Message;
 use gam::modal::*;
 
-/// This is a "well known name" used by `libstd` to connect to the time server
-/// Even thought it is "public" nobody connects to it directly, they connect to it via `libstd`
-/// hence the scope of the name is private to this crate.
-pub const TIME_SERVER_PUBLIC: &'static [u8; 16] = b"timeserverpublic";
-
 /// Dictionary for RTC settings.
 pub(crate) const TIME_SERVER_DICT: &'static str = "sys.rtc";
 /// This is the UTC offset from the current hardware RTC reading. This should be fixed once time is set.
@@ -62,27 +57,6 @@ const MONTHS: usize = 6;
 #[allow(dead_code)]
 const YEARS: usize = 7;
 
-/// Do not modify the discriminants in this structure. They are used in `libstd` directly.
-#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
-pub(crate) enum TimeOp {
-    /// Sync offsets to hardware RTC
-    HwSync = 0,
-    /// Suspend/resume call
-    SusRes = 1,
-    /// Indicates the current time is precisely the provided number of ms since EPOCH
-    SetUtcTimeMs = 2,
-    /// Get UTC time in ms since EPOCH
-    GetUtcTimeMs = 3,
-    /// Get local time in ms since EPOCH
-    GetLocalTimeMs = 4,
-    /// Sets the timezone offset, in milliseconds.
-    SetTzOffsetMs = 5,
-    /// Query to see if timezone and time relative to UTC have been set.
-    WallClockTimeInit = 6,
-    /// Self-poll for PDDB mount
-    PddbMountPoll = 7,
-}
-
 /// Do not modify the discriminants in this structure. They are used in `libstd` directly.
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 pub(crate) enum PrivTimeOp {
@@ -400,6 +374,9 @@ pub fn start_time_server() {
             }
         }
     });
+
+    // keeps the UTC offset in step with a network time server, once there is a network
+    crate::sntp::start_sntp_client();
 }
 
 #[allow(dead_code)]
@@ -438,7 +415,7 @@ pub fn start_time_ux(sid: xous::SID) {
             // some RTC UX structures
             let xns = xous_names::XousNames::new().unwrap();
             let modals = modals::Modals::new(&xns).unwrap();
-            let timeserver_cid = xous::connect(xous::SID::from_bytes(crate::time::TIME_SERVER_PUBLIC).unwrap()).unwrap();
+            let timeserver_cid = xous::connect(xous::SID::from_bytes(TIME_SERVER_PUBLIC).unwrap()).unwrap();
             let pddb_poller = pddb::PddbMountPoller::new();
 
             loop {
@@ -478,7 +455,7 @@ pub fn start_time_ux(sid: xous::SID) {
                             tz_offset_ms = (tz * 3600.0 * 1000.0) as i64;
                             xous::send_message(timeserver_cid,
                                 Message::new_scalar(
-                                    crate::time::TimeOp::SetTzOffsetMs.to_usize().unwrap(),
+                                    TimeOp::SetTzOffsetMs.to_usize().unwrap(),
                                     (tz_offset_ms >> 32) as usize,
                                     (tz_offset_ms & 0xFFFF_FFFF) as usize,
                                     0, 0,
@@ -535,12 +512,15 @@ pub fn start_time_ux(sid: xous::SID) {
                         .parse::<u8>().expect("pre-validated input failed to re-parse!");
                         log::debug!("got seconds {}", secs);
 
+                        // a hand-set time is a fresh start as far as SNTP's sanity bound and drift estimate go
+                        tz_set_handle.delete_key(TIME_SERVER_DICT, crate::sntp::TIME_SERVER_SNTP_LAST_STEP, None).ok();
+                        tz_set_handle.delete_key(TIME_SERVER_DICT, crate::sntp::TIME_SERVER_SNTP_DRIFT, None).ok();
                         log::info!("Setting time: {}/{}/{} {}:{}:{}", months, days, years, hours, mins, secs);
                         let new_dt = chrono::FixedOffset::east((tz_offset_ms / 1000) as i32).ymd(years as i32 + 2000, months as u32, days as u32)
                         .and_hms(hours as u32, mins as u32, secs as u32);
                         xous::send_message(timeserver_cid,
                             Message::new_scalar(
-                                crate::time::TimeOp::SetUtcTimeMs.to_usize().unwrap(),
+                                TimeOp::SetUtcTimeMs.to_usize().unwrap(),
                                 ((new_dt.timestamp_millis() as u64) >> 32) as usize,
                                 (new_dt.timestamp_millis() as u64 & 0xFFFF_FFFF) as usize,
                                 0, 0,
@@ -561,7 +541,7 @@ pub fn start_time_ux(sid: xous::SID) {
                         let tzoff_ms = (tz * 3600.0 * 1000.0) as i64;
                         xous::send_message(timeserver_cid,
                             Message::new_scalar(
-                                crate::time::TimeOp::SetTzOffsetMs.to_usize().unwrap(),
+                                TimeOp::SetTzOffsetMs.to_usize().unwrap(),
                                 (tzoff_ms >> 32) as usize,
                                 (tzoff_ms & 0xFFFF_FFFF) as usize,
                                 0, 0,
@@ -39,7 +39,7 @@ mod implementation {
     use num_traits::ToPrimitive;
 
     const SYSTEM_CLOCK_FREQUENCY: u32 = 12_000_000; // timer0 is now in the always-on domain
-    const SYSTEM_TICK_INTERVAL_MS: u32 = 20;
+    const SYSTEM_TICK_INTERVAL_MS: u32 = xous::PREEMPTION_TICK_MS;
 
     fn timer_tick(_irq_no: usize, arg: *mut usize) {
         let mut timer = CSR::new(arg as *mut u32);
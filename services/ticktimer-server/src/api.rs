@@ -48,6 +48,48 @@ pub(crate) enum Opcode {
     /// *arg1*: An integer of some sort, such as the address of the Condvar
     /// *arg2*: The number of conditions to notify
     NotifyCondition = 9,
+
+    /// Inform the ticktimer that the system clock has been slowed down, so the hardware
+    /// tick no longer advances once per millisecond. Sent by `llio` whenever it changes
+    /// the clock mode; elapsed time is rebased at the moment of the change.
+    ///
+    /// # Arguments
+    ///
+    /// *arg1*: The number of milliseconds that elapse per hardware tick, from now on (1 is nominal)
+    SetClockDivider = 10,
+
+    /// Send a scalar message to a server once, after a delay. The alarm is described by
+    /// an `AlarmRequest` lent to us, and its ID is written back into the request.
+    ScheduleAlarm = 11,
+
+    /// Send a scalar message to a server every `ms` milliseconds until it is cancelled.
+    /// The alarm is described by an `AlarmRequest` lent to us, and its ID is written back
+    /// into the request.
+    ScheduleRepeating = 12,
+
+    /// Cancel an alarm. Returns 1 if the alarm was pending, or 0 if it had already fired
+    /// or did not belong to the caller.
+    ///
+    /// # Arguments
+    ///
+    /// *arg1*: The ID returned when the alarm was scheduled
+    CancelAlarm = 13,
+}
+
+/// Describes an alarm for `ScheduleAlarm` or `ScheduleRepeating`. When the alarm fires,
+/// the ticktimer sends `Message::new_scalar(opcode, arg, id, 0, 0)` to the server at `sid`.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Copy, Clone)]
+pub struct AlarmRequest {
+    /// The server that receives the alarm message
+    pub sid: [u32; 4],
+    /// The message ID of the alarm message
+    pub opcode: u32,
+    /// Passed back unchanged as `arg1` of the alarm message
+    pub arg: u32,
+    /// The delay before a one-shot alarm fires, or the period of a repeating alarm
+    pub ms: u64,
+    /// Filled in by the ticktimer: the ID of the new alarm, or 0 if it couldn't be scheduled
+    pub id: u32,
 }
 
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
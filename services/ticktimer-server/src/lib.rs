@@ -53,6 +53,23 @@ impl Ticktimer {
         .map(|_| ())
     }
 
+    /// Tell the ticktimer how many milliseconds elapse per hardware tick after a system clock change.
+    /// This is meant to be called by `llio` only, right after it reprograms the clock; anyone else
+    /// calling it will simply skew the system time.
+    pub fn set_clock_divider(&self, divider: u32) -> Result<(), Error> {
+        send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::SetClockDivider.to_usize().unwrap(),
+                divider as usize,
+                0,
+                0,
+                0,
+            ),
+        )
+        .map(|_| ())
+    }
+
     pub fn ping_wdt(&self) {
         send_message(
             self.conn,
@@ -167,6 +184,87 @@ impl Ticktimer {
         .map(|r| r == xous::Result::Scalar1(0))
         .expect("couldn't notify condition");
     }
+
+    /// Have `Message::new_scalar(opcode, arg, id, 0, 0)` sent to the server `sid` once,
+    /// `delay_ms` milliseconds from now. The message is sent with `try_send_message()`, so
+    /// it is dropped if the server's queue is full at that moment.
+    ///
+    /// # Returns:
+    ///
+    ///     * The `id` of the alarm, which can be passed to `cancel_alarm()`
+    pub fn schedule_alarm(
+        &self,
+        sid: xous::SID,
+        opcode: u32,
+        arg: u32,
+        delay_ms: u64,
+    ) -> Result<u32, Error> {
+        self.schedule(api::Opcode::ScheduleAlarm, sid, opcode, arg, delay_ms)
+    }
+
+    /// Have `Message::new_scalar(opcode, arg, id, 0, 0)` sent to the server `sid` every
+    /// `period_ms` milliseconds, starting `period_ms` milliseconds from now, until the
+    /// alarm is cancelled. Periods that are missed, for example while the system is
+    /// suspended, are skipped rather than sent all at once.
+    ///
+    /// # Returns:
+    ///
+    ///     * The `id` of the alarm, which can be passed to `cancel_alarm()`
+    pub fn schedule_repeating(
+        &self,
+        sid: xous::SID,
+        opcode: u32,
+        arg: u32,
+        period_ms: u64,
+    ) -> Result<u32, Error> {
+        self.schedule(api::Opcode::ScheduleRepeating, sid, opcode, arg, period_ms)
+    }
+
+    fn schedule(
+        &self,
+        op: api::Opcode,
+        sid: xous::SID,
+        opcode: u32,
+        arg: u32,
+        ms: u64,
+    ) -> Result<u32, Error> {
+        let alloc = api::AlarmRequest {
+            sid: sid.to_array(),
+            opcode,
+            arg,
+            ms,
+            id: 0,
+        };
+        let mut buf = xous_ipc::Buffer::into_buf(alloc).or(Err(Error::InternalError))?;
+        buf.lend_mut(self.conn, op.to_u32().unwrap())?;
+        let request = buf
+            .to_original::<api::AlarmRequest, _>()
+            .or(Err(Error::InternalError))?;
+        match request.id {
+            0 => Err(Error::ServerNotFound),
+            id => Ok(id),
+        }
+    }
+
+    /// Cancel an alarm made by `schedule_alarm()` or `schedule_repeating()`.
+    ///
+    /// # Returns:
+    ///
+    ///     * true: the alarm was cancelled
+    ///     * false: the alarm had already fired, or was not scheduled by this process
+    pub fn cancel_alarm(&self, id: u32) -> Result<bool, Error> {
+        send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::CancelAlarm.to_usize().unwrap(),
+                id as usize,
+                0,
+                0,
+                0,
+            ),
+        )
+        .map(|r| r == xous::Result::Scalar1(1))
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
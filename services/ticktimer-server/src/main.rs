@@ -3,6 +3,7 @@
 #![cfg_attr(target_os = "none", no_main)]
 
 mod api;
+mod timebase;
 mod version;
 
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
@@ -15,6 +16,9 @@ type TimeoutExpiry = i64;
 pub enum RequestKind {
     Sleep = 0,
     Timeout = 1,
+    /// Nobody is waiting on this request. It only wakes us up so that the alarm
+    /// whose ID is in `data` can be sent.
+    Alarm = 2,
 }
 
 #[derive(Eq)]
@@ -25,6 +29,19 @@ pub struct TimerRequest {
     data: usize,
 }
 
+/// An alarm scheduled by `ScheduleAlarm` or `ScheduleRepeating`
+struct Alarm {
+    /// The process that scheduled the alarm, which is the only one allowed to cancel it
+    owner: Option<xous::PID>,
+    cid: xous::CID,
+    opcode: usize,
+    arg: usize,
+    /// Elapsed time, in milliseconds, at which the alarm fires next
+    next_ms: TimeoutExpiry,
+    /// Time between firings, for repeating alarms
+    period: Option<TimeoutExpiry>,
+}
+
 impl core::fmt::Display for TimerRequest {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "TimerRequest {{ msec: {}, {} }}", self.msec, self.sender)
@@ -64,7 +81,7 @@ impl core::cmp::PartialEq for TimerRequest {
 #[cfg(any(target_os = "none", target_os = "xous"))]
 mod implementation {
     const TICKS_PER_MS: u64 = 1;
-    use super::TimerRequest;
+    use super::{RequestKind, TimerRequest};
     use susres::{RegManager, RegOrField, SuspendResume};
     use utralib::generated::*;
 
@@ -75,6 +92,7 @@ mod implementation {
         ticktimer_sr_manager: RegManager<{ utra::ticktimer::TICKTIMER_NUMREGS }>,
         wdt_sr_manager: RegManager<{ utra::wdt::WDT_NUMREGS }>,
         wdt: utralib::CSR<u32>,
+        timebase: crate::timebase::TimeBase,
     }
 
     fn handle_irq(_irq_no: usize, arg: *mut usize) {
@@ -84,8 +102,10 @@ mod implementation {
         // Safe because we're in an interrupt, and this interrupt is only
         // enabled when this value is not None.
         let response = xtt.current_response.take().unwrap();
-        xous::return_scalar(response.sender, response.kind as usize)
-            .expect("couldn't send response");
+        if response.kind != RequestKind::Alarm {
+            xous::return_scalar(response.sender, response.kind as usize)
+                .expect("couldn't send response");
+        }
 
         // Disable the timer
         xtt.csr.wfo(utra::ticktimer::EV_ENABLE_ALARM, 0);
@@ -135,6 +155,7 @@ mod implementation {
                 ticktimer_sr_manager,
                 wdt_sr_manager,
                 wdt: CSR::new(wdt.as_mut_ptr() as *mut u32),
+                timebase: crate::timebase::TimeBase::new(TICKS_PER_MS),
             };
 
             #[cfg(feature = "watchdog")]
@@ -166,6 +187,7 @@ mod implementation {
         pub fn reset(&mut self) {
             self.csr.wfo(utra::ticktimer::CONTROL_RESET, 0b1);
             self.csr.wo(utra::ticktimer::CONTROL, 0); // not paused, not reset -> free-run
+            self.timebase.reset();
         }
 
         pub fn raw_ticktime(&self) -> u64 {
@@ -176,7 +198,14 @@ mod implementation {
         }
 
         pub fn elapsed_ms(&self) -> u64 {
-            self.raw_ticktime() / TICKS_PER_MS
+            self.timebase.elapsed_ms(self.raw_ticktime())
+        }
+
+        /// Rebases elapsed time on the current tick count. The caller must stop and restart any
+        /// pending sleep around this, since its hardware target depends on the divider.
+        pub fn set_clock_divider(&mut self, divider: u64) {
+            let now = self.raw_ticktime();
+            self.timebase.set_clock_divider(now, divider);
         }
 
         pub fn stop_interrupt(&mut self) -> Option<TimerRequest> {
@@ -202,9 +231,10 @@ mod implementation {
         }
 
         pub fn schedule_response(&mut self, request: TimerRequest) {
-            let irq_target = request.msec;
+            let irq_target = self.timebase.target_ticks(request.msec);
             log::trace!(
-                "setting a response at {} ms (current time: {} ms)",
+                "setting a response at {} ms (tick {}, current time: {} ms)",
+                request.msec,
                 irq_target,
                 self.elapsed_ms()
             );
@@ -294,11 +324,7 @@ mod implementation {
     #[derive(Debug)]
     enum SleepComms {
         InterruptSleep,
-        StartSleep(
-            xous::MessageSender,
-            i64, /* ms */
-            u64, /* elapsed */
-        ),
+        StartSleep(TimerRequest, u64 /* elapsed */),
     }
     pub struct XousTickTimer {
         start: std::time::Instant,
@@ -323,10 +349,12 @@ mod implementation {
                     match result {
                         Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                             let response = current_response.take().unwrap();
-                            #[cfg(feature = "debug-print")]
-                            log::info!("Returning scalar to {}", response.sender);
-                            xous::return_scalar(response.sender, response.kind as usize)
-                                .expect("couldn't send response");
+                            if response.kind != RequestKind::Alarm {
+                                #[cfg(feature = "debug-print")]
+                                log::info!("Returning scalar to {}", response.sender);
+                                xous::return_scalar(response.sender, response.kind as usize)
+                                    .expect("couldn't send response");
+                            }
 
                             // This is dangerous and may panic if the queue is full.
                             xous::try_send_message(
@@ -349,33 +377,28 @@ mod implementation {
                             timeout = None;
                             time_remaining_sender.send(current_response.take()).unwrap()
                         }
-                        Ok(SleepComms::StartSleep(new_sender, expiry, elapsed)) => {
-                            let mut duration = expiry - (elapsed as i64);
+                        Ok(SleepComms::StartSleep(request, elapsed)) => {
+                            let mut duration = request.msec - (elapsed as i64);
                             if duration > 0 {
                                 #[cfg(feature = "debug-print")]
                                 log::info!(
                                     "Starting sleep for {} ms, returning to {}",
                                     duration,
-                                    new_sender
+                                    request.sender
                                 );
                             } else {
                                 #[cfg(feature = "debug-print")]
                                 log::info!(
                                     "Clamping duration to 0 (was: {})m returning to {}",
                                     duration,
-                                    new_sender
+                                    request.sender
                                 );
                                 duration = 0;
                             }
                             timeout = Some(std::time::Duration::from_millis(
                                 duration.try_into().unwrap(),
                             ));
-                            current_response = Some(TimerRequest {
-                                sender: new_sender,
-                                msec: expiry,
-                                kind: RequestKind::Sleep,
-                                data: 0,
-                            });
+                            current_response = Some(request);
                         }
                     }
                 }
@@ -411,11 +434,7 @@ mod implementation {
                 request.sender
             );
             self.sleep_comms
-                .send(SleepComms::StartSleep(
-                    request.sender,
-                    request.msec as i64,
-                    self.elapsed_ms(),
-                ))
+                .send(SleepComms::StartSleep(request, self.elapsed_ms()))
                 .unwrap();
         }
 
@@ -432,6 +451,9 @@ mod implementation {
         }
         pub fn suspend(&self) {}
         pub fn resume(&self) {}
+        pub fn set_clock_divider(&mut self, divider: u64) {
+            log::info!("hosted mode: ignoring clock divider change to {}", divider);
+        }
     }
 }
 
@@ -516,6 +538,123 @@ fn recalculate_sleep(
     start_sleep(ticktimer, sleep_heap);
 }
 
+/// Add a request to the sleep heap that wakes us up at `msec` to send alarm `id`. The
+/// timer must be stopped while this is called.
+fn queue_alarm(
+    sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
+    id: u32,
+    mut msec: TimeoutExpiry,
+) {
+    while sleep_heap.contains_key(&msec) {
+        msec += 1;
+    }
+    sleep_heap.insert(
+        msec,
+        TimerRequest {
+            msec,
+            sender: xous::MessageSender::from_usize(0),
+            kind: RequestKind::Alarm,
+            data: id as usize,
+        },
+    );
+}
+
+/// Remove any pending wakeups for alarm `id`. The timer must be stopped while this is called.
+fn unqueue_alarm(sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, id: u32) {
+    sleep_heap.retain(|_, v| !(v.kind == RequestKind::Alarm && v.data == id as usize));
+}
+
+/// Forget alarm `id`, returning its connection if no other alarm shares it. Connections
+/// to the same server are shared within a process, so the last alarm to go releases it.
+fn remove_alarm(alarms: &mut HashMap<u32, Alarm>, id: u32) -> Option<xous::CID> {
+    let alarm = alarms.remove(&id)?;
+    if alarms.values().any(|other| other.cid == alarm.cid) {
+        None
+    } else {
+        Some(alarm.cid)
+    }
+}
+
+/// Release connections returned by `remove_alarm()`
+fn disconnect_alarms(cids: Vec<xous::CID>) {
+    for cid in cids {
+        // Safe because no alarm refers to the connection anymore, and it is only
+        // used for alarms.
+        unsafe { xous::disconnect(cid) }.ok();
+    }
+}
+
+/// Hand every alarm that is due at `now`, as well as alarm `fired`, to `send`, in the
+/// order they came due. Repeating alarms are queued up again, skipping any periods that
+/// were missed (for example, while the system was suspended) rather than sending them all
+/// at once. Returns the connections that are no longer used by any alarm. The timer must
+/// be stopped while this is called.
+fn take_due_alarms(
+    sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
+    alarms: &mut HashMap<u32, Alarm>,
+    now: TimeoutExpiry,
+    fired: Option<u32>,
+    mut send: impl FnMut(u32, &Alarm) -> Result<(), xous::Error>,
+) -> Vec<xous::CID> {
+    let mut due: Vec<(TimeoutExpiry, u32)> = alarms
+        .iter()
+        .filter(|(id, alarm)| alarm.next_ms <= now || fired == Some(**id))
+        .map(|(id, alarm)| (alarm.next_ms, *id))
+        .collect();
+    due.sort_unstable();
+
+    let mut released = Vec::new();
+    for (_, id) in due {
+        unqueue_alarm(sleep_heap, id);
+        let alarm = alarms.get_mut(&id).unwrap();
+        match (send(id, alarm), alarm.period) {
+            // Nobody is left to receive this alarm
+            (Err(xous::Error::ServerNotFound), _) => {
+                info!("alarm {} has no server anymore, dropping it", id);
+                released.extend(remove_alarm(alarms, id));
+            }
+            (_, Some(period)) => {
+                alarm.next_ms += period;
+                while alarm.next_ms <= now {
+                    alarm.next_ms += period;
+                }
+                queue_alarm(sleep_heap, id, alarm.next_ms);
+            }
+            (_, None) => {
+                released.extend(remove_alarm(alarms, id));
+            }
+        }
+    }
+    released
+}
+
+/// Send every alarm that is due, as well as alarm `fired` if the timer says it has gone off.
+fn fire_alarms(
+    ticktimer: &mut XousTickTimer,
+    sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
+    alarms: &mut HashMap<u32, Alarm>,
+    fired: Option<u32>,
+) {
+    let now = ticktimer.elapsed_ms() as TimeoutExpiry;
+    if !alarms
+        .iter()
+        .any(|(id, alarm)| alarm.next_ms <= now || fired == Some(*id))
+    {
+        return;
+    }
+
+    stop_sleep(ticktimer, sleep_heap);
+    let released = take_due_alarms(sleep_heap, alarms, now, fired, |id, alarm| {
+        xous::try_send_message(
+            alarm.cid,
+            xous::Message::new_scalar(alarm.opcode, alarm.arg, id as usize, 0, 0),
+        )
+        .map(|_| ())
+    });
+    start_sleep(ticktimer, sleep_heap);
+    disconnect_alarms(released);
+}
+
 #[xous::xous_main]
 fn xmain() -> ! {
     log_server::init_wait().unwrap();
@@ -587,6 +726,11 @@ fn xmain() -> ! {
     let mut mutex_hash: HashMap<Option<xous::PID>, HashMap<usize, VecDeque<xous::MessageSender>>> =
         HashMap::new();
 
+    // Alarms scheduled by `ScheduleAlarm` and `ScheduleRepeating`, indexed by alarm ID. Each
+    // pending alarm also has a `RequestKind::Alarm` entry in the sleep heap to wake us up.
+    let mut alarms: HashMap<u32, Alarm> = HashMap::new();
+    let mut next_alarm_id: u32 = 1;
+
     loop {
         #[cfg(feature = "watchdog")]
         ticktimer.reset_wdt();
@@ -649,6 +793,15 @@ fn xmain() -> ! {
                             entries.remove(idx);
                         }
                     }
+
+                    let fired = if sender_pid == xous::process::id()
+                        && request_kind == RequestKind::Alarm as usize
+                    {
+                        Some(condvar as u32)
+                    } else {
+                        None
+                    };
+                    fire_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms, fired);
                 }
                 recalculate_sleep(&mut ticktimer, &mut sleep_heap, None);
             }
@@ -658,7 +811,74 @@ fn xmain() -> ! {
                     .suspend_until_resume(token)
                     .expect("couldn't execute suspend/resume");
                 ticktimer.resume();
+                // catch up on any alarms that came due while we were suspended
+                fire_alarms(&mut ticktimer, &mut sleep_heap, &mut alarms, None);
+            }),
+            Some(api::Opcode::SetClockDivider) => xous::msg_blocking_scalar_unpack!(msg, divider, _, _, _, {
+                // pending sleeps are stored as absolute milliseconds, so they only need their
+                // hardware target recomputed once the time base has moved
+                stop_sleep(&mut ticktimer, &mut sleep_heap);
+                ticktimer.set_clock_divider(divider as u64);
+                start_sleep(&mut ticktimer, &mut sleep_heap);
+                xous::return_scalar(msg.sender, 0).expect("couldn't confirm clock divider change");
             }),
+            Some(api::Opcode::ScheduleAlarm) | Some(api::Opcode::ScheduleRepeating) => {
+                let repeating = msg.body.id() == api::Opcode::ScheduleRepeating as usize;
+                let mut buf = unsafe {
+                    xous_ipc::Buffer::from_memory_message_mut(
+                        msg.body.memory_message_mut().unwrap(),
+                    )
+                };
+                let mut request = buf.to_original::<api::AlarmRequest, _>().unwrap();
+                request.id = 0;
+                match xous::try_connect(xous::SID::from_array(request.sid)) {
+                    Ok(cid) => {
+                        // a repeating alarm with no period would never let us do anything else
+                        let ms = request.ms as TimeoutExpiry;
+                        let ms = if repeating { ms.max(1) } else { ms };
+                        let alarm = Alarm {
+                            owner: msg.sender.pid(),
+                            cid,
+                            opcode: request.opcode as usize,
+                            arg: request.arg as usize,
+                            next_ms: ticktimer.elapsed_ms() as TimeoutExpiry + ms,
+                            period: if repeating { Some(ms) } else { None },
+                        };
+                        let id = next_alarm_id;
+                        // 0 is reserved to indicate failure
+                        next_alarm_id = next_alarm_id.wrapping_add(1).max(1);
+
+                        stop_sleep(&mut ticktimer, &mut sleep_heap);
+                        queue_alarm(&mut sleep_heap, id, alarm.next_ms);
+                        start_sleep(&mut ticktimer, &mut sleep_heap);
+                        alarms.insert(id, alarm);
+                        request.id = id;
+                    }
+                    Err(e) => error!("couldn't connect to server for alarm: {:?}", e),
+                }
+                buf.replace(request).unwrap();
+            }
+            Some(api::Opcode::CancelAlarm) => {
+                xous::msg_blocking_scalar_unpack!(msg, id, _, _, _, {
+                    let id = id as u32;
+                    let cancelled = if alarms
+                        .get(&id)
+                        .map(|a| a.owner == msg.sender.pid())
+                        .unwrap_or(false)
+                    {
+                        let released = remove_alarm(&mut alarms, id);
+                        stop_sleep(&mut ticktimer, &mut sleep_heap);
+                        unqueue_alarm(&mut sleep_heap, id);
+                        start_sleep(&mut ticktimer, &mut sleep_heap);
+                        disconnect_alarms(released.into_iter().collect());
+                        1
+                    } else {
+                        0
+                    };
+                    xous::return_scalar(msg.sender, cancelled)
+                        .expect("couldn't confirm alarm cancellation");
+                })
+            }
             Some(api::Opcode::PingWdt) => {
                 ticktimer.reset_wdt();
             }
@@ -847,3 +1067,129 @@ fn xmain() -> ! {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alarm(cid: xous::CID, next_ms: TimeoutExpiry, period: Option<TimeoutExpiry>) -> Alarm {
+        Alarm { owner: None, cid, opcode: 0, arg: 0, next_ms, period }
+    }
+
+    fn schedule(
+        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
+        alarms: &mut HashMap<u32, Alarm>,
+        id: u32,
+        alarm: Alarm,
+    ) {
+        queue_alarm(sleep_heap, id, alarm.next_ms);
+        alarms.insert(id, alarm);
+    }
+
+    fn queued(sleep_heap: &BTreeMap<TimeoutExpiry, TimerRequest>) -> Vec<(TimeoutExpiry, usize)> {
+        sleep_heap.iter().map(|(msec, request)| (*msec, request.data)).collect()
+    }
+
+    #[test]
+    fn queue_alarm_keeps_expiries_unique() {
+        let mut sleep_heap = BTreeMap::new();
+        queue_alarm(&mut sleep_heap, 1, 100);
+        queue_alarm(&mut sleep_heap, 2, 100);
+        queue_alarm(&mut sleep_heap, 3, 101);
+        queue_alarm(&mut sleep_heap, 4, 50);
+        assert_eq!(queued(&sleep_heap), vec![(50, 4), (100, 1), (101, 2), (102, 3)]);
+        assert!(sleep_heap.values().all(|request| request.kind == RequestKind::Alarm));
+    }
+
+    #[test]
+    fn due_alarms_fire_in_order() {
+        let mut sleep_heap = BTreeMap::new();
+        let mut alarms = HashMap::new();
+        schedule(&mut sleep_heap, &mut alarms, 1, alarm(10, 300, None));
+        schedule(&mut sleep_heap, &mut alarms, 2, alarm(11, 100, None));
+        schedule(&mut sleep_heap, &mut alarms, 3, alarm(12, 200, None));
+        schedule(&mut sleep_heap, &mut alarms, 4, alarm(13, 500, None));
+
+        let mut sent = Vec::new();
+        let released = take_due_alarms(&mut sleep_heap, &mut alarms, 300, None, |id, _| {
+            sent.push(id);
+            Ok(())
+        });
+        assert_eq!(sent, vec![2, 3, 1]);
+        assert_eq!(released, vec![11, 12, 10]);
+        assert_eq!(alarms.keys().copied().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(queued(&sleep_heap), vec![(500, 4)]);
+    }
+
+    #[test]
+    fn fired_alarm_is_sent_early() {
+        let mut sleep_heap = BTreeMap::new();
+        let mut alarms = HashMap::new();
+        schedule(&mut sleep_heap, &mut alarms, 1, alarm(10, 100, None));
+        schedule(&mut sleep_heap, &mut alarms, 2, alarm(11, 200, None));
+
+        let mut sent = Vec::new();
+        take_due_alarms(&mut sleep_heap, &mut alarms, 99, Some(1), |id, _| {
+            sent.push(id);
+            Ok(())
+        });
+        assert_eq!(sent, vec![1]);
+        assert_eq!(queued(&sleep_heap), vec![(200, 2)]);
+    }
+
+    #[test]
+    fn repeating_alarm_skips_missed_periods() {
+        let mut sleep_heap = BTreeMap::new();
+        let mut alarms = HashMap::new();
+        schedule(&mut sleep_heap, &mut alarms, 1, alarm(10, 100, Some(50)));
+
+        let mut sent = 0;
+        let released = take_due_alarms(&mut sleep_heap, &mut alarms, 260, None, |_, _| {
+            sent += 1;
+            Ok(())
+        });
+        assert_eq!(sent, 1);
+        assert!(released.is_empty());
+        assert_eq!(alarms[&1].next_ms, 300);
+        assert_eq!(queued(&sleep_heap), vec![(300, 1)]);
+    }
+
+    #[test]
+    fn alarm_without_server_is_dropped() {
+        let mut sleep_heap = BTreeMap::new();
+        let mut alarms = HashMap::new();
+        schedule(&mut sleep_heap, &mut alarms, 1, alarm(10, 100, Some(50)));
+
+        let released = take_due_alarms(&mut sleep_heap, &mut alarms, 100, None, |_, _| {
+            Err(xous::Error::ServerNotFound)
+        });
+        assert_eq!(released, vec![10]);
+        assert!(alarms.is_empty());
+        assert!(sleep_heap.is_empty());
+    }
+
+    #[test]
+    fn cancel_releases_the_last_user_of_a_connection() {
+        let mut sleep_heap = BTreeMap::new();
+        let mut alarms = HashMap::new();
+        schedule(&mut sleep_heap, &mut alarms, 1, alarm(10, 100, None));
+        schedule(&mut sleep_heap, &mut alarms, 2, alarm(10, 200, Some(100)));
+        schedule(&mut sleep_heap, &mut alarms, 3, alarm(11, 300, None));
+
+        assert_eq!(remove_alarm(&mut alarms, 2), None);
+        unqueue_alarm(&mut sleep_heap, 2);
+        assert_eq!(queued(&sleep_heap), vec![(100, 1), (300, 3)]);
+
+        assert_eq!(remove_alarm(&mut alarms, 1), Some(10));
+        unqueue_alarm(&mut sleep_heap, 1);
+        assert_eq!(remove_alarm(&mut alarms, 1), None);
+
+        // a cancelled alarm is not sent once its time has come
+        let mut sent = Vec::new();
+        take_due_alarms(&mut sleep_heap, &mut alarms, 1000, None, |id, _| {
+            sent.push(id);
+            Ok(())
+        });
+        assert_eq!(sent, vec![3]);
+    }
+}
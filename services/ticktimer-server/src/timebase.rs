@@ -0,0 +1,105 @@
+/// Converts between hardware ticks and elapsed milliseconds across changes of the system
+/// clock divider. The tick prescaler is fixed in hardware, so when llio slows the clock
+/// down, every tick counted from then on covers `clock_divider` times as many milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeBase {
+    ticks_per_ms: u64,
+    /// milliseconds per hardware tick prescaler period
+    clock_divider: u64,
+    /// hardware tick count at the last clock divider change
+    base_ticks: u64,
+    /// elapsed milliseconds at the last clock divider change
+    base_ms: u64,
+}
+
+impl TimeBase {
+    pub const fn new(ticks_per_ms: u64) -> TimeBase {
+        TimeBase { ticks_per_ms, clock_divider: 1, base_ticks: 0, base_ms: 0 }
+    }
+
+    /// Forgets any previous rebasing, for when the hardware counter is reset to 0
+    pub fn reset(&mut self) {
+        self.base_ticks = 0;
+        self.base_ms = 0;
+    }
+
+    pub fn elapsed_ms(&self, now: u64) -> u64 {
+        self.base_ms + (now - self.base_ticks) / self.ticks_per_ms * self.clock_divider
+    }
+
+    /// Converts a target time in milliseconds into the hardware tick count at which it is reached,
+    /// rounding up so that sleeps never return early.
+    pub fn target_ticks(&self, msec: i64) -> u64 {
+        let remaining = (msec.max(0) as u64).saturating_sub(self.base_ms);
+        self.base_ticks + (remaining + self.clock_divider - 1) / self.clock_divider * self.ticks_per_ms
+    }
+
+    /// Rebases elapsed time on `now`, so that ticks counted from here on are scaled by the new
+    /// divider. Any hardware target computed with `target_ticks()` before this is stale.
+    pub fn set_clock_divider(&mut self, now: u64, divider: u64) {
+        self.base_ms = self.elapsed_ms(now);
+        self.base_ticks = now;
+        self.clock_divider = divider.max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undivided() {
+        let tb = TimeBase::new(1);
+        assert_eq!(tb.elapsed_ms(0), 0);
+        assert_eq!(tb.elapsed_ms(1234), 1234);
+        assert_eq!(tb.target_ticks(1234), 1234);
+        assert_eq!(tb.target_ticks(-5), 0);
+    }
+
+    #[test]
+    fn rebase_keeps_time_continuous() {
+        let mut tb = TimeBase::new(1);
+        tb.set_clock_divider(1000, 2);
+        // nothing jumps at the switch itself
+        assert_eq!(tb.elapsed_ms(1000), 1000);
+        // afterwards each tick is worth two milliseconds
+        assert_eq!(tb.elapsed_ms(1010), 1020);
+        tb.set_clock_divider(1010, 1);
+        assert_eq!(tb.elapsed_ms(1010), 1020);
+        assert_eq!(tb.elapsed_ms(1015), 1025);
+    }
+
+    #[test]
+    fn targets_round_up() {
+        let mut tb = TimeBase::new(1);
+        tb.set_clock_divider(100, 2);
+        assert_eq!(tb.target_ticks(100), 100);
+        // 101 ms is half a tick away: wake on the tick after it, never before
+        assert_eq!(tb.target_ticks(101), 101);
+        assert_eq!(tb.target_ticks(102), 101);
+        assert_eq!(tb.target_ticks(103), 102);
+        // a target in the past is due right away
+        assert_eq!(tb.target_ticks(50), 100);
+        for msec in 100..200 {
+            assert!(tb.elapsed_ms(tb.target_ticks(msec)) >= msec as u64);
+        }
+    }
+
+    #[test]
+    fn prescaled_ticks() {
+        let mut tb = TimeBase::new(4);
+        assert_eq!(tb.elapsed_ms(40), 10);
+        tb.set_clock_divider(40, 2);
+        assert_eq!(tb.elapsed_ms(48), 14);
+        assert_eq!(tb.target_ticks(13), 48);
+        tb.reset();
+        assert_eq!(tb.elapsed_ms(0), 0);
+    }
+
+    #[test]
+    fn zero_divider_is_treated_as_one() {
+        let mut tb = TimeBase::new(1);
+        tb.set_clock_divider(0, 0);
+        assert_eq!(tb.elapsed_ms(7), 7);
+    }
+}
@@ -1,6 +1,10 @@
 #[allow(dead_code)]
 pub const AUTHENTICATE_TIMEOUT: u32 = 10_000; // time in ms that a process has to respond to an authentication request
 
+use core::convert::TryInto;
+use aes::{Aes256, BlockEncrypt, NewBlockCipher};
+use aes::cipher::generic_array::GenericArray;
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive)]
 #[non_exhaustive]
 #[repr(C)]
@@ -76,6 +80,8 @@ pub(crate) enum Return {
 pub(crate) struct Registration {
     pub name: xous_ipc::String<64>,
     pub conn_limit: Option<u32>,
+    /// if specified, processes that can prove knowledge of this key may connect beyond `conn_limit`
+    pub auth_key: Option<[u32; 8]>,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -99,6 +105,60 @@ pub(crate) struct AuthenticateRequest {
     pub challenge: [u32; 4],
 }
 
+/// Computes the ID of an authentication key, which is handed out in an `AuthenticateRequest` so the
+/// requester can confirm it holds the right key before answering. This is a key check value: a zero block
+/// encrypted once and then again under the key, truncated to 160 bits. It does not reveal the key.
+pub fn authenticate_key_id(key: &[u32; 8]) -> [u8; 20] {
+    let cipher = auth_cipher(key);
+    let mut block = [0u8; 16];
+    let mut id = [0u8; 20];
+    cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
+    id[..16].copy_from_slice(&block);
+    cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
+    id[16..].copy_from_slice(&block[..4]);
+    id
+}
+
+/// Computes the response to an authentication challenge: an AES-256 CBC-MAC under the shared key, over the
+/// challenge, then a block holding the length of the server name, then the whole name, zero-padded to a block
+/// boundary. Including the name binds the response to the server that issued the challenge; leading with its
+/// length keeps names that differ only in trailing zero padding apart. The first MAC block (the encrypted
+/// challenge) and the final one are returned.
+pub fn authenticate_response(key: &[u32; 8], name: &str, challenge: &[u32; 4]) -> [u32; 8] {
+    let cipher = auth_cipher(key);
+    let mut block = [0u8; 16];
+    for (dst, src) in block.chunks_mut(4).zip(challenge.iter()) {
+        dst.copy_from_slice(&src.to_le_bytes());
+    }
+    cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
+    let mut response = [0u32; 8];
+    for (dst, src) in response[..4].iter_mut().zip(block.chunks(4)) {
+        *dst = u32::from_le_bytes(src.try_into().unwrap());
+    }
+    for (dst, src) in block.iter_mut().zip((name.len() as u64).to_le_bytes().iter()) {
+        *dst ^= src;
+    }
+    cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
+    for chunk in name.as_bytes().chunks(16) {
+        for (dst, src) in block.iter_mut().zip(chunk.iter()) {
+            *dst ^= src;
+        }
+        cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
+    }
+    for (dst, src) in response[4..].iter_mut().zip(block.chunks(4)) {
+        *dst = u32::from_le_bytes(src.try_into().unwrap());
+    }
+    response
+}
+
+fn auth_cipher(key: &[u32; 8]) -> Aes256 {
+    let mut key_bytes = [0u8; 32];
+    for (dst, src) in key_bytes.chunks_mut(4).zip(key.iter()) {
+        dst.copy_from_slice(&src.to_le_bytes());
+    }
+    Aes256::new(GenericArray::from_slice(&key_bytes))
+}
+
 //////////////////////////////////////////////////////////////////////////////////////////////
 // We keep XousServerName around because want to be able to index off the server name, without
 // burdening the Kernel String type with the Hash32 methods
@@ -237,3 +297,39 @@ impl core::fmt::Display for XousServerName {
         write!(f, "{}", self.to_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_KEY: [u32; 8] = [
+        0x03020100, 0x07060504, 0x0b0a0908, 0x0f0e0d0c,
+        0x13121110, 0x17161514, 0x1b1a1918, 0x1f1e1d1c,
+    ];
+    const TEST_CHALLENGE: [u32; 4] = [0xdeadbeef, 0x01234567, 0x89abcdef, 0xfeedface];
+    #[test]
+    fn test_auth_key_id() {
+        // AES-256 of a zero block under the key 00..1f, then re-encrypted
+        assert_eq!(authenticate_key_id(&TEST_KEY), [
+            0xf2, 0x90, 0x00, 0xb6, 0x2a, 0x49, 0x9f, 0xd0, 0xa9, 0xf3,
+            0x9a, 0x6a, 0xdd, 0x2e, 0x77, 0x80, 0xd4, 0xe9, 0x69, 0x25,
+        ]);
+    }
+    #[test]
+    fn test_auth_response() {
+        assert_eq!(authenticate_response(&TEST_KEY, "test-auth-server", &TEST_CHALLENGE), [
+            0xeb14ca96, 0x639c17b4, 0x6c1deb51, 0xb0c9094a,
+            0xfa9d87b9, 0xf68a0f39, 0x99c54afa, 0x0367a810,
+        ]);
+    }
+    #[test]
+    fn test_auth_response_binding() {
+        let reference = authenticate_response(&TEST_KEY, "test-auth-server", &TEST_CHALLENGE);
+        let mut other_key = TEST_KEY;
+        other_key[7] ^= 1;
+        assert_ne!(authenticate_response(&other_key, "test-auth-server", &TEST_CHALLENGE), reference);
+        assert_ne!(authenticate_response(&TEST_KEY, "test-auth-serveR", &TEST_CHALLENGE), reference);
+        let mut other_challenge = TEST_CHALLENGE;
+        other_challenge[0] ^= 1;
+        assert_ne!(authenticate_response(&TEST_KEY, "test-auth-server", &other_challenge), reference);
+    }
+}
@@ -56,10 +56,31 @@ impl XousNames {
         &self,
         name: &str,
         max_conns: Option<u32>,
+    ) -> Result<xous::SID, xous::Error> {
+        self.register(name, max_conns, None)
+    }
+
+    /// Registers a server that, in addition to its `max_conns` inherently trusted connections, admits any
+    /// process that can prove knowledge of `auth_key` via `request_connection_authenticated()`.
+    pub fn register_name_authenticated(
+        &self,
+        name: &str,
+        max_conns: Option<u32>,
+        auth_key: [u32; 8],
+    ) -> Result<xous::SID, xous::Error> {
+        self.register(name, max_conns, Some(auth_key))
+    }
+
+    fn register(
+        &self,
+        name: &str,
+        max_conns: Option<u32>,
+        auth_key: Option<[u32; 8]>,
     ) -> Result<xous::SID, xous::Error> {
         let mut registration = api::Registration {
             name: String::<64>::new(),
             conn_limit: max_conns,
+            auth_key,
         };
         // could also do String::from_str() but in this case we want things to fail if the string is too long.
         write!(registration.name, "{}", name).expect("name probably too long");
@@ -93,7 +114,7 @@ impl XousNames {
 
         match buf.to_original().unwrap() {
             api::Return::CID((cid, token)) => Ok((cid, token)),
-            // api::Return::AuthenticateRequest(_) => Err(xous::Error::AccessDenied),
+            api::Return::AuthenticateRequest(_) => Err(xous::Error::AccessDenied),
             _ => Err(xous::Error::ServerNotFound),
         }
     }
@@ -123,11 +144,46 @@ impl XousNames {
 
         match buf.to_original().unwrap() {
             api::Return::CID((cid, _)) => Ok(cid),
-            // api::Return::AuthenticateRequest(_) => Err(xous::Error::AccessDenied),
+            api::Return::AuthenticateRequest(_) => Err(xous::Error::AccessDenied),
             _ => Err(xous::Error::ServerNotFound),
         }
     }
 
+    /// Connects to a server registered with `register_name_authenticated()`. If the server still has
+    /// inherently trusted connection slots open, this behaves like `request_connection()`; otherwise, the
+    /// name server issues a challenge, which is answered using `auth_key`.
+    pub fn request_connection_authenticated(&self, name: &str, auth_key: &[u32; 8]) -> Result<xous::CID, xous::Error> {
+        let mut lookup_name = xous_ipc::String::<64>::new();
+        write!(lookup_name, "{}", name).expect("name problably too long");
+
+        let mut buf = Buffer::into_buf(lookup_name).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::Lookup.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        let request = match buf.to_original().unwrap() {
+            api::Return::CID((cid, _)) => return Ok(cid),
+            api::Return::AuthenticateRequest(request) => request,
+            _ => return Err(xous::Error::ServerNotFound),
+        };
+        // don't hand out a response computed with the wrong key
+        if request.pubkey_id != api::authenticate_key_id(auth_key) {
+            return Err(xous::Error::AccessDenied);
+        }
+        let auth_lookup = api::AuthenticatedLookup {
+            name: request.name,
+            pubkey_id: request.pubkey_id,
+            response: api::authenticate_response(auth_key, name, &request.challenge),
+        };
+        let mut buf = Buffer::into_buf(auth_lookup).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::AuthenticatedLookup.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::CID((cid, _)) => Ok(cid),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
     /// note: you probably want to use this one, to avoid synchronization issues on startup as servers register asynhcronously
     pub fn request_connection_blocking(&self, name: &str) -> Result<xous::CID, xous::Error> {
         loop {
@@ -163,11 +219,6 @@ impl XousNames {
             Err(xous::Error::InternalError)
         }
     }
-
-    // todo:
-    // pub fn authenticated_connection(&self, name: &str, key: Authkey)
-    // this function will create an authenticated connection, if such are allowed
-    // it's intended for use by dynamically-loaded third-party apps. As of Xous 0.8 this isn't supported, so it's just a "todo"
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -109,10 +109,23 @@ struct Connection {
     pub sid: xous::SID,
     pub current_conns: u32, // number of unauthenticated (inherentely trusted) connections
     pub max_conns: Option<u32>, // if None, unlimited connections allowed
-    pub _allow_authenticate: bool,
-    pub _auth_conns: u32,        // number of authenticated connections
+    pub auth_key: Option<AuthKey>, // if Some, processes that know this key may connect once max_conns is reached
+    pub auth_conns: u32,        // number of authenticated connections
     pub token: Option<[u32; 4]>, // a random number that must be presented to allow for disconnection for single-connection servers
 }
+/// Shared secret for authenticated connections. Wrapped so that dumps of the name table don't leak it.
+#[derive(Copy, Clone)]
+struct AuthKey([u32; 8]);
+impl core::fmt::Debug for AuthKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "AuthKey({:x?})", authenticate_key_id(&self.0))
+    }
+}
+/// A challenge that has been issued to a process, along with the response we expect back.
+struct PendingAuth {
+    pub response: [u32; 8],
+    pub issued: std::time::Instant,
+}
 #[derive(Debug)]
 struct CheckedHashMap {
     pub map: HashMap<XousServerName, Connection>,
@@ -128,6 +141,7 @@ impl CheckedHashMap {
         name: XousServerName,
         sid: xous::SID,
         max_conns: Option<u32>,
+        auth_key: Option<[u32; 8]>,
     ) -> Result<(), xous::Error> {
         let token = if max_conns == Some(1) {
             // for the special case of 1-connection servers, provision a one-time use token for disconnects
@@ -145,8 +159,8 @@ impl CheckedHashMap {
                 sid,
                 current_conns: 0,
                 max_conns,
-                _allow_authenticate: false, // for now, we don't support authenticated connections
-                _auth_conns: 0,
+                auth_key: auth_key.map(|k| AuthKey(k)),
+                auth_conns: 0,
                 token,
             },
         );
@@ -205,6 +219,34 @@ impl CheckedHashMap {
         }
     }
 
+    /// If the named server accepts authenticated connections, returns the ID of its key and the response
+    /// expected to `challenge`.
+    pub fn auth_challenge(&self, name: &XousServerName, challenge: &[u32; 4]) -> Option<([u8; 20], [u32; 8])> {
+        let key = self.map.get(name)?.auth_key?;
+        Some((authenticate_key_id(&key.0), authenticate_response(&key.0, name.to_str(), challenge)))
+    }
+
+    /// Admits a connection that has passed authentication. Authenticated connections are counted separately,
+    /// and do not count against the server's connection limit.
+    pub fn authenticated_connect(&mut self, name: &XousServerName) -> Option<xous::SID> {
+        let entry = self.map.get_mut(name)?;
+        if entry.auth_key.is_some() {
+            (*entry).auth_conns += 1;
+            Some(entry.sid)
+        } else {
+            None
+        }
+    }
+
+    /// Reverses an `authenticated_connect()` that could not be completed.
+    pub fn authenticated_disconnect(&mut self, name: &XousServerName) {
+        if let Some(entry) = self.map.get_mut(name) {
+            if entry.auth_conns > 0 {
+                (*entry).auth_conns -= 1;
+            }
+        }
+    }
+
     pub fn trusted_init_done(&self) -> bool {
         let mut trusted_done = true;
         for (name, entry) in self.map.iter() {
@@ -261,6 +303,12 @@ impl CheckedHashMap {
     }
 }
 
+/// Compares two authentication responses without short-circuiting, so the comparison time doesn't leak
+/// how many words matched.
+fn response_matches(a: &[u32; 8], b: &[u32; 8]) -> bool {
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 fn name_from_msg(env: &MessageEnvelope) -> Result<XousServerName, ConnectError> {
     let msg = env
         .body
@@ -371,6 +419,8 @@ fn xmain() -> ! {
     // this limits the number of available servers to be requested to 128...!
     //let mut name_table = FnvIndexMap::<XousServerName, xous::SID, 128>::new();
     let mut name_table = CheckedHashMap::new();
+    // outstanding authentication challenges, indexed by the requesting process and the server it asked for
+    let mut pending_auth: HashMap<(xous::PID, XousServerName), PendingAuth> = HashMap::new();
 
     info!("started");
     loop {
@@ -396,7 +446,7 @@ fn xmain() -> ! {
                     let new_sid =
                         xous::create_server_id().expect("create server failed, maybe OOM?");
                     name_table
-                        .insert(name, new_sid, registration.conn_limit)
+                        .insert(name, new_sid, registration.conn_limit, registration.auth_key)
                         .expect("register name failure, maybe out of HashMap capacity?");
                     log::trace!("request successful, SID is {:?}", new_sid);
                     should_connect = true;
@@ -505,35 +555,89 @@ fn xmain() -> ! {
                         }
                     }
                 } else {
-                    log::debug!("Can't find request '{}' in table, dumping table:", name);
-                    for (_name, conn) in name_table.map.iter() {
-                        log::debug!("{:?}", conn);
-                    }
-                    // no authenticate remedy currently supported, but we'd put that code somewhere around here eventually.
                     let (c1, c2, c3, c4) = xous::create_server_id().unwrap().to_u32();
-                    let auth_request = AuthenticateRequest {
-                        name: String::<64>::from_str(
-                            name_string
-                                .as_str()
-                                .expect("couldn't convert server name to string"),
-                        ),
-                        pubkey_id: [0; 20], // placeholder
-                        challenge: [c1, c2, c3, c4],
-                    };
-                    d11ctimeout.hosted_delay();
-                    response = api::Return::AuthenticateRequest(auth_request) // this code just exists to exercise the return path
+                    let challenge = [c1, c2, c3, c4];
+                    if let Some((pubkey_id, expected)) = name_table.auth_challenge(&name, &challenge) {
+                        let sender_pid = msg
+                            .sender
+                            .pid()
+                            .expect("can't extract sender PID on Lookup");
+                        log::trace!("issuing authentication challenge for '{}' to {:?}", name, sender_pid);
+                        // a new Lookup replaces any challenge still outstanding for the same process and server
+                        pending_auth.insert(
+                            (sender_pid, name),
+                            PendingAuth {
+                                response: expected,
+                                issued: std::time::Instant::now(),
+                            },
+                        );
+                        let auth_request = AuthenticateRequest {
+                            name: String::<64>::from_str(
+                                name_string
+                                    .as_str()
+                                    .expect("couldn't convert server name to string"),
+                            ),
+                            pubkey_id,
+                            challenge,
+                        };
+                        response = api::Return::AuthenticateRequest(auth_request)
+                    } else {
+                        log::debug!("Can't find request '{}' in table, dumping table:", name);
+                        for (_name, conn) in name_table.map.iter() {
+                            log::debug!("{:?}", conn);
+                        }
+                        d11ctimeout.hosted_delay();
+                        response = api::Return::Failure
+                    }
                 }
                 buffer
                     .replace(response)
                     .expect("Lookup can't serialize return value");
             }
             Some(api::Opcode::AuthenticatedLookup) => {
+                let sender_pid = msg
+                    .sender
+                    .pid()
+                    .expect("can't extract sender PID on AuthenticatedLookup");
                 let mem = msg.body.memory_message_mut().unwrap();
-                let buffer = unsafe { Buffer::from_memory_message_mut(mem) };
-                let auth_lookup: AuthenticatedLookup = buffer.to_original().unwrap();
-                info!("AuthenticatedLookup request {:?}", auth_lookup);
-                error!("AuthenticatedLookup not yet implemented");
-                unimplemented!("AuthenticatedLookup not yet implemented");
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let auth_lookup = buffer.to_original::<AuthenticatedLookup, _>().unwrap();
+                let name = XousServerName::from_str(
+                    auth_lookup
+                        .name
+                        .as_str()
+                        .expect("couldn't convert server name to string"),
+                );
+                log::trace!("AuthenticatedLookup request for '{}' from {:?}", name, sender_pid);
+                // expire stale challenges, so the table doesn't grow without bound
+                pending_auth.retain(|_, p| p.issued.elapsed().as_millis() < AUTHENTICATE_TIMEOUT as u128);
+                // challenges are single-use: remove it whether or not the response is correct
+                let authenticated = match pending_auth.remove(&(sender_pid, name)) {
+                    Some(pending) => response_matches(&pending.response, &auth_lookup.response),
+                    None => false,
+                };
+                let mut response = api::Return::Failure;
+                if authenticated {
+                    if let Some(server_sid) = name_table.authenticated_connect(&name) {
+                        match xous::connect_for_process(sender_pid, server_sid) {
+                            Ok(xous::Result::ConnectionID(connection_id)) => {
+                                log::trace!("authenticated lookup success, returning connection {}", connection_id);
+                                response = api::Return::CID((connection_id, None));
+                            }
+                            result => {
+                                log::error!("error when making authenticated connection: {:?}", result);
+                                name_table.authenticated_disconnect(&name);
+                            }
+                        }
+                    }
+                }
+                if let api::Return::Failure = response {
+                    info!("authenticated lookup failed, waiting for deterministic timeout");
+                    d11ctimeout.deterministic_busy_wait();
+                }
+                buffer
+                    .replace(response)
+                    .expect("AuthenticatedLookup can't serialize return value");
             }
             Some(api::Opcode::TrustedInitDone) => {
                 if name_table.trusted_init_done() {
@@ -566,3 +670,65 @@ fn xmain() -> ! {
     log::trace!("quitting");
     xous::terminate_process(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TEST_KEY: [u32; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn test_table() -> (CheckedHashMap, XousServerName, xous::SID) {
+        let mut table = CheckedHashMap::new();
+        let name = XousServerName::from_str("test-auth-server");
+        let sid = xous::SID::from_u32(0x1, 0x2, 0x3, 0x4);
+        // more than one connection, so no disconnect token is provisioned
+        table.insert(name, sid, Some(2), Some(TEST_KEY)).unwrap();
+        (table, name, sid)
+    }
+    #[test]
+    fn test_auth_challenge() {
+        let (mut table, name, _sid) = test_table();
+        let challenge = [0x10, 0x20, 0x30, 0x40];
+        let (key_id, expected) = table.auth_challenge(&name, &challenge).unwrap();
+        assert_eq!(key_id, authenticate_key_id(&TEST_KEY));
+        assert!(response_matches(&expected, &authenticate_response(&TEST_KEY, "test-auth-server", &challenge)));
+        assert!(!response_matches(&expected, &authenticate_response(&[0; 8], "test-auth-server", &challenge)));
+
+        let plain = XousServerName::from_str("test-plain-server");
+        table.insert(plain, xous::SID::from_u32(0x5, 0x6, 0x7, 0x8), Some(2), None).unwrap();
+        assert!(table.auth_challenge(&plain, &challenge).is_none());
+        assert!(table.authenticated_connect(&plain).is_none());
+        assert!(table.auth_challenge(&XousServerName::from_str("no-such-server"), &challenge).is_none());
+    }
+    #[test]
+    fn test_auth_response_covers_name() {
+        let challenge = [0x10, 0x20, 0x30, 0x40];
+        let response = authenticate_response(&TEST_KEY, "test-auth-server", &challenge);
+        // names that share their first block, or differ only by padding, get different responses
+        assert!(!response_matches(&response, &authenticate_response(&TEST_KEY, "test-auth-server-2", &challenge)));
+        assert!(!response_matches(&response, &authenticate_response(&TEST_KEY, "test-auth-server\0", &challenge)));
+        assert!(!response_matches(
+            &authenticate_response(&TEST_KEY, "test", &challenge),
+            &authenticate_response(&TEST_KEY, "test\0", &challenge)
+        ));
+        assert!(response_matches(&response, &authenticate_response(&TEST_KEY, "test-auth-server", &challenge)));
+    }
+    #[test]
+    fn test_auth_conns_bypass_limit() {
+        let (mut table, name, sid) = test_table();
+        assert!(table.connect(&name).0 == Some(sid));
+        assert!(table.connect(&name).0 == Some(sid));
+        assert!(table.connect(&name).0.is_none(), "connection limit was not enforced");
+        assert!(table.trusted_init_done());
+
+        assert!(table.authenticated_connect(&name) == Some(sid));
+        assert!(table.authenticated_connect(&name) == Some(sid));
+        let entry = table.map.get(&name).unwrap();
+        assert_eq!(entry.current_conns, 2);
+        assert_eq!(entry.auth_conns, 2);
+        // authenticated connections don't disturb the trusted connection accounting
+        assert!(table.trusted_init_done());
+
+        table.authenticated_disconnect(&name);
+        assert_eq!(table.map.get(&name).unwrap().auth_conns, 1);
+    }
+}
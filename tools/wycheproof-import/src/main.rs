@@ -7,6 +7,11 @@ use std::process::exit;
 use eyre::{bail, Result, WrapErr};
 use serde::Deserialize;
 
+#[derive(Deserialize)]
+struct Header {
+    algorithm: String,
+}
+
 #[derive(Deserialize)]
 struct TestData {
     #[serde(rename(deserialize = "testGroups"))]
@@ -18,6 +23,39 @@ struct TestGroup {
     tests: Vec<TestCase>,
 }
 
+#[derive(Deserialize)]
+struct AeadTestData {
+    #[serde(rename(deserialize = "testGroups"))]
+    test_groups: Vec<AeadTestGroup>,
+}
+
+#[derive(Deserialize)]
+struct AeadTestGroup {
+    #[serde(rename(deserialize = "tagSize"))]
+    tag_size: usize,
+    tests: Vec<AeadTestCase>,
+}
+
+#[serde_with::serde_as]
+#[derive(Debug, Deserialize)]
+struct AeadTestCase {
+    #[serde(rename(deserialize = "tcId"))]
+    id: u32,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    key: Vec<u8>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    iv: Vec<u8>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    aad: Vec<u8>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    msg: Vec<u8>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    ct: Vec<u8>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    tag: Vec<u8>,
+    result: String,
+}
+
 #[serde_with::serde_as]
 #[derive(Debug, Deserialize)]
 struct TestCase {
@@ -34,25 +72,51 @@ struct TestCase {
 
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        println!("usage: wycheproof-import <input.json> <output.bin>");
+    if args.len() < 3 {
+        println!("usage: wycheproof-import <input.json|input.blb>... <output.bin>");
         exit(0);
     }
-    let input_file_path = Path::new(&args[1]);
-    let output_file_path = Path::new(&args[2]);
-
-    let test_data = std::fs::read_to_string(input_file_path).wrap_err(format!(
-        "Could not read test data from '{}'",
-        input_file_path.to_string_lossy()
-    ))?;
-    let test_data: TestData =
-        serde_json::from_str(&test_data).wrap_err("Error parsing test vectors")?;
+    let output_file_path = Path::new(&args[args.len() - 1]);
 
-    let expected_results = vec!["valid".to_string(), "acceptable".to_string()];
     let mut output_file = std::fs::File::create(output_file_path).wrap_err(format!(
         "Error creating output file '{}'",
         output_file_path.to_string_lossy()
     ))?;
+    let mut count = 0;
+    for input in &args[1..args.len() - 1] {
+        let input_file_path = Path::new(input);
+        if input_file_path.extension() == Some(std::ffi::OsStr::new("blb")) {
+            let test_data = std::fs::read(input_file_path).wrap_err(format!(
+                "Could not read test data from '{}'",
+                input_file_path.to_string_lossy()
+            ))?;
+            import_aead_blobby(&test_data, &mut output_file, &mut count)?;
+            continue;
+        }
+        let test_data = std::fs::read_to_string(input_file_path).wrap_err(format!(
+            "Could not read test data from '{}'",
+            input_file_path.to_string_lossy()
+        ))?;
+        let header: Header =
+            serde_json::from_str(&test_data).wrap_err("Error parsing test vector header")?;
+
+        match header.algorithm.as_str() {
+            "XDH" => import_x25519(&test_data, &mut output_file)?,
+            "AES-GCM" | "AES-GCM-SIV" => import_aead(&test_data, &mut output_file, &mut count)?,
+            other => bail!("Unsupported algorithm '{}'", other),
+        }
+    }
+    if count > 0 {
+        println!("Imported {} test cases", count);
+    }
+    Ok(())
+}
+
+fn import_x25519(test_data: &str, output_file: &mut std::fs::File) -> Result<()> {
+    let test_data: TestData =
+        serde_json::from_str(test_data).wrap_err("Error parsing test vectors")?;
+
+    let expected_results = vec!["valid".to_string(), "acceptable".to_string()];
     let mut last_id = 0;
 
     for test_case in &test_data.test_groups[0].tests {
@@ -73,3 +137,115 @@ fn main() -> Result<()> {
     }
     Ok(())
 }
+
+/// AEAD test cases are variable length, so each one is written as eight little-endian u32 words
+/// (tcId, 1 if the case must be accepted else 0, then the lengths of key, iv, aad, msg, ct and tag)
+/// followed by the bytes of those six fields in the same order. Only groups with full 128-bit tags
+/// are kept, since that is all the `aes` crate modes produce.
+fn import_aead(test_data: &str, output_file: &mut std::fs::File, count: &mut u32) -> Result<()> {
+    let test_data: AeadTestData =
+        serde_json::from_str(test_data).wrap_err("Error parsing test vectors")?;
+
+    for group in test_data.test_groups.iter().filter(|g| g.tag_size == 128) {
+        for test_case in &group.tests {
+            let valid = match test_case.result.as_str() {
+                "valid" => true,
+                "invalid" => false,
+                // "acceptable" cases exercise legal-but-discouraged parameters; we don't make a claim either way
+                _ => continue,
+            };
+            write_aead_case(output_file, test_case.id, valid, [
+                &test_case.key,
+                &test_case.iv,
+                &test_case.aad,
+                &test_case.msg,
+                &test_case.ct,
+                &test_case.tag,
+            ])?;
+            *count += 1;
+        }
+    }
+    Ok(())
+}
+
+/// RustCrypto's `aes-gcm` and `aes-gcm-siv` crates ship the Wycheproof cases with 96-bit nonces in
+/// `blobby` format: a VLQ count of shared blobs, the shared blobs themselves (each a VLQ length and
+/// the bytes), then the entries. An entry is a VLQ `v`: odd values refer to shared blob `v >> 1`,
+/// even values are followed by an inline blob of `v >> 1` bytes. Each case is six entries: key,
+/// nonce, aad, msg, ct with the tag appended, and 1 if the case must be accepted else 0.
+///
+/// The files don't carry Wycheproof's tcIds, so cases are numbered in the order they're imported.
+fn import_aead_blobby(data: &[u8], output_file: &mut std::fs::File, count: &mut u32) -> Result<()> {
+    fn read_vlq(data: &[u8], pos: &mut usize) -> Result<usize> {
+        let mut val = 0;
+        loop {
+            let b = match data.get(*pos) {
+                Some(&b) => b,
+                None => bail!("Truncated blobby data"),
+            };
+            *pos += 1;
+            val = (val << 7) | (b & 0x7f) as usize;
+            if b & 0x80 == 0 {
+                return Ok(val);
+            }
+            // blobby's VLQ is bijective: every continuation carries an implicit +1
+            val += 1;
+        }
+    }
+    fn read_blob<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+        if *pos + len > data.len() {
+            bail!("Truncated blobby data");
+        }
+        *pos += len;
+        Ok(&data[*pos - len..*pos])
+    }
+
+    let mut pos = 0;
+    let mut shared = Vec::new();
+    for _ in 0..read_vlq(data, &mut pos)? {
+        let len = read_vlq(data, &mut pos)?;
+        shared.push(read_blob(data, &mut pos, len)?);
+    }
+    let mut entries = Vec::new();
+    while pos < data.len() {
+        let v = read_vlq(data, &mut pos)?;
+        if v & 1 == 1 {
+            match shared.get(v >> 1) {
+                Some(&blob) => entries.push(blob),
+                None => bail!("Reference to unknown shared blob {}", v >> 1),
+            }
+        } else {
+            entries.push(read_blob(data, &mut pos, v >> 1)?);
+        }
+    }
+    if entries.len() % 6 != 0 {
+        bail!("Expect AEAD test cases to be six blobs each, found {} blobs", entries.len());
+    }
+
+    for case in entries.chunks(6) {
+        let valid = match case[5] {
+            [0] => false,
+            [1] => true,
+            _ => bail!("Expect the pass flag to be 0 or 1, was {:?}", case[5]),
+        };
+        if case[4].len() < 16 {
+            bail!("Expect the ciphertext to end in a 128-bit tag");
+        }
+        let (ct, tag) = case[4].split_at(case[4].len() - 16);
+        *count += 1;
+        write_aead_case(output_file, *count, valid, [case[0], case[1], case[2], case[3], ct, tag])?;
+    }
+    Ok(())
+}
+
+fn write_aead_case(output_file: &mut std::fs::File, id: u32, valid: bool, fields: [&[u8]; 6]) -> Result<()> {
+    output_file.write_all(&id.to_le_bytes())?;
+    output_file.write_all(&(valid as u32).to_le_bytes())?;
+    for field in fields.iter() {
+        output_file.write_all(&(field.len() as u32).to_le_bytes())?;
+    }
+    for field in fields.iter() {
+        output_file.write_all(field)?;
+    }
+    Ok(())
+}
@@ -41,6 +41,10 @@ pub const EC_REGION_LEN: u32 = 0x0008_0000;
 pub const PDDB_LOC: u32 = 0x01D8_0000; // PDDB start
 pub const PDDB_LEN: u32 = EC_REGION_LOC - PDDB_LOC; // must be 64k-aligned (bulk erase block size) for proper function.
 
+/// Period of the preemption tick that `susres` runs on TIMER0. The kernel counts these ticks to
+/// expire `ReceiveMessageTimeout` and `SendMessageTimeout`, so both sides must use this value.
+pub const PREEMPTION_TICK_MS: u32 = 20;
+
 #[cfg(not(any(target_os = "none", target_os = "xous")))]
 use core::sync::atomic::AtomicU64;
 
@@ -751,6 +755,81 @@ impl core::fmt::Display for MemoryType {
     }
 }
 
+/// How urgently the kernel should run a thread. When more than one thread is
+/// ready, the one with the highest priority is picked, and threads of equal
+/// priority take turns. A busy thread will keep lower-priority threads from
+/// running, so only use `High` and `Realtime` for work that blocks often.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThreadPriority {
+    /// Background work that should only run when nothing else wants to
+    Low = 0,
+
+    /// The priority threads get unless they ask for something else
+    Normal = 1,
+
+    /// Latency-sensitive work such as input handling
+    High = 2,
+
+    /// Work that must not be starved, such as feeding audio buffers
+    Realtime = 3,
+}
+
+impl Default for ThreadPriority {
+    fn default() -> Self {
+        ThreadPriority::Normal
+    }
+}
+
+impl ThreadPriority {
+    pub fn from_usize(value: usize) -> Option<ThreadPriority> {
+        match value {
+            0 => Some(ThreadPriority::Low),
+            1 => Some(ThreadPriority::Normal),
+            2 => Some(ThreadPriority::High),
+            3 => Some(ThreadPriority::Realtime),
+            _ => None,
+        }
+    }
+}
+
+/// Resource usage of a process, as returned by `get_process_stats()`.
+///
+/// Times are measured in kernel ticks. On hardware these are CPU cycles, and
+/// on hosted mode they are microseconds. Compare two snapshots against their
+/// `timestamp` to work out how busy a process was in between.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ProcessStats {
+    /// Bytes of RAM owned by this process
+    pub ram_bytes: usize,
+
+    /// Number of threads currently allocated in this process
+    pub threads: usize,
+
+    /// Total ticks spent running this process, including threads that have
+    /// since exited
+    pub runtime: u64,
+
+    /// Number of times the kernel has switched to this process
+    pub switches: usize,
+
+    /// Kernel tick count at the time these stats were taken
+    pub timestamp: u64,
+}
+
+/// CPU usage of a single thread, as returned by `get_thread_stats()`. The
+/// counters are reset whenever a thread ID is reused.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ThreadStats {
+    /// Total ticks spent running this thread
+    pub runtime: u64,
+
+    /// Number of times the kernel has switched to this thread
+    pub switches: usize,
+
+    /// Kernel tick count at the time these stats were taken
+    pub timestamp: u64,
+}
+
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub enum Result {
@@ -802,6 +881,12 @@ pub enum Result {
         Option<MemorySize>, /* valid */
     ),
 
+    /// Resource usage of a process
+    ProcessStats(ProcessStats),
+
+    /// CPU usage of a thread
+    ThreadStats(ThreadStats),
+
     UnknownResult(usize, usize, usize, usize, usize, usize, usize),
 }
 
@@ -858,6 +943,26 @@ impl Result {
                 0,
                 0,
             ],
+            Result::ProcessStats(stats) => [
+                19,
+                stats.ram_bytes,
+                stats.threads,
+                (stats.runtime & 0xffff_ffff) as usize,
+                (stats.runtime >> 32) as usize,
+                stats.switches,
+                (stats.timestamp & 0xffff_ffff) as usize,
+                (stats.timestamp >> 32) as usize,
+            ],
+            Result::ThreadStats(stats) => [
+                20,
+                (stats.runtime & 0xffff_ffff) as usize,
+                (stats.runtime >> 32) as usize,
+                stats.switches,
+                (stats.timestamp & 0xffff_ffff) as usize,
+                (stats.timestamp >> 32) as usize,
+                0,
+                0,
+            ],
             Result::UnknownResult(arg1, arg2, arg3, arg4, arg5, arg6, arg7) => {
                 [usize::MAX, *arg1, *arg2, *arg3, *arg4, *arg5, *arg6, *arg7]
             }
@@ -934,6 +1039,18 @@ impl Result {
             16 => Result::RetryCall,
             17 => Result::None,
             18 => Result::MemoryReturned(MemorySize::new(src[1]), MemorySize::new(src[2])),
+            19 => Result::ProcessStats(ProcessStats {
+                ram_bytes: src[1],
+                threads: src[2],
+                runtime: src[3] as u64 | ((src[4] as u64) << 32),
+                switches: src[5],
+                timestamp: src[6] as u64 | ((src[7] as u64) << 32),
+            }),
+            20 => Result::ThreadStats(ThreadStats {
+                runtime: src[1] as u64 | ((src[2] as u64) << 32),
+                switches: src[3],
+                timestamp: src[4] as u64 | ((src[5] as u64) << 32),
+            }),
             _ => Result::UnknownResult(src[0], src[1], src[2], src[3], src[4], src[5], src[6]),
         }
     }
@@ -1,7 +1,8 @@
 use crate::{
     pid_from_usize, CpuID, Error, MemoryAddress, MemoryFlags, MemoryMessage, MemoryRange,
     MemorySize, MemoryType, Message, MessageEnvelope, MessageSender, ProcessArgs, ProcessInit,
-    Result, ScalarMessage, SysCallResult, ThreadInit, CID, PID, SID, TID,
+    ProcessStats, Result, ScalarMessage, SysCallResult, ThreadInit, ThreadPriority, ThreadStats,
+    CID, PID, SID, TID,
 };
 use core::convert::{TryFrom, TryInto};
 /* https://github.com/betrusted-io/xous-core/issues/90
@@ -425,6 +426,75 @@ pub enum SysCall {
         usize, /* stack pointer */
     ),
 
+    /// Get the amount of RAM, number of threads and CPU time used by the given
+    /// process.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a `Result::ProcessStats`
+    ///
+    /// ## Errors
+    ///
+    /// * **ProcessNotFound**: The given PID is not running
+    GetProcessStats(PID),
+
+    /// Get the CPU time used by a thread within the given process.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a `Result::ThreadStats`
+    ///
+    /// ## Errors
+    ///
+    /// * **ProcessNotFound**: The given PID is not running
+    /// * **ThreadNotAvailable**: The given TID is out of range
+    GetThreadStats(PID, TID),
+
+    /// Change how urgently the given thread in the current process is
+    /// scheduled. Threads start out with the priority of the thread that
+    /// created them.
+    ///
+    /// ## Returns
+    ///
+    /// Returns `Result::Ok`
+    ///
+    /// ## Errors
+    ///
+    /// * **ThreadNotAvailable**: The given TID is out of range
+    SetThreadPriority(TID, ThreadPriority),
+
+    /// Wait for a message on the given server, as with `ReceiveMessage`, but
+    /// give up after the given number of milliseconds. On hardware the kernel
+    /// counts time in preemption ticks of `PREEMPTION_TICK_MS`, so a timeout
+    /// may run up to two ticks long, but never expires early.
+    ///
+    /// # Returns
+    ///
+    /// * **Message**: A valid message from the queue
+    ///
+    /// # Errors
+    ///
+    /// * **ServerNotFound**: The given SID is not active or has terminated
+    /// * **Timeout**: No message arrived before the timeout expired
+    /// * **OutOfMemory**: Too many threads are already waiting with a timeout
+    ReceiveMessageTimeout(SID, usize /* milliseconds */),
+
+    /// Send a message to a server, as with `SendMessage`, but give up if the
+    /// server's queue is still full after the given number of milliseconds.
+    /// Only the wait for room in the queue is bounded: once the message has
+    /// been queued, a blocking message waits for its response exactly as it
+    /// would with `SendMessage`, however long that takes.
+    ///
+    /// The timeout shares an argument with the message type, so it may be at
+    /// most `usize::MAX >> 8` milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// * **ServerNotFound**: The server could not be found.
+    /// * **Timeout**: The server's queue stayed full until the timeout expired
+    /// * **OutOfMemory**: Too many threads are already waiting with a timeout
+    SendMessageTimeout(CID, Message, usize /* milliseconds */),
+
     /// This syscall does not exist. It captures all possible
     /// arguments so detailed analysis can be performed.
     Invalid(usize, usize, usize, usize, usize, usize, usize),
@@ -468,6 +538,11 @@ pub enum SysCallNumber {
     Disconnect = 35,
     JoinThread = 36,
     SetExceptionHandler = 37,
+    GetProcessStats = 38,
+    GetThreadStats = 39,
+    SetThreadPriority = 40,
+    ReceiveMessageTimeout = 41,
+    SendMessageTimeout = 42,
     Invalid,
 }
 
@@ -511,6 +586,11 @@ impl SysCallNumber {
             35 => Disconnect,
             36 => JoinThread,
             37 => SetExceptionHandler,
+            38 => GetProcessStats,
+            39 => GetThreadStats,
+            40 => SetThreadPriority,
+            41 => ReceiveMessageTimeout,
+            42 => SendMessageTimeout,
             _ => Invalid,
         }
     }
@@ -852,6 +932,73 @@ impl SysCall {
                 0,
                 0,
             ],
+            SysCall::GetProcessStats(pid) => [
+                SysCallNumber::GetProcessStats as usize,
+                pid.get() as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::GetThreadStats(pid, tid) => [
+                SysCallNumber::GetThreadStats as usize,
+                pid.get() as usize,
+                *tid as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::SetThreadPriority(tid, priority) => [
+                SysCallNumber::SetThreadPriority as usize,
+                *tid as usize,
+                *priority as usize,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+            SysCall::ReceiveMessageTimeout(sid, ms) => {
+                let s = sid.to_u32();
+                [
+                    SysCallNumber::ReceiveMessageTimeout as usize,
+                    s.0 as _,
+                    s.1 as _,
+                    s.2 as _,
+                    s.3 as _,
+                    *ms,
+                    0,
+                    0,
+                ]
+            }
+            // There's no room for a separate timeout argument, so the timeout
+            // rides along in the upper bits of the message type.
+            SysCall::SendMessageTimeout(a1, ref a2, ms) => match a2 {
+                Message::MutableBorrow(mm) | Message::Borrow(mm) | Message::Move(mm) => [
+                    SysCallNumber::SendMessageTimeout as usize,
+                    *a1 as usize,
+                    a2.message_type() | (ms << 8),
+                    mm.id as usize,
+                    mm.buf.as_ptr() as usize,
+                    mm.buf.len(),
+                    mm.offset.map(|x| x.get()).unwrap_or(0) as usize,
+                    mm.valid.map(|x| x.get()).unwrap_or(0) as usize,
+                ],
+                Message::Scalar(sc) | Message::BlockingScalar(sc) => [
+                    SysCallNumber::SendMessageTimeout as usize,
+                    *a1 as usize,
+                    a2.message_type() | (ms << 8),
+                    sc.id as usize,
+                    sc.arg1,
+                    sc.arg2,
+                    sc.arg3,
+                    sc.arg4,
+                ],
+            },
             SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7) => [
                 SysCallNumber::Invalid as usize,
                 *a1,
@@ -1015,6 +1162,23 @@ impl SysCall {
             SysCallNumber::Disconnect => SysCall::Disconnect(a1 as _),
             SysCallNumber::JoinThread => SysCall::JoinThread(a1 as _),
             SysCallNumber::SetExceptionHandler => SysCall::SetExceptionHandler(a1 as _, a2 as _),
+            SysCallNumber::GetProcessStats => {
+                SysCall::GetProcessStats(PID::new(a1 as _).ok_or(Error::InvalidSyscall)?)
+            }
+            SysCallNumber::GetThreadStats => {
+                SysCall::GetThreadStats(PID::new(a1 as _).ok_or(Error::InvalidSyscall)?, a2 as _)
+            }
+            SysCallNumber::SetThreadPriority => SysCall::SetThreadPriority(
+                a1 as _,
+                ThreadPriority::from_usize(a2).ok_or(Error::InvalidSyscall)?,
+            ),
+            SysCallNumber::ReceiveMessageTimeout => SysCall::ReceiveMessageTimeout(
+                SID::from_u32(a1 as _, a2 as _, a3 as _, a4 as _),
+                a5,
+            ),
+            SysCallNumber::SendMessageTimeout => Message::try_from((a2 & 0xff, a3, a4, a5, a6, a7))
+                .map(|m| SysCall::SendMessageTimeout(a1.try_into().unwrap(), m, a2 >> 8))
+                .unwrap_or_else(|_| SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7)),
             SysCallNumber::Invalid => SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7),
         })
     }
@@ -1022,7 +1186,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that has memory attached to it
     pub fn has_memory(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(
                     msg,
                     Message::Move(_) | Message::Borrow(_) | Message::MutableBorrow(_)
@@ -1036,7 +1202,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that is a Move
     pub fn is_move(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(msg, Message::Move(_))
             }
             _ => false,
@@ -1046,7 +1214,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that is a Borrow
     pub fn is_borrow(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(msg, Message::Borrow(_))
             }
             _ => false,
@@ -1056,7 +1226,9 @@ impl SysCall {
     /// Returns `true` if the associated syscall is a message that is a MutableBorrow
     pub fn is_mutableborrow(&self) -> bool {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => {
                 matches!(msg, Message::MutableBorrow(_))
             }
             _ => false,
@@ -1071,7 +1243,9 @@ impl SysCall {
     /// If the syscall has memory attached to it, return the memory
     pub fn memory(&self) -> Option<MemoryRange> {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => match msg {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => match msg {
                 Message::Move(memory_message)
                 | Message::Borrow(memory_message)
                 | Message::MutableBorrow(memory_message) => Some(memory_message.buf),
@@ -1090,7 +1264,9 @@ impl SysCall {
     /// not be used for any other purpose.
     pub unsafe fn memory_mut(&mut self) -> Option<&mut MemoryRange> {
         match self {
-            SysCall::TrySendMessage(_, msg) | SysCall::SendMessage(_, msg) => match msg {
+            SysCall::TrySendMessage(_, msg)
+            | SysCall::SendMessage(_, msg)
+            | SysCall::SendMessageTimeout(_, msg, _) => match msg {
                 Message::Move(memory_message)
                 | Message::Borrow(memory_message)
                 | Message::MutableBorrow(memory_message) => Some(&mut memory_message.buf),
@@ -1416,6 +1592,25 @@ pub fn try_receive_message(server: SID) -> core::result::Result<Option<MessageEn
     }
 }
 
+/// Retrieve a message from the message queue for the provided server. If no message
+/// arrives within `timeout_ms` milliseconds, returns `Err(Error::Timeout)`. On hardware
+/// the timeout is rounded up to whole preemption ticks (see `PREEMPTION_TICK_MS`).
+///
+/// # Errors
+///
+/// * **ServerNotFound**: The server does not exist
+/// * **Timeout**: No message arrived before the timeout expired
+pub fn receive_message_timeout(
+    server: SID,
+    timeout_ms: usize,
+) -> core::result::Result<MessageEnvelope, Error> {
+    match rsyscall(SysCall::ReceiveMessageTimeout(server, timeout_ms)) {
+        Ok(Result::Message(envelope)) => Ok(envelope),
+        Ok(Result::Error(e)) | Err(e) => Err(e),
+        Ok(_) => Err(Error::InternalError),
+    }
+}
+
 /// Send a message to a server.  Depending on the mesage type (move or borrow), it
 /// will either block (borrow) or return immediately (move).
 /// If the message type is `borrow`, then the memory addresses pointed to will be
@@ -1481,6 +1676,34 @@ pub fn send_message(connection: CID, message: Message) -> core::result::Result<R
     }
 }
 
+/// Send a message to a server, waiting at most `timeout_ms` milliseconds for
+/// room in its queue. Timeouts longer than `usize::MAX >> 8` milliseconds are
+/// shortened to that limit. Once the message is queued this behaves exactly
+/// like `send_message()`: the timeout does not bound the wait for the response
+/// to a blocking message, which lasts until the server replies.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: The server does not exist so the connection is now invalid
+/// * **BadAddress**: The client tried to pass a Memory message using an address it doesn't own
+/// * **Timeout**: The server's queue was still full when the timeout expired
+pub fn send_message_timeout(
+    connection: CID,
+    message: Message,
+    timeout_ms: usize,
+) -> core::result::Result<Result, Error> {
+    let timeout_ms = timeout_ms.min(usize::MAX >> 8);
+    let result = rsyscall(SysCall::SendMessageTimeout(connection, message, timeout_ms));
+    match result {
+        Ok(Result::Ok) => Ok(Result::Ok),
+        Ok(Result::Scalar1(a)) => Ok(Result::Scalar1(a)),
+        Ok(Result::Scalar2(a, b)) => Ok(Result::Scalar2(a, b)),
+        Ok(Result::MemoryReturned(offset, valid)) => Ok(Result::MemoryReturned(offset, valid)),
+        Err(e) => Err(e),
+        v => panic!("Unexpected return value: {:?}", v),
+    }
+}
+
 pub fn terminate_process(exit_code: u32) -> ! {
     rsyscall(SysCall::TerminateProcess(exit_code)).expect("terminate_process returned an error");
     panic!("process didn't terminate");
@@ -1740,6 +1963,53 @@ pub fn join_thread(tid: TID) -> core::result::Result<usize, Error> {
         }
     })
 }
+
+/// Get the amount of RAM, number of threads and CPU time used by a process.
+///
+/// # Errors
+///
+/// * **ProcessNotFound**: The given PID is not running
+pub fn get_process_stats(pid: PID) -> core::result::Result<ProcessStats, Error> {
+    rsyscall(SysCall::GetProcessStats(pid)).and_then(|result| {
+        if let Result::ProcessStats(stats) = result {
+            Ok(stats)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Get the CPU time used by a single thread within a process.
+///
+/// # Errors
+///
+/// * **ProcessNotFound**: The given PID is not running
+/// * **ThreadNotAvailable**: The given TID is out of range
+pub fn get_thread_stats(pid: PID, tid: TID) -> core::result::Result<ThreadStats, Error> {
+    rsyscall(SysCall::GetThreadStats(pid, tid)).and_then(|result| {
+        if let Result::ThreadStats(stats) = result {
+            Ok(stats)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Change the scheduling priority of a thread in the current process. Threads
+/// created afterwards by that thread start out with the same priority.
+///
+/// # Errors
+///
+/// * **ThreadNotAvailable**: The given TID is out of range
+pub fn set_thread_priority(tid: TID, priority: ThreadPriority) -> core::result::Result<(), Error> {
+    rsyscall(SysCall::SetThreadPriority(tid, priority)).and_then(|result| {
+        if let Result::Ok = result {
+            Ok(())
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
 /* https://github.com/betrusted-io/xous-core/issues/90
 static EXCEPTION_HANDLER: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
 fn handle_exception(exception_type: usize, arg1: usize, arg2: usize) -> isize {
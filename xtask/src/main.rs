@@ -597,7 +597,7 @@ Various debug configurations:
  ro-test [soc.svd]       builds an image for ring oscillator only TRNG testing
  av-test [soc.svd]       builds an image for avalanche generater only TRNG testing
  sr-test [soc.svd]       builds the suspend/resume testing image
- wycheproof-import       generate binary test vectors for engine-25519 from whycheproof-import/x25519.json
+ wycheproof-import       generate binary test vectors for engine-25519 and the aes modes from tools/wycheproof-import
  pddb-dev                PDDB testing only for live hardware
  pddb-hosted             PDDB testing in a hosted environment
  pddb-ci                 PDDB config for CI testing (eg: TRNG->deterministic for reproducible errors)
@@ -1472,25 +1472,43 @@ fn generate_locales() -> Result<(), std::io::Error> {
 }
 
 fn whycheproof_import() -> Result<(), DynError> {
-    let input_file = "tools/wycheproof-import/x25519_test.json";
-    let output_file = "services/shellchat/src/cmds/x25519_test.bin";
-    let status = Command::new(cargo())
-        .current_dir(project_root())
-        .args(&[
-            "run",
-            "--package",
-            "wycheproof-import",
-            "--",
-            input_file,
-            output_file,
-        ])
-        .status()?;
-    if !status.success() {
-        return Err("wycheproof-import failed. If any, the output will not be usable.".into());
-    }
+    // (inputs, output). The AEAD sets come from the 96-bit nonce Wycheproof cases that RustCrypto ships
+    // in blobby form, unless upstream's full JSON has been copied in next to them.
+    let aead_inputs = |json: &'static str, blobs: [&'static str; 2]| {
+        if project_root().join(json).exists() { vec![json] } else { blobs.to_vec() }
+    };
+    let imports = [
+        (vec!["tools/wycheproof-import/x25519_test.json"], "services/shellchat/src/cmds/x25519_test.bin"),
+        (
+            aead_inputs("tools/wycheproof-import/aes_gcm_test.json",
+                ["tools/wycheproof-import/aes_gcm_128_test.blb", "tools/wycheproof-import/aes_gcm_256_test.blb"]),
+            "services/aes/src/modes/aes_gcm_test.bin",
+        ),
+        (
+            aead_inputs("tools/wycheproof-import/aes_gcm_siv_test.json",
+                ["tools/wycheproof-import/aes_gcm_siv_128_test.blb", "tools/wycheproof-import/aes_gcm_siv_256_test.blb"]),
+            "services/aes/src/modes/aes_gcm_siv_test.bin",
+        ),
+    ];
+    for (input_files, output_file) in imports.iter() {
+        let status = Command::new(cargo())
+            .current_dir(project_root())
+            .args(&[
+                "run",
+                "--package",
+                "wycheproof-import",
+                "--",
+            ])
+            .args(input_files)
+            .arg(output_file)
+            .status()?;
+        if !status.success() {
+            return Err("wycheproof-import failed. If any, the output will not be usable.".into());
+        }
 
-    println!();
-    println!("Wrote wycheproof x25519 testvectors to '{}'.", output_file);
+        println!();
+        println!("Wrote wycheproof testvectors to '{}'.", output_file);
+    }
 
     return Ok(());
 }